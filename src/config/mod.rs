@@ -1,5 +1,40 @@
 use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use crate::utils::duration::parse_duration;
+use crate::llm::budget::Limits;
+
+// What happens to a command translated from natural language
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TranslationMode {
+    // Shown with its explanation and left in the editor, never run
+    Suggest,
+    // Always asks before running
+    Confirm,
+    // Runs, asking only when the confirmation policy says so
+    Auto,
+}
+
+impl TranslationMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "suggest" => Some(TranslationMode::Suggest),
+            "confirm" => Some(TranslationMode::Confirm),
+            "auto" => Some(TranslationMode::Auto),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TranslationMode::Suggest => "suggest",
+            TranslationMode::Confirm => "confirm",
+            TranslationMode::Auto => "auto",
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Config {
@@ -8,14 +43,308 @@ pub struct Config {
     pub max_context_items: usize,
     pub suggestion_count: usize,
     pub command_preview: bool,
+    pub tmux_target_pane: Option<String>,
+    pub cloud_prompt: bool,
+    // Collapse the two-line prompt of accepted commands into one line
+    pub transient_prompt: bool,
+    // Show the model and whether it is reachable in the prompt
+    pub llm_prompt: bool,
+    // Right prompt template, e.g. `{status} {jobs} {time}`
+    pub rprompt: Option<String>,
+    pub llm_command_timeout: Option<Duration>,
+    pub demo_mode: bool,
+    pub prefetch_suggestions: bool,
+    // Pause in typing before a suggestion is prefetched
+    pub prefetch_debounce: Duration,
+    pub workspace: Option<String>,
+    pub long_command_threshold: Option<Duration>,
+    pub desktop_notifications: bool,
+    // Extra `COMMAND KIND...` argument completion rules
+    pub completion_specs: Vec<String>,
+    // Size in characters past which older context is summarized; None keeps everything
+    pub context_limit: Option<usize>,
+    // Natural language for chat answers and explanations; None follows the locale
+    pub answer_language: Option<String>,
+    // Daily LLM spending limits for the provider, and its price per million tokens
+    pub token_budget: Limits,
+    pub dollar_budget: Limits,
+    pub token_price: f64,
+    pub translation_mode: TranslationMode,
+    // Recent `good`/`bad` ratings added to translation prompts; 0 adds none
+    pub feedback_examples: usize,
+    // Library examples matched to each translation request; 0 turns them off
+    pub library_examples: usize,
+    // Entries kept in ~/.llm_shell_history
+    pub history_size: usize,
+}
+
+// Config file settings and the environment variable each one stands in for.
+// Nested keys are written as `[prompt]` and `[safety]` tables.
+pub const FILE_SETTINGS: &[(&str, &str)] = &[
+    ("llm_host", "LLM_HOST"),
+    ("llm_model", "LLM_MODEL"),
+    ("suggestion_count", "LLM_SHELL_SUGGESTIONS"),
+    ("history_size", "LLM_SHELL_HISTORY_SIZE"),
+    ("prompt.transient", "LLM_SHELL_TRANSIENT_PROMPT"),
+    ("prompt.llm", "LLM_SHELL_LLM_PROMPT"),
+    ("prompt.cloud", "LLM_SHELL_CLOUD_PROMPT"),
+    ("prompt.right", "LLM_SHELL_RPROMPT"),
+    ("safety.confirm", "LLM_SHELL_CONFIRM"),
+    ("safety.confirm_allow", "LLM_SHELL_CONFIRM_ALLOW"),
+    ("safety.translation_mode", "LLM_SHELL_TRANSLATION_MODE"),
+    ("safety.command_timeout", "LLM_SHELL_LLM_COMMAND_TIMEOUT"),
+    ("safety.llm_limits", "LLM_SHELL_LLM_LIMITS"),
+];
+
+// Values from the config files, by environment variable, and what was wrong
+// with the files
+#[derive(Debug, Default)]
+pub struct FileConfig {
+    pub files: Vec<PathBuf>,
+    values: HashMap<&'static str, String>,
+    pub errors: Vec<String>,
+}
+
+impl FileConfig {
+    // The system-wide file first, so the user's overrides it
+    pub fn paths() -> Vec<PathBuf> {
+        let mut paths = vec![PathBuf::from("/etc/llmsh/config.toml")];
+        if let Some(dir) = dirs::config_dir().or_else(|| dirs::home_dir().map(|home| home.join(".config"))) {
+            paths.push(dir.join("llmsh").join("config.toml"));
+        }
+        paths
+    }
+
+    pub fn load() -> Self {
+        let mut config = FileConfig::default();
+        for path in Self::paths() {
+            if let Ok(content) = std::fs::read_to_string(&path) {
+                config.read(&path.display().to_string(), &content);
+                config.files.push(path);
+            }
+        }
+        config
+    }
+
+    fn read(&mut self, name: &str, content: &str) {
+        let table: toml::Table = match content.parse() {
+            Ok(table) => table,
+            Err(e) => {
+                let line = e.span().map(|span| content[..span.start].matches('\n').count() + 1).unwrap_or(1);
+                self.errors.push(format!("{}:{}: {}", name, line, e.message()));
+                return;
+            }
+        };
+        self.read_table(name, "", &table);
+    }
+
+    fn read_table(&mut self, name: &str, prefix: &str, table: &toml::Table) {
+        for (key, value) in table {
+            let key = format!("{}{}", prefix, key);
+            if let toml::Value::Table(table) = value {
+                self.read_table(name, &format!("{}.", key), table);
+                continue;
+            }
+            let Some((_, env)) = FILE_SETTINGS.iter().find(|(setting, _)| *setting == key) else {
+                self.errors.push(format!("{}: unknown setting `{}`", name, key));
+                continue;
+            };
+            let text = match value {
+                toml::Value::String(text) => Some(text.clone()),
+                toml::Value::Integer(n) => Some(n.to_string()),
+                toml::Value::Float(n) => Some(n.to_string()),
+                toml::Value::Boolean(b) => Some(b.to_string()),
+                // Lists such as confirm_allow become one entry per line
+                toml::Value::Array(items) => items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .map(|items| items.join("\n")),
+                _ => None,
+            };
+            match text {
+                Some(text) => {
+                    self.values.insert(env, text);
+                }
+                None => self.errors.push(format!("{}: `{}` has an unsupported value", name, key)),
+            }
+        }
+    }
+
+    pub fn get(&self, env: &str) -> Option<String> {
+        self.values.get(env).cloned()
+    }
 }
 
 lazy_static! {
+    pub static ref FILE_CONFIG: FileConfig = FileConfig::load();
+
     pub static ref CONFIG: Arc<Config> = Arc::new(Config {
-        llm_host: "http://192.168.86.201:11434".to_string(),
-        llm_model: "qwen2.5:14b".to_string(),
+        llm_host: env_opt("LLM_HOST").unwrap_or_else(|| "http://localhost:11434".to_string()),
+        llm_model: env_opt("LLM_MODEL").unwrap_or_else(|| "qwen2.5:14b".to_string()),
         max_context_items: 10,
-        suggestion_count: 3,
+        suggestion_count: env_opt("LLM_SHELL_SUGGESTIONS").and_then(|v| v.parse().ok()).filter(|n| *n > 0).unwrap_or(3),
         command_preview: true,
+        tmux_target_pane: env_opt("LLM_SHELL_TMUX_PANE"),
+        cloud_prompt: env_flag("LLM_SHELL_CLOUD_PROMPT", false),
+        transient_prompt: env_flag("LLM_SHELL_TRANSIENT_PROMPT", false),
+        llm_prompt: env_flag("LLM_SHELL_LLM_PROMPT", true),
+        rprompt: env_opt("LLM_SHELL_RPROMPT"),
+        llm_command_timeout: env_opt("LLM_SHELL_LLM_COMMAND_TIMEOUT").and_then(|v| parse_duration(&v).ok()),
+        demo_mode: env_flag("LLM_SHELL_DEMO_MODE", false),
+        prefetch_suggestions: env_flag("LLM_SHELL_PREFETCH", true),
+        prefetch_debounce: env_opt("LLM_SHELL_PREFETCH_DEBOUNCE")
+            .and_then(|v| parse_duration(&v).ok())
+            .unwrap_or(Duration::from_millis(300)),
+        workspace: env_opt("LLM_SHELL_WORKSPACE"),
+        long_command_threshold: match env_opt("LLM_SHELL_LONG_COMMAND") {
+            Some(value) if value == "off" => None,
+            Some(value) => parse_duration(&value).ok().filter(|d| !d.is_zero()),
+            None => Some(Duration::from_secs(10)),
+        },
+        desktop_notifications: env_flag("LLM_SHELL_NOTIFY", false),
+        completion_specs: env_opt("LLM_SHELL_COMPLETE")
+            .map(|specs| specs.split(';').map(|spec| spec.trim().to_string()).filter(|spec| !spec.is_empty()).collect())
+            .unwrap_or_default(),
+        context_limit: match env_opt("LLM_SHELL_CONTEXT_LIMIT") {
+            Some(value) => value.parse().ok().filter(|limit| *limit > 0),
+            None => Some(4000),
+        },
+        answer_language: env_opt("LLM_SHELL_ANSWER_LANGUAGE").filter(|value| value != "auto"),
+        token_budget: env_opt("LLM_SHELL_TOKEN_BUDGET").and_then(|v| Limits::parse(&v).ok()).unwrap_or_default(),
+        dollar_budget: env_opt("LLM_SHELL_DOLLAR_BUDGET").and_then(|v| Limits::parse(&v).ok()).unwrap_or_default(),
+        token_price: env_opt("LLM_SHELL_TOKEN_PRICE").and_then(|v| v.trim_start_matches('$').parse().ok()).unwrap_or(0.0),
+        translation_mode: env_opt("LLM_SHELL_TRANSLATION_MODE")
+            .and_then(|v| TranslationMode::parse(&v))
+            .unwrap_or(TranslationMode::Auto),
+        library_examples: env_opt("LLM_SHELL_EXAMPLES").and_then(|v| v.parse().ok()).unwrap_or(3),
+        feedback_examples: env_opt("LLM_SHELL_FEEDBACK_EXAMPLES").and_then(|v| v.parse().ok()).unwrap_or(0),
+        history_size: env_opt("LLM_SHELL_HISTORY_SIZE").and_then(|v| v.parse().ok()).filter(|n| *n > 0).unwrap_or(1000),
     });
 }
+
+// Settings that are set but can't be used; CONFIG quietly falls back to the
+// default for them. Each entry is the variable and what is wrong with it.
+pub fn problems(lookup: impl Fn(&str) -> Option<String>) -> Vec<(&'static str, String)> {
+    const FLAGS: &[&str] = &[
+        "LLM_SHELL_CLOUD_PROMPT",
+        "LLM_SHELL_TRANSIENT_PROMPT",
+        "LLM_SHELL_LLM_PROMPT",
+        "LLM_SHELL_DEMO_MODE",
+        "LLM_SHELL_PREFETCH",
+        "LLM_SHELL_NOTIFY",
+    ];
+    const DURATIONS: &[&str] = &["LLM_SHELL_LLM_COMMAND_TIMEOUT", "LLM_SHELL_PREFETCH_DEBOUNCE", "LLM_SHELL_LONG_COMMAND"];
+    const COUNTS: &[&str] = &["LLM_SHELL_CONTEXT_LIMIT", "LLM_SHELL_EXAMPLES", "LLM_SHELL_FEEDBACK_EXAMPLES"];
+    const SIZES: &[&str] = &["LLM_SHELL_SUGGESTIONS", "LLM_SHELL_HISTORY_SIZE"];
+    const BUDGETS: &[&str] = &["LLM_SHELL_TOKEN_BUDGET", "LLM_SHELL_DOLLAR_BUDGET"];
+
+    let mut problems = Vec::new();
+    let value = |key: &str| lookup(key).filter(|value| !value.trim().is_empty());
+    for key in FLAGS {
+        if let Some(value) = value(key) {
+            if !matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on" | "0" | "false" | "no" | "off") {
+                problems.push((*key, format!("'{}' is not a yes/no value, so it is treated as off", value)));
+            }
+        }
+    }
+    for key in DURATIONS {
+        match value(key) {
+            Some(value) if value == "off" && *key == "LLM_SHELL_LONG_COMMAND" => {}
+            Some(value) => {
+                if let Err(e) = parse_duration(&value) {
+                    problems.push((*key, format!("{}; use a duration such as 500ms, 10s or 5m", e)));
+                }
+            }
+            None => {}
+        }
+    }
+    for key in COUNTS {
+        if let Some(value) = value(key) {
+            if value.parse::<usize>().is_err() {
+                problems.push((*key, format!("'{}' is not a number", value)));
+            }
+        }
+    }
+    for key in SIZES {
+        if let Some(value) = value(key) {
+            if !value.parse::<usize>().is_ok_and(|n| n > 0) {
+                problems.push((*key, format!("'{}' is not a number above 0", value)));
+            }
+        }
+    }
+    for key in BUDGETS {
+        if let Some(value) = value(key) {
+            if let Err(e) = Limits::parse(&value) {
+                problems.push((*key, e.to_string()));
+            }
+        }
+    }
+    if let Some(value) = value("LLM_SHELL_TOKEN_PRICE") {
+        if value.trim_start_matches('$').parse::<f64>().is_err() {
+            problems.push(("LLM_SHELL_TOKEN_PRICE", format!("'{}' is not a price in dollars per million tokens", value)));
+        }
+    }
+    if let Some(value) = value("LLM_SHELL_TRANSLATION_MODE") {
+        if TranslationMode::parse(&value).is_none() {
+            problems.push(("LLM_SHELL_TRANSLATION_MODE", format!("'{}' is not suggest, confirm or auto", value)));
+        }
+    }
+    problems
+}
+
+// Optional settings can be provided through the environment (or a .env file),
+// which takes precedence over the config files
+pub fn env_opt(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .or_else(|| FILE_CONFIG.get(key))
+        .filter(|value| !value.trim().is_empty())
+}
+
+fn env_flag(key: &str, default: bool) -> bool {
+    match env_opt(key) {
+        Some(value) => matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+        None => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems() {
+        let settings = |key: &str| match key {
+            "LLM_SHELL_PREFETCH" => Some("nope".to_string()),
+            "LLM_SHELL_NOTIFY" => Some("yes".to_string()),
+            "LLM_SHELL_LONG_COMMAND" => Some("off".to_string()),
+            "LLM_SHELL_PREFETCH_DEBOUNCE" => Some("soon".to_string()),
+            "LLM_SHELL_EXAMPLES" => Some("three".to_string()),
+            "LLM_SHELL_TRANSLATION_MODE" => Some("Confirm".to_string()),
+            "LLM_SHELL_TOKEN_PRICE" => Some("$0.5".to_string()),
+            _ => None,
+        };
+        let keys: Vec<&str> = problems(settings).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["LLM_SHELL_PREFETCH", "LLM_SHELL_PREFETCH_DEBOUNCE", "LLM_SHELL_EXAMPLES"]);
+        assert!(problems(|_| None).is_empty());
+    }
+
+    #[test]
+    fn test_file_config() {
+        let mut config = FileConfig::default();
+        config.read("system", "llm_model = \"llama3\"\nsuggestion_count = 5\n");
+        config.read(
+            "user",
+            "llm_model = \"qwen2.5:14b\"\nhistory = 10\n[prompt]\ntransient = true\n[safety]\nconfirm_allow = [\"^git push\", \"^ls\"]\n",
+        );
+        config.read("broken", "llm_host = ");
+        assert_eq!(config.get("LLM_MODEL").as_deref(), Some("qwen2.5:14b"));
+        assert_eq!(config.get("LLM_SHELL_SUGGESTIONS").as_deref(), Some("5"));
+        assert_eq!(config.get("LLM_SHELL_TRANSIENT_PROMPT").as_deref(), Some("true"));
+        assert_eq!(config.get("LLM_SHELL_CONFIRM_ALLOW").as_deref(), Some("^git push\n^ls"));
+        assert_eq!(config.errors.len(), 2);
+        assert!(config.errors[0].contains("unknown setting `history`"));
+        assert!(config.errors[1].starts_with("broken:"));
+    }
+}
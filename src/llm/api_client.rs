@@ -2,12 +2,25 @@ use anyhow::Result;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use crate::config::CONFIG;
+use super::budget;
+use super::untrusted;
+use crate::utils::i18n::user_language_name;
 use regex::Regex;
 use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use crate::utils::performance::PERFORMANCE_MONITOR;
 
 #[derive(Clone)]
 pub struct APIClient {
     client: Client,
+    // Shared between clones so a workspace switch reaches every component
+    model: Arc<RwLock<String>>,
+    usage: Arc<Mutex<Usage>>,
+    in_flight: Arc<AtomicUsize>,
+    // Off in POSIX mode, when nothing may reach the model
+    enabled: Arc<AtomicBool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -26,6 +39,32 @@ struct Message {
 #[derive(Debug, Deserialize)]
 struct OllamaResponse {
     choices: Vec<Choice>,
+    // Token counts, when the server reports them
+    usage: Option<ResponseUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+// Running totals for every request made through a client and its clones
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -33,24 +72,302 @@ struct Choice {
     message: Message,
 }
 
+// One server-sent event of a streamed response
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    // Only in the last event, and only from servers that report it
+    usage: Option<ResponseUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    delta: Delta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+// A streamed request stopped by the user, e.g. with Ctrl+C
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+// How often a streamed request checks whether it was cancelled
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
 lazy_static! {
     static ref CODE_BLOCK_RE: Regex = Regex::new(r"```(?:shell|bash)?\s*([^`]+)```").unwrap();
+    // A whole fenced block, which in a script may contain backticks
+    static ref SCRIPT_BLOCK_RE: Regex = Regex::new(r"(?s)```[\w-]*\n(.*?)```").unwrap();
+}
+
+// Prose answers come back in the configured language, or the locale's;
+// commands are always left as they are
+fn in_user_language(prompt: &str) -> String {
+    let language = CONFIG.answer_language.as_deref().or_else(user_language_name);
+    match language {
+        Some(language) => format!(
+            "{} Answer in {}, but keep commands, options, file names and code exactly as they are.",
+            prompt, language
+        ),
+        None => prompt.to_string(),
+    }
+}
+
+// Counts a request as in flight until it is dropped, including on error
+struct Pending<'a>(&'a AtomicUsize);
+
+impl<'a> Pending<'a> {
+    fn start(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Pending(counter)
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 impl APIClient {
     pub fn new() -> Self {
         APIClient {
             client: Client::new(),
+            model: Arc::new(RwLock::new(CONFIG.llm_model.clone())),
+            usage: Arc::new(Mutex::new(Usage::default())),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            enabled: Arc::new(AtomicBool::new(true)),
         }
     }
 
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    fn check_enabled(&self) -> Result<()> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("LLM features are off in POSIX mode; `set +o posix` turns them back on"));
+        }
+        Ok(())
+    }
+
+    pub fn usage(&self) -> Usage {
+        *self.usage.lock().unwrap()
+    }
+
+    // Requests waiting for a response, for the prompt's activity indicator
+    pub fn in_flight(&self) -> Arc<AtomicUsize> {
+        self.in_flight.clone()
+    }
+
+    async fn send(&self, request: &OllamaRequest) -> Result<OllamaResponse> {
+        self.check_enabled()?;
+        budget::check()?;
+        let _pending = Pending::start(&self.in_flight);
+        let start = Instant::now();
+        let response = async {
+            self.client
+                .post(format!("{}/v1/chat/completions", CONFIG.llm_host))
+                .json(request)
+                .send()
+                .await?
+                .json::<OllamaResponse>()
+                .await
+        }
+        .await;
+        PERFORMANCE_MONITOR.lock().unwrap().record_llm_request(start.elapsed(), response.is_ok());
+        let response = response?;
+        self.record_usage(response.usage.as_ref());
+        Ok(response)
+    }
+
+    fn record_usage(&self, counts: Option<&ResponseUsage>) {
+        {
+            let mut usage = self.usage.lock().unwrap();
+            usage.requests += 1;
+            if let Some(counts) = counts {
+                usage.prompt_tokens += counts.prompt_tokens;
+                usage.completion_tokens += counts.completion_tokens;
+            }
+        }
+        if let Some(counts) = counts {
+            budget::record(counts.prompt_tokens + counts.completion_tokens);
+        }
+    }
+
+    // Sends the request with `stream: true` and passes the text to `on_text`
+    // as it arrives. `interrupted` is polled while waiting, and the request
+    // is dropped with a `Cancelled` error once it returns true. Returns the
+    // whole text.
+    async fn send_stream(
+        &self,
+        request: &OllamaRequest,
+        on_text: &mut (dyn FnMut(&str) + Send),
+        interrupted: fn() -> bool,
+    ) -> Result<String> {
+        self.check_enabled()?;
+        budget::check()?;
+        let _pending = Pending::start(&self.in_flight);
+        let start = Instant::now();
+        let mut body = serde_json::to_value(request)?;
+        body["stream"] = true.into();
+        body["stream_options"] = serde_json::json!({ "include_usage": true });
+        let result = self.read_stream(&body, on_text, interrupted).await;
+        let cancelled = result.as_ref().is_err_and(|e| e.is::<Cancelled>());
+        if !cancelled {
+            PERFORMANCE_MONITOR.lock().unwrap().record_llm_request(start.elapsed(), result.is_ok());
+        }
+        let (text, usage) = result?;
+        self.record_usage(usage.as_ref());
+        Ok(text)
+    }
+
+    async fn read_stream(
+        &self,
+        body: &serde_json::Value,
+        on_text: &mut (dyn FnMut(&str) + Send),
+        interrupted: fn() -> bool,
+    ) -> Result<(String, Option<ResponseUsage>)> {
+        let send = self.client.post(format!("{}/v1/chat/completions", CONFIG.llm_host)).json(body).send();
+        tokio::pin!(send);
+        let mut response = loop {
+            tokio::select! {
+                response = &mut send => break response?.error_for_status()?,
+                _ = tokio::time::sleep(CANCEL_POLL) => {
+                    if interrupted() {
+                        return Err(Cancelled.into());
+                    }
+                }
+            }
+        };
+
+        // Servers that ignore `stream` answer with the whole response at once
+        let streamed = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("event-stream"));
+        if !streamed {
+            let response: OllamaResponse = response.json().await?;
+            let text = response.choices.first().map(|choice| choice.message.content.clone()).unwrap_or_default();
+            on_text(&text);
+            return Ok((text, response.usage));
+        }
+
+        let mut text = String::new();
+        let mut usage = None;
+        let mut pending: Vec<u8> = Vec::new();
+        loop {
+            let chunk = tokio::select! {
+                chunk = response.chunk() => chunk?,
+                _ = tokio::time::sleep(CANCEL_POLL) => {
+                    if interrupted() {
+                        return Err(Cancelled.into());
+                    }
+                    continue;
+                }
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
+            pending.extend_from_slice(&chunk);
+            // Events are `data: JSON` lines; a line may be split across chunks
+            while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    return Ok((text, usage));
+                }
+                let event: StreamChunk = match serde_json::from_str(data) {
+                    Ok(event) => event,
+                    Err(e) => {
+                        log::debug!("Skipping unreadable stream event {}: {}", data, e);
+                        continue;
+                    }
+                };
+                if let Some(piece) = event.choices.into_iter().find_map(|choice| choice.delta.content) {
+                    on_text(&piece);
+                    text.push_str(&piece);
+                }
+                if event.usage.is_some() {
+                    usage = event.usage;
+                }
+            }
+        }
+        Ok((text, usage))
+    }
+
+    pub fn model(&self) -> String {
+        self.model.read().unwrap().clone()
+    }
+
+    pub fn set_model(&self, model: &str) {
+        *self.model.write().unwrap() = model.to_string();
+    }
+
+    pub async fn health_check(&self) -> Result<()> {
+        self.client
+            .get(format!("{}/v1/models", CONFIG.llm_host))
+            .timeout(std::time::Duration::from_secs(2))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    // Models the host offers, from the same endpoint as `health_check`
+    pub async fn models(&self) -> Result<Vec<String>> {
+        let list: ModelList = self.client
+            .get(format!("{}/v1/models", CONFIG.llm_host))
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(list.data.into_iter().map(|model| model.id).collect())
+    }
+
     pub async fn chat(&self, question: &str) -> Result<String> {
-        let request = OllamaRequest {
-            model: CONFIG.llm_model.clone(),
+        let response = self.send(&self.chat_request(question)).await?;
+
+        Ok(response.choices[0].message.content.trim().to_string())
+    }
+
+    // The answer is passed to `on_text` as it is written
+    pub async fn chat_stream(
+        &self,
+        question: &str,
+        on_text: &mut (dyn FnMut(&str) + Send),
+        interrupted: fn() -> bool,
+    ) -> Result<String> {
+        let answer = self.send_stream(&self.chat_request(question), on_text, interrupted).await?;
+        Ok(answer.trim().to_string())
+    }
+
+    fn chat_request(&self, question: &str) -> OllamaRequest {
+        OllamaRequest {
+            model: self.model(),
             messages: vec![
                 Message {
                     role: "system".to_string(),
-                    content: "You are a helpful command-line assistant. Provide clear, concise answers.".to_string(),
+                    content: in_user_language("You are a helpful command-line assistant. Provide clear, concise answers."),
                 },
                 Message {
                     role: "user".to_string(),
@@ -58,54 +375,84 @@ impl APIClient {
                 },
             ],
             stream: false,
-        };
+        }
+    }
 
-        let response = self.client
-            .post(format!("{}/v1/chat/completions", CONFIG.llm_host))
-            .json(&request)
-            .send()
-            .await?
-            .json::<OllamaResponse>()
-            .await?;
+    // `examples` are request/command pairs shown to the model as earlier turns
+    pub async fn translate_command(&self, natural_command: &str, context: &str, examples: &[(String, String)]) -> Result<String> {
+        let response = self.send(&self.translate_request(natural_command, context, examples)).await?;
 
-        Ok(response.choices[0].message.content.trim().to_string())
+        let command = response.choices[0].message.content.clone();
+        Ok(self.clean_command_output(&command))
+    }
+
+    // The command is passed to `on_text` as it is written, before cleanup
+    pub async fn translate_command_stream(
+        &self,
+        natural_command: &str,
+        context: &str,
+        examples: &[(String, String)],
+        on_text: &mut (dyn FnMut(&str) + Send),
+        interrupted: fn() -> bool,
+    ) -> Result<String> {
+        let request = self.translate_request(natural_command, context, examples);
+        let command = self.send_stream(&request, on_text, interrupted).await?;
+        Ok(self.clean_command_output(&command))
+    }
+
+    fn translate_request(&self, natural_command: &str, context: &str, examples: &[(String, String)]) -> OllamaRequest {
+        let mut messages = vec![Message {
+            role: "system".to_string(),
+            content: format!(
+                "You are a shell command translator. Convert natural language to shell commands. Respond ONLY with the exact command to execute, nothing else. No markdown, no explanations. {}\n\nEnvironment:\n{}",
+                untrusted::INSTRUCTION,
+                untrusted::wrap(context)
+            ),
+        }];
+        for (request, command) in examples {
+            messages.push(Message { role: "user".to_string(), content: request.clone() });
+            messages.push(Message { role: "assistant".to_string(), content: command.clone() });
+        }
+        messages.push(Message {
+            role: "user".to_string(),
+            content: natural_command.to_string(),
+        });
+        OllamaRequest {
+            model: self.model(),
+            messages,
+            stream: false,
+        }
     }
 
-    pub async fn translate_command(&self, natural_command: &str) -> Result<String> {
+    // A command written under a tool-specific system prompt
+    pub async fn generate_command(&self, template: &str, request: &str) -> Result<String> {
         let request = OllamaRequest {
-            model: CONFIG.llm_model.clone(),
+            model: self.model(),
             messages: vec![
                 Message {
                     role: "system".to_string(),
-                    content: "You are a shell command translator. Convert natural language to shell commands. Respond ONLY with the exact command to execute, nothing else. No markdown, no explanations.".to_string(),
+                    content: template.to_string(),
                 },
                 Message {
                     role: "user".to_string(),
-                    content: natural_command.to_string(),
+                    content: request.to_string(),
                 },
             ],
             stream: false,
         };
 
-        let response = self.client
-            .post(format!("{}/v1/chat/completions", CONFIG.llm_host))
-            .json(&request)
-            .send()
-            .await?
-            .json::<OllamaResponse>()
-            .await?;
+        let response = self.send(&request).await?;
 
-        let command = response.choices[0].message.content.clone();
-        Ok(self.clean_command_output(&command))
+        Ok(self.clean_command_output(&response.choices[0].message.content))
     }
 
     pub async fn get_command_explanation(&self, command: &str) -> Result<String> {
         let request = OllamaRequest {
-            model: CONFIG.llm_model.clone(),
+            model: self.model(),
             messages: vec![
                 Message {
                     role: "system".to_string(),
-                    content: "Explain what this shell command does in one brief sentence:".to_string(),
+                    content: in_user_language("Explain what this shell command does in one brief sentence:"),
                 },
                 Message {
                     role: "user".to_string(),
@@ -115,13 +462,7 @@ impl APIClient {
             stream: false,
         };
 
-        let response = self.client
-            .post(format!("{}/v1/chat/completions", CONFIG.llm_host))
-            .json(&request)
-            .send()
-            .await?
-            .json::<OllamaResponse>()
-            .await?;
+        let response = self.send(&request).await?;
 
         Ok(response.choices[0].message.content.trim().to_string())
     }
@@ -129,15 +470,21 @@ impl APIClient {
     pub async fn suggest_commands(&self, context: &str, command_prefix: Option<&str>) -> Result<Vec<String>> {
         let system_prompt = if let Some(prefix) = command_prefix {
             format!(
-                "Suggest 3 useful variations or related commands for '{}'. Provide only the commands, one per line, no explanations.",
-                prefix
+                "The user has typed the partial command line '{}'. Suggest {} complete commands that continue it, keeping the subcommand and arguments already typed and fitting the context. Provide only the commands, one per line, no explanations. {}",
+                prefix,
+                CONFIG.suggestion_count,
+                untrusted::INSTRUCTION
             )
         } else {
-            "Suggest 3 useful shell commands based on the current context. Provide only the commands, one per line, no explanations.".to_string()
+            format!(
+                "Suggest {} useful shell commands based on the current context. Provide only the commands, one per line, no explanations. {}",
+                CONFIG.suggestion_count,
+                untrusted::INSTRUCTION
+            )
         };
     
         let request = OllamaRequest {
-            model: CONFIG.llm_model.clone(),
+            model: self.model(),
             messages: vec![
                 Message {
                     role: "system".to_string(),
@@ -145,27 +492,182 @@ impl APIClient {
                 },
                 Message {
                     role: "user".to_string(),
-                    content: context.to_string(),
+                    content: untrusted::wrap(context),
                 },
             ],
             stream: false,
         };
     
-        let response = self.client
-            .post(format!("{}/v1/chat/completions", CONFIG.llm_host))
-            .json(&request)
-            .send()
-            .await?
-            .json::<OllamaResponse>()
-            .await?;
+        let response = self.send(&request).await?;
     
         Ok(response.choices[0].message.content
             .lines()
             .map(|s| self.clean_command_output(s))
             .filter(|s| !s.is_empty())
+            .take(CONFIG.suggestion_count)
             .collect())
     }
 
+    pub async fn summarize_context(&self, summary: Option<&str>, entries: &[String]) -> Result<String> {
+        let mut history = String::new();
+        if let Some(summary) = summary {
+            history.push_str(&format!("Summary so far: {}\n", summary));
+        }
+        history.push_str(&entries.join("\n"));
+
+        let request = OllamaRequest {
+            model: self.model(),
+            messages: vec![
+                Message {
+                    role: "system".to_string(),
+                    content: format!(
+                        "Compress this shell session history into a summary of at most five sentences: what the user is working on, facts learned and outcomes. Keep exact paths, hosts and command names. Respond ONLY with the summary. {}",
+                        untrusted::INSTRUCTION
+                    ),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: untrusted::wrap(&history),
+                },
+            ],
+            stream: false,
+        };
+
+        let response = self.send(&request).await?;
+
+        Ok(response.choices[0].message.content.trim().to_string())
+    }
+
+    // `chunk` is part of a script with its line numbers; `outline` the
+    // script's overall shape, so each part is explained in its place
+    pub async fn explain_script(&self, name: &str, outline: &str, chunk: &str) -> Result<String> {
+        let request = OllamaRequest {
+            model: self.model(),
+            messages: vec![
+                Message {
+                    role: "system".to_string(),
+                    content: in_user_language(&format!(
+                        "You explain shell scripts to someone about to run them. You are given the outline of the script {} and one part of it, each line prefixed with its number. Walk through the part in order with one short bullet per step, starting each bullet with the line or lines it covers, like `12-14:`. Say what the step does and why it matters, not how the syntax works, and point out anything surprising. Respond ONLY with the bullets. {}",
+                        name,
+                        untrusted::INSTRUCTION
+                    )),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: format!("Outline:\n{}\n\nPart:\n{}", outline, untrusted::wrap(chunk)),
+                },
+            ],
+            stream: false,
+        };
+
+        let response = self.send(&request).await?;
+
+        Ok(response.choices[0].message.content.trim().to_string())
+    }
+
+    // A complete script for the task; `request` may carry problems found
+    // in an earlier attempt
+    pub async fn write_script(&self, request: &str) -> Result<String> {
+        let request = OllamaRequest {
+            model: self.model(),
+            messages: vec![
+                Message {
+                    role: "system".to_string(),
+                    content: "You write bash scripts. Start with `#!/usr/bin/env bash` and `set -euo pipefail`, comment each step briefly, quote every expansion, and make the script pass shellcheck with no warnings. Take paths and limits the task leaves open as variables at the top. Respond ONLY with the script.".to_string(),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: request.to_string(),
+                },
+            ],
+            stream: false,
+        };
+
+        let response = self.send(&request).await?;
+
+        let content = &response.choices[0].message.content;
+        let script = SCRIPT_BLOCK_RE
+            .captures(content)
+            .and_then(|captures| captures.get(1))
+            .map(|script| script.as_str())
+            .unwrap_or(content);
+        Ok(format!("{}\n", script.trim()))
+    }
+
+    pub async fn write_regex(&self, request: &str) -> Result<String> {
+        let request = OllamaRequest {
+            model: self.model(),
+            messages: vec![
+                Message {
+                    role: "system".to_string(),
+                    content: "You write regular expressions that work in grep -E and sed -E: POSIX extended syntax with [0-9] instead of \\d, no lookarounds, no lazy quantifiers and no (?...) groups. Answer in exactly this form, with at least four examples of each kind, including near misses; examples are whole strings the pattern must match entirely or not at all:\nREGEX: <pattern>\nMATCH: <example>\nNO MATCH: <example>".to_string(),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: request.to_string(),
+                },
+            ],
+            stream: false,
+        };
+
+        let response = self.send(&request).await?;
+
+        Ok(response.choices[0].message.content.trim().to_string())
+    }
+
+    // One of the pager's actions on part of a command's output
+    pub async fn analyze_output(&self, task: &str, command: &str, output: &str) -> Result<String> {
+        let request = OllamaRequest {
+            model: self.model(),
+            messages: vec![
+                Message {
+                    role: "system".to_string(),
+                    content: in_user_language(&format!(
+                        "You help someone reading the output of the shell command `{}`. {} {}",
+                        command,
+                        task,
+                        untrusted::INSTRUCTION
+                    )),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: untrusted::wrap(output),
+                },
+            ],
+            stream: false,
+        };
+
+        let response = self.send(&request).await?;
+
+        Ok(response.choices[0].message.content.trim().to_string())
+    }
+
+    pub async fn suggest_undo(&self, command: &str) -> Result<Option<String>> {
+        let request = OllamaRequest {
+            model: self.model(),
+            messages: vec![
+                Message {
+                    role: "system".to_string(),
+                    content: "Give the single shell command that would reverse the effect of the following command. Respond ONLY with the command. If it cannot be reversed, respond with NONE.".to_string(),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: command.to_string(),
+                },
+            ],
+            stream: false,
+        };
+
+        let response = self.send(&request).await?;
+
+        let hint = self.clean_command_output(&response.choices[0].message.content);
+        if hint.is_empty() || hint.eq_ignore_ascii_case("none") {
+            Ok(None)
+        } else {
+            Ok(Some(hint))
+        }
+    }
+
     fn clean_command_output(&self, output: &str) -> String {
         // First try to extract command from code blocks
         if let Some(captures) = CODE_BLOCK_RE.captures(output) {
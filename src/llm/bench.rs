@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::time::{Duration, Instant};
+use crate::config::CONFIG;
+use super::LLMClient;
+
+// A fixed battery so results are comparable between models and hosts
+const TRANSLATIONS: &[&str] = &[
+    "list all files larger than 100MB in this directory",
+    "show the 5 processes using the most memory",
+    "count the lines in all rust files under src",
+];
+const SUGGESTIONS: &[(&str, Option<&str>)] = &[
+    ("Recent commands: git status, cargo build", None),
+    ("Recent commands: docker ps", Some("docker")),
+];
+const QUESTIONS: &[&str] = &[
+    "What is the difference between a hard link and a symbolic link?",
+    "How do I see which process is listening on a port?",
+];
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Translate,
+    Suggest,
+    Chat,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Translate => "translate",
+            Kind::Suggest => "suggest",
+            Kind::Chat => "chat",
+        }
+    }
+}
+
+struct Results {
+    latencies: Vec<Duration>,
+    failures: usize,
+    completion_tokens: u64,
+}
+
+const USAGE: &str = "usage: llm-shell bench [--runs N] [MODEL...]";
+
+// `llm-shell bench`: times each kind of request against the configured host,
+// for the configured model or each model given
+pub async fn run(args: &[String]) -> Result<()> {
+    let mut runs = 5;
+    let mut models = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--runs" | "-n" => {
+                runs = args
+                    .next()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|n| *n > 0)
+                    .context(USAGE)?;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            model => models.push(model.to_string()),
+        }
+    }
+    if models.is_empty() {
+        models.push(CONFIG.llm_model.clone());
+    }
+
+    let client = LLMClient::new();
+    client
+        .health_check()
+        .await
+        .with_context(|| format!("Cannot reach the LLM at {}", CONFIG.llm_host))?;
+
+    println!("Benchmarking {} ({} runs per request kind)", CONFIG.llm_host, runs);
+    println!(
+        "\n{}",
+        format!("{:<20} {:<10} {:>6} {:>8} {:>8} {:>8} {:>9}", "model", "request", "ok", "p50", "p90", "max", "tokens/s").bold()
+    );
+    for model in &models {
+        client.set_model(model);
+        for kind in [Kind::Translate, Kind::Suggest, Kind::Chat] {
+            let results = bench_kind(&client, kind, runs).await;
+            print_row(model, kind, runs, &results);
+        }
+    }
+    Ok(())
+}
+
+async fn bench_kind(client: &LLMClient, kind: Kind, runs: usize) -> Results {
+    let mut results = Results { latencies: Vec::new(), failures: 0, completion_tokens: 0 };
+    for i in 0..runs {
+        let before = client.usage();
+        let start = Instant::now();
+        let outcome = match kind {
+            Kind::Translate => client
+                .translate_command(TRANSLATIONS[i % TRANSLATIONS.len()], "Linux, bash", &[])
+                .await
+                .map(|_| ()),
+            Kind::Suggest => {
+                let (context, prefix) = SUGGESTIONS[i % SUGGESTIONS.len()];
+                client.suggest_commands(context, prefix).await.map(|_| ())
+            }
+            Kind::Chat => client.chat(QUESTIONS[i % QUESTIONS.len()]).await.map(|_| ()),
+        };
+        match outcome {
+            Ok(()) => {
+                results.latencies.push(start.elapsed());
+                results.completion_tokens += client.usage().completion_tokens - before.completion_tokens;
+            }
+            Err(_) => results.failures += 1,
+        }
+    }
+    results
+}
+
+fn print_row(model: &str, kind: Kind, runs: usize, results: &Results) {
+    let ms = |d: Option<Duration>| d.map(|d| format!("{}ms", d.as_millis())).unwrap_or_else(|| "-".to_string());
+    let total: Duration = results.latencies.iter().sum();
+    // Servers that don't report usage leave the rate unknown
+    let rate = if results.completion_tokens > 0 && !total.is_zero() {
+        format!("{:.1}", results.completion_tokens as f64 / total.as_secs_f64())
+    } else {
+        "-".to_string()
+    };
+    let ok = format!("{:>6}", format!("{}/{}", runs - results.failures, runs));
+    println!(
+        "{:<20} {:<10} {} {:>8} {:>8} {:>8} {:>9}",
+        model,
+        kind.name(),
+        if results.failures > 0 { ok.red() } else { ok.normal() },
+        ms(percentile(&results.latencies, 50)),
+        ms(percentile(&results.latencies, 90)),
+        ms(percentile(&results.latencies, 100)),
+        rate
+    );
+}
+
+// Nearest-rank percentile
+fn percentile(samples: &[Duration], pct: usize) -> Option<Duration> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<Duration> = [50, 10, 40, 20, 30].iter().map(|ms| Duration::from_millis(*ms)).collect();
+        assert_eq!(percentile(&samples, 50), Some(Duration::from_millis(30)));
+        assert_eq!(percentile(&samples, 90), Some(Duration::from_millis(50)));
+        assert_eq!(percentile(&samples, 0), Some(Duration::from_millis(10)));
+        assert_eq!(percentile(&[], 50), None);
+    }
+}
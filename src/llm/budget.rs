@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::fs;
+use std::path::PathBuf;
+use crate::config::CONFIG;
+use crate::system::scheduler;
+use crate::utils::path_utils;
+
+// A soft limit that warns and a hard limit that stops LLM requests
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub soft: Option<f64>,
+    pub hard: Option<f64>,
+}
+
+impl Limits {
+    // "SOFT[,HARD]" with optional k/M suffixes and `$`, e.g. "50k,100k" or "$1,$2.50"
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut values = spec.split(',').map(|value| {
+            let value = value.trim().trim_start_matches('$');
+            let (number, scale) = match value.char_indices().last() {
+                Some((i, 'k' | 'K')) => (&value[..i], 1e3),
+                Some((i, 'm' | 'M')) => (&value[..i], 1e6),
+                _ => (value, 1.0),
+            };
+            number
+                .parse::<f64>()
+                .map(|n| n * scale)
+                .with_context(|| format!("invalid budget '{}'", spec))
+        });
+        let soft = values.next().transpose()?;
+        let hard = values.next().transpose()?;
+        if values.next().is_some() {
+            return Err(anyhow::anyhow!("invalid budget '{}': expected SOFT[,HARD]", spec));
+        }
+        Ok(Limits { soft, hard })
+    }
+
+    fn is_set(&self) -> bool {
+        self.soft.is_some() || self.hard.is_some()
+    }
+}
+
+// Tokens spent today with the configured provider, kept in
+// $XDG_STATE_HOME/llm-shell/usage/<provider> as "DATE TOKENS"
+pub struct Ledger {
+    date: String,
+    pub tokens: u64,
+}
+
+impl Ledger {
+    fn path() -> Result<PathBuf> {
+        let host = CONFIG.llm_host.split("://").last().unwrap_or(&CONFIG.llm_host);
+        let provider: String = host
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .collect();
+        Ok(path_utils::state_dir()?.join("usage").join(provider))
+    }
+
+    fn today() -> String {
+        let tm = scheduler::local_time(scheduler::now());
+        format!("{:04}-{:02}-{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday)
+    }
+
+    // A ledger from an earlier day starts over at zero
+    pub fn load() -> Self {
+        let today = Self::today();
+        let tokens = Self::path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| {
+                let (date, tokens) = content.trim().split_once(' ')?;
+                if date == today { tokens.parse().ok() } else { None }
+            })
+            .unwrap_or(0);
+        Ledger { date: today, tokens }
+    }
+
+    fn save(&self) -> Result<()> {
+        path_utils::write_state(&Self::path()?, format!("{} {}\n", self.date, self.tokens))
+    }
+
+    pub fn dollars(&self) -> f64 {
+        self.tokens as f64 * CONFIG.token_price / 1e6
+    }
+
+    // The first limit of the given kind that today's spending has reached
+    fn reached(&self, pick: fn(&Limits) -> Option<f64>) -> Option<String> {
+        if let Some(limit) = pick(&CONFIG.token_budget).filter(|limit| self.tokens as f64 >= *limit) {
+            return Some(format!("{} of {} tokens", self.tokens, limit));
+        }
+        if CONFIG.token_price > 0.0 {
+            if let Some(limit) = pick(&CONFIG.dollar_budget).filter(|limit| self.dollars() >= *limit) {
+                return Some(format!("${:.2} of ${:.2}", self.dollars(), limit));
+            }
+        }
+        None
+    }
+
+    pub fn reset() -> Result<()> {
+        Ledger { date: Self::today(), tokens: 0 }.save()
+    }
+}
+
+fn budgets_set() -> bool {
+    CONFIG.token_budget.is_set() || (CONFIG.dollar_budget.is_set() && CONFIG.token_price > 0.0)
+}
+
+// Fails once today's hard limit is reached, so callers fall back to offline behavior
+pub fn check() -> Result<()> {
+    if !budgets_set() {
+        return Ok(());
+    }
+    match Ledger::load().reached(|limits| limits.hard) {
+        Some(spent) => Err(anyhow::anyhow!(
+            "daily LLM budget reached ({}); `usage reset` to continue",
+            spent
+        )),
+        None => Ok(()),
+    }
+}
+
+// Adds a request's tokens to today's total, warning when it crosses the soft limit
+pub fn record(tokens: u64) {
+    if !budgets_set() || tokens == 0 {
+        return;
+    }
+    let mut ledger = Ledger::load();
+    let was_over = ledger.reached(|limits| limits.soft).is_some();
+    ledger.tokens += tokens;
+    if let Err(e) = ledger.save() {
+        log::debug!("Failed to save LLM usage: {}", e);
+    }
+    if !was_over {
+        if let Some(spent) = ledger.reached(|limits| limits.soft) {
+            eprintln!("{}", format!("Warning: daily LLM budget soft limit reached ({})", spent).yellow());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(Limits::parse("50k,100k").unwrap(), Limits { soft: Some(50_000.0), hard: Some(100_000.0) });
+        assert_eq!(Limits::parse("$1.50").unwrap(), Limits { soft: Some(1.5), hard: None });
+        assert_eq!(Limits::parse("2M").unwrap().soft, Some(2_000_000.0));
+        assert!(Limits::parse("lots").is_err());
+        assert!(Limits::parse("1,2,3").is_err());
+    }
+}
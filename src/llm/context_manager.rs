@@ -1,36 +1,289 @@
+use anyhow::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use crate::config::CONFIG;
+use super::LLMClient;
+use super::memory::ProjectMemory;
+use super::untrusted;
+use colored::*;
+
+// Newest transcript entries that are never folded into the summary
+const KEEP_RECENT: usize = 6;
+// Chat answers are clipped before they go into the transcript
+const MAX_ANSWER_CHARS: usize = 400;
+
+// How much of the session is shared with the model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContextScope {
+    Full,
+    Directory,
+    None,
+}
+
+impl ContextScope {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "full" => Ok(ContextScope::Full),
+            "directory" | "dir" => Ok(ContextScope::Directory),
+            "none" => Ok(ContextScope::None),
+            _ => Err(anyhow::anyhow!("invalid context scope '{}' (use full, directory or none)", value)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContextScope::Full => "full",
+            ContextScope::Directory => "directory",
+            ContextScope::None => "none",
+        }
+    }
+}
+
+// Commands and chat turns of the session; older ones are compressed into
+// `summary` by the model once the transcript grows past the configured size
+#[derive(Default)]
+struct Transcript {
+    entries: Vec<String>,
+    summary: Option<String>,
+    summarizing: bool,
+    // Bumped by `clear` so a summary started before it is dropped
+    generation: u64,
+}
+
+impl Transcript {
+    fn size(&self) -> usize {
+        self.entries.iter().map(|entry| entry.len()).sum()
+    }
+
+    // The entries to summarize, leaving the newest in place
+    fn take_older(&mut self) -> Vec<String> {
+        let split = self.entries.len().saturating_sub(KEEP_RECENT);
+        self.entries.drain(..split).collect()
+    }
+}
+
+// The transcript as a saved session keeps it, so a restored session carries
+// on the same conversation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedContext {
+    pub entries: Vec<String>,
+    pub summary: Option<String>,
+}
+
+// Session content only ever reaches the model marked as data, but text that
+// addresses the model directly is worth pointing out
+fn warn_if_injection(text: &str) {
+    if let Some(reason) = untrusted::detect_injection(text) {
+        eprintln!(
+            "{}",
+            format!("Warning: content added to the LLM context {}; it is passed on only as data (`context clear` removes it)", reason).yellow()
+        );
+    }
+}
+
 #[derive(Clone)]
 pub struct ContextManager {
     current_dir: String,
     last_commands: Vec<String>,
+    system_info: Vec<(String, String)>,
+    pub scope: ContextScope,
+    // Shared so a background summary lands in the shell's copy
+    transcript: Arc<Mutex<Transcript>>,
+    // Memory of the project the current directory belongs to
+    memory: ProjectMemory,
 }
 
 impl ContextManager {
     pub fn new() -> Self {
+        let current_dir = std::env::current_dir().unwrap_or_default();
         ContextManager {
-            current_dir: std::env::current_dir()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
+            memory: ProjectMemory::load(&ProjectMemory::project_root(&current_dir)),
+            current_dir: current_dir.to_string_lossy().to_string(),
             last_commands: Vec::new(),
+            system_info: Vec::new(),
+            scope: ContextScope::Full,
+            transcript: Arc::new(Mutex::new(Transcript::default())),
         }
     }
 
     pub fn get_context(&self) -> String {
-        format!(
-            "Current directory: {}. Last commands: {}",
-            self.current_dir,
-            self.last_commands.join(", ")
-        )
+        match self.scope {
+            ContextScope::None => return String::new(),
+            ContextScope::Directory => {
+                let mut context = format!("Current directory: {}", self.current_dir);
+                if let Some(memory) = self.memory.describe() {
+                    context.push_str(&format!(". {}", memory));
+                }
+                return context;
+            }
+            ContextScope::Full => {}
+        }
+
+        let mut context = format!("Current directory: {}", self.current_dir);
+        if let Some(memory) = self.memory.describe() {
+            context.push_str(&format!(". {}", memory));
+        }
+        let transcript = self.transcript.lock().unwrap();
+        if let Some(summary) = &transcript.summary {
+            context.push_str(&format!(". Earlier in this session: {}", summary));
+        }
+        if !transcript.entries.is_empty() {
+            context.push_str(&format!(". Recent activity: {}", transcript.entries.join(" | ")));
+        }
+        for (_, info) in &self.system_info {
+            context.push_str(&format!(". {}", info));
+        }
+        context
+    }
+
+    pub fn set_system_info(&mut self, key: &str, info: &str) {
+        match self.system_info.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = info.to_string(),
+            None => self.system_info.push((key.to_string(), info.to_string())),
+        }
     }
 
+    pub fn remove_system_info(&mut self, key: &str) {
+        self.system_info.retain(|(k, _)| k != key);
+    }
+
+    // Entering another project swaps in its memory
     pub fn update_directory(&mut self, new_dir: &str) {
+        if new_dir != self.current_dir {
+            let root = ProjectMemory::project_root(std::path::Path::new(new_dir));
+            if root != self.memory.root {
+                self.memory = ProjectMemory::load(&root);
+            }
+        }
         self.current_dir = new_dir.to_string();
     }
 
+    pub fn memory(&self) -> &ProjectMemory {
+        &self.memory
+    }
+
+    // Applies a change to the project memory and saves it
+    pub fn update_memory<T>(&mut self, update: impl FnOnce(&mut ProjectMemory) -> T) -> Result<T> {
+        let result = update(&mut self.memory);
+        self.memory.save()?;
+        Ok(result)
+    }
+
     pub fn add_command(&mut self, command: &str) {
+        warn_if_injection(command);
+        self.transcript.lock().unwrap().entries.push(format!("$ {}", command));
         self.last_commands.push(command.to_string());
         if self.last_commands.len() > 5 {
             self.last_commands.remove(0);
         }
     }
-}
\ No newline at end of file
+
+    pub fn add_chat_turn(&mut self, question: &str, answer: &str) {
+        let answer: String = answer.chars().take(MAX_ANSWER_CHARS).collect();
+        warn_if_injection(&answer);
+        self.transcript.lock().unwrap().entries.push(format!("Q: {} A: {}", question, answer));
+    }
+
+    // Once the transcript outgrows LLM_SHELL_CONTEXT_LIMIT, has the model
+    // compress everything but the newest entries in the background. If that
+    // fails the entries go back, to be tried again later.
+    pub fn summarize_if_needed(&self, llm_client: &LLMClient) {
+        let limit = match CONFIG.context_limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        let (older, summary, generation) = {
+            let mut transcript = self.transcript.lock().unwrap();
+            if transcript.summarizing || transcript.size() <= limit || transcript.entries.len() <= KEEP_RECENT {
+                return;
+            }
+            transcript.summarizing = true;
+            (transcript.take_older(), transcript.summary.clone(), transcript.generation)
+        };
+
+        let llm_client = llm_client.clone();
+        let transcript = Arc::clone(&self.transcript);
+        tokio::spawn(async move {
+            let result = llm_client.summarize_context(summary.as_deref(), &older).await;
+            let mut transcript = transcript.lock().unwrap();
+            if transcript.generation != generation {
+                return;
+            }
+            match result {
+                Ok(summary) => transcript.summary = Some(summary),
+                Err(e) => {
+                    debug!("Context summary failed: {}", e);
+                    transcript.entries.splice(..0, older);
+                }
+            }
+            transcript.summarizing = false;
+        });
+    }
+
+    // Drops everything the session has shared with the model: commands, chat
+    // turns and their summary. Host details and project memory stay.
+    pub fn clear(&mut self) {
+        self.last_commands.clear();
+        let mut transcript = self.transcript.lock().unwrap();
+        transcript.entries.clear();
+        transcript.summary = None;
+        transcript.summarizing = false;
+        transcript.generation += 1;
+    }
+
+    pub fn saved(&self) -> SavedContext {
+        let transcript = self.transcript.lock().unwrap();
+        SavedContext { entries: transcript.entries.clone(), summary: transcript.summary.clone() }
+    }
+
+    // Replaces the transcript with a saved session's
+    pub fn restore(&mut self, saved: SavedContext, recent_commands: &[String]) {
+        self.clear();
+        self.last_commands = recent_commands.to_vec();
+        let mut transcript = self.transcript.lock().unwrap();
+        transcript.entries = saved.entries;
+        transcript.summary = saved.summary;
+    }
+
+    pub fn recent_commands(&self) -> &[String] {
+        &self.last_commands
+    }
+
+    pub fn last_command(&self) -> Option<&str> {
+        self.last_commands.last().map(|s| s.as_str())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_summary() {
+        let mut context = ContextManager::new();
+        context.update_directory("/src");
+        for i in 0..8 {
+            context.add_command(&format!("make test{}", i));
+        }
+        let older = context.transcript.lock().unwrap().take_older();
+        assert_eq!(older, vec!["$ make test0", "$ make test1"]);
+
+        context.transcript.lock().unwrap().summary = Some("Running the test suite".to_string());
+        let prompt = context.get_context();
+        assert!(prompt.starts_with("Current directory: /src. Earlier in this session: Running the test suite. Recent activity: $ make test2"));
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut context = ContextManager::new();
+        context.add_command("ls");
+        context.add_chat_turn("what is here?", "a Rust project");
+        let saved = context.saved();
+
+        let mut restored = ContextManager::new();
+        restored.add_command("pwd");
+        restored.restore(saved, &["ls".to_string()]);
+        assert_eq!(restored.saved().entries, vec!["$ ls", "Q: what is here? A: a Rust project"]);
+        assert_eq!(restored.last_command(), Some("ls"));
+    }
+}
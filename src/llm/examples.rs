@@ -0,0 +1,119 @@
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use std::fs;
+
+// Curated translations for tools small models often get wrong. Users add
+// their own as `REQUEST => COMMAND` lines in ~/.llm_shell_examples.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("find files larger than 100MB", "find . -type f -size +100M"),
+    ("find files modified in the last 2 days", "find . -type f -mtime -2"),
+    ("delete all .tmp files recursively", "find . -type f -name '*.tmp' -delete"),
+    ("find empty directories", "find . -type d -empty"),
+    ("compress the src directory into a tar.gz", "tar -czf src.tar.gz src"),
+    ("extract archive.tar.gz into /tmp", "tar -xzf archive.tar.gz -C /tmp"),
+    ("list the contents of backup.tar", "tar -tf backup.tar"),
+    ("convert video.mov to mp4", "ffmpeg -i video.mov -c:v libx264 -c:a aac video.mp4"),
+    ("extract the audio from video.mp4 as mp3", "ffmpeg -i video.mp4 -vn -q:a 2 audio.mp3"),
+    ("cut the first 30 seconds of input.mp4", "ffmpeg -i input.mp4 -t 30 -c copy clip.mp4"),
+    ("scale video.mp4 to 720p", "ffmpeg -i video.mp4 -vf scale=-2:720 video_720p.mp4"),
+    ("undo the last commit but keep the changes", "git reset --soft HEAD~1"),
+    ("show what changed in the last commit", "git show --stat HEAD"),
+    ("delete local branches that were merged", "git branch --merged | grep -v '^\\*' | xargs -r git branch -d"),
+    ("discard changes to file.txt", "git restore file.txt"),
+    ("show the commit history as a graph", "git log --oneline --graph --all"),
+];
+
+// Words that would match nearly every example
+const STOP_WORDS: &[&str] = &["the", "and", "all", "for", "from", "with", "into", "that", "this"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Example {
+    pub request: String,
+    pub command: String,
+}
+
+impl Example {
+    fn tool(&self) -> &str {
+        self.command.split_whitespace().next().unwrap_or("")
+    }
+}
+
+fn defaults() -> Vec<Example> {
+    DEFAULTS
+        .iter()
+        .map(|(request, command)| Example { request: request.to_string(), command: command.to_string() })
+        .collect()
+}
+
+// The shipped examples followed by the user's
+pub fn library() -> Vec<Example> {
+    let mut examples = defaults();
+    if let Some(content) = dirs::home_dir().and_then(|home| fs::read_to_string(home.join(".llm_shell_examples")).ok()) {
+        examples.extend(parse(&content));
+    }
+    examples
+}
+
+// `REQUEST => COMMAND` lines; blank lines and `#` comments are skipped
+fn parse(content: &str) -> Vec<Example> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(" => "))
+        .map(|(request, command)| Example { request: request.trim().to_string(), command: command.trim().to_string() })
+        .collect()
+}
+
+// Lowercase words, with file names split at dots ("logs.tar.gz" is logs, tar, gz)
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Up to `count` examples most like the request: each of its words is fuzzy
+// matched against the example's words, and naming its tool counts double
+pub fn relevant(examples: &[Example], request: &str, count: usize) -> Vec<Example> {
+    let matcher = SkimMatcherV2::default();
+    let wanted: Vec<String> = words(request)
+        .into_iter()
+        .filter(|word| word.len() >= 2 && !STOP_WORDS.contains(&word.as_str()))
+        .collect();
+    let mut scored: Vec<(i64, &Example)> = examples
+        .iter()
+        .map(|example| {
+            let known = words(&format!("{} {}", example.request, example.command));
+            let mut score: i64 = wanted
+                .iter()
+                .filter_map(|word| known.iter().filter_map(|known| matcher.fuzzy_match(known, word)).max())
+                .sum();
+            if wanted.iter().any(|word| word == example.tool()) {
+                score *= 2;
+            }
+            (score, example)
+        })
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().take(count).map(|(_, example)| example.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relevant_examples() {
+        let mut examples = defaults();
+        examples.extend(parse("# mine\nship it => make release\n\nnot an example\n"));
+        assert_eq!(examples.last().unwrap().command, "make release");
+
+        let picked = relevant(&examples, "extract the tar archive logs.tar.gz", 2);
+        assert_eq!(picked.len(), 2);
+        assert!(picked.iter().all(|example| example.command.starts_with("tar")));
+        assert_eq!(relevant(&examples, "convert clip.mov to mp4 with ffmpeg", 1)[0].command, "ffmpeg -i video.mov -c:v libx264 -c:a aac video.mp4");
+        assert!(relevant(&examples, "zz", 3).is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+use crate::utils::path_utils;
+
+// Ratings kept; older ones are dropped
+const MAX_RATINGS: usize = 200;
+
+// A translated command rated with `good` or `bad [NOTE]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rating {
+    pub request: String,
+    pub command: String,
+    pub good: bool,
+    pub note: String,
+}
+
+impl Rating {
+    // "good|bad<TAB>REQUEST<TAB>COMMAND<TAB>NOTE"
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, '\t');
+        let good = match fields.next()? {
+            "good" => true,
+            "bad" => false,
+            _ => return None,
+        };
+        Some(Rating {
+            request: fields.next()?.to_string(),
+            command: fields.next()?.to_string(),
+            good,
+            note: fields.next().unwrap_or("").to_string(),
+        })
+    }
+
+    fn format(&self) -> String {
+        let clean = |text: &str| text.replace(['\t', '\n'], " ");
+        format!(
+            "{}\t{}\t{}\t{}",
+            if self.good { "good" } else { "bad" },
+            clean(&self.request),
+            clean(&self.command),
+            clean(&self.note)
+        )
+    }
+}
+
+// Stored in $XDG_STATE_HOME/llm-shell/feedback, oldest first
+fn path() -> Result<PathBuf> {
+    Ok(path_utils::state_dir()?.join("feedback"))
+}
+
+pub fn load() -> Vec<Rating> {
+    path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|content| content.lines().filter_map(Rating::parse).collect())
+        .unwrap_or_default()
+}
+
+// A newer rating of the same translation replaces the older one
+pub fn record(rating: Rating) -> Result<()> {
+    let mut ratings = load();
+    ratings.retain(|known| known.request != rating.request || known.command != rating.command);
+    ratings.push(rating);
+    if ratings.len() > MAX_RATINGS {
+        ratings.drain(..ratings.len() - MAX_RATINGS);
+    }
+    let content: String = ratings.iter().map(|rating| rating.format() + "\n").collect();
+    path_utils::write_state(&path()?, content)
+}
+
+// The latest `count` ratings, for the translation prompt
+pub fn recent(count: usize) -> Vec<Rating> {
+    let ratings = load();
+    ratings[ratings.len().saturating_sub(count)..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rating_lines() {
+        let rating = Rating {
+            request: "list big files".to_string(),
+            command: "find . -size\t+100M".to_string(),
+            good: false,
+            note: "wrong flag".to_string(),
+        };
+        let parsed = Rating::parse(&rating.format()).unwrap();
+        assert_eq!(parsed.command, "find . -size +100M");
+        assert_eq!(parsed.note, "wrong flag");
+        assert!(!parsed.good);
+        assert_eq!(Rating::parse("good\tls\tls -la").unwrap().note, "");
+        assert_eq!(Rating::parse("meh\ta\tb\tc"), None);
+    }
+}
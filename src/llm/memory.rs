@@ -0,0 +1,143 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::utils::path_utils;
+
+// Translations kept per project; older ones are dropped
+const MAX_TRANSLATIONS: usize = 20;
+
+// What the model should know about a project across sessions: facts the
+// user asked to remember and translations that ran successfully. Stored in
+// $XDG_STATE_HOME/llm-shell/memory/, one file per project root.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectMemory {
+    pub root: PathBuf,
+    pub facts: Vec<String>,
+    pub translations: Vec<(String, String)>,
+}
+
+impl ProjectMemory {
+    // The enclosing git work tree, or the directory itself
+    pub fn project_root(dir: &Path) -> PathBuf {
+        dir.ancestors()
+            .find(|ancestor| ancestor.join(".git").exists())
+            .unwrap_or(dir)
+            .to_path_buf()
+    }
+
+    fn file(root: &Path) -> Result<PathBuf> {
+        let name: String = root
+            .to_string_lossy()
+            .chars()
+            .map(|c| if c == '/' { '%' } else { c })
+            .collect();
+        Ok(path_utils::state_dir()?.join("memory").join(name))
+    }
+
+    pub fn load(root: &Path) -> Self {
+        let content = Self::file(root)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        let mut memory = Self::parse(&content);
+        memory.root = root.to_path_buf();
+        memory
+    }
+
+    // "fact: TEXT" and "translation: REQUEST => COMMAND" lines
+    fn parse(content: &str) -> Self {
+        let mut memory = ProjectMemory::default();
+        for line in content.lines() {
+            if let Some(fact) = line.strip_prefix("fact: ") {
+                memory.facts.push(fact.to_string());
+            } else if let Some((request, command)) = line.strip_prefix("translation: ").and_then(|t| t.split_once(" => ")) {
+                memory.translations.push((request.to_string(), command.to_string()));
+            }
+        }
+        memory
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::file(&self.root)?;
+        if self.facts.is_empty() && self.translations.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+        let mut content = String::new();
+        for fact in &self.facts {
+            content.push_str(&format!("fact: {}\n", fact));
+        }
+        for (request, command) in &self.translations {
+            content.push_str(&format!("translation: {} => {}\n", request, command));
+        }
+        path_utils::write_state(&path, content)
+    }
+
+    pub fn add_fact(&mut self, fact: &str) {
+        if !self.facts.iter().any(|known| known == fact) {
+            self.facts.push(fact.to_string());
+        }
+    }
+
+    // Removes facts by 1-based number or containing the text; returns how many went
+    pub fn forget(&mut self, what: &str) -> usize {
+        let before = self.facts.len();
+        match what.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= self.facts.len() => {
+                self.facts.remove(n - 1);
+            }
+            _ => self.facts.retain(|fact| !fact.contains(what)),
+        }
+        before - self.facts.len()
+    }
+
+    pub fn add_translation(&mut self, request: &str, command: &str) {
+        // Requests and commands are single lines in the file
+        if request.contains('\n') || command.contains('\n') || command.contains(" => ") {
+            return;
+        }
+        self.translations.retain(|(known, _)| known != request);
+        self.translations.push((request.to_string(), command.to_string()));
+        if self.translations.len() > MAX_TRANSLATIONS {
+            self.translations.remove(0);
+        }
+    }
+
+    // The memory as a sentence for the model's context
+    pub fn describe(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if !self.facts.is_empty() {
+            parts.push(format!("Project notes: {}", self.facts.join("; ")));
+        }
+        if !self.translations.is_empty() {
+            let examples: Vec<String> = self
+                .translations
+                .iter()
+                .map(|(request, command)| format!("'{}' -> {}", request, command))
+                .collect();
+            parts.push(format!("Previously used here: {}", examples.join("; ")));
+        }
+        if parts.is_empty() { None } else { Some(parts.join(". ")) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory() {
+        let mut memory = ProjectMemory::parse("fact: deploys go through make release\ntranslation: run the tests => cargo test\nbogus\n");
+        assert_eq!(memory.facts, vec!["deploys go through make release"]);
+        assert_eq!(memory.translations, vec![("run the tests".to_string(), "cargo test".to_string())]);
+
+        memory.add_fact("staging is at stage.internal");
+        memory.add_translation("run the tests", "cargo test --all");
+        assert_eq!(memory.translations.len(), 1);
+        assert_eq!(memory.forget("2"), 1);
+        assert_eq!(memory.forget("deploys"), 1);
+        assert_eq!(memory.describe(), Some("Previously used here: 'run the tests' -> cargo test --all".to_string()));
+    }
+}
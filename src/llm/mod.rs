@@ -1,8 +1,16 @@
 mod api_client;
+pub mod bench;
+pub mod budget;
 pub mod context_manager;
+pub mod examples;
+pub mod feedback;
+pub mod memory;
+pub mod untrusted;
 
 use anyhow::Result;
 
+pub use self::api_client::Cancelled;
+
 #[derive(Clone)]
 pub struct LLMClient {
     pub(crate) api_client: api_client::APIClient,
@@ -17,8 +25,23 @@ impl LLMClient {
         }
     }
 
-    pub async fn translate_command(&self, natural_command: &str) -> Result<String> {
-        self.api_client.translate_command(natural_command).await
+    pub async fn translate_command(&self, natural_command: &str, context: &str, examples: &[(String, String)]) -> Result<String> {
+        self.api_client.translate_command(natural_command, context, examples).await
+    }
+
+    pub async fn translate_command_stream(
+        &self,
+        natural_command: &str,
+        context: &str,
+        examples: &[(String, String)],
+        on_text: &mut (dyn FnMut(&str) + Send),
+        interrupted: fn() -> bool,
+    ) -> Result<String> {
+        self.api_client.translate_command_stream(natural_command, context, examples, on_text, interrupted).await
+    }
+
+    pub async fn generate_command(&self, template: &str, request: &str) -> Result<String> {
+        self.api_client.generate_command(template, request).await
     }
 
     pub async fn get_command_explanation(&self, command: &str) -> Result<String> {
@@ -29,7 +52,93 @@ impl LLMClient {
         self.api_client.suggest_commands(context, command_prefix).await
     }
 
+    pub async fn explain_script(&self, name: &str, outline: &str, chunk: &str) -> Result<String> {
+        self.api_client.explain_script(name, outline, chunk).await
+    }
+
+    pub async fn write_script(&self, request: &str) -> Result<String> {
+        self.api_client.write_script(request).await
+    }
+
+    pub async fn write_regex(&self, request: &str) -> Result<String> {
+        self.api_client.write_regex(request).await
+    }
+
+    pub async fn analyze_output(&self, task: &str, command: &str, output: &str) -> Result<String> {
+        self.api_client.analyze_output(task, command, output).await
+    }
+
+    pub async fn suggest_undo(&self, command: &str) -> Result<Option<String>> {
+        self.api_client.suggest_undo(command).await
+    }
+
+    pub fn usage(&self) -> api_client::Usage {
+        self.api_client.usage()
+    }
+
+    pub fn in_flight(&self) -> std::sync::Arc<std::sync::atomic::AtomicUsize> {
+        self.api_client.in_flight()
+    }
+
+    pub fn model(&self) -> String {
+        self.api_client.model()
+    }
+
+    pub fn set_model(&self, model: &str) {
+        self.api_client.set_model(model)
+    }
+
+    // Shared by every clone, so the prefetcher and Ctrl+G stop too
+    pub fn set_enabled(&self, enabled: bool) {
+        self.api_client.set_enabled(enabled)
+    }
+
+    pub async fn health_check(&self) -> Result<()> {
+        self.api_client.health_check().await
+    }
+
+    pub async fn models(&self) -> Result<Vec<String>> {
+        self.api_client.models().await
+    }
+
+    pub async fn summarize_context(&self, summary: Option<&str>, entries: &[String]) -> Result<String> {
+        self.api_client.summarize_context(summary, entries).await
+    }
+
     pub async fn chat(&self, question: &str) -> Result<String> {
         self.api_client.chat(question).await
     }
-}
\ No newline at end of file
+
+    pub async fn chat_stream(&self, question: &str, on_text: &mut (dyn FnMut(&str) + Send), interrupted: fn() -> bool) -> Result<String> {
+        self.api_client.chat_stream(question, on_text, interrupted).await
+    }
+}
+// Asks the model and checks its answer. An invalid answer is sent back once,
+// in the prompt `retry` writes from it and what was wrong with it; when that
+// one is invalid too, the last answer and its problem are given back.
+pub async fn ask_checked<T, E, F>(
+    prompt: String,
+    mut ask: impl FnMut(String) -> F,
+    mut check: impl FnMut(&str) -> std::result::Result<T, E>,
+    retry: impl Fn(&str, &E) -> String,
+) -> Result<std::result::Result<T, (String, E)>>
+where
+    F: std::future::Future<Output = Result<String>>,
+{
+    let mut prompt = prompt;
+    let mut attempt = 1;
+    loop {
+        let answer = ask(prompt).await?;
+        match check(&answer) {
+            Ok(value) => return Ok(Ok(value)),
+            Err(problem) if attempt == 2 => return Ok(Err((answer, problem))),
+            Err(problem) => prompt = retry(&answer, &problem),
+        }
+        attempt += 1;
+    }
+}
+
+// The request again, with what was wrong with the previous answer to it
+pub fn correction(request: &str, answer: &str, problem: impl std::fmt::Display) -> String {
+    format!("{}\n\nYour previous answer `{}` was invalid: {}. Correct it.", request, answer.trim(), problem)
+}
@@ -5,13 +5,14 @@ mod system;
 mod utils;
 mod config;
 
-use crate::shell::Shell;
+use crate::shell::{rescue, Shell};
 use anyhow::Result;
 use std::env;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    crate::utils::crash::init_logging();
+    crate::utils::crash::install_hook();
     dotenv::dotenv().ok();
     
     // Handle installation if --install flag is present
@@ -23,8 +24,39 @@ async fn main() -> Result<()> {
         return Ok(());
     }
     
-    let mut shell = Shell::new();
-    shell.run().await?;
+    // Latency report for the configured LLM, to help pick a model or host
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("bench") {
+        return crate::llm::bench::run(&args[2..]).await;
+    }
+    
+    // Checks the setup and prints fixes, the first stop for bug reports
+    if args.get(1).map(String::as_str) == Some("doctor") {
+        let status = crate::system::doctor::run(&args[2..]).await?;
+        std::process::exit(status);
+    }
+    
+    // Background instance that runs jobs added with `schedule` and `in`
+    if env::args().any(|arg| arg == "--scheduler") {
+        return crate::system::scheduler::Scheduler::run_daemon();
+    }
     
-    Ok(())
+    let started = std::panic::catch_unwind(|| -> Result<Shell> {
+        let mut shell = Shell::new();
+        shell.initialize()?;
+        Ok(shell)
+    });
+    // A login shell that can't start would lock its user out, so it falls
+    // back to a rescue shell instead of exiting
+    let mut shell = match started {
+        Ok(Ok(shell)) => shell,
+        Ok(Err(e)) if crate::shell::is_login_shell() => std::process::exit(rescue::run(&format!("{:#}", e)).await),
+        Err(_) if crate::shell::is_login_shell() => std::process::exit(rescue::run("it crashed while starting").await),
+        Ok(Err(e)) => return Err(e),
+        Err(panic) => std::panic::resume_unwind(panic),
+    };
+    let status = shell.run().await?;
+    // Dropped first so history is saved before the process exits
+    drop(shell);
+    std::process::exit(status);
 }
\ No newline at end of file
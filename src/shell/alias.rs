@@ -1,7 +1,14 @@
 use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
 use std::collections::HashMap;
 use std::fs;
 
+lazy_static! {
+    // $1-$9, $@, $* and $#, optionally in braces
+    static ref POSITIONAL_RE: Regex = Regex::new(r"\$(?:[1-9@*#]|\{[1-9@*#]\})").unwrap();
+}
+
 pub struct AliasManager {
     aliases: HashMap<String, String>,
 }
@@ -85,6 +92,10 @@ impl AliasManager {
         }
         
         if let Some(alias) = self.aliases.get(parts[0]) {
+            // Aliases that use their arguments work like functions
+            if POSITIONAL_RE.is_match(alias) {
+                return expand_positional(alias, command.trim_start()[parts[0].len()..].trim());
+            }
             if parts.len() > 1 {
                 format!("{} {}", alias, parts[1..].join(" "))
             } else {
@@ -101,6 +112,23 @@ impl AliasManager {
         Ok(())
     }
     
+    // Several at once, saved together
+    pub fn add_aliases(&mut self, aliases: &[(String, String)]) -> Result<()> {
+        for (name, value) in aliases {
+            self.aliases.insert(name.clone(), value.clone());
+        }
+        self.save_aliases()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.aliases.get(name).map(String::as_str)
+    }
+
+    // Whether an alias uses its arguments like a function
+    pub fn takes_arguments(&self, name: &str) -> bool {
+        self.get(name).map(|value| POSITIONAL_RE.is_match(value)).unwrap_or(false)
+    }
+    
     pub fn remove_alias(&mut self, name: &str) -> Result<()> {
         self.aliases.remove(name);
         self.save_aliases()?;
@@ -128,4 +156,118 @@ impl AliasManager {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// The arguments end at the first operator; whatever follows is kept as is
+fn expand_positional(alias: &str, rest: &str) -> String {
+    let end = arguments_end(rest);
+    let args = shellwords::split(&rest[..end]).unwrap_or_else(|_| rest[..end].split_whitespace().map(String::from).collect());
+    let expanded = substitute(alias, &args);
+    let tail = rest[end..].trim();
+    if tail.is_empty() {
+        expanded
+    } else {
+        format!("{} {}", expanded, tail)
+    }
+}
+
+fn arguments_end(rest: &str) -> usize {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match quote {
+            _ if escaped => escaped = false,
+            Some(q) if c == q => quote = None,
+            Some('"') if c == '\\' => escaped = true,
+            Some(_) => {}
+            None => match c {
+                '\\' => escaped = true,
+                '\'' | '"' => quote = Some(c),
+                '|' | ';' | '&' | '<' | '>' => return i,
+                _ => {}
+            },
+        }
+    }
+    rest.len()
+}
+
+// Replaces positional parameters with the arguments, quoted to survive
+// parsing; like bash, nothing is replaced inside single quotes
+fn substitute(alias: &str, args: &[String]) -> String {
+    let mut result = String::new();
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut chars = alias.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if !in_single => {
+                result.push(c);
+                if let Some((_, next)) = chars.next() {
+                    result.push(next);
+                }
+                continue;
+            }
+            '$' if !in_single => {
+                if let Some(found) = POSITIONAL_RE.find(&alias[i..]).filter(|found| found.start() == 0) {
+                    let name = found.as_str().trim_matches(|c| c == '$' || c == '{' || c == '}');
+                    result.push_str(&replacement(name, args, in_double));
+                    while chars.peek().map(|(j, _)| *j < i + found.end()).unwrap_or(false) {
+                        chars.next();
+                    }
+                    continue;
+                }
+            }
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            _ => {}
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn replacement(name: &str, args: &[String], in_double: bool) -> String {
+    let values: Vec<&String> = match name {
+        "#" => return args.len().to_string(),
+        "@" | "*" => args.iter().collect(),
+        n => args.get(n.parse::<usize>().unwrap_or(1) - 1).into_iter().collect(),
+    };
+    if !in_double {
+        return values.iter().map(|value| quote(value)).collect::<Vec<_>>().join(" ");
+    }
+    let escaped: Vec<String> = values
+        .iter()
+        .map(|value| value.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "\\$").replace('`', "\\`"))
+        .collect();
+    // "$@" keeps each argument a separate word
+    escaped.join(if name == "@" { "\" \"" } else { " " })
+}
+
+fn quote(value: &str) -> String {
+    if value.chars().all(|c| c.is_alphanumeric() || "/._-+,:@=".contains(c)) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_arguments() {
+        let mut manager = AliasManager::new();
+        manager.aliases.insert("gco".to_string(), "git checkout $1".to_string());
+        manager.aliases.insert("mkcd".to_string(), "mkdir -p \"$1\" && cd \"$1\"".to_string());
+        manager.aliases.insert("each".to_string(), "for f in \"$@\"; do echo $f; done; echo '$1' $#".to_string());
+        manager.aliases.insert("gs".to_string(), "git status".to_string());
+
+        assert_eq!(manager.expand("gco main"), "git checkout main");
+        assert_eq!(manager.expand("gco 'my branch' | cat"), "git checkout 'my branch' | cat");
+        assert_eq!(manager.expand("gco"), "git checkout ");
+        assert_eq!(manager.expand("mkcd \"new dir\""), "mkdir -p \"new dir\" && cd \"new dir\"");
+        assert_eq!(manager.expand("each a \"b c\""), "for f in \"a\" \"b c\"; do echo $f; done; echo '$1' 2");
+        assert_eq!(manager.expand("gs -s"), "git status -s");
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::Result;
+
+// Integer arithmetic for `$((...))`: + - * / %, comparisons, ! && || and
+// parentheses. Variables are named with or without `$` and hold a number;
+// `lookup` gives their value, and an unset one counts as 0 unless `nounset` is on.
+pub fn evaluate(expression: &str, lookup: &dyn Fn(&str) -> Option<String>, nounset: bool) -> Result<i64> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0, lookup, nounset };
+    let value = parser.or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(token) => Err(anyhow::anyhow!("{}: syntax error near '{}'", expression.trim(), token.text())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match self {
+            Token::Number(n) => n.to_string(),
+            Token::Name(name) => name.clone(),
+            Token::Op(op) => op.to_string(),
+        }
+    }
+}
+
+// Longest first, so `<=` isn't read as `<` then `=`
+const OPERATORS: &[&str] = &["&&", "||", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "!", "(", ")"];
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = expression;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_digit() {
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            let number = rest[..end].parse().map_err(|_| anyhow::anyhow!("{}: invalid number", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c == '$' || c == '_' || c.is_ascii_alphabetic() {
+            let name = rest.trim_start_matches('$');
+            let (name, after) = match name.strip_prefix('{') {
+                Some(braced) => {
+                    let end = braced.find('}').ok_or_else(|| anyhow::anyhow!("{}: missing '}}'", expression.trim()))?;
+                    (&braced[..end], &braced[end + 1..])
+                }
+                None => {
+                    let end = name.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(name.len());
+                    (&name[..end], &name[end..])
+                }
+            };
+            if name.is_empty() {
+                return Err(anyhow::anyhow!("{}: syntax error near '$'", expression.trim()));
+            }
+            tokens.push(Token::Name(name.to_string()));
+            rest = after;
+        } else {
+            let op = OPERATORS.iter().find(|op| rest.starts_with(**op))
+                .ok_or_else(|| anyhow::anyhow!("{}: syntax error near '{}'", expression.trim(), c))?;
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    lookup: &'a dyn Fn(&str) -> Option<String>,
+    nounset: bool,
+}
+
+impl Parser<'_> {
+    // The operator at the current position, if it is one of `ops`
+    fn take(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn or(&mut self) -> Result<i64> {
+        let mut value = self.and()?;
+        while self.take(&["||"]).is_some() {
+            let right = self.and()?;
+            value = (value != 0 || right != 0) as i64;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<i64> {
+        let mut value = self.comparison()?;
+        while self.take(&["&&"]).is_some() {
+            let right = self.comparison()?;
+            value = (value != 0 && right != 0) as i64;
+        }
+        Ok(value)
+    }
+
+    fn comparison(&mut self) -> Result<i64> {
+        let mut value = self.sum()?;
+        while let Some(op) = self.take(&["==", "!=", "<=", ">=", "<", ">"]) {
+            let right = self.sum()?;
+            value = match op {
+                "==" => value == right,
+                "!=" => value != right,
+                "<=" => value <= right,
+                ">=" => value >= right,
+                "<" => value < right,
+                _ => value > right,
+            } as i64;
+        }
+        Ok(value)
+    }
+
+    fn sum(&mut self) -> Result<i64> {
+        let mut value = self.product()?;
+        while let Some(op) = self.take(&["+", "-"]) {
+            let right = self.product()?;
+            value = if op == "+" { value.wrapping_add(right) } else { value.wrapping_sub(right) };
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<i64> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.take(&["*", "/", "%"]) {
+            let right = self.unary()?;
+            if op != "*" && right == 0 {
+                return Err(anyhow::anyhow!("division by 0"));
+            }
+            value = match op {
+                "*" => value.wrapping_mul(right),
+                "/" => value.wrapping_div(right),
+                _ => value.wrapping_rem(right),
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64> {
+        match self.take(&["-", "+", "!"]) {
+            Some("-") => Ok(self.unary()?.wrapping_neg()),
+            Some("!") => Ok((self.unary()? == 0) as i64),
+            Some(_) => self.unary(),
+            None => self.operand(),
+        }
+    }
+
+    fn operand(&mut self) -> Result<i64> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Name(name)) => match (self.lookup)(&name) {
+                Some(value) if value.trim().is_empty() => Ok(0),
+                Some(value) => value.trim().parse().map_err(|_| anyhow::anyhow!("{}: '{}' is not a number", name, value)),
+                None if self.nounset => Err(anyhow::anyhow!("{}: unbound variable", name)),
+                None => Ok(0),
+            },
+            Some(Token::Op("(")) => {
+                let value = self.or()?;
+                match self.take(&[")"]) {
+                    Some(_) => Ok(value),
+                    None => Err(anyhow::anyhow!("missing ')'")),
+                }
+            }
+            Some(token) => Err(anyhow::anyhow!("syntax error near '{}'", token.text())),
+            None => Err(anyhow::anyhow!("syntax error: operand expected")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let lookup = |name: &str| match name {
+            "N" => Some("4".to_string()),
+            "S" => Some("abc".to_string()),
+            _ => None,
+        };
+        assert_eq!(evaluate("N + 1", &lookup, false).unwrap(), 5);
+        assert_eq!(evaluate("$N * (2 + ${N}) % 5", &lookup, false).unwrap(), 4);
+        assert_eq!(evaluate("-N / 3 - 1", &lookup, false).unwrap(), -2);
+        assert_eq!(evaluate("N < 3 || !(N != 4) && N * 2 == 8", &lookup, false).unwrap(), 1);
+        assert_eq!(evaluate("UNSET + 2", &lookup, false).unwrap(), 2);
+        assert!(evaluate("UNSET + 2", &lookup, true).is_err());
+        assert!(evaluate("N / 0", &lookup, false).is_err());
+        assert!(evaluate("S + 1", &lookup, false).is_err());
+        assert!(evaluate("N +", &lookup, false).is_err());
+        assert!(evaluate("(N", &lookup, false).is_err());
+    }
+}
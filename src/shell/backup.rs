@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::shell::command_parser::{CommandParser, Redirection};
+use crate::utils::path_utils;
+
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub original: PathBuf,
+    pub copy: PathBuf,
+}
+
+// Copies of files taken before `>` redirections overwrote them, kept in
+// $XDG_STATE_HOME/llm-shell/backups/<pid> until the shell exits
+pub struct Backups {
+    dir: Option<PathBuf>,
+    entries: Vec<Backup>,
+}
+
+impl Backups {
+    pub fn new() -> Self {
+        let dir = path_utils::state_dir()
+            .ok()
+            .map(|state| state.join("backups").join(std::process::id().to_string()));
+        Backups { dir, entries: Vec::new() }
+    }
+
+    // Existing regular files the command's `>`, `2>`, `&>` or `3>` redirections would overwrite
+    pub fn targets(command: &str) -> Vec<PathBuf> {
+        let pipeline = match CommandParser::parse(command) {
+            Ok(pipeline) => pipeline,
+            Err(_) => return Vec::new(),
+        };
+        let mut targets: Vec<PathBuf> = Vec::new();
+        for redirection in pipeline.commands.iter().flat_map(|stage| &stage.redirections) {
+            if let Redirection::Output(file) | Redirection::Clobber(file) | Redirection::ErrorOutput(file) | Redirection::Both(file) | Redirection::FdOutput(_, file) = redirection {
+                let path = Path::new(file);
+                if path.is_file() {
+                    if let Ok(path) = path.canonicalize() {
+                        if !targets.contains(&path) {
+                            targets.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        targets
+    }
+
+    // Returns the backup's number for `restore`
+    pub fn save(&mut self, original: &Path) -> Result<usize> {
+        let dir = self.dir.as_ref().context("Could not determine state directory")?;
+        fs::create_dir_all(dir)?;
+        let name = original.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let copy = dir.join(format!("{}-{}", self.entries.len() + 1, name));
+        fs::copy(original, &copy).with_context(|| format!("Failed to back up {}", original.display()))?;
+        self.entries.push(Backup { original: original.to_path_buf(), copy });
+        Ok(self.entries.len())
+    }
+
+    pub fn entries(&self) -> &[Backup] {
+        &self.entries
+    }
+
+    // By 1-based number, or the latest backup of a file
+    pub fn find(&self, what: &str) -> Option<&Backup> {
+        if let Ok(n) = what.parse::<usize>() {
+            return n.checked_sub(1).and_then(|i| self.entries.get(i));
+        }
+        let path = Path::new(what).canonicalize().unwrap_or_else(|_| PathBuf::from(what));
+        self.entries.iter().rev().find(|backup| backup.original == path)
+    }
+}
+
+impl Drop for Backups {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            if !self.entries.is_empty() {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backups() {
+        let dir = std::env::temp_dir().join(format!("llm-shell-backup-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("config.ini");
+        fs::write(&file, "old").unwrap();
+        let file = file.canonicalize().unwrap();
+
+        let command = format!("echo new > {} 2> {}", file.display(), dir.join("missing.log").display());
+        assert_eq!(Backups::targets(&command), vec![file.clone()]);
+        assert!(Backups::targets(&format!("echo new >> {}", file.display())).is_empty());
+
+        let mut backups = Backups { dir: Some(dir.join("backups")), entries: Vec::new() };
+        assert_eq!(backups.save(&file).unwrap(), 1);
+        fs::write(&file, "new").unwrap();
+        let backup = backups.find(&file.to_string_lossy()).unwrap();
+        assert_eq!(fs::read_to_string(&backup.copy).unwrap(), "old");
+        assert!(backups.find("2").is_none());
+
+        drop(backups);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
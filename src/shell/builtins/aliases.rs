@@ -0,0 +1,120 @@
+use anyhow::Result;
+use colored::*;
+use std::fs;
+use std::io::Write;
+use crate::shell::Shell;
+use crate::shell::migrate;
+use super::Invocation;
+
+pub(super) fn alias(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() == 1 {
+        // List all aliases
+        for (name, value) in shell.alias_manager.list_aliases() {
+            println!("alias {}='{}'", name, value);
+        }
+    } else if inv.parts.len() == 2 && !inv.parts[1].contains('=') {
+        // Show specific alias
+        let aliases = shell.alias_manager.list_aliases();
+        let name = inv.parts[1];
+        let found = aliases.iter().find(|(n, _)| n == name);
+        if let Some((_, value)) = found {
+            println!("alias {}='{}'", name, value);
+        } else {
+            println!("alias: {} not found", name);
+            return Ok(1);
+        }
+    } else {
+        // Define new alias
+        let alias_def = inv.input["alias ".len()..].trim();
+        if let Some(equals_pos) = alias_def.find('=') {
+            let name = alias_def[..equals_pos].trim();
+            let mut value = alias_def[equals_pos + 1..].trim();
+            // Remove surrounding quotes if present
+            if (value.starts_with('\'') && value.ends_with('\'')) || 
+               (value.starts_with('"') && value.ends_with('"')) {
+                value = &value[1..value.len() - 1];
+            }
+            if let Err(e) = shell.alias_manager.add_alias(name, value) {
+                eprintln!("Error adding alias: {}", e);
+                return Ok(1);
+            }
+        } else {
+            eprintln!("Invalid alias format. Use: alias name='value'");
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+pub(super) fn unalias(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() < 2 {
+        eprintln!("unalias: missing alias name");
+        return Ok(1);
+    }
+    let mut status = 0;
+    for name in &inv.parts[1..] {
+        if let Err(e) = shell.alias_manager.remove_alias(name) {
+            eprintln!("Error removing alias {}: {}", name, e);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+pub(super) fn migrate(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let dry_run = inv.parts[1..].iter().any(|arg| *arg == "-n" || *arg == "--dry-run");
+    let shells: Vec<&str> = match inv.parts[1..].iter().find(|arg| !arg.starts_with('-')) {
+        Some(&name) if name == "zsh" || name == "fish" => vec![name],
+        Some(name) => {
+            eprintln!("migrate: don't know how to import from {} (zsh or fish)", name);
+            return Ok(2);
+        }
+        None => vec!["zsh", "fish"],
+    };
+
+    let mut imported: Vec<(String, String)> = Vec::new();
+    let mut found_any = false;
+    for name in shells {
+        for (path, importer) in migrate::sources(name) {
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    eprintln!("migrate: {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let import = importer(&content);
+            if import.aliases.is_empty() && import.skipped.is_empty() {
+                continue;
+            }
+            found_any = true;
+            writeln!(inv.stdout, "{}", format!("{} ({})", name, path.display()).bold())?;
+            for (alias, value) in import.aliases {
+                let existing = imported.iter().find(|(n, _)| *n == alias).map(|(_, v)| v.as_str()).or_else(|| shell.alias_manager.get(&alias));
+                match existing {
+                    Some(current) if current == value => {}
+                    Some(current) => {
+                        writeln!(inv.stdout, "  {} {}='{}' (keeping '{}')", "skipped".yellow(), alias, value, current)?;
+                    }
+                    None => {
+                        writeln!(inv.stdout, "  {} {}='{}'", "imported".green(), alias, value)?;
+                        imported.push((alias, value));
+                    }
+                }
+            }
+            for (line, reason) in import.skipped {
+                writeln!(inv.stdout, "  {} {}: {}", "skipped".yellow(), line, reason)?;
+            }
+        }
+    }
+
+    if !found_any {
+        writeln!(inv.stdout, "No aliases or functions found in zsh or fish configuration.")?;
+    } else if dry_run {
+        writeln!(inv.stdout, "{} aliases would be imported.", imported.len())?;
+    } else if !imported.is_empty() {
+        shell.alias_manager.add_aliases(&imported)?;
+        writeln!(inv.stdout, "{} aliases imported into ~/.llm_shell_aliases.", imported.len())?;
+    }
+    Ok(0)
+}
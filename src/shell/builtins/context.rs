@@ -0,0 +1,83 @@
+use anyhow::Result;
+use crate::llm::feedback::{self, Rating};
+use crate::shell::Shell;
+use super::Invocation;
+
+pub(super) fn remember(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let fact = inv.input["remember".len()..].trim().trim_matches(|c| c == '"' || c == '\'');
+    let memory = shell.context_manager.memory();
+    if fact.is_empty() {
+        if memory.facts.is_empty() {
+            println!("Nothing remembered for {}", memory.root.display());
+        }
+        for (i, fact) in memory.facts.iter().enumerate() {
+            println!("{:>3}  {}", i + 1, fact);
+        }
+        return Ok(0);
+    }
+    shell.context_manager.update_memory(|memory| memory.add_fact(fact))?;
+    Ok(0)
+}
+
+pub(super) fn forget(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let what = inv.input["forget".len()..].trim().trim_matches(|c| c == '"' || c == '\'');
+    if what.is_empty() {
+        return clear_context(shell);
+    }
+    let removed = if what == "--all" {
+        shell.context_manager.update_memory(|memory| {
+            let count = memory.facts.len() + memory.translations.len();
+            memory.facts.clear();
+            memory.translations.clear();
+            count
+        })?
+    } else {
+        shell.context_manager.update_memory(|memory| memory.forget(what))?
+    };
+    if removed == 0 {
+        eprintln!("forget: nothing matches '{}'", what);
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+pub(super) fn context(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1).copied() {
+        None | Some("show") => {
+            let context = shell.context_manager.get_context();
+            println!("{}", if context.is_empty() { "(no context is shared)" } else { context.as_str() });
+            Ok(0)
+        }
+        Some("clear") => clear_context(shell),
+        Some(_) => {
+            eprintln!("context: usage: context [show|clear]");
+            Ok(2)
+        }
+    }
+}
+
+// `good` and `bad [NOTE]` rate the last translated command
+pub(super) fn rate(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let name = inv.parts[0];
+    let (request, command) = match &shell.last_translation {
+        Some(translation) => translation.clone(),
+        None => {
+            eprintln!("{}: no translated command to rate", name);
+            return Ok(1);
+        }
+    };
+    let note = inv.input[name.len()..].trim().trim_matches(|c| c == '"' || c == '\'');
+    println!("Rated {} as {}.", command, name);
+    feedback::record(Rating { request, command, good: name == "good", note: note.to_string() })?;
+    Ok(0)
+}
+
+// Forgets the session right away, including what prefetching and the saved
+// session file hold, e.g. after a secret was printed
+fn clear_context(shell: &mut Shell) -> Result<i32> {
+    shell.context_manager.clear();
+    shell.prefetcher.reset("", false);
+    shell.save_session(false);
+    println!("LLM context cleared.");
+    Ok(0)
+}
@@ -0,0 +1,365 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use crate::shell::Shell;
+use crate::shell::script::{self, LoopControl};
+use crate::utils::i18n::tr;
+use crate::utils::path_utils;
+use crate::system::tty;
+use crate::shell::signal_handler::SignalHandler;
+use log::debug;
+use std::io::Write;
+use super::{BuiltinFuture, Invocation};
+
+pub(super) fn history(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let entries = shell.terminal.get_history().get_entries();
+    let count = if inv.parts.len() > 1 {
+        inv.parts[1].parse::<usize>().unwrap_or(entries.len())
+    } else {
+        entries.len()
+    };
+    
+    for (i, entry) in entries.iter().rev().take(count).rev().enumerate() {
+        println!("{:5} {}", entries.len() - count + i + 1, entry);
+    }
+    Ok(0)
+}
+
+pub(super) fn exit(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    // Without an argument the shell exits with the status of the last command
+    let exit_code = match inv.parts.get(1) {
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(code) => code,
+            Err(_) => {
+                eprintln!("exit: {}: numeric argument required", arg);
+                2
+            }
+        },
+        None => shell.last_status,
+    };
+
+    // Signal to exit the shell
+    shell.exit_requested = true;
+    Ok(exit_code)
+}
+
+pub(super) fn logout(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if !shell.is_login_shell() {
+        eprintln!("{}", tr("logout.not_login"));
+        return Ok(1);
+    }
+    exit(shell, inv)
+}
+
+pub(super) fn source<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        if inv.parts.len() > 1 {
+            let path = std::path::Path::new(inv.parts[1]);
+            if let Ok(content) = std::fs::read_to_string(path) {
+                // Parsed as a whole so compound commands can span lines
+                let nodes = script::parse(&content)?;
+                return shell.run_script(&nodes).await;
+            } else {
+                eprintln!("{}: cannot open {}: No such file or directory", inv.parts[0], inv.parts[1]);
+            }
+        } else {
+            eprintln!("{}: filename argument required", inv.parts[0]);
+        }
+        Ok(1)
+    })
+}
+
+pub(super) fn eval<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        // Like bash, the arguments are unquoted and joined, then parsed again
+        let words = match shellwords::split(inv.input) {
+            Ok(words) => words,
+            Err(_) => return Err(anyhow::anyhow!("eval: unbalanced quotes")),
+        };
+        if words.len() > 1 {
+            let cmd = words[1..].join(" ");
+            return shell.execute_line(&cmd).await;
+        }
+        Ok(0)
+    })
+}
+
+// What running NAME would do, checked in the order the shell does it
+enum Resolution {
+    Builtin,
+    Alias(String),
+    Program { path: PathBuf, hashed: bool },
+    NotFound,
+}
+
+fn resolve(shell: &Shell, name: &str) -> Resolution {
+    if shell.is_builtin(name) {
+        return Resolution::Builtin;
+    }
+    if let Some(value) = shell.alias_manager.get(name) {
+        return Resolution::Alias(value.to_string());
+    }
+    let hashed = path_utils::is_hashed(name);
+    match path_utils::lookup(name) {
+        Some(path) => Resolution::Program { path, hashed },
+        None => Resolution::NotFound,
+    }
+}
+
+pub(super) fn type_(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    // -p only prints the file that would run, and nothing for builtins and aliases
+    let path_only = inv.parts.get(1) == Some(&"-p");
+    let names = &inv.parts[if path_only { 2 } else { 1 }..];
+    if names.is_empty() {
+        eprintln!("type: missing argument");
+        return Ok(1);
+    }
+    let mut status = 0;
+    for &cmd in names {
+        match resolve(shell, cmd) {
+            Resolution::Builtin | Resolution::Alias(_) if path_only => {}
+            Resolution::Program { path, .. } if path_only => writeln!(inv.stdout, "{}", path.display())?,
+            Resolution::Builtin => writeln!(inv.stdout, "{} is a shell builtin", cmd)?,
+            Resolution::Alias(value) if shell.alias_manager.takes_arguments(cmd) => {
+                writeln!(inv.stdout, "{} is aliased to `{}' (takes arguments like a function)", cmd, value)?
+            }
+            Resolution::Alias(value) => writeln!(inv.stdout, "{} is aliased to `{}'", cmd, value)?,
+            Resolution::Program { path, hashed: true } => writeln!(inv.stdout, "{} is hashed ({})", cmd, path.display())?,
+            Resolution::Program { path, hashed: false } => writeln!(inv.stdout, "{} is {}", cmd, path.display())?,
+            Resolution::NotFound => {
+                if !path_only {
+                    eprintln!("type: {}: not found", cmd);
+                }
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
+
+// Unlike an external which, this knows about builtins, aliases and the hash table
+pub(super) fn which(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() < 2 {
+        eprintln!("usage: which NAME...");
+        return Ok(2);
+    }
+    let mut status = 0;
+    for &cmd in &inv.parts[1..] {
+        match resolve(shell, cmd) {
+            Resolution::Builtin => writeln!(inv.stdout, "{}: shell builtin", cmd)?,
+            Resolution::Alias(value) => {
+                writeln!(inv.stdout, "{}: aliased to {}", cmd, value)?;
+                // Aliases expand once, so their first word is looked up as a builtin or program
+                let target = value.split_whitespace().next().unwrap_or("");
+                match resolve(shell, target) {
+                    Resolution::Builtin => writeln!(inv.stdout, "\t{}: shell builtin", target)?,
+                    Resolution::Program { path, .. } => writeln!(inv.stdout, "\t{}", path.display())?,
+                    Resolution::Alias(_) | Resolution::NotFound => {
+                        writeln!(inv.stdout, "\t{}: not found", target)?;
+                        status = 1;
+                    }
+                }
+            }
+            Resolution::Program { path, .. } => writeln!(inv.stdout, "{}", path.display())?,
+            Resolution::NotFound => {
+                eprintln!("which: no {} in PATH", cmd);
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
+
+pub(super) fn fixterm(_shell: &mut Shell, _inv: &mut Invocation) -> Result<i32> {
+    tty::repair()?;
+    Ok(0)
+}
+
+pub(super) fn hash(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let mut status = 0;
+    for arg in &inv.parts[1..] {
+        match *arg {
+            "-r" => path_utils::forget_hashed(),
+            name if path_utils::hash(name).is_none() => {
+                eprintln!("hash: {}: not found", name);
+                status = 1;
+            }
+            _ => {}
+        }
+    }
+    if inv.parts.len() == 1 {
+        let entries = path_utils::hashed();
+        if entries.is_empty() {
+            writeln!(inv.stdout, "hash: hash table empty")?;
+        } else {
+            writeln!(inv.stdout, "hits\tcommand")?;
+            for (_, hits, path) in entries {
+                writeln!(inv.stdout, "{:4}\t{}", hits, path.display())?;
+            }
+        }
+    }
+    Ok(status)
+}
+
+pub(super) fn help(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1) {
+        Some(name) => shell.show_builtin_help(name),
+        None => {
+            shell.show_help();
+            Ok(0)
+        }
+    }
+}
+
+pub(super) fn true_(_shell: &mut Shell, _inv: &mut Invocation) -> Result<i32> {
+    Ok(0)
+}
+
+pub(super) fn false_(_shell: &mut Shell, _inv: &mut Invocation) -> Result<i32> {
+    Ok(1)
+}
+
+pub(super) fn loop_control(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let count = match inv.parts.get(1).map(|n| n.parse::<u32>()) {
+        None => 1,
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => return Err(anyhow::anyhow!("{}: {}: loop count out of range", inv.parts[0], inv.parts[1])),
+    };
+    if shell.loop_depth == 0 {
+        eprintln!("{}: only meaningful in a loop", inv.parts[0]);
+        return Ok(1);
+    } else {
+        let count = count.min(shell.loop_depth);
+        shell.loop_control = Some(if inv.parts[0] == "break" {
+            LoopControl::Break(count)
+        } else {
+            LoopControl::Continue(count)
+        });
+    }
+    Ok(0)
+}
+
+pub(super) fn test(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    // Handle the closing bracket for [ command
+    let test_parts = if inv.parts[0] == "[" {
+        if inv.parts[inv.parts.len() - 1] != "]" {
+            eprintln!("[: missing closing ]");
+            return Ok(2);
+        }
+        &inv.parts[1..inv.parts.len() - 1]
+    } else {
+        &inv.parts[1..]
+    };
+    
+    // Status 0 when the condition holds, 1 when it doesn't, 2 for an unknown expression
+    match evaluate(test_parts) {
+        Ok(holds) => Ok(if holds { 0 } else { 1 }),
+        Err(e) => {
+            eprintln!("{}: {}", inv.parts[0], e);
+            Ok(2)
+        }
+    }
+}
+
+fn evaluate(parts: &[&str]) -> Result<bool> {
+    let path = std::path::Path::new;
+    let number = |word: &str| word.trim().parse::<i64>().map_err(|_| anyhow::anyhow!("{}: integer expression expected", word));
+    let access = |file: &str, flags| nix::unistd::access(path(file), flags).is_ok();
+    Ok(match parts {
+        // Empty test is false
+        [] => false,
+        ["!", rest @ ..] => !evaluate(rest)?,
+        [word] => !word.is_empty(),
+        ["-f", file] => path(file).is_file(),
+        ["-d", file] => path(file).is_dir(),
+        ["-e", file] => path(file).exists(),
+        ["-L" | "-h", file] => path(file).is_symlink(),
+        ["-s", file] => std::fs::metadata(file).map(|metadata| metadata.len() > 0).unwrap_or(false),
+        ["-r", file] => access(file, nix::unistd::AccessFlags::R_OK),
+        ["-w", file] => access(file, nix::unistd::AccessFlags::W_OK),
+        ["-x", file] => access(file, nix::unistd::AccessFlags::X_OK),
+        ["-n", word] => !word.is_empty(),
+        ["-z", word] => word.is_empty(),
+        [a, "=", b] | [a, "==", b] => a == b,
+        [a, "!=", b] => a != b,
+        [a, "-eq", b] => number(a)? == number(b)?,
+        [a, "-ne", b] => number(a)? != number(b)?,
+        [a, "-lt", b] => number(a)? < number(b)?,
+        [a, "-le", b] => number(a)? <= number(b)?,
+        [a, "-gt", b] => number(a)? > number(b)?,
+        [a, "-ge", b] => number(a)? >= number(b)?,
+        _ => return Err(anyhow::anyhow!("unsupported expression: {}", parts.join(" "))),
+    })
+}
+
+pub(super) fn time<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        if inv.parts.len() < 2 {
+            eprintln!("time: missing command");
+            return Ok(2);
+        }
+        // The timed command keeps its own quoting
+        let cmd = inv.input.trim_start()[inv.parts[0].len()..].trim().to_string();
+        let (user_before, sys_before) = child_cpu_time();
+        let start = Instant::now();
+        let result = shell.execute_line(&cmd).await;
+        let real = start.elapsed();
+        let (user_after, sys_after) = child_cpu_time();
+
+        eprintln!("\nreal\t{}", format_time(real));
+        eprintln!("user\t{}", format_time(user_after.saturating_sub(user_before)));
+        eprintln!("sys\t{}", format_time(sys_after.saturating_sub(sys_before)));
+        result
+    })
+}
+
+// User and system CPU time used by finished child processes
+fn child_cpu_time() -> (Duration, Duration) {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) } != 0 {
+        return (Duration::ZERO, Duration::ZERO);
+    }
+    let to_duration = |tv: libc::timeval| Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000);
+    (to_duration(usage.ru_utime), to_duration(usage.ru_stime))
+}
+
+// Same layout as bash: 0m1.234s
+fn format_time(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    format!("{}m{:.3}s", (secs / 60.0).floor(), secs % 60.0)
+}
+
+pub(super) fn exec(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let words = match shellwords::split(inv.input) {
+        Ok(words) => words,
+        Err(_) => return Err(anyhow::anyhow!("exec: unbalanced quotes")),
+    };
+    // No command specified, just continue
+    let Some(cmd) = words.get(1).map(String::as_str) else {
+        return Ok(0);
+    };
+    let Some(path) = path_utils::lookup(cmd) else {
+        eprintln!("exec: {}: command not found", cmd);
+        return Ok(127);
+    };
+
+    // Nothing runs after a successful exec, so wrap up the session first and
+    // hand over the terminal and signals the way the shell found them
+    shell.save_session(true);
+    shell.terminal.save_history();
+    crate::shell::fifo::remove_temporary();
+    if let Err(e) = tty::restore() {
+        debug!("Failed to restore terminal settings: {}", e);
+    }
+    if let Err(e) = SignalHandler::reset() {
+        debug!("Failed to reset signal handlers: {}", e);
+    }
+
+    use std::os::unix::process::CommandExt;
+    let err = std::process::Command::new(path).arg0(cmd).args(&words[2..]).exec();
+
+    // If we get here, exec failed and the shell carries on
+    let _ = SignalHandler::initialize();
+    eprintln!("exec: failed to execute {}: {}", cmd, err);
+    Ok(126)
+}
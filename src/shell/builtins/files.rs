@@ -0,0 +1,130 @@
+use anyhow::Result;
+use crate::shell::Shell;
+use crate::shell::fifo;
+use super::Invocation;
+use std::io::Write;
+
+pub(super) fn touch(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() < 2 {
+        eprintln!("touch: missing file operand");
+        return Ok(1);
+    }
+    let mut status = 0;
+    for file in &inv.parts[1..] {
+        let path = std::path::Path::new(file);
+        let result = if !path.exists() {
+            std::fs::File::create(path).map(|_| ())
+        } else {
+            // Update file times (simplified - just recreates the file)
+            let content = std::fs::read(path).unwrap_or_default();
+            std::fs::write(path, content)
+        };
+        if let Err(e) = result {
+            eprintln!("touch: cannot touch '{}': {}", file, e);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+pub(super) fn mkdir(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() < 2 {
+        eprintln!("mkdir: missing operand");
+        return Ok(1);
+    }
+    let create_parents = inv.parts[1] == "-p";
+    let dirs_start = if create_parents { 2 } else { 1 };
+    
+    let mut status = 0;
+    for dir in &inv.parts[dirs_start..] {
+        let path = std::path::Path::new(dir);
+        let result = if create_parents {
+            std::fs::create_dir_all(path)
+        } else {
+            std::fs::create_dir(path)
+        };
+        
+        if let Err(e) = result {
+            eprintln!("mkdir: cannot create directory '{}': {}", dir, e);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+pub(super) fn mkfifo(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let mut mode = 0o666;
+    let mut temporary = false;
+    let mut names = Vec::new();
+    let mut args = inv.parts[1..].iter();
+    while let Some(&arg) = args.next() {
+        match arg {
+            "-t" => temporary = true,
+            "-m" => match args.next().and_then(|mode| u32::from_str_radix(mode, 8).ok()) {
+                Some(value) => mode = value,
+                None => {
+                    eprintln!("mkfifo: -m needs an octal mode");
+                    return Ok(2);
+                }
+            },
+            name => names.push(name),
+        }
+    }
+    if temporary {
+        let path = fifo::create_temporary()?;
+        writeln!(inv.stdout, "{}", path.display())?;
+        return Ok(0);
+    }
+    if names.is_empty() {
+        eprintln!("mkfifo: missing operand");
+        return Ok(1);
+    }
+    let mut status = 0;
+    for name in names {
+        if let Err(e) = fifo::create(std::path::Path::new(name), mode) {
+            eprintln!("{:#}", e);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+pub(super) fn rmdir(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() < 2 {
+        eprintln!("rmdir: missing operand");
+        return Ok(1);
+    }
+    let mut status = 0;
+    for dir in &inv.parts[1..] {
+        if let Err(e) = std::fs::remove_dir(dir) {
+            eprintln!("rmdir: failed to remove '{}': {}", dir, e);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+pub(super) fn restore(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let what = match inv.parts.get(1) {
+        Some(what) => *what,
+        None => {
+            if shell.backups.entries().is_empty() {
+                println!("No backups in this session (`shopt -s backup` turns them on)");
+            }
+            for (i, backup) in shell.backups.entries().iter().enumerate() {
+                println!("{:>3}  {}", i + 1, backup.original.display());
+            }
+            return Ok(0);
+        }
+    };
+    let backup = match shell.backups.find(what) {
+        Some(backup) => backup,
+        None => {
+            eprintln!("restore: no backup matches '{}'", what);
+            return Ok(1);
+        }
+    };
+    std::fs::copy(&backup.copy, &backup.original)?;
+    println!("Restored {}", backup.original.display());
+    Ok(0)
+}
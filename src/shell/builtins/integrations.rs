@@ -0,0 +1,1016 @@
+use anyhow::Result;
+use colored::*;
+use crate::shell::Shell;
+use crate::config::{CONFIG, TranslationMode};
+use crate::llm::{self, budget::{self, Ledger, Limits}};
+use super::{BuiltinFuture, Invocation};
+use crate::shell::policy::{self, CommandOrigin};
+use crate::shell::{explain, genscript, page, pipe_builder, regex_builder, search, structure, wizard};
+use crate::system::clipboard::Clipboard;
+use crate::system::crontab;
+use crate::system::remote::RemoteTarget;
+use crate::system::scheduler::{self, Scheduler, When};
+use crate::system::tmux::Tmux;
+use crate::terminal;
+use crate::utils::i18n::tr;
+use crate::utils::path_utils::find_executable;
+use crate::utils::duration::format_duration;
+use crate::utils::performance::{Summary, PERFORMANCE_MONITOR};
+use crate::utils::telemetry;
+use std::io::{IsTerminal, Write};
+
+pub(super) fn copy(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    // The last captured output by default, -c for the last command line
+    let text = match inv.parts.get(1) {
+        Some(&"-c") => match shell.context_manager.last_command() {
+            Some(last) => last.to_string(),
+            None => {
+                eprintln!("copy: no command to copy");
+                return Ok(1);
+            }
+        },
+        Some(_) => inv.input.trim_start()["copy".len()..].trim().to_string(),
+        None => match &shell.last_output {
+            Some(output) => output.clone(),
+            None => {
+                eprintln!("copy: no captured output; run `COMMAND | copy` to copy what a command prints");
+                return Ok(1);
+            }
+        },
+    };
+
+    match Clipboard::copy(&text) {
+        Ok(_) => {
+            let lines = text.lines().count();
+            if lines > 1 {
+                println!("Copied {} lines to the clipboard", lines);
+            } else {
+                println!("Copied to clipboard: {}", text.trim_end().bright_cyan());
+            }
+            Ok(0)
+        }
+        Err(e) => {
+            eprintln!("copy: {}", e);
+            Ok(1)
+        }
+    }
+}
+
+pub(super) fn paste_run(shell: &mut Shell, _inv: &mut Invocation) -> Result<i32> {
+    let command = match Clipboard::paste() {
+        Ok(content) => content.trim().to_string(),
+        Err(e) => {
+            eprintln!("paste-run: {}", e);
+            return Ok(1);
+        }
+    };
+
+    if command.is_empty() {
+        eprintln!("paste-run: clipboard is empty");
+        return Ok(1);
+    }
+
+    println!("Clipboard command: {}", command.bright_green());
+    match terminal::confirm(tr("confirm.run_it")) {
+        Ok(true) => shell.execute_command(&command),
+        Ok(false) => {
+            println!("{}", tr("confirm.aborted"));
+            Ok(1)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub(super) fn tmux_pane(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() == 1 {
+        match &shell.tmux_pane {
+            Some(pane) => println!("Translated commands are sent to tmux pane {}", pane),
+            None => println!("Translated commands are executed locally"),
+        }
+    } else if inv.parts[1] == "off" {
+        shell.tmux_pane = None;
+        println!("Translated commands will be executed locally");
+    } else if !Tmux::is_active() {
+        eprintln!("tmux-pane: not running inside tmux");
+        return Ok(1);
+    } else if !Tmux::pane_exists(inv.parts[1]) {
+        eprintln!("tmux-pane: {}: no such pane", inv.parts[1]);
+        return Ok(1);
+    } else {
+        shell.tmux_pane = Some(inv.parts[1].to_string());
+        println!("Translated commands will be sent to tmux pane {}", inv.parts[1]);
+    }
+    Ok(0)
+}
+
+pub(super) fn target(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1).copied() {
+        None => match &shell.remote_target {
+            Some(target) => println!("{} ({})", target.url(), target.os.as_deref().unwrap_or("unknown OS")),
+            None => println!("Translated commands are executed locally"),
+        },
+        Some("set") if inv.parts.len() > 2 => {
+            let mut target = RemoteTarget::parse(inv.parts[2])?;
+            target.connect()?;
+            println!("Translated commands will run on {}", target.url().bright_cyan());
+            shell.context_manager.set_system_info("target", &target.describe());
+            shell.terminal.set_target_indicator(Some(target.host.clone()));
+            if let Some(previous) = shell.remote_target.replace(target) {
+                previous.disconnect();
+            }
+        },
+        Some("clear") => {
+            if let Some(target) = shell.remote_target.take() {
+                target.disconnect();
+            }
+            shell.context_manager.remove_system_info("target");
+            shell.terminal.set_target_indicator(None);
+            println!("Translated commands will be executed locally");
+        },
+        _ => {
+            eprintln!("target: usage: target [set ssh://[user@]host[:port] | clear]");
+            return Ok(2);
+        }
+    }
+    Ok(0)
+}
+
+pub(super) fn timeout_run(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() < 3 {
+        eprintln!("timeout-run: usage: timeout-run DURATION COMMAND [ARGS...]");
+        return Ok(2);
+    }
+    
+    let timeout = match crate::utils::duration::parse_duration(inv.parts[1]) {
+        Ok(timeout) => timeout,
+        Err(e) => return Err(anyhow::anyhow!("timeout-run: {}", e)),
+    };
+    
+    // Keep the original quoting of the wrapped command
+    let duration_end = inv.input.find(inv.parts[1]).unwrap_or(0) + inv.parts[1].len();
+    let command = inv.input[duration_end..].trim().to_string();
+    if !shell.options.observemode && !shell.confirm_command(&command, CommandOrigin::User)? {
+        println!("{}", tr("confirm.aborted"));
+        return Ok(1);
+    }
+    shell.execute_command_as(&command, CommandOrigin::User, Some(timeout))
+}
+
+pub(super) fn schedule(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let words = match shellwords::split(inv.input) {
+        Ok(words) => words,
+        Err(_) => return Err(anyhow::anyhow!("schedule: unbalanced quotes")),
+    };
+    
+    let result = match words.get(1).map(|w| w.as_str()) {
+        None | Some("list") => shell.list_scheduled(),
+        Some("remove") if words.len() == 3 => match words[2].parse::<u32>() {
+            Ok(id) => Scheduler::remove(id).and_then(|removed| match removed {
+                true => Ok(()),
+                false => Err(anyhow::anyhow!("schedule: no job {}", id)),
+            }),
+            Err(_) => Err(anyhow::anyhow!("schedule: invalid job id '{}'", words[2])),
+        },
+        // Either a quoted expression or five bare fields followed by the command
+        Some(_) if words.len() == 3 => shell.add_scheduled(When::Cron(words[1].clone()), &words[2]),
+        Some(_) if words.len() >= 7 => shell.add_scheduled(When::Cron(words[1..6].join(" ")), &words[6..].join(" ")),
+        _ => Err(anyhow::anyhow!("schedule: usage: schedule [list | remove ID | \"CRON\" COMMAND]")),
+    };
+    result.map(|_| 0)
+}
+
+pub(super) fn run_in(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let words = match shellwords::split(inv.input) {
+        Ok(words) => words,
+        Err(_) => return Err(anyhow::anyhow!("in: unbalanced quotes")),
+    };
+    if words.len() < 3 {
+        return Err(anyhow::anyhow!("in: usage: in DURATION COMMAND"));
+    }
+    
+    let result = crate::utils::duration::parse_duration(&words[1])
+        .map_err(|e| anyhow::anyhow!("in: {}", e))
+        .and_then(|delay| {
+            let at = scheduler::now() + delay.as_secs().max(1);
+            shell.add_scheduled(When::Once(at), &words[2..].join(" "))
+        });
+    result.map(|_| 0)
+}
+
+// Shows an answer the model has to correct, with what was wrong with it
+fn invalid_shown(answer: &str, checked: Result<()>) -> Result<()> {
+    if let Err(e) = &checked {
+        eprintln!("{} {} ({})", "Invalid:".yellow(), answer, e);
+    }
+    checked
+}
+
+// Asks for the tool's inputs, writes the command with a prompt made for the
+// tool and checks it before it goes through the usual translation flow
+pub(super) fn wizard<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        let wizard = match inv.parts.get(1).and_then(|tool| wizard::find(tool)) {
+            Some(wizard) => wizard,
+            None => {
+                let tools: Vec<&str> = wizard::WIZARDS.iter().map(|wizard| wizard.tool).collect();
+                eprintln!("wizard: usage: wizard {{{}}}", tools.join("|"));
+                return Ok(2);
+            }
+        };
+        if find_executable(wizard.tool).is_none() {
+            eprintln!("{}", format!("wizard: {} is not installed here", wizard.tool).yellow());
+        }
+
+        let mut answers = Vec::new();
+        for question in wizard.questions {
+            match shell.terminal.read_reply(&format!("{}: ", question))? {
+                Some(answer) => answers.push(answer),
+                None => return Ok(1),
+            }
+        }
+        let request = wizard.request(&answers);
+
+        let client = &shell.llm_client;
+        let checked = llm::ask_checked(
+            request.clone(),
+            |prompt| async move { client.generate_command(wizard.template, &prompt).await },
+            |command| invalid_shown(command, wizard.validate(command)).map(|()| command.to_string()),
+            |command, e| llm::correction(&request, command, e),
+        )
+        .await;
+        match checked {
+            Ok(Ok(command)) => shell.run_translation(&request, &command, None, None).await,
+            Ok(Err(_)) => {
+                eprintln!("wizard: could not get a valid {} command", wizard.tool);
+                Ok(1)
+            }
+            Err(e) => {
+                eprintln!("wizard: {}", e);
+                Ok(1)
+            }
+        }
+    })
+}
+
+// `find! DESCRIPTION`: a find (or fd) command for the files described,
+// written with a prompt made for it and checked to only list files. A next
+// line starting with `|` gets the results.
+pub(super) fn search<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        let description = inv.parts[1..].join(" ");
+        if description.is_empty() {
+            eprintln!("find!: usage: find! DESCRIPTION");
+            return Ok(2);
+        }
+        let tool = search::tool();
+        let request = format!("Find {}", description);
+
+        let client = &shell.llm_client;
+        let checked = llm::ask_checked(
+            request.clone(),
+            |prompt| async move { client.generate_command(search::template(tool), &prompt).await },
+            |command| invalid_shown(command, search::validate(command)).map(|()| command.to_string()),
+            |command, e| llm::correction(&request, command, e),
+        )
+        .await;
+        let command = match checked {
+            Ok(Ok(command)) => command,
+            Ok(Err(_)) => {
+                eprintln!("find!: could not get a valid {} command", tool);
+                return Ok(1);
+            }
+            Err(e) => {
+                eprintln!("find!: {}", e);
+                return Ok(1);
+            }
+        };
+        let status = shell.run_translation(&request, &command, None, None).await?;
+        if status == 0 && CONFIG.translation_mode != TranslationMode::Suggest {
+            println!("{}", "Start the next line with `|` to pipe these files into a command.".dimmed());
+            shell.last_search = Some(command);
+        }
+        Ok(status)
+    })
+}
+
+// `pipe! GOAL`: the model proposes a pipeline one stage at a time. Each
+// stage is checked to only read, run with the stages before it and its
+// output shown, so the next one can be kept, steered or replaced. The
+// finished pipeline is left at the prompt.
+pub(super) fn pipe<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        let goal = inv.parts[1..].join(" ");
+        if goal.is_empty() {
+            eprintln!("pipe!: usage: pipe! GOAL");
+            return Ok(2);
+        }
+        let mut stages: Vec<String> = Vec::new();
+        let mut sample = String::new();
+        let mut steer: Option<String> = None;
+        'stages: loop {
+            // None when the model says the pipeline is done
+            let request = pipe_builder::request(&goal, &stages, &sample, steer.as_deref());
+            let client = &shell.llm_client;
+            let checked = llm::ask_checked(
+                request.clone(),
+                |prompt| async move { client.generate_command(pipe_builder::TEMPLATE, &prompt).await },
+                |answer| {
+                    let stage = answer.trim_start_matches('|').trim();
+                    if stage.eq_ignore_ascii_case("done") {
+                        return Ok(None);
+                    }
+                    invalid_shown(stage, pipe_builder::validate(stage)).map(|()| Some(stage.to_string()))
+                },
+                |stage, e| llm::correction(&request, stage.trim_start_matches('|'), e),
+            )
+            .await;
+            let stage = match checked {
+                Ok(Ok(Some(stage))) => stage,
+                Ok(Ok(None)) => break 'stages,
+                Ok(Err(_)) => {
+                    eprintln!("pipe!: could not get a valid stage");
+                    return Ok(1);
+                }
+                Err(e) => {
+                    eprintln!("pipe!: {}", e);
+                    return Ok(1);
+                }
+            };
+
+            let mut candidate = stages.clone();
+            candidate.push(stage.clone());
+            println!("\n{} {}", format!("Stage {}:", candidate.len()).bold(), candidate.join(" | ").bright_green());
+            let output = pipe_builder::run(&candidate).await;
+            match &output {
+                Ok(output) => println!("{}", pipe_builder::sample(output).dimmed()),
+                Err(e) => println!("{}", format!("Failed: {}", e).yellow()),
+            }
+
+            let reply = shell.terminal.read_reply("Enter to keep it and go on, d to keep it and finish, q to quit, or say what to change: ")?;
+            match (reply.as_deref(), output) {
+                (None, _) | (Some("q"), _) => return Ok(1),
+                (Some(""), Ok(output)) | (Some("d"), Ok(output)) => {
+                    stages.push(stage);
+                    sample = pipe_builder::sample(&output);
+                    steer = None;
+                    if reply.as_deref() == Some("d") {
+                        break;
+                    }
+                }
+                // A failed stage is replaced rather than kept
+                (Some(""), Err(e)) | (Some("d"), Err(e)) => steer = Some(format!("`{}` failed ({}); write a different stage.", stage, e)),
+                (Some(change), _) => steer = Some(format!("Instead of `{}`: {}", stage, change)),
+            }
+        }
+
+        if stages.is_empty() {
+            eprintln!("pipe!: no pipeline was built");
+            return Ok(1);
+        }
+        let pipeline = stages.join(" | ");
+        telemetry::count("pipeline.built");
+        println!("\nPipeline: {}", pipeline.bright_green());
+        shell.terminal.set_prefill(&pipeline);
+        Ok(0)
+    })
+}
+
+// `explain-script FILE`: the script a part at a time with the model's
+// walkthrough of each, then the lines that reach the network, delete data
+// or escalate privileges
+pub(super) fn explain_script<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        let Some(path) = inv.parts.get(1).copied() else {
+            eprintln!("explain-script: usage: explain-script FILE");
+            return Ok(2);
+        };
+        let script = match std::fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) => {
+                eprintln!("explain-script: {}: {}", path, e);
+                return Ok(1);
+            }
+        };
+        let name = std::path::Path::new(path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| path.to_string());
+        let outline = explain::outline(&script);
+        println!("{} {}", path.bold(), format!("({})", outline.replace('\n', ", ")).dimmed());
+
+        // Ctrl+C stops after the part being explained
+        crate::shell::signal_handler::SignalHandler::was_interrupted();
+        let mut status = 0;
+        for chunk in explain::chunks(&script) {
+            println!("\n{}", format!("Lines {}-{}", chunk.first_line, chunk.last_line()).bright_yellow());
+            println!("{}", chunk.numbered().dimmed());
+            match shell.documentation.get_script_help(&name, &outline, &chunk.numbered()).await {
+                Ok(walkthrough) => println!("{}", walkthrough.bright_blue()),
+                Err(e) => {
+                    eprintln!("explain-script: {}", e);
+                    status = 1;
+                    break;
+                }
+            }
+            if crate::shell::signal_handler::SignalHandler::was_interrupted() {
+                status = 130;
+                break;
+            }
+        }
+
+        let risks = explain::risks(&script);
+        println!("\n{}", "Risks:".bold());
+        if risks.is_empty() {
+            println!("  No network access, deletions or privilege escalation found.");
+        }
+        for (line, risk, text) in risks {
+            let label = format!("{:<10}", risk.label());
+            let label = match risk {
+                explain::Risk::Privilege => label.bright_red(),
+                explain::Risk::Deletion => label.red(),
+                explain::Risk::Network => label.yellow(),
+            };
+            println!("  {:>4}  {} {}", line, label, text);
+        }
+        Ok(status)
+    })
+}
+
+// `genscript DESCRIPTION [> FILE]`: a complete script for the task, checked
+// and sent back once with its problems, then written out. It is never run.
+pub(super) fn genscript<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        let description = inv.parts[1..].join(" ");
+        if description.is_empty() {
+            eprintln!("genscript: usage: genscript DESCRIPTION [> FILE]");
+            return Ok(2);
+        }
+
+        let request = format!("Write a script that will: {}", description);
+        let client = &shell.llm_client;
+        let checked = llm::ask_checked(
+            request.clone(),
+            |prompt| async move { client.write_script(&prompt).await },
+            |script| {
+                let problems = genscript::problems(script);
+                if problems.is_empty() { Ok(script.to_string()) } else { Err(problems) }
+            },
+            |script, problems| format!("{}\n\nYour previous script had these problems; fix all of them:\n{}\n\nPrevious script:\n{}", request, problems.join("\n"), script),
+        )
+        .await;
+        // A script that still has problems is written out anyway, with them listed
+        let (script, problems) = match checked {
+            Ok(Ok(script)) => (script, Vec::new()),
+            Ok(Err(last)) => last,
+            Err(e) => {
+                eprintln!("genscript: {}", e);
+                return Ok(1);
+            }
+        };
+        telemetry::count("genscript");
+        if !problems.is_empty() {
+            eprintln!("{}", "genscript: the script still has problems:".yellow());
+            for problem in &problems {
+                eprintln!("  {}", problem);
+            }
+        }
+
+        write!(inv.stdout, "{}", script)?;
+        inv.stdout.flush()?;
+        // Written to a file, it is still shown, as a reminder to read it first
+        if !std::io::stdout().is_terminal() {
+            eprintln!("{}", script.trim_end().dimmed());
+            eprintln!("\nWrote the script ({} lines). Review it before running it.", script.lines().count());
+        }
+        Ok(if problems.is_empty() { 0 } else { 1 })
+    })
+}
+
+// `cron SCHEDULE [COMMAND]`: the crontab line for a schedule described in
+// words, explained without the model. With a command it can be installed
+// through `crontab -` after a copy of the current crontab is saved.
+pub(super) fn cron<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        let words = shellwords::split(inv.input).map_err(|e| anyhow::anyhow!("cron: {}", e))?;
+        let Some(schedule) = words.get(1).filter(|schedule| !schedule.trim().is_empty()) else {
+            eprintln!("cron: usage: cron \"SCHEDULE\" [COMMAND]");
+            return Ok(2);
+        };
+        let command = words[2..].join(" ");
+        let request = format!("Schedule: {}", schedule);
+
+        let client = &shell.llm_client;
+        let checked = llm::ask_checked(
+            request.clone(),
+            |prompt| async move { client.generate_command(crontab::TEMPLATE, &prompt).await },
+            |answer| {
+                let expression = answer.split_whitespace().collect::<Vec<_>>().join(" ");
+                invalid_shown(&expression, crontab::validate(&expression)).map(|()| expression)
+            },
+            |answer, e| llm::correction(&request, answer, e),
+        )
+        .await;
+        let expression = match checked {
+            Ok(Ok(expression)) => expression,
+            Ok(Err(_)) => {
+                eprintln!("cron: could not get a valid cron expression");
+                return Ok(1);
+            }
+            Err(e) => {
+                eprintln!("cron: {}", e);
+                return Ok(1);
+            }
+        };
+        telemetry::count("cron");
+
+        let line = if command.is_empty() { expression.clone() } else { format!("{} {}", expression, command) };
+        writeln!(inv.stdout, "{}", line)?;
+        inv.stdout.flush()?;
+        println!("{}", format!("Runs {}.", crontab::describe(&expression)).bright_green());
+        println!("{}", format!("Next: {}", crontab::next_runs(&expression, 3).join(", ")).dimmed());
+        if command.is_empty() {
+            println!("{}", "Add a command after the schedule to install it in your crontab.".dimmed());
+            return Ok(0);
+        }
+        if find_executable("crontab").is_none() {
+            eprintln!("{}", "cron: crontab is not installed here".yellow());
+            return Ok(1);
+        }
+
+        let current = match crontab::current() {
+            Ok(current) => current,
+            Err(e) => {
+                eprintln!("cron: {}", e);
+                return Ok(1);
+            }
+        };
+        if current.lines().any(|existing| existing.trim() == line) {
+            println!("It is already in your crontab.");
+            return Ok(0);
+        }
+        if !terminal::confirm("Install it in your crontab? [y/N] ")? {
+            println!("Not installed.");
+            return Ok(1);
+        }
+        let backup = match crontab::backup(&current) {
+            Ok(backup) => backup,
+            Err(e) => {
+                eprintln!("cron: {}", e);
+                return Ok(1);
+            }
+        };
+        let mut updated = current;
+        if !updated.is_empty() && !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        updated.push_str(&line);
+        updated.push('\n');
+        if let Err(e) = crontab::install(&updated) {
+            eprintln!("cron: {}", e);
+            return Ok(1);
+        }
+        telemetry::count("cron.installed");
+        println!("Installed. The previous crontab was saved to {}; restore it with `crontab {}`.", backup.display(), backup.display());
+        Ok(0)
+    })
+}
+
+// `regex DESCRIPTION`: a regular expression for grep -E and sed -E, shown
+// only once it gets the model's own examples right, with both invocations
+pub(super) fn regex<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        let words = shellwords::split(inv.input).map_err(|e| anyhow::anyhow!("regex: {}", e))?;
+        let description = words[1..].join(" ");
+        if description.is_empty() {
+            eprintln!("regex: usage: regex DESCRIPTION");
+            return Ok(2);
+        }
+        let request = format!("A regular expression to {}", description);
+
+        let client = &shell.llm_client;
+        let checked = llm::ask_checked(
+            request.clone(),
+            |prompt| async move { client.write_regex(&prompt).await },
+            |reply| {
+                let answer = regex_builder::parse(reply).map_err(|e| vec![e.to_string()])?;
+                let problems = regex_builder::problems(&answer);
+                if problems.is_empty() {
+                    return Ok(answer);
+                }
+                eprintln!("{} {} ({})", "Invalid:".yellow(), answer.pattern, problems.join("; "));
+                Err(problems)
+            },
+            |reply, problems| format!("{}\n\nYour previous answer was wrong: {}. Correct it.\n\nPrevious answer:\n{}", request, problems.join("; "), reply),
+        )
+        .await;
+        let answer = match checked {
+            Ok(Ok(answer)) => answer,
+            Ok(Err(_)) => {
+                eprintln!("regex: could not get a regex that passes its own examples");
+                return Ok(1);
+            }
+            Err(e) => {
+                eprintln!("regex: {}", e);
+                return Ok(1);
+            }
+        };
+        telemetry::count("regex");
+
+        writeln!(inv.stdout, "{}", answer.pattern)?;
+        inv.stdout.flush()?;
+        println!("{}", "Checked against examples it matches (✓) and doesn't (✗):".dimmed());
+        for example in &answer.matches {
+            println!("  {} {}", "✓".green(), example);
+        }
+        for example in &answer.non_matches {
+            println!("  {} {}", "✗".red(), example);
+        }
+        println!("grep: {}", format!("grep -E {} FILE", regex_builder::quote(&answer.pattern)).bright_cyan());
+        println!("sed:  {}", regex_builder::sed_command(&answer.pattern).bright_cyan());
+        Ok(0)
+    })
+}
+
+// `page COMMAND` (or `COMMAND | page`): the command's output in a pager
+// where the model can summarize what's on screen, explain an error line or
+// turn a table into CSV, without leaving the output
+pub(super) fn page<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move {
+        let command = inv.input.trim_start()["page".len()..].trim();
+        if command.is_empty() {
+            eprintln!("page: usage: page COMMAND");
+            return Ok(2);
+        }
+        let (output, status) = match page::capture(command).await {
+            Ok(captured) => captured,
+            Err(e) => {
+                eprintln!("page: {}", e);
+                return Ok(1);
+            }
+        };
+        shell.last_output = Some(output.clone());
+        if !shell.terminal.is_interactive() || !std::io::stdout().is_terminal() {
+            write!(inv.stdout, "{}", output)?;
+            inv.stdout.flush()?;
+            return Ok(status);
+        }
+        telemetry::count("page");
+
+        let mut pager = terminal::Pager::new(command, &output);
+        pager.open()?;
+        let result = browse(shell, &mut pager, command).await;
+        pager.close()?;
+        result?;
+        Ok(status)
+    })
+}
+
+async fn browse(shell: &mut Shell, pager: &mut terminal::Pager, command: &str) -> Result<()> {
+    loop {
+        pager.draw()?;
+        let Some(key) = terminal::read_key()? else {
+            return Ok(());
+        };
+        let (title, task, text) = match pager.handle(key)? {
+            terminal::PagerAction::None => continue,
+            terminal::PagerAction::Quit => return Ok(()),
+            terminal::PagerAction::Summarize => ("Summary", page::SUMMARIZE, pager.visible()),
+            terminal::PagerAction::Explain(line) => ("Explanation", page::EXPLAIN, pager.around(line)),
+            terminal::PagerAction::Table => {
+                table(shell, pager, command).await?;
+                continue;
+            }
+        };
+        pager.set_status("Asking the model...");
+        pager.draw()?;
+        match shell.llm_client.analyze_output(task, command, &text).await {
+            Ok(answer) => {
+                telemetry::count("page.action");
+                terminal::Pager::show(title, &answer)?;
+            }
+            Err(e) => pager.set_status(&format!("page: {}", e)),
+        }
+    }
+}
+
+// Saves the table on screen as CSV, asking the model again once if its
+// CSV doesn't hold together
+async fn table(shell: &mut Shell, pager: &mut terminal::Pager, command: &str) -> Result<()> {
+    let Some(file) = pager.prompt("Save CSV as: ", "table.csv")?.filter(|file| !file.trim().is_empty()) else {
+        return Ok(());
+    };
+    if shell.options.noclobber && std::path::Path::new(&file).exists() {
+        pager.set_status(&format!("page: {}: cannot overwrite existing file", file));
+        return Ok(());
+    }
+    pager.set_status("Asking the model...");
+    pager.draw()?;
+    let visible = pager.visible();
+    match ask_table(shell, command, &visible).await {
+        Ok(Ok((csv, rows))) => {
+            match std::fs::write(&file, csv) {
+                Ok(()) => pager.set_status(&format!("Saved {} row{} to {}", rows, if rows == 1 { "" } else { "s" }, file)),
+                Err(e) => pager.set_status(&format!("page: {}: {}", file, e)),
+            }
+            telemetry::count("page.action");
+        }
+        Ok(Err((_, problem))) => pager.set_status(&format!("page: could not get a valid table ({})", problem)),
+        Err(e) => pager.set_status(&format!("page: {}", e)),
+    }
+    Ok(())
+}
+
+// The output as CSV and its number of rows
+async fn ask_table(shell: &Shell, command: &str, output: &str) -> Result<std::result::Result<(String, usize), (String, anyhow::Error)>> {
+    let client = &shell.llm_client;
+    llm::ask_checked(
+        page::TABLE.to_string(),
+        |task| async move { client.analyze_output(&task, command, output).await },
+        page::csv_table,
+        |answer, e| format!("{} Your previous answer was invalid: {}. Correct it.\n\nPrevious answer:\n{}", page::TABLE, e, answer),
+    )
+    .await
+}
+
+// `as-json COMMAND` (or `COMMAND | as-json`): the command's output as JSON,
+// one object per row of a table, for jq or saving
+pub(super) fn as_json<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move { post_process(shell, inv, "as-json").await })
+}
+
+// `as-table COMMAND` (or `COMMAND | as-table`): the command's output as
+// aligned columns
+pub(super) fn as_table<'a>(shell: &'a mut Shell, inv: &'a mut Invocation) -> BuiltinFuture<'a> {
+    Box::pin(async move { post_process(shell, inv, "as-table").await })
+}
+
+async fn post_process(shell: &mut Shell, inv: &mut Invocation<'_>, name: &str) -> Result<i32> {
+    let command = inv.input.trim_start()[name.len()..].trim();
+    if command.is_empty() {
+        eprintln!("{}: usage: {} COMMAND", name, name);
+        return Ok(2);
+    }
+    let (output, status) = match structure::capture(command).await {
+        Ok(captured) => captured,
+        Err(e) => {
+            eprintln!("{}: {}", name, e);
+            return Ok(1);
+        }
+    };
+    shell.last_output = Some(output.clone());
+    if output.trim().is_empty() {
+        return Ok(status);
+    }
+
+    // The model is only asked when the layout isn't one the heuristics know
+    let structured = match structure::detect(&output) {
+        Some(structured) => structured,
+        None => {
+            let lines = output.lines().count();
+            if lines > structure::MAX_MODEL_LINES {
+                eprintln!("{}: the output isn't a layout {} recognizes and is too long ({} lines) to send to the model", name, name, lines);
+                return Ok(1);
+            }
+            match structure_with_model(shell, command, &output).await {
+                Ok(structured) => structured,
+                Err(e) => {
+                    eprintln!("{}: {}", name, e);
+                    return Ok(1);
+                }
+            }
+        }
+    };
+    telemetry::count(name);
+    let text = if name == "as-json" { structure::to_json(&structured) } else { structure::to_table(&structured) };
+    write!(inv.stdout, "{}", text)?;
+    inv.stdout.flush()?;
+    Ok(0)
+}
+
+// The records the model finds in the output, asked again once if its CSV
+// doesn't hold together
+async fn structure_with_model(shell: &mut Shell, command: &str, output: &str) -> Result<structure::Structured> {
+    eprintln!("{}", "Asking the model to find the records...".dimmed());
+    match ask_table(shell, command, output).await? {
+        Ok((csv, _)) => structure::from_csv(&csv),
+        Err((_, problem)) => Err(anyhow::anyhow!("could not get a valid table ({})", problem)),
+    }
+}
+
+pub(super) fn usage(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1).copied() {
+        None => {
+            let ledger = Ledger::load();
+            let session = shell.llm_client.usage();
+            println!("Today ({}): {} tokens", CONFIG.llm_host, ledger.tokens);
+            if CONFIG.token_price > 0.0 {
+                println!("Estimated cost: ${:.2}", ledger.dollars());
+            }
+            println!("This session: {} requests, {} tokens", session.requests, session.prompt_tokens + session.completion_tokens);
+            let show = |name: &str, limits: &Limits, unit: &str| {
+                let value = |limit: Option<f64>| limit.map(|l| format!("{}{}", unit, l)).unwrap_or_else(|| "none".to_string());
+                println!("{} budget: soft {}, hard {}", name, value(limits.soft), value(limits.hard));
+            };
+            show("Token", &CONFIG.token_budget, "");
+            show("Dollar", &CONFIG.dollar_budget, "$");
+            if let Err(e) = budget::check() {
+                println!("{}", e.to_string().yellow());
+            }
+            Ok(0)
+        }
+        Some("reset") => {
+            Ledger::reset()?;
+            println!("Today's LLM usage count was reset.");
+            Ok(0)
+        }
+        Some(_) => {
+            eprintln!("usage: usage [reset]");
+            Ok(2)
+        }
+    }
+}
+
+pub(super) fn stats(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let monitor = PERFORMANCE_MONITOR.lock().unwrap();
+    let show = |summary: Summary, noun: &str| {
+        if summary.count == 0 {
+            return format!("none {}", noun);
+        }
+        format!(
+            "{} {}, {} failed; avg {}, p95 {}, max {}",
+            summary.count,
+            noun,
+            summary.failed,
+            format_duration(summary.average),
+            format_duration(summary.p95),
+            format_duration(summary.max)
+        )
+    };
+    writeln!(inv.stdout, "Commands:      {}", show(monitor.commands(), "run"))?;
+    writeln!(inv.stdout, "LLM requests:  {}", show(monitor.llm_requests(), "sent"))?;
+    let slowest = monitor.slowest(5);
+    if !slowest.is_empty() {
+        writeln!(inv.stdout, "Slowest commands:")?;
+        for execution in slowest {
+            writeln!(inv.stdout, "  {:>9}  {}", format_duration(execution.duration), execution.command)?;
+        }
+    }
+    Ok(0)
+}
+
+pub(super) fn telemetry(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let result = match inv.parts[1..] {
+        [] | ["show"] => return show_usage(inv),
+        ["on"] => telemetry::set_collect(true),
+        ["off"] => telemetry::set_collect(false),
+        ["upload", "on"] if telemetry::upload_url().is_none() => {
+            eprintln!("telemetry: set LLM_SHELL_TELEMETRY_URL to where the counters should go first");
+            return Ok(1);
+        }
+        ["upload", "on"] => telemetry::set_upload(true),
+        ["upload", "off"] => telemetry::set_upload(false),
+        ["export"] => {
+            telemetry::flush();
+            writeln!(inv.stdout, "{}", serde_json::to_string_pretty(&telemetry::load().report())?)?;
+            return Ok(0);
+        }
+        ["reset"] => telemetry::reset(),
+        _ => {
+            eprintln!("usage: telemetry [show | on | off | upload on|off | export | reset]");
+            return Ok(2);
+        }
+    };
+    match result {
+        Ok(()) => Ok(0),
+        Err(e) => {
+            eprintln!("telemetry: {}", e);
+            Ok(1)
+        }
+    }
+}
+
+fn show_usage(inv: &mut Invocation) -> Result<i32> {
+    telemetry::flush();
+    let usage = telemetry::load();
+    let since = scheduler::local_time(usage.since);
+    let upload = match (usage.upload, telemetry::upload_url()) {
+        (true, Some(url)) => format!("uploaded daily to {}", url),
+        (true, None) => "upload on, but LLM_SHELL_TELEMETRY_URL is not set".to_string(),
+        (false, _) => "never uploaded".to_string(),
+    };
+    writeln!(
+        inv.stdout,
+        "Usage counted on this machine since {:04}-{:02}-{:02} (counting {}, {})",
+        since.tm_year + 1900,
+        since.tm_mon + 1,
+        since.tm_mday,
+        if usage.collect { "on" } else { "off" },
+        upload
+    )?;
+
+    writeln!(inv.stdout, "\n{}", "Translations".bold())?;
+    writeln!(
+        inv.stdout,
+        "  {} requested, {} accepted, {} rejected, {} left to edit, {} failed",
+        usage.get("translation.requested"),
+        usage.get("translation.accepted"),
+        usage.get("translation.rejected"),
+        usage.get("translation.suggested"),
+        usage.get("translation.failed")
+    )?;
+    if let Some(acceptance) = usage.acceptance() {
+        writeln!(
+            inv.stdout,
+            "  {:.0}% of confirmed translations accepted; {} of the accepted commands exited with an error",
+            acceptance * 100.0,
+            usage.get("translation.command_failed")
+        )?;
+    }
+
+    let mut features: Vec<(&str, u64)> = usage
+        .counters
+        .iter()
+        .filter(|(name, _)| !name.starts_with("translation."))
+        .map(|(name, count)| (name.as_str(), *count))
+        .chain(std::iter::once(("translation", usage.get("translation.requested"))))
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    features.sort_by_key(|(name, count)| (std::cmp::Reverse(*count), *name));
+    writeln!(inv.stdout, "\n{}", "Features".bold())?;
+    if features.is_empty() {
+        writeln!(inv.stdout, "  nothing counted yet")?;
+    }
+    let most = features.first().map(|(_, count)| *count).unwrap_or(1);
+    for (name, count) in features {
+        let bar = "█".repeat(((count * 30).div_ceil(most)) as usize);
+        writeln!(inv.stdout, "  {:<20} {:>6}  {}", name, count, bar.bright_blue())?;
+    }
+    Ok(0)
+}
+
+pub(super) fn config(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1).copied() {
+        None => shell.show_config(),
+        Some("set") if inv.parts.len() == 4 && inv.parts[2] == "confirm" => {
+            match policy::ConfirmationLevel::parse(inv.parts[3]) {
+                Ok(level) => shell.confirmation_policy.level = level,
+                Err(e) => {
+                    eprintln!("config: {}", e);
+                    return Ok(1);
+                }
+            }
+        },
+        Some("allow") if inv.parts.len() > 2 => {
+            let pattern = inv.input.splitn(3, char::is_whitespace).nth(2).unwrap_or("").trim();
+            let pattern = pattern.trim_matches('\'').trim_matches('"');
+            if let Err(e) = shell.confirmation_policy.allow(pattern) {
+                eprintln!("config: {}", e);
+                return Ok(1);
+            }
+        },
+        Some("disallow") if inv.parts.len() > 2 => {
+            let pattern = inv.input.splitn(3, char::is_whitespace).nth(2).unwrap_or("").trim();
+            let pattern = pattern.trim_matches('\'').trim_matches('"');
+            if !shell.confirmation_policy.disallow(pattern) {
+                eprintln!("config: {}: not in the allowlist", pattern);
+                return Ok(1);
+            }
+        },
+        _ => {
+            eprintln!("config: usage: config [set confirm LEVEL | allow REGEX | disallow REGEX]");
+            return Ok(2);
+        }
+    }
+    Ok(0)
+}
+
+pub(super) fn demo(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1).copied() {
+        Some("on") => shell.options.observemode = true,
+        Some("off") => shell.options.observemode = false,
+        None => {},
+        Some(other) => {
+            eprintln!("demo: invalid argument '{}' (use on or off)", other);
+            return Ok(2);
+        }
+    }
+    if shell.options.observemode {
+        println!("Demo mode is {}: commands are explained but never executed", "on".bright_yellow());
+    } else {
+        println!("Demo mode is off");
+    }
+    Ok(0)
+}
+
+pub(super) fn undo(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.get(1) == Some(&"list") {
+        for (i, entry) in shell.undo_stack.entries().iter().enumerate().rev() {
+            println!("{:3}  {}\n     undo: {}", i + 1, entry.command, entry.hint.bright_cyan());
+        }
+    } else if let Some(entry) = shell.undo_stack.pop() {
+        // Only ever propose the reversal, never run it
+        println!("To undo: {}", entry.command);
+        println!("Run:     {}", entry.hint.bright_cyan());
+    } else {
+        println!("undo: nothing to undo");
+        return Ok(1);
+    }
+    Ok(0)
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{FromRawFd, RawFd};
+use crate::shell::Shell;
+use super::Invocation;
+
+pub(super) fn echo(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() > 1 {
+        // Check for -n option (no newline)
+        let no_newline = inv.parts[1] == "-n";
+        let start_idx = if no_newline { 2 } else { 1 };
+
+        // The words arrive expanded, so they are only joined
+        let text = inv.parts[start_idx..].join(" ");
+
+        if no_newline {
+            write!(inv.stdout, "{}", text)?;
+        } else {
+            writeln!(inv.stdout, "{}", text)?;
+        }
+    } else {
+        // Just echo a newline
+        writeln!(inv.stdout)?;
+    }
+    inv.stdout.flush()?;
+    Ok(0)
+}
+
+pub(super) fn printf(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() > 1 {
+        // Very simplified printf implementation
+        let format_str = inv.parts[1].to_string();
+        let args = &inv.parts[2..];
+
+        // Basic % substitution (simplified)
+        let mut result = format_str.clone();
+        for arg in args {
+            if let Some(pos) = result.find('%') {
+                let end = pos + 2.min(result.len() - pos);
+                result.replace_range(pos..end, arg);
+            }
+        }
+
+        write!(inv.stdout, "{}", result)?;
+        inv.stdout.flush()?;
+    } else {
+        eprintln!("printf: missing format string");
+        return Ok(2);
+    }
+    Ok(0)
+}
+
+pub(super) fn read(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() > 1 {
+        // -p PROMPT prints a prompt, -u FD reads from a descriptor such as a coprocess's
+        let mut var_start = 1;
+        let mut fd_input = None;
+        loop {
+            match inv.parts.get(var_start..var_start + 2) {
+                Some(["-p", prompt]) if inv.parts.len() > var_start + 2 => {
+                    print!("{}", prompt);
+                    std::io::stdout().flush().unwrap_or(());
+                }
+                Some(["-u", fd]) => match fd.parse::<RawFd>().ok().and_then(|fd| nix::unistd::dup(fd).ok()) {
+                    // SAFETY: the duplicate belongs to this builtin and is closed with the File
+                    Some(copy) => fd_input = Some(unsafe { File::from_raw_fd(copy) }),
+                    None => {
+                        eprintln!("read: {}: invalid file descriptor", fd);
+                        return Ok(1);
+                    }
+                },
+                _ => break,
+            }
+            var_start += 2;
+        }
+        if let Some(file) = fd_input {
+            inv.stdin = Box::new(file);
+        }
+
+        // Byte at a time, so nothing past the newline is consumed
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        let mut newline = false;
+        while inv.stdin.read(&mut byte)? == 1 {
+            if byte[0] == b'\n' {
+                newline = true;
+                break;
+            }
+            line.push(byte[0]);
+        }
+        // Like bash, end of input is a failure so `while read` loops stop
+        let status = if newline || !line.is_empty() { 0 } else { 1 };
+        let line = String::from_utf8_lossy(&line).trim().to_string();
+
+        // Assign to variables
+        if inv.parts.len() > var_start {
+            let var_name = inv.parts[var_start];
+            std::env::set_var(var_name, line);
+        }
+        Ok(status)
+    } else {
+        eprintln!("read: missing variable name");
+        Ok(2)
+    }
+}
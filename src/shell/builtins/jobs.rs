@@ -0,0 +1,118 @@
+use anyhow::Result;
+use crate::shell::Shell;
+use super::Invocation;
+
+pub(super) fn jobs(shell: &mut Shell, _inv: &mut Invocation) -> Result<i32> {
+    match shell.job_control.list_jobs() {
+        Ok(_) => Ok(0),
+        Err(e) => {
+            eprintln!("Error listing jobs: {}", e);
+            Ok(1)
+        }
+    }
+}
+
+pub(super) fn fg(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let args = inv.parts.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    match shell.job_control.bring_to_foreground(&args) {
+        Ok(_) => Ok(0),
+        Err(e) => {
+            eprintln!("Error bringing job to foreground: {}", e);
+            Ok(1)
+        }
+    }
+}
+
+pub(super) fn bg(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let args = inv.parts.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    match shell.job_control.continue_in_background(&args) {
+        Ok(_) => Ok(0),
+        Err(e) => {
+            eprintln!("Error continuing job in background: {}", e);
+            Ok(1)
+        }
+    }
+}
+
+pub(super) fn kill(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() < 2 {
+        eprintln!("kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]");
+        return Ok(2);
+    }
+    
+    // Handle -l option to list signals
+    if inv.parts[1] == "-l" {
+        println!("HUP INT QUIT ILL TRAP ABRT BUS FPE KILL USR1 SEGV USR2 PIPE ALRM TERM STKFLT CHLD CONT STOP TSTP TTIN TTOU URG XCPU XFSZ VTALRM PROF WINCH POLL PWR SYS");
+        return Ok(0);
+    }
+    
+    // Parse signal if provided
+    let mut signal = 15; // Default to SIGTERM
+    let mut arg_start = 1;
+    
+    if inv.parts[1].starts_with('-') {
+        if let Ok(sig) = inv.parts[1][1..].parse::<i32>() {
+            signal = sig;
+            arg_start = 2;
+        } else if inv.parts[1] == "-KILL" || inv.parts[1] == "-9" {
+            signal = 9;
+            arg_start = 2;
+        } else if inv.parts[1] == "-HUP" || inv.parts[1] == "-1" {
+            signal = 1;
+            arg_start = 2;
+        } else if inv.parts[1] == "-INT" || inv.parts[1] == "-2" {
+            signal = 2;
+            arg_start = 2;
+        } else if inv.parts[1] == "-TERM" || inv.parts[1] == "-15" {
+            signal = 15;
+            arg_start = 2;
+        }
+    }
+    
+    // Send signal to each PID
+    let mut status = 0;
+    for pid_str in &inv.parts[arg_start..] {
+        if let Ok(pid) = pid_str.parse::<i32>() {
+            unsafe {
+                if libc::kill(pid, signal) != 0 {
+                    eprintln!("kill: ({}) - No such process", pid);
+                    status = 1;
+                }
+            }
+        } else {
+            eprintln!("kill: ({}) - Invalid process id", pid_str);
+            status = 1;
+        }
+    }
+    
+    Ok(status)
+}
+
+pub(super) fn wait(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() > 1 {
+        // Like bash, the status is that of the last process waited for
+        let mut last = 0;
+        for pid_str in &inv.parts[1..] {
+            if let Ok(pid) = pid_str.parse::<i32>() {
+                let mut status = 0;
+                if unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+                    last = 127;
+                } else if libc::WIFEXITED(status) {
+                    last = libc::WEXITSTATUS(status);
+                } else if libc::WIFSIGNALED(status) {
+                    last = 128 + libc::WTERMSIG(status);
+                }
+            } else {
+                eprintln!("wait: {}: invalid process id", pid_str);
+                last = 2;
+            }
+        }
+        return Ok(last);
+    } else {
+        // Wait for all children
+        unsafe {
+            libc::wait(std::ptr::null_mut());
+        }
+    }
+    Ok(0)
+}
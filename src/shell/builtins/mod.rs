@@ -0,0 +1,553 @@
+use anyhow::Result;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::pin::Pin;
+use std::sync::Arc;
+use crate::shell::Shell;
+
+mod aliases;
+mod context;
+mod control;
+mod files;
+mod integrations;
+mod io;
+mod jobs;
+mod limits;
+mod navigation;
+mod vars;
+
+// Declarative description of a builtin; `help`, `is_builtin`, demo mode and
+// completion are all driven from it
+#[derive(Clone, Copy)]
+pub struct BuiltinSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub usage: &'static str,
+    pub summary: &'static str,
+    // Longer description and flags for `help NAME`
+    pub details: &'static str,
+    // Only inspects or navigates, so it stays usable in demo mode
+    pub demo_safe: bool,
+    // Takes free text or a command line, which it reads from the input as
+    // typed; it isn't split into words, redirected or piped
+    pub raw: bool,
+    // Argument kinds for completion, in the `COMMAND KIND...` spec format without
+    // the command; empty completes file names
+    pub completion: &'static str,
+}
+
+// One run of a builtin: the command line and the streams it should use, so
+// pipelines and redirections can hand it something other than the terminal
+pub struct Invocation<'a> {
+    pub input: &'a str,
+    pub parts: Vec<&'a str>,
+    pub stdin: Box<dyn Read + 'a>,
+    pub stdout: Box<dyn Write + 'a>,
+}
+
+impl<'a> Invocation<'a> {
+    // For raw builtins: the parts are the input split on whitespace
+    pub fn new(input: &'a str) -> Self {
+        Self::with_words(input, input.split_whitespace().collect())
+    }
+
+    // The parts are the command's words, expanded and unquoted by the parser
+    pub fn with_words(input: &'a str, parts: Vec<&'a str>) -> Self {
+        Invocation {
+            input,
+            parts,
+            stdin: Box::new(std::io::stdin()),
+            stdout: Box::new(std::io::stdout()),
+        }
+    }
+}
+
+// Resolves to the builtin's exit status
+pub type BuiltinFuture<'a> = Pin<Box<dyn Future<Output = Result<i32>> + 'a>>;
+
+pub trait Builtin: Send + Sync {
+    fn spec(&self) -> &BuiltinSpec;
+
+    fn run<'a>(&'a self, shell: &'a mut Shell, invocation: &'a mut Invocation<'_>) -> BuiltinFuture<'a>;
+}
+
+// Most builtins finish immediately; the ones that run other commands
+// (source, eval, time) need the async execution pipeline
+type SyncHandler = fn(&mut Shell, &mut Invocation) -> Result<i32>;
+type AsyncHandler = for<'a, 'b> fn(&'a mut Shell, &'a mut Invocation<'b>) -> BuiltinFuture<'a>;
+
+#[derive(Clone, Copy)]
+enum Handler {
+    Sync(SyncHandler),
+    Async(AsyncHandler),
+}
+
+// A builtin that ships with the shell: its spec plus the function implementing it
+#[derive(Clone, Copy)]
+struct Core {
+    spec: BuiltinSpec,
+    handler: Handler,
+}
+
+impl Core {
+    const fn aliases(mut self, aliases: &'static [&'static str]) -> Self {
+        self.spec.aliases = aliases;
+        self
+    }
+
+    const fn completes(mut self, completion: &'static str) -> Self {
+        self.spec.completion = completion;
+        self
+    }
+
+    const fn raw(mut self) -> Self {
+        self.spec.raw = true;
+        self
+    }
+}
+
+impl Builtin for Core {
+    fn spec(&self) -> &BuiltinSpec {
+        &self.spec
+    }
+
+    fn run<'a>(&'a self, shell: &'a mut Shell, invocation: &'a mut Invocation<'_>) -> BuiltinFuture<'a> {
+        match self.handler {
+            Handler::Sync(handler) => Box::pin(std::future::ready(handler(shell, invocation))),
+            Handler::Async(handler) => handler(shell, invocation),
+        }
+    }
+}
+
+const fn spec(
+    name: &'static str,
+    handler: SyncHandler,
+    usage: &'static str,
+    summary: &'static str,
+    details: &'static str,
+) -> Core {
+    Core {
+        spec: BuiltinSpec { name, aliases: &[], usage, summary, details, demo_safe: false, raw: false, completion: "" },
+        handler: Handler::Sync(handler),
+    }
+}
+
+const fn safe(
+    name: &'static str,
+    handler: SyncHandler,
+    usage: &'static str,
+    summary: &'static str,
+    details: &'static str,
+) -> Core {
+    let mut core = spec(name, handler, usage, summary, details);
+    core.spec.demo_safe = true;
+    core
+}
+
+const fn spec_async(
+    name: &'static str,
+    handler: AsyncHandler,
+    usage: &'static str,
+    summary: &'static str,
+    details: &'static str,
+) -> Core {
+    Core {
+        spec: BuiltinSpec { name, aliases: &[], usage, summary, details, demo_safe: false, raw: false, completion: "" },
+        handler: Handler::Async(handler),
+    }
+}
+
+const CORE: &[Core] = &[
+    safe("cd", navigation::cd, "cd [DIR | @BOOKMARK]", "Change directory",
+        "Without DIR, changes to $HOME. `~` expands to the home directory and\n\
+         @NAME jumps to a bookmark of the active workspace.")
+        .completes("dirs"),
+    spec("pushd", navigation::pushd, "pushd DIR", "Change directory and push the old one on the stack", "")
+        .completes("dirs"),
+    spec("popd", navigation::popd, "popd", "Return to the directory on top of the stack", ""),
+    safe("dirs", navigation::dirs, "dirs", "Show the directory stack", ""),
+    safe("pwd", navigation::pwd, "pwd", "Print the working directory", ""),
+    spec("export", vars::export, "export [-n] [NAME[=VALUE]...]", "Export variables to commands",
+        "Without arguments, lists the environment.\n\
+         -n    keep the variables' values but stop passing them to commands")
+        .completes("variables")
+        .raw(),
+    safe("printenv", vars::printenv, "printenv [NAME...]", "Print exported variables", "")
+        .completes("variables"),
+    spec("unset", vars::unset, "unset NAME...", "Remove variables", "Readonly variables cannot be unset.")
+        .completes("variables"),
+    spec("declare", vars::declare, "declare [-airxp] [NAME[=VALUE]...]", "Set variable attributes",
+        "-a    array, assigned as NAME=(a b c) and read as ${NAME[1]} or ${NAME[@]}\n\
+         -i    integer; assignments are evaluated like $((...))\n\
+         -r    readonly\n\
+         -x    export\n\
+         -p    print the variables as declare commands\n\
+         With only flags, lists the variables that have those attributes.")
+        .completes("variables")
+        .raw(),
+    spec("typeset", vars::declare, "typeset [-airxp] [NAME[=VALUE]...]", "Same as declare", "")
+        .completes("variables")
+        .raw(),
+    spec("readonly", vars::declare, "readonly [-p] [NAME[=VALUE]...]", "Make variables unchangeable",
+        "Assigning to or unsetting a readonly variable is an error.\n\
+         Without names, lists the readonly variables.")
+        .completes("variables")
+        .raw(),
+    safe("shopt", vars::shopt, "shopt [-s|-u] [-p] [--save] [NAME...]", "Show or toggle shell options",
+        "Options: autocd, confirmations, histappend, ignoreeof, nl-detection, observemode.\n\
+         -s    enable the options (or list the enabled ones)\n\
+         -u    disable the options (or list the disabled ones)\n\
+         -p    print as shopt commands\n\
+         --save  also record the setting in ~/.llm_shellrc")
+        .completes("{autocd,confirmations,histappend,ignoreeof,nl-detection,observemode}"),
+    spec("set", vars::set, "set [-o | +o NAME | -C | +C | -u | +u]", "List shell variables or set shell options",
+        "Without arguments, lists all shell variables.\n\
+         -o NAME  turn an option on (+o turns it off); -o alone lists them\n\
+         -C       same as -o noclobber: `>` refuses to overwrite existing files,\n\
+                  `>|` still does\n\
+         -u       same as -o nounset: expanding an unset variable is an error\n\
+         -o posix lines are read as a POSIX shell would: no natural-language\n\
+                  detection, `?` questions, suggestions, autocd or `| page` and\n\
+                  `| as-json` forms, and nothing reaches the model (--posix at startup)")
+        .completes("{-o,+o} {noclobber,nounset,posix}"),
+    spec("envsnap", vars::envsnap, "envsnap [list | save NAME | diff NAME [OTHER]]", "Save the environment and show what changed since",
+        "save NAME        record the current environment as NAME\n\
+         diff NAME        show variables added (+), removed (-) or changed (~) since\n\
+         diff NAME OTHER  compare two saved snapshots\n\
+         Snapshots are kept in $XDG_STATE_HOME/llm-shell/envsnap in the format of\n\
+         `env -0`, so `env -0 > .../envsnap/bash` in another shell can be compared too.")
+        .completes("{list,save,diff} none"),
+    spec("envexport", vars::envexport, "envexport [--format bash|fish|json] [> FILE]", "Export the session's variables and aliases for another shell",
+        "Prints the shell variables and arrays, the environment variables set or\n\
+         changed since the shell started, and the aliases, in a form bash or zsh\n\
+         (the default), fish or a JSON reader can load, e.g.\n\
+         `envexport --format fish > ~/llm-shell.fish` then `source ~/llm-shell.fish`\n\
+         in fish. Aliases that take arguments ($1, $@) become functions.")
+        .completes("{--format} {bash,fish,json}"),
+    safe("echo", io::echo, "echo [-n] [TEXT...]", "Display text", "-n    do not print the trailing newline"),
+    safe("printf", io::printf, "printf FORMAT [ARGS...]", "Print formatted text", ""),
+    safe("jobs", jobs::jobs, "jobs", "List background jobs", ""),
+    spec("fg", jobs::fg, "fg [JOB]", "Bring a job to the foreground", "")
+        .completes("none"),
+    spec("bg", jobs::bg, "bg [JOB]", "Continue a job in the background", "")
+        .completes("none"),
+    spec("kill", jobs::kill, "kill [-SIGNAL] PID... | kill -l", "Send a signal to processes",
+        "-l    list signal names\n\
+         -N, -NAME  send signal N or NAME (HUP, INT, KILL, TERM) instead of TERM")
+        .completes("none"),
+    spec("wait", jobs::wait, "wait [PID...]", "Wait for processes to finish", "")
+        .completes("none"),
+    safe("alias", aliases::alias, "alias [NAME[=VALUE]]", "List or set aliases",
+        "An alias that uses $1-$9, $@, $* or $# takes its arguments like a function:\n\
+         alias gco='git checkout $1' runs `gco main` as `git checkout main`. Other\n\
+         aliases have their arguments appended.")
+        .completes("none")
+        .raw(),
+    spec("unalias", aliases::unalias, "unalias NAME", "Remove an alias", "")
+        .completes("none"),
+    spec("migrate", aliases::migrate, "migrate [-n] [zsh|fish]", "Import aliases from zsh or fish configuration",
+        "Reads ~/.zshrc and ~/.zsh_aliases, or fish's config.fish, conf.d, functions\n\
+         and universal abbreviations, and reports what was imported and what wasn't\n\
+         (multi-line functions, suffix aliases, abbreviations expanded anywhere). Imported\n\
+         aliases are saved to ~/.llm_shell_aliases; aliases that already exist are kept.\n\
+         -n    only report what would be imported")
+        .completes("{zsh,fish,-n} none"),
+    safe("history", control::history, "history", "View command history", ""),
+    spec("touch", files::touch, "touch FILE...", "Create files or update their timestamps", ""),
+    spec("mkdir", files::mkdir, "mkdir [-p] DIR...", "Create directories", "-p    create parent directories as needed")
+        .completes("dirs"),
+    spec("mkfifo", files::mkfifo, "mkfifo [-m MODE] NAME... | mkfifo -t", "Create named pipes",
+        "-m MODE  octal permissions (default 666, less the umask)\n\
+         -t       create one in a private temporary directory and print its path; it\n\
+                  is removed when the shell exits\n\
+         A command that waits a while to open a FIFO nobody else has opened gets a\n\
+         hint saying so.")
+        .completes("none"),
+    spec("restore", files::restore, "restore [N|FILE]", "Restore a file backed up before a redirection overwrote it",
+        "Without arguments, lists this session's backups. `shopt -s backup` makes\n\
+         commands flagged as destructive copy the files their `>` redirections\n\
+         would overwrite; the copies are deleted when the shell exits.")
+        .completes("files"),
+    spec("rmdir", files::rmdir, "rmdir DIR...", "Remove empty directories", "")
+        .completes("dirs"),
+    safe("exit", control::exit, "exit [N]", "Exit the shell", "")
+        .aliases(&["bye"]),
+    safe("logout", control::logout, "logout [N]", "Exit a login shell", ""),
+    spec_async("source", control::source, "source FILE", "Run commands from a file in the current shell", "")
+        .aliases(&["."]),
+    spec_async("eval", control::eval, "eval ARGS...", "Run the arguments as a command", "")
+        .completes("commands")
+        .raw(),
+    safe("type", control::type_, "type [-p] NAME...", "Describe how a name would be interpreted",
+        "Builtins come first, then aliases (shown with their definitions), then\n\
+         programs in the hash table or on PATH. -p prints only the file that would\n\
+         run, and nothing for builtins and aliases.")
+        .completes("commands"),
+    safe("which", control::which, "which NAME...", "Show what running a name would run",
+        "Follows the shell's own lookup: builtins, aliases (and what their first word\n\
+         runs), then the hash table and PATH.")
+        .completes("commands"),
+    safe("fixterm", control::fixterm, "fixterm", "Fix a terminal a crashed program left unusable",
+        "Restores the terminal settings the shell started with, leaves the alternate\n\
+         screen, shows the cursor and turns off colors and mouse reporting. This also\n\
+         happens on its own when a command is killed by a signal or leaves the\n\
+         terminal in raw mode.")
+        .completes("none"),
+    spec("hash", control::hash, "hash [-r] [NAME...]", "Show or update where programs were found on PATH",
+        "Each program is searched for on PATH once and then run from the remembered\n\
+         location until PATH changes. NAME looks a program up now, -r forgets them\n\
+         all. The most used programs are looked up at startup, in the background.")
+        .completes("commands"),
+    safe("help", control::help, "help [NAME]", "Show help for all builtins or for one", "")
+        .completes("commands"),
+    safe("true", control::true_, "true", "Do nothing, successfully", ""),
+    safe("false", control::false_, "false", "Do nothing, unsuccessfully", ""),
+    spec("break", control::loop_control, "break [N]", "Leave the enclosing loop or select menu",
+        "N leaves N enclosing loops."),
+    spec("continue", control::loop_control, "continue [N]", "Start the next iteration of the enclosing loop",
+        "N resumes the Nth enclosing loop."),
+    safe("test", control::test, "test EXPR | [ EXPR ]", "Evaluate a condition",
+        "-f FILE   FILE is a regular file\n\
+         -d DIR    DIR is a directory\n\
+         A = B     the strings are equal\n\
+         A != B    the strings differ")
+        .aliases(&["["]),
+    spec_async("time", control::time, "time COMMAND", "Time a command", "")
+        .completes("commands")
+        .raw(),
+    spec("umask", limits::umask, "umask [MODE]", "Show or set the file creation mask", "MODE is octal, e.g. 022.")
+        .completes("none"),
+    spec("ulimit", limits::ulimit, "ulimit [-a]", "Show resource limits", "-a    show all limits")
+        .completes("none"),
+    spec("read", io::read, "read [-p PROMPT] [-u FD] NAME", "Read a line into a variable",
+        "-p PROMPT  print PROMPT first\n\
+         -u FD      read from descriptor FD, e.g. ${COPROC[0]}")
+        .completes("none"),
+    spec("exec", control::exec, "exec COMMAND [ARGS...]", "Replace the shell with a command",
+        "The session and history are saved first, and the terminal settings and\n\
+         signal handling are put back the way they were when the shell started, so\n\
+         `exec bash` gets a working terminal even if something here went wrong.")
+        .completes("commands")
+        .raw(),
+    spec("copy", integrations::copy, "copy [-c | TEXT]", "Copy the last captured output, the last command or text to the clipboard", "")
+        .raw(),
+    spec("paste-run", integrations::paste_run, "paste-run", "Run the clipboard contents after confirmation", ""),
+    spec("tmux-pane", integrations::tmux_pane, "tmux-pane [PANE | off]", "Send translated commands to a tmux pane", "")
+        .completes("none"),
+    spec("target", integrations::target, "target [set URL | clear]", "Run translated commands on an ssh:// host", "")
+        .completes("{set,clear} none"),
+    spec("timeout-run", integrations::timeout_run, "timeout-run DURATION COMMAND", "Run a command, stopping it after DURATION",
+        "DURATION is like 30s, 5m or 1h.")
+        .completes("none commands")
+        .raw(),
+    safe("undo", integrations::undo, "undo [list]", "Show how to reverse the last destructive command",
+        "list  show all recorded undo hints")
+        .completes("{list}"),
+    safe("demo", integrations::demo, "demo [on | off]", "Explain commands without executing them", "")
+        .completes("{on,off}"),
+    spec("remember", context::remember, "remember [TEXT]", "Remember a fact about this project for the LLM",
+        "Facts and translations that worked are kept per project (the enclosing git\n\
+         work tree, or the directory) and given to the model as context there.\n\
+         Without TEXT, lists the remembered facts.")
+        .completes("none")
+        .raw(),
+    spec("forget", context::forget, "forget [N|TEXT|--all]", "Forget remembered facts, or the session's LLM context",
+        "Without arguments, clears the session's LLM context like `context clear`.\n\
+         N       the fact numbered N in `remember`'s list\n\
+         TEXT    every fact containing TEXT\n\
+         --all   all facts and remembered translations")
+        .completes("none")
+        .raw(),
+    spec("context", context::context, "context [show|clear]", "Show or clear what is shared with the LLM",
+        "show    print the context sent with requests (the default)\n\
+         clear   forget this session's commands, chat turns and their summary")
+        .completes("{show,clear}"),
+    spec("good", context::rate, "good", "Rate the last translated command as right",
+        "Ratings are kept in $XDG_STATE_HOME/llm-shell/feedback. With\n\
+         LLM_SHELL_FEEDBACK_EXAMPLES=N the latest N are added to translation prompts.")
+        .completes("none"),
+    spec("bad", context::rate, "bad [NOTE]", "Rate the last translated command as wrong",
+        "NOTE says what was wrong, e.g. bad \"wrong flag\". Ratings are kept in\n\
+         $XDG_STATE_HOME/llm-shell/feedback; with LLM_SHELL_FEEDBACK_EXAMPLES=N the\n\
+         latest N are added to translation prompts.")
+        .completes("none")
+        .raw(),
+    spec_async("wizard", integrations::wizard, "wizard {ffmpeg|jq|awk}", "Build a command for a tricky tool step by step",
+        "Asks for the input file and what you want, has the model write the command\n\
+         with a prompt specific to the tool and checks its syntax (jq filters are\n\
+         compiled) before it is shown, confirmed and run like a translation.")
+        .completes("{ffmpeg,jq,awk}"),
+    spec_async("find!", integrations::search, "find! DESCRIPTION", "Find files described in plain English",
+        "Writes an fd command (find when fd isn't installed) with a prompt made for\n\
+         file searches, e.g. `find! rust files touched this week over 1MB`. Only\n\
+         options that list files are accepted: no -delete, -exec, pipes or\n\
+         redirections. Start the next line with `|` to pipe the files into a\n\
+         command, e.g. `| xargs du -h`.")
+        .completes("none")
+        .raw(),
+    spec_async("pipe!", integrations::pipe, "pipe! GOAL", "Build a pipeline with the model one stage at a time",
+        "The model proposes the first stage, which is run and the start of its output\n\
+         shown; Enter keeps it and asks for the next, d keeps it and finishes, q\n\
+         quits, and anything else is sent back as what to change. Stages must only\n\
+         read: read-only commands and filters such as jq, awk, sed and sort that\n\
+         don't write files or run commands. The pipeline is left at the prompt.")
+        .completes("none")
+        .raw(),
+    spec_async("explain-script", integrations::explain_script, "explain-script FILE", "Walk through a shell script before running it",
+        "Sends the script to the model a part at a time, split between top-level\n\
+         commands so blocks and functions stay whole, and prints each part with\n\
+         a walkthrough keyed to its line numbers. Ends with the lines that reach\n\
+         the network, delete data or use sudo, found without the model. Parts\n\
+         already explained this session aren't sent again.")
+        .completes("files"),
+    spec_async("genscript", integrations::genscript, "genscript DESCRIPTION [> FILE]", "Write a script for a task to a file, without running it",
+        "Has the model write a complete bash script with a shebang, `set -euo\n\
+         pipefail` and comments, e.g. `genscript rotate logs older than 7 days >\n\
+         rotate.sh`. The script is checked with `bash -n` and shellcheck (when\n\
+         installed) and sent back once with any problems. Without a file it is\n\
+         printed. It is never run.")
+        .completes("none"),
+    spec_async("cron", integrations::cron, "cron \"SCHEDULE\" [COMMAND]", "Turn a schedule in words into a crontab line and install it",
+        "Has the model write the cron expression for a schedule, e.g. `cron\n\
+         \"every weekday at 9am\" ~/bin/report`, checks it and explains it in\n\
+         words with its next run times. With a command it offers to add the line\n\
+         to your crontab through `crontab -`, saving a copy of the current one\n\
+         under $XDG_STATE_HOME/llm-shell/crontab first.")
+        .completes("none")
+        .raw(),
+    spec_async("regex", integrations::regex, "regex DESCRIPTION", "Write a regular expression tested against examples",
+        "Has the model write a POSIX extended regex with examples it should and\n\
+         shouldn't match, e.g. `regex \"match IPv4 addresses\"`. It is only shown\n\
+         once it compiles and gets every example right as a whole-string match\n\
+         (a wrong answer is sent back once), with the grep -E and sed -E\n\
+         commands that use it.")
+        .completes("none")
+        .raw(),
+    spec_async("page", integrations::page, "page COMMAND", "Page a command's output with LLM actions",
+        "Runs the command and shows its output and errors in a pager; `COMMAND |\n\
+         page` does the same. ↑/↓ or j/k scroll, Space/b page, g/G jump to the\n\
+         ends, / searches and n finds the next match. s summarizes the lines on\n\
+         screen, e explains the line the search found or the first error line on\n\
+         screen, and t saves the table on screen as CSV. q quits.")
+        .completes("commands")
+        .raw(),
+    spec_async("as-json", integrations::as_json, "as-json COMMAND", "Print a command's output as JSON",
+        "Runs the command and turns its output into JSON: a table like df or ps\n\
+         prints becomes an array with an object per row, `key: value` or\n\
+         KEY=value lines an object, and one word per line an array. Numbers\n\
+         become JSON numbers. When the layout isn't one of these, the model is\n\
+         asked to find the records. `COMMAND | as-json` does the same, and\n\
+         `COMMAND | as-json | jq ...` passes the JSON on.")
+        .completes("commands")
+        .raw(),
+    spec_async("as-table", integrations::as_table, "as-table COMMAND", "Print a command's output as aligned columns",
+        "Runs the command and prints its output as columns two spaces apart,\n\
+         recognizing the same layouts as as-json, JSON included, and asking the\n\
+         model when it can't. `COMMAND | as-table` does the same.")
+        .completes("commands")
+        .raw(),
+    spec("usage", integrations::usage, "usage [reset]", "Show today's LLM token use against the budget",
+        "reset   start today's count over, lifting the hard limit")
+        .completes("{reset}"),
+    safe("stats", integrations::stats, "stats", "Show how long recent commands and LLM requests took",
+        "Commands and LLM round trips are timed separately, so waiting for the model\n\
+         never counts as command time. Covers the last 100 of each this session;\n\
+         every command's timing is also kept in $XDG_STATE_HOME/llm-shell/timings.")
+        .completes("none"),
+    spec("telemetry", integrations::telemetry, "telemetry [show | on | off | upload on|off | export | reset]", "Show which features you use; control counting and upload",
+        "Counts which builtins and features are used and how often translations are\n\
+         accepted or rejected, in $XDG_STATE_HOME/llm-shell/telemetry.json. Only\n\
+         feature names and counts are kept, never commands, paths or requests.\n\
+         on/off           start or stop counting (on by default, purely local)\n\
+         upload on|off    send the counters to LLM_SHELL_TELEMETRY_URL once a day (off by default)\n\
+         export           print exactly what an upload would send\n\
+         reset            start the counts over")
+        .completes("{show,on,off,upload,export,reset} {on,off}"),
+    spec("config", integrations::config, "config [set confirm LEVEL | allow REGEX | disallow REGEX]", "Show settings; edit the confirmation policy",
+        "LEVEL is never, llm, destructive or always.\n\
+         allow/disallow add or remove patterns that never need confirmation.")
+        .completes("{set,allow,disallow} none")
+        .raw(),
+    spec("session", navigation::session, "session [list | restore [ID] | delete ID]", "List, restore or delete saved sessions", "")
+        .completes("{list,restore,delete} none"),
+    spec("workspace", navigation::workspace, "workspace [list | use NAME | off]", "List or switch settings profiles",
+        "Workspaces are defined in ~/.llm_shell_workspaces.")
+        .completes("{list,use,off} none"),
+    spec("schedule", integrations::schedule, "schedule \"CRON\" COMMAND | list | remove ID", "Run a command on a cron schedule", "")
+        .completes("{list,remove} none")
+        .raw(),
+    spec("in", integrations::run_in, "in DURATION COMMAND", "Run a command once after a delay", "")
+        .completes("none commands")
+        .raw(),
+];
+
+// The builtins a shell knows about, looked up by name or alias
+#[derive(Clone)]
+pub struct Registry {
+    builtins: Vec<Arc<dyn Builtin>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        let mut registry = Registry { builtins: Vec::new() };
+        for core in CORE {
+            registry.register(Arc::new(*core));
+        }
+        registry
+    }
+
+    // Adds a builtin, replacing an existing one with the same name
+    pub fn register(&mut self, builtin: Arc<dyn Builtin>) {
+        self.builtins.retain(|existing| existing.spec().name != builtin.spec().name);
+        self.builtins.push(builtin);
+    }
+
+    pub fn find(&self, name: &str) -> Option<Arc<dyn Builtin>> {
+        self.builtins
+            .iter()
+            .find(|builtin| builtin.spec().name == name || builtin.spec().aliases.contains(&name))
+            .cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BuiltinSpec> {
+        self.builtins.iter().map(|builtin| builtin.spec())
+    }
+
+    // `NAME KIND...` completion rules for the builtins that complete something other than files
+    pub fn completion_specs(&self) -> Vec<String> {
+        self.iter()
+            .filter(|spec| !spec.completion.is_empty())
+            .flat_map(|spec| {
+                std::iter::once(spec.name)
+                    .chain(spec.aliases.iter().copied())
+                    .map(move |name| format!("{} {}", name, spec.completion))
+            })
+            .collect()
+    }
+
+    // Every name a builtin answers to, for completion
+    pub fn names(&self) -> Vec<&'static str> {
+        self.iter()
+            .flat_map(|spec| std::iter::once(spec.name).chain(spec.aliases.iter().copied()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_lookup() {
+        let mut registry = Registry::new();
+        assert_eq!(registry.find("bye").map(|b| b.spec().name), Some("exit"));
+        assert_eq!(registry.find("[").map(|b| b.spec().name), Some("test"));
+        assert!(registry.find("ls").is_none());
+        assert!(registry.completion_specs().contains(&"cd dirs".to_string()));
+
+        let count = registry.iter().count();
+        registry.register(Arc::new(safe("pwd", navigation::pwd, "pwd", "Print the directory", "")));
+        assert_eq!(registry.iter().count(), count);
+        assert_eq!(registry.find("pwd").map(|b| b.spec().summary), Some("Print the directory"));
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use colored::*;
+use std::io::Write;
+use crate::shell::Shell;
+use crate::utils::path_utils;
+use super::Invocation;
+
+pub(super) fn cd(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let dir_to_use = if inv.parts.len() > 1 {
+        inv.parts[1].to_string()
+    } else {
+        // Default to home directory
+        dirs::home_dir()
+            .and_then(|p| p.to_str().map(|s| s.to_string()))
+            .unwrap_or_else(|| ".".to_string())
+    };
+    
+    // Workspace bookmarks are addressed as @name
+    let dir_to_use = match dir_to_use.strip_prefix('@') {
+        Some(name) => match shell.workspaces.active().and_then(|w| w.bookmark(name)) {
+            Some(path) => path.to_string(),
+            None => return Err(anyhow::anyhow!("cd: @{}: no such bookmark in the active workspace", name)),
+        },
+        None => dir_to_use,
+    };
+    
+    let expanded_dir = path_utils::expand_tilde(&dir_to_use);
+    
+    // Relative names are also looked up in CDPATH; like bash, print where we ended up
+    if let Some(found) = search_cdpath(&expanded_dir) {
+        shell.change_directory(&found)?;
+        writeln!(inv.stdout, "{}", shell.working_dir.display())?;
+        return Ok(0);
+    }
+    
+    shell.change_directory(&expanded_dir).map(|_| 0)
+}
+
+fn search_cdpath(dir: &str) -> Option<String> {
+    if dir.starts_with('/') || dir == "." || dir == ".." || dir.starts_with("./") || dir.starts_with("../") {
+        return None;
+    }
+    let cdpath = std::env::var("CDPATH").ok()?;
+    for entry in cdpath.split(':') {
+        // An empty entry means the current directory, which cd falls back to anyway
+        if entry.is_empty() || entry == "." {
+            if std::path::Path::new(dir).is_dir() {
+                return None;
+            }
+            continue;
+        }
+        let candidate = std::path::Path::new(entry).join(dir);
+        if candidate.is_dir() {
+            return Some(candidate.to_string_lossy().to_string());
+        }
+    }
+    None
+}
+
+pub(super) fn pushd(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let previous = shell.working_dir.clone();
+    let result = match inv.parts.get(1) {
+        Some(dir) => shell.change_directory(&path_utils::expand_tilde(dir)),
+        None => match shell.dir_stack.pop() {
+            Some(top) => shell.change_directory(&top.to_string_lossy()),
+            None => Err(anyhow::anyhow!("pushd: no other directory")),
+        },
+    };
+    if result.is_ok() {
+        shell.dir_stack.push(previous);
+        shell.print_dir_stack();
+    }
+    result.map(|_| 0)
+}
+
+pub(super) fn popd(shell: &mut Shell, _inv: &mut Invocation) -> Result<i32> {
+    let result = match shell.dir_stack.pop() {
+        Some(dir) => shell.change_directory(&dir.to_string_lossy()),
+        None => Err(anyhow::anyhow!("popd: directory stack empty")),
+    };
+    if result.is_ok() {
+        shell.print_dir_stack();
+    }
+    result.map(|_| 0)
+}
+
+pub(super) fn dirs(shell: &mut Shell, _inv: &mut Invocation) -> Result<i32> {
+    shell.print_dir_stack();
+    Ok(0)
+}
+
+pub(super) fn workspace(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1).copied() {
+        None | Some("list") => shell.list_workspaces(),
+        Some("use") if inv.parts.len() > 2 => {
+            shell.use_workspace(Some(inv.parts[2]))?;
+            println!("Using workspace {}", inv.parts[2].bright_cyan());
+        },
+        Some("off") => {
+            shell.use_workspace(None)?;
+        },
+        _ => {
+            eprintln!("workspace: usage: workspace [list | use NAME | off]");
+            return Ok(2);
+        }
+    }
+    Ok(0)
+}
+
+pub(super) fn session(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1).copied() {
+        None | Some("list") => {
+            shell.list_sessions()?;
+        },
+        Some("restore") => {
+            shell.restore_session(inv.parts.get(2).copied().unwrap_or(""))?;
+        },
+        Some("delete") if inv.parts.len() > 2 => {
+            shell.session.remove(inv.parts[2])?;
+        },
+        _ => {
+            eprintln!("session: usage: session [list | restore [ID] | delete ID]");
+            return Ok(2);
+        }
+    }
+    Ok(0)
+}
+
+pub(super) fn pwd(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    writeln!(inv.stdout, "{}", shell.working_dir.display())?;
+    Ok(0)
+}
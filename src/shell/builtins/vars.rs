@@ -0,0 +1,202 @@
+use anyhow::Result;
+use colored::*;
+use std::io::Write;
+use crate::shell::Shell;
+use crate::shell::envexport::{self, Export, Format};
+use crate::shell::envsnap::{self, Change};
+use crate::shell::options::SET_OPTION_NAMES;
+use crate::shell::variables;
+use crate::utils::path_utils;
+use super::Invocation;
+
+pub(super) fn export(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let input = variables::expand_tildes(inv.input, path_utils::tilde_directory);
+    let input = shell.substitute_commands(&input, variables::Insert::Word)?;
+    let words = match shellwords::split(&input) {
+        Ok(words) => words,
+        Err(e) => return Err(anyhow::anyhow!("export: {}", e)),
+    };
+    if words.len() == 1 {
+        // Just 'export' - list all environment variables
+        for (key, value) in std::env::vars() {
+            writeln!(inv.stdout, "{}={}", key, value)?;
+        }
+    } else if words[1] == "-n" {
+        for name in &words[2..] {
+            shell.variables.unexport(name);
+        }
+    } else {
+        let mut status = 0;
+        for word in &words[1..] {
+            match variables::parse_assignment(word) {
+                Some((name, value)) => {
+                    let value = shell.expand_env_vars(value)?;
+                    shell.variables.export(name, Some(&value))?;
+                }
+                None if variables::is_name(word) => {
+                    shell.variables.export(word, None)?;
+                }
+                None => {
+                    eprintln!("export: '{}': not a valid identifier", word);
+                    status = 1;
+                }
+            }
+        }
+        return Ok(status);
+    }
+    Ok(0)
+}
+
+pub(super) fn printenv(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() == 1 {
+        let mut vars: Vec<(String, String)> = std::env::vars().collect();
+        vars.sort();
+        for (key, value) in vars {
+            writeln!(inv.stdout, "{}={}", key, value)?;
+        }
+    } else {
+        // Only exported variables, as a child process would see them
+        let mut status = 0;
+        for name in &inv.parts[1..] {
+            match std::env::var(name) {
+                Ok(value) => writeln!(inv.stdout, "{}", value)?,
+                Err(_) => status = 1,
+            }
+        }
+        return Ok(status);
+    }
+    Ok(0)
+}
+
+pub(super) fn unset(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() > 1 {
+        for var in &inv.parts[1..] {
+            if let Err(e) = shell.variables.unset(var) {
+                return Err(anyhow::anyhow!("unset: {}", e));
+            }
+        }
+    } else {
+        eprintln!("unset: missing variable name");
+        return Ok(2);
+    }
+    Ok(0)
+}
+
+pub(super) fn shopt(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    shell.shopt(inv.input).map(|_| 0)
+}
+
+pub(super) fn declare(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    shell.declare(inv.input).map(|_| 0)
+}
+
+pub(super) fn set(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    if inv.parts.len() == 1 {
+        // Just 'set' - list all shell variables, exported or not
+        for (key, value) in shell.variables.all() {
+            writeln!(inv.stdout, "{}={}", key, value)?;
+        }
+        return Ok(0);
+    }
+    // -o NAME / +o NAME turn an option on or off; -C is -o noclobber, -u -o nounset
+    let mut args = inv.parts[1..].iter();
+    while let Some(arg) = args.next() {
+        let (name, on) = match *arg {
+            "-o" | "+o" => match args.next() {
+                Some(name) => (*name, *arg == "-o"),
+                None => {
+                    for name in SET_OPTION_NAMES {
+                        let on = shell.options.get(name).unwrap_or(false);
+                        writeln!(inv.stdout, "{:<15} {}", name, if on { "on" } else { "off" })?;
+                    }
+                    continue;
+                }
+            },
+            "-C" | "+C" => ("noclobber", *arg == "-C"),
+            "-u" | "+u" => ("nounset", *arg == "-u"),
+            other => {
+                // Other options are accepted so rc files written for bash still load
+                eprintln!("Note: set {} is not supported", other);
+                continue;
+            }
+        };
+        if !SET_OPTION_NAMES.contains(&name) {
+            eprintln!("set: {}: invalid option name", name);
+            return Ok(2);
+        }
+        if name == "posix" {
+            shell.set_posix(on);
+        } else {
+            shell.options.set(name, on)?;
+        }
+    }
+    Ok(0)
+}
+
+pub(super) fn envsnap(_shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    match inv.parts.get(1..).unwrap_or_default() {
+        [] | ["list"] => {
+            for name in envsnap::list() {
+                writeln!(inv.stdout, "{}", name)?;
+            }
+        }
+        ["save", name] => envsnap::save(name, &envsnap::current())?,
+        ["diff", name, rest @ ..] if rest.len() <= 1 => {
+            let before = envsnap::load(name)?;
+            let after = match rest.first() {
+                Some(other) => envsnap::load(other)?,
+                None => envsnap::current(),
+            };
+            for change in envsnap::diff(&before, &after) {
+                let line = match change {
+                    Change::Added(key, value) => format!("+ {}={}", key, value).green(),
+                    Change::Removed(key, value) => format!("- {}={}", key, value).red(),
+                    Change::Changed(key, old, new) => format!("~ {}: {} -> {}", key, old, new).yellow(),
+                };
+                writeln!(inv.stdout, "{}", line)?;
+            }
+        }
+        _ => {
+            eprintln!("envsnap: usage: envsnap [list | save NAME | diff NAME [OTHER]]");
+            return Ok(2);
+        }
+    }
+    Ok(0)
+}
+
+// `envexport [--format bash|fish|json] [> FILE]`: the session's variables,
+// aliases and alias functions in a form another shell can source
+pub(super) fn envexport(shell: &mut Shell, inv: &mut Invocation) -> Result<i32> {
+    let format = match inv.parts[1..] {
+        [] => Ok(Format::Bash),
+        ["--format" | "-f", name] => Format::parse(name),
+        [arg] if arg.starts_with("--format=") => Format::parse(&arg["--format=".len()..]),
+        _ => Err(anyhow::anyhow!("usage: envexport [--format bash|fish|json] [> FILE]")),
+    };
+    let format = match format {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("envexport: {}", e);
+            return Ok(2);
+        }
+    };
+
+    let (functions, aliases) = shell
+        .alias_manager
+        .list_aliases()
+        .into_iter()
+        .partition(|(name, _)| shell.alias_manager.takes_arguments(name));
+    let mut export = Export {
+        variables: shell.variables.unexported(),
+        environment: shell.session.changed_env(),
+        aliases,
+        functions,
+    };
+    export.environment.retain(|(name, _)| !envexport::MANAGED.contains(&name.as_str()));
+    export.aliases.sort();
+    export.functions.sort();
+    let script = envexport::render(&export, format);
+
+    write!(inv.stdout, "{}", script)?;
+    Ok(0)
+}
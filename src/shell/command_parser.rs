@@ -1,14 +1,23 @@
 // src/shell/command_parser.rs
-use anyhow::{Result, Context};
+use anyhow::Result;
 use std::path::PathBuf;
+use crate::shell::variables::parse_assignment;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Redirection {
     Input(String),      // <
     Output(String),     // >
+    Clobber(String),    // >| (overwrites even with noclobber set)
     Append(String),     // >>
     ErrorOutput(String), // 2>
     ErrorAppend(String), // 2>>
+    Both(String),       // &> (stdout and stderr)
+    BothAppend(String), // &>>
+    FdInput(i32, String),  // 3<
+    FdOutput(i32, String), // 3>
+    FdAppend(i32, String), // 3>>
+    Duplicate(i32, i32),   // 2>&1: the first descriptor becomes a copy of the second
+    Close(i32),         // 3>&-
     Pipe,               // |
 }
 
@@ -17,6 +26,8 @@ pub struct SimpleCommand {
     pub program: String,
     pub args: Vec<String>,
     pub redirections: Vec<Redirection>,
+    // `VAR=value` words before the program, set only in the child's environment
+    pub assignments: Vec<(String, String)>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,149 +36,89 @@ pub struct Pipeline {
     pub background: bool,
 }
 
+// How a command in a list depends on the one before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    // `;`, or the start of the list: always runs
+    Sequence,
+    // `&&`: runs if the previous command succeeded
+    And,
+    // `||`: runs if it failed
+    Or,
+}
+
+impl Connector {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Connector::Sequence => ";",
+            Connector::And => "&&",
+            Connector::Or => "||",
+        }
+    }
+
+    pub fn runs_after(self, status: i32) -> bool {
+        match self {
+            Connector::Sequence => true,
+            Connector::And => status == 0,
+            Connector::Or => status != 0,
+        }
+    }
+}
+
 pub struct CommandParser;
 
+// Expands the `$` reference at the start of the text it is given (the text
+// after the `$`), returning the value and how many bytes it took, or None
+// to keep the `$` as written
+pub type Expand<'a> = &'a dyn Fn(&str) -> Result<Option<(String, usize)>>;
+
 impl CommandParser {
     pub fn parse(input: &str) -> Result<Pipeline> {
+        Self::parse_with(input, None)
+    }
+
+    // Parses with the variables in each word expanded as it is read, so a
+    // value is never parsed as shell syntax. Unquoted values are split into
+    // words on whitespace, except in assignments.
+    pub fn parse_expanding(input: &str, expand: Expand) -> Result<Pipeline> {
+        Self::parse_with(input, Some(expand))
+    }
+
+    fn parse_with(input: &str, expand: Option<Expand>) -> Result<Pipeline> {
         let mut commands = Vec::new();
         let mut current_command = SimpleCommand {
             program: String::new(),
             args: Vec::new(),
             redirections: Vec::new(),
+            assignments: Vec::new(),
         };
         let mut background = false;
-        let mut in_quotes = false;
-        let mut quote_char = ' ';
-        let mut current_token = String::new();
         let mut i = 0;
         let chars: Vec<char> = input.chars().collect();
         
         while i < chars.len() {
             let c = chars[i];
             
-            // Handle quotes
-            if (c == '"' || c == '\'') && (!in_quotes || quote_char == c) {
-                if in_quotes {
-                    in_quotes = false;
-                } else {
-                    in_quotes = true;
-                    quote_char = c;
-                }
-                i += 1;
-                continue;
-            }
-            
-            // Inside quotes, just add the character
-            if in_quotes {
-                current_token.push(c);
-                i += 1;
-                continue;
-            }
-            
             // Handle pipe
             if c == '|' {
-                if !current_token.is_empty() {
-                    if current_command.program.is_empty() {
-                        current_command.program = current_token;
-                    } else {
-                        current_command.args.push(current_token);
-                    }
-                    current_token = String::new();
-                }
                 current_command.redirections.push(Redirection::Pipe);
                 commands.push(current_command);
                 current_command = SimpleCommand {
                     program: String::new(),
                     args: Vec::new(),
                     redirections: Vec::new(),
+                    assignments: Vec::new(),
                 };
                 i += 1;
                 continue;
             }
             
-            // Handle redirections
-            if c == '<' || c == '>' {
-                if !current_token.is_empty() {
-                    if current_command.program.is_empty() {
-                        current_command.program = current_token;
-                    } else {
-                        current_command.args.push(current_token);
-                    }
-                    current_token = String::new();
-                }
-                
-                // Check for >> or 2> or 2>>
-                if c == '>' && i + 1 < chars.len() && chars[i + 1] == '>' {
-                    // >>
-                    i += 2;
-                    // Skip whitespace
-                    while i < chars.len() && chars[i].is_whitespace() {
-                        i += 1;
-                    }
-                    // Read the filename
-                    let mut filename = String::new();
-                    while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '|' && chars[i] != '<' && chars[i] != '>' {
-                        filename.push(chars[i]);
-                        i += 1;
-                    }
-                    current_command.redirections.push(Redirection::Append(filename));
-                } else if i > 0 && chars[i - 1] == '2' && c == '>' && i + 1 < chars.len() && chars[i + 1] == '>' {
-                    // 2>>
-                    i += 2;
-                    // Skip whitespace
-                    while i < chars.len() && chars[i].is_whitespace() {
-                        i += 1;
-                    }
-                    // Read the filename
-                    let mut filename = String::new();
-                    while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '|' && chars[i] != '<' && chars[i] != '>' {
-                        filename.push(chars[i]);
-                        i += 1;
-                    }
-                    current_command.redirections.push(Redirection::ErrorAppend(filename));
-                } else if i > 0 && chars[i - 1] == '2' && c == '>' {
-                    // 2>
-                    i += 1;
-                    // Skip whitespace
-                    while i < chars.len() && chars[i].is_whitespace() {
-                        i += 1;
-                    }
-                    // Read the filename
-                    let mut filename = String::new();
-                    while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '|' && chars[i] != '<' && chars[i] != '>' {
-                        filename.push(chars[i]);
-                        i += 1;
-                    }
-                    current_command.redirections.push(Redirection::ErrorOutput(filename));
-                } else if c == '>' {
-                    // >
-                    i += 1;
-                    // Skip whitespace
-                    while i < chars.len() && chars[i].is_whitespace() {
-                        i += 1;
-                    }
-                    // Read the filename
-                    let mut filename = String::new();
-                    while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '|' && chars[i] != '<' && chars[i] != '>' {
-                        filename.push(chars[i]);
-                        i += 1;
-                    }
-                    current_command.redirections.push(Redirection::Output(filename));
-                } else if c == '<' {
-                    // <
-                    i += 1;
-                    // Skip whitespace
-                    while i < chars.len() && chars[i].is_whitespace() {
-                        i += 1;
-                    }
-                    // Read the filename
-                    let mut filename = String::new();
-                    while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '|' && chars[i] != '<' && chars[i] != '>' {
-                        filename.push(chars[i]);
-                        i += 1;
-                    }
-                    current_command.redirections.push(Redirection::Input(filename));
-                }
+            // Handle redirections; a descriptor number right before the
+            // operator is part of it
+            let digits = chars[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+            if c == '<' || c == '>' || (c == '&' && chars.get(i + 1) == Some(&'>')) || (digits > 0 && matches!(chars.get(i + digits), Some('<' | '>'))) {
+                let redirection = Self::read_redirection(&chars, &mut i, expand)?;
+                current_command.redirections.push(redirection);
                 continue;
             }
             
@@ -179,29 +130,13 @@ impl CommandParser {
             
             // Handle whitespace
             if c.is_whitespace() {
-                if !current_token.is_empty() {
-                    if current_command.program.is_empty() {
-                        current_command.program = current_token;
-                    } else {
-                        current_command.args.push(current_token);
-                    }
-                    current_token = String::new();
-                }
                 i += 1;
                 continue;
             }
             
-            // Add character to current token
-            current_token.push(c);
-            i += 1;
-        }
-        
-        // Add the last token
-        if !current_token.is_empty() {
-            if current_command.program.is_empty() {
-                current_command.program = current_token;
-            } else {
-                current_command.args.push(current_token);
+            let assignment = current_command.program.is_empty();
+            for word in Self::read_word(&chars, &mut i, expand, assignment)? {
+                Self::push_word(&mut current_command, word);
             }
         }
         
@@ -215,6 +150,319 @@ impl CommandParser {
             background,
         })
     }
+
+    // Reads an operator like `<`, `>|`, `2>>`, `&>`, `2>&1` or `3<` and its target
+    fn read_redirection(chars: &[char], i: &mut usize, expand: Option<Expand>) -> Result<Redirection> {
+        let digits: String = chars[*i..].iter().take_while(|c| c.is_ascii_digit()).collect();
+        *i += digits.len();
+        let fd = match digits.as_str() {
+            "" => None,
+            digits => Some(digits.parse::<i32>().map_err(|_| anyhow::anyhow!("{}: bad file descriptor", digits))?),
+        };
+        let both = chars[*i] == '&';
+        if both {
+            *i += 1;
+        }
+        let mut operator = chars[*i].to_string();
+        *i += 1;
+        if let Some(&next) = chars.get(*i) {
+            let joins = match (operator.as_str(), next) {
+                (">", '>') => true,
+                (">", '|' | '&') | ("<", '&') => !both,
+                _ => false,
+            };
+            if joins {
+                operator.push(next);
+                *i += 1;
+            }
+        }
+
+        if operator.ends_with('&') {
+            let fd = fd.unwrap_or(if operator == "<&" { 0 } else { 1 });
+            let target = Self::read_target(chars, i, expand)?;
+            return match target.parse::<i32>() {
+                Ok(source) => Ok(Redirection::Duplicate(fd, source)),
+                Err(_) if target == "-" => Ok(Redirection::Close(fd)),
+                // `>&file` is another way to write `&>file`
+                Err(_) if operator == ">&" && digits.is_empty() => Ok(Redirection::Both(target)),
+                Err(_) => Err(anyhow::anyhow!("{}: bad file descriptor", target)),
+            };
+        }
+        let target = Self::read_target(chars, i, expand)?;
+        Ok(match (both, fd, operator.as_str()) {
+            (true, _, ">>") => Redirection::BothAppend(target),
+            (true, _, _) => Redirection::Both(target),
+            (_, None | Some(0), "<") => Redirection::Input(target),
+            (_, Some(fd), "<") => Redirection::FdInput(fd, target),
+            (_, None | Some(1), ">") => Redirection::Output(target),
+            (_, None | Some(1), ">|") => Redirection::Clobber(target),
+            (_, None | Some(1), _) => Redirection::Append(target),
+            (_, Some(2), ">" | ">|") => Redirection::ErrorOutput(target),
+            (_, Some(2), _) => Redirection::ErrorAppend(target),
+            (_, Some(fd), ">" | ">|") => Redirection::FdOutput(fd, target),
+            (_, Some(fd), _) => Redirection::FdAppend(fd, target),
+        })
+    }
+
+    // The file name after a redirection operator
+    fn read_target(chars: &[char], i: &mut usize, expand: Option<Expand>) -> Result<String> {
+        while *i < chars.len() && chars[*i].is_whitespace() {
+            *i += 1;
+        }
+        let start = *i;
+        let mut words = Self::read_word(chars, i, expand, false)?;
+        match words.len() {
+            0 if *i == start => Err(anyhow::anyhow!("syntax error: missing file name after redirection")),
+            1 => Ok(words.remove(0)),
+            _ => Err(anyhow::anyhow!("{}: ambiguous redirect", chars[start..*i].iter().collect::<String>())),
+        }
+    }
+
+    // Reads one word starting at `i`, removing its quotes and escapes, and
+    // leaves `i` at the character that ended it. Quoted parts join the text
+    // around them (`foo"bar"baz`), and `""` is an empty word rather than none.
+    // Expanded values outside quotes can make it more than one word, or none.
+    fn read_word(chars: &[char], i: &mut usize, expand: Option<Expand>, assignment: bool) -> Result<Vec<String>> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut quoted = false;
+        while *i < chars.len() {
+            let c = chars[*i];
+            match c {
+                _ if c.is_whitespace() => break,
+                '|' | '<' | '>' => break,
+                '&' if *i == chars.len() - 1 || chars.get(*i + 1) == Some(&'>') => break,
+                '\\' => {
+                    *i += 1;
+                    match chars.get(*i) {
+                        // A backslash at the end of a line joins it to the next
+                        Some('\n') => {}
+                        Some(&next) => word.push(next),
+                        None => word.push('\\'),
+                    }
+                }
+                '\'' => {
+                    let end = Self::closing_quote(chars, *i + 1, '\'')?;
+                    word.extend(&chars[*i + 1..end]);
+                    quoted = true;
+                    *i = end;
+                }
+                '"' => {
+                    let end = Self::closing_quote(chars, *i + 1, '"')?;
+                    let mut j = *i + 1;
+                    while j < end {
+                        // In double quotes a backslash only escapes what is special there
+                        match (chars[j], chars.get(j + 1)) {
+                            ('\\', Some('\n')) => j += 1,
+                            ('\\', Some(&next @ ('$' | '`' | '"' | '\\'))) => {
+                                word.push(next);
+                                j += 1;
+                            }
+                            ('$', _) => match Self::expand_at(&chars[..end], j, expand)? {
+                                Some((value, length)) => {
+                                    word.push_str(&value);
+                                    j += length;
+                                }
+                                None => word.push('$'),
+                            },
+                            (c, _) => word.push(c),
+                        }
+                        j += 1;
+                    }
+                    quoted = true;
+                    *i = end;
+                }
+                '$' if chars.get(*i + 1) == Some(&'\'') => {
+                    *i += 2;
+                    word.push_str(&Self::ansi_c(chars, i)?);
+                    quoted = true;
+                }
+                '$' => match Self::expand_at(chars, *i, expand)? {
+                    // The value of `NAME=$X` stays one word
+                    Some((value, length)) if assignment && words.is_empty() && parse_assignment(&word).is_some() => {
+                        word.push_str(&value);
+                        *i += length;
+                    }
+                    Some((value, length)) => {
+                        for (n, field) in value.split(char::is_whitespace).enumerate() {
+                            if n > 0 && (!word.is_empty() || quoted) {
+                                words.push(std::mem::take(&mut word));
+                                quoted = false;
+                            }
+                            word.push_str(field);
+                        }
+                        *i += length;
+                    }
+                    None => word.push(c),
+                },
+                _ => word.push(c),
+            }
+            *i += 1;
+        }
+        if !word.is_empty() || quoted {
+            words.push(word);
+        }
+        Ok(words)
+    }
+
+    // The value of the reference of the `$` at `at` and how many characters
+    // after the `$` it takes
+    fn expand_at(chars: &[char], at: usize, expand: Option<Expand>) -> Result<Option<(String, usize)>> {
+        let Some(expand) = expand else {
+            return Ok(None);
+        };
+        let rest: String = chars[at + 1..].iter().collect();
+        Ok(expand(&rest)?.map(|(value, length)| (value, rest[..length].chars().count())))
+    }
+
+    // Where a quoted part opened before `start` ends; double quotes can
+    // contain escaped double quotes
+    fn closing_quote(chars: &[char], start: usize, quote: char) -> Result<usize> {
+        let mut j = start;
+        while j < chars.len() {
+            match chars[j] {
+                '\\' if quote == '"' => j += 1,
+                c if c == quote => return Ok(j),
+                _ => {}
+            }
+            j += 1;
+        }
+        Err(anyhow::anyhow!("syntax error: unterminated {} quote", if quote == '"' { "double" } else { "single" }))
+    }
+
+    // The text of `$'...'` from `i`, with its C-style escapes replaced; `i`
+    // is left on the closing quote
+    fn ansi_c(chars: &[char], i: &mut usize) -> Result<String> {
+        let mut text = String::new();
+        // Up to `max` digits in the radix, as a character
+        let number = |i: &mut usize, radix: u32, max: usize| -> Option<char> {
+            let digits: String = chars[*i..].iter().take(max).take_while(|c| c.is_digit(radix)).collect();
+            *i += digits.len();
+            u32::from_str_radix(&digits, radix).ok().and_then(char::from_u32)
+        };
+        while *i < chars.len() {
+            let c = chars[*i];
+            if c == '\'' {
+                return Ok(text);
+            }
+            *i += 1;
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+            let Some(&escape) = chars.get(*i) else {
+                break;
+            };
+            *i += 1;
+            let decoded = match escape {
+                'a' => Some('\x07'),
+                'b' => Some('\x08'),
+                'e' | 'E' => Some('\x1b'),
+                'f' => Some('\x0c'),
+                'n' => Some('\n'),
+                'r' => Some('\r'),
+                't' => Some('\t'),
+                'v' => Some('\x0b'),
+                '\\' | '\'' | '"' | '?' => Some(escape),
+                'x' => number(i, 16, 2),
+                'u' => number(i, 16, 4),
+                'U' => number(i, 16, 8),
+                'c' => {
+                    *i += 1;
+                    chars.get(*i - 1).map(|c| ((*c as u8) & 0x1f) as char)
+                }
+                '0'..='7' => {
+                    *i -= 1;
+                    number(i, 8, 3)
+                }
+                _ => None,
+            };
+            match decoded {
+                Some(decoded) => text.push(decoded),
+                // Unknown escapes, and `\x` without digits, are kept as written
+                None => {
+                    text.push('\\');
+                    text.push(escape);
+                }
+            }
+        }
+        Err(anyhow::anyhow!("syntax error: unterminated $' quote"))
+    }
+    
+    // Splits a line on `;`, `&&` and `||` outside quotes and `$(...)`, keeping
+    // each command's text so it can go through the usual line handling
+    pub fn parse_list(input: &str) -> Result<Vec<(Connector, String)>> {
+        let mut list = Vec::new();
+        let mut connector = Connector::Sequence;
+        let mut current = String::new();
+        let mut quote = None;
+        let mut depth = 0;
+        let mut chars = input.chars().peekable();
+        while let Some(c) = chars.next() {
+            let separator = match (quote, c, chars.peek()) {
+                // Backslashes escape everywhere but in single quotes
+                (q, '\\', Some(_)) if q != Some('\'') => {
+                    current.push(c);
+                    current.extend(chars.next());
+                    continue;
+                }
+                (Some(q), _, _) if q == c => {
+                    quote = None;
+                    None
+                }
+                (Some(_), _, _) => None,
+                (None, '\'' | '"' | '`', _) => {
+                    quote = Some(c);
+                    None
+                }
+                (None, '$', Some('(')) => {
+                    depth += 1;
+                    None
+                }
+                (None, ')', _) if depth > 0 => {
+                    depth -= 1;
+                    None
+                }
+                _ if depth > 0 => None,
+                (None, ';', _) => Some(Connector::Sequence),
+                (None, '&', Some('&')) => Some(Connector::And),
+                (None, '|', Some('|')) => Some(Connector::Or),
+                _ => None,
+            };
+            let Some(next) = separator else {
+                current.push(c);
+                continue;
+            };
+            if next != Connector::Sequence {
+                chars.next();
+            }
+            let command = current.trim().to_string();
+            current.clear();
+            if !command.is_empty() {
+                list.push((connector, command));
+            } else if next != Connector::Sequence || connector != Connector::Sequence {
+                return Err(anyhow::anyhow!("syntax error near `{}`", next.as_str()));
+            }
+            connector = next;
+        }
+        let command = current.trim().to_string();
+        if !command.is_empty() {
+            list.push((connector, command));
+        } else if connector != Connector::Sequence {
+            return Err(anyhow::anyhow!("syntax error: nothing after `{}`", connector.as_str()));
+        }
+        Ok(list)
+    }
+
+    fn push_word(command: &mut SimpleCommand, word: String) {
+        if !command.program.is_empty() {
+            command.args.push(word);
+        } else if let Some((name, value)) = parse_assignment(&word) {
+            command.assignments.push((name.to_string(), value.to_string()));
+        } else {
+            command.program = word;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -281,6 +529,14 @@ mod tests {
         assert_eq!(pipeline.background, false);
     }
 
+    #[test]
+    fn test_clobber_redirection() {
+        let pipeline = CommandParser::parse("date >| stamp.txt").unwrap();
+        assert_eq!(pipeline.commands.len(), 1);
+        assert_eq!(pipeline.commands[0].args.len(), 0);
+        assert_eq!(pipeline.commands[0].redirections, vec![Redirection::Clobber("stamp.txt".to_string())]);
+    }
+
     #[test]
     fn test_error_redirection() {
         let input = "gcc program.c 2> errors.txt";
@@ -296,6 +552,79 @@ mod tests {
         assert_eq!(pipeline.background, false);
     }
 
+    #[test]
+    fn test_quoting() {
+        let args = |input: &str| CommandParser::parse(input).unwrap().commands[0].args.clone();
+        assert_eq!(args(r#"echo foo"bar"baz 'a  b' "c  d""#), vec!["foobarbaz", "a  b", "c  d"]);
+        assert_eq!(args(r#"echo a\ b \"q\" \$HOME \| x\\y"#), vec!["a b", "\"q\"", "$HOME", "|", "x\\y"]);
+        assert_eq!(args(r#"echo "a \"b\" \$c \d" 'e\f'"#), vec!["a \"b\" $c \\d", "e\\f"]);
+        assert_eq!(args("echo $'tab\\there' $'it\\'s' $'\\x41\\101\\u00e9\\q'"), vec!["tab\there", "it's", "AAé\\q"]);
+        assert_eq!(args("printf '' \"\" x''"), vec!["", "", "x"]);
+        assert_eq!(args("echo 'it'\\''s' \"a | b > c\""), vec!["it's", "a | b > c"]);
+        assert_eq!(args("echo one\\\ntwo"), vec!["onetwo"]);
+
+        let pipeline = CommandParser::parse("grep x > \"my file.txt\" < in\\ put").unwrap();
+        assert_eq!(pipeline.commands[0].redirections, vec![
+            Redirection::Output("my file.txt".to_string()),
+            Redirection::Input("in put".to_string()),
+        ]);
+        assert_eq!(CommandParser::parse("FOO='a b' env").unwrap().commands[0].assignments, vec![("FOO".to_string(), "a b".to_string())]);
+        assert!(CommandParser::parse("echo 'open").is_err());
+        assert!(CommandParser::parse("echo \"open \\\"").is_err());
+        assert!(CommandParser::parse("cat >").is_err());
+    }
+
+    #[test]
+    fn test_descriptor_redirections() {
+        let redirections = |input: &str| CommandParser::parse(input).unwrap().commands[0].redirections.clone();
+        assert_eq!(redirections("make > out.log 2>&1"), vec![
+            Redirection::Output("out.log".to_string()),
+            Redirection::Duplicate(2, 1),
+        ]);
+        assert_eq!(redirections("echo oops 1>&2 3<in 4>>log 5> out"), vec![
+            Redirection::Duplicate(1, 2),
+            Redirection::FdInput(3, "in".to_string()),
+            Redirection::FdAppend(4, "log".to_string()),
+            Redirection::FdOutput(5, "out".to_string()),
+        ]);
+        assert_eq!(redirections("cmd &> all.log"), vec![Redirection::Both("all.log".to_string())]);
+        assert_eq!(redirections("cmd&>>all.log"), vec![Redirection::BothAppend("all.log".to_string())]);
+        assert_eq!(redirections("cmd >&all.log 0<&3 3>&- 2>>err 1>|out"), vec![
+            Redirection::Both("all.log".to_string()),
+            Redirection::Duplicate(0, 3),
+            Redirection::Close(3),
+            Redirection::ErrorAppend("err".to_string()),
+            Redirection::Clobber("out".to_string()),
+        ]);
+        // Digits not right before the operator are words
+        let pipeline = CommandParser::parse("sleep 2 > /dev/null").unwrap();
+        assert_eq!(pipeline.commands[0].args, vec!["2"]);
+        assert_eq!(pipeline.commands[0].redirections, vec![Redirection::Output("/dev/null".to_string())]);
+        let pipeline = CommandParser::parse("echo a2>x").unwrap();
+        assert_eq!(pipeline.commands[0].args, vec!["a2"]);
+        assert!(CommandParser::parse("cmd 2>&x").is_err());
+        assert!(CommandParser::parse("cmd 2>&").is_err());
+    }
+
+    #[test]
+    fn test_command_list() {
+        let list = CommandParser::parse_list("cargo build && cargo test || echo 'failed; && ||'; ls").unwrap();
+        assert_eq!(list, vec![
+            (Connector::Sequence, "cargo build".to_string()),
+            (Connector::And, "cargo test".to_string()),
+            (Connector::Or, "echo 'failed; && ||'".to_string()),
+            (Connector::Sequence, "ls".to_string()),
+        ]);
+        let list = CommandParser::parse_list("echo $(true && echo ok) \\&\\& x | wc -l").unwrap();
+        assert_eq!(list.len(), 1);
+        assert!(CommandParser::parse_list("make &&").is_err());
+        assert!(CommandParser::parse_list("|| ls").is_err());
+        assert!(CommandParser::parse_list("a && ; b").is_err());
+
+        assert!(Connector::And.runs_after(0) && !Connector::And.runs_after(1));
+        assert!(Connector::Or.runs_after(2) && !Connector::Or.runs_after(0));
+    }
+
     #[test]
     fn test_background() {
         let input = "sleep 10 &";
@@ -329,4 +658,28 @@ mod tests {
         }
         assert_eq!(pipeline.background, true);
     }
+
+    #[test]
+    fn test_parse_expanding() {
+        let lookup = |name: &str| match name {
+            "X" => Some("a b".to_string()),
+            "Y" => Some("it\"s".to_string()),
+            "Z" => Some("/tmp > /tmp/pwned".to_string()),
+            _ => None,
+        };
+        let expand = |rest: &str| crate::shell::variables::expand_reference(rest, &lookup, false);
+
+        let pipeline = CommandParser::parse_expanding("ls $X \"$X\" '$X' $Z \"$Y\" $UNSET", &expand).unwrap();
+        assert_eq!(pipeline.commands[0].program, "ls");
+        assert_eq!(pipeline.commands[0].args, vec!["a", "b", "a b", "$X", "/tmp", ">", "/tmp/pwned", "it\"s"]);
+        assert!(pipeline.commands[0].redirections.is_empty());
+
+        let pipeline = CommandParser::parse_expanding("A=$X env > \"$X\"", &expand).unwrap();
+        assert_eq!(pipeline.commands[0].assignments, vec![("A".to_string(), "a b".to_string())]);
+        assert_eq!(pipeline.commands[0].redirections[0], Redirection::Output("a b".to_string()));
+
+        let error = CommandParser::parse_expanding("cat > $X", &expand).unwrap_err();
+        assert!(error.to_string().contains("ambiguous redirect"));
+        assert!(CommandParser::parse_expanding("cat > $UNSET", &expand).is_err());
+    }
 }
@@ -1,10 +1,12 @@
 // src/shell/command_processor.rs
 use anyhow::Result;
+use super::detection::{Detection, Trigger};
 
 #[derive(Debug)]
 pub struct Command {
     pub command: String,
-    pub is_natural_language: bool,
+    // Why it looks like natural language, if it does
+    pub trigger: Option<Trigger>,
 }
 
 pub struct CommandProcessor;
@@ -14,7 +16,7 @@ impl CommandProcessor {
         CommandProcessor
     }
 
-    pub fn parse(&self, input: &str) -> Result<Vec<Command>> {
+    pub fn parse(&self, input: &str, detection: &Detection) -> Result<Vec<Command>> {
         let mut commands = Vec::new();
         
         // Split by semicolons to handle multiple commands
@@ -25,18 +27,18 @@ impl CommandProcessor {
             }
             
             // Check if this looks like natural language
-            let is_natural_language = self.detect_natural_language(trimmed);
+            let trigger = self.detect_natural_language(trimmed, detection);
             
             commands.push(Command {
                 command: trimmed.to_string(),
-                is_natural_language,
+                trigger,
             });
         }
         
         Ok(commands)
     }
     
-    fn detect_natural_language(&self, input: &str) -> bool {
+    fn detect_natural_language(&self, input: &str, detection: &Detection) -> Option<Trigger> {
         // Simple heuristic: if it has multiple words and doesn't start with a common command
         let common_commands = [
             "ls", "cd", "grep", "find", "cat", "echo", "mkdir", "rm", "cp", "mv",
@@ -47,17 +49,12 @@ impl CommandProcessor {
         
         let words: Vec<&str> = input.split_whitespace().collect();
         if words.is_empty() {
-            return false;
+            return None;
         }
         
         // If it starts with a common command, probably not natural language
         if common_commands.contains(&words[0]) {
-            return false;
-        }
-        
-        // If it has 4+ words, likely natural language
-        if words.len() >= 4 {
-            return true;
+            return None;
         }
         
         // Check for natural language patterns
@@ -67,6 +64,11 @@ impl CommandProcessor {
             "explain", "help", "search", "look", "count", "calculate", "summarize",
         ];
         
-        natural_patterns.iter().any(|&pattern| words[0].eq_ignore_ascii_case(pattern))
+        if words.len() >= detection.pattern_words && natural_patterns.iter().any(|&pattern| words[0].eq_ignore_ascii_case(pattern)) {
+            return Some(Trigger::Pattern);
+        }
+        
+        // With enough words, likely natural language
+        (words.len() >= detection.min_words).then_some(Trigger::WordCount)
     }
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use nix::fcntl::OFlag;
+use nix::unistd::pipe2;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::process::{Child, Command, Stdio};
+
+// A background process started with `coproc`, whose input and output stay
+// connected to the shell through a pair of pipes
+pub struct Coproc {
+    pub name: String,
+    child: Child,
+    // The shell's ends of the pipes: what the coprocess writes, and its input.
+    // They are close-on-exec, so other programs only get them through a
+    // redirection like `{ ...; } >&FD`.
+    output: OwnedFd,
+    input: OwnedFd,
+}
+
+impl Coproc {
+    // The body runs in sh, like other background pipelines
+    pub fn start(name: &str, body: &str) -> Result<Self> {
+        let (output, child_stdout) = pipe()?;
+        let (child_stdin, input) = pipe()?;
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(body)
+            .stdin(Stdio::from(child_stdin))
+            .stdout(Stdio::from(child_stdout))
+            .spawn()
+            .with_context(|| format!("coproc: failed to start {}", body))?;
+        Ok(Coproc { name: name.to_string(), child, output, input })
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    // (read, write) descriptors, as stored in NAME[0] and NAME[1]
+    pub fn fds(&self) -> (RawFd, RawFd) {
+        (self.output.as_raw_fd(), self.input.as_raw_fd())
+    }
+
+    pub fn finished(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+fn pipe() -> Result<(OwnedFd, OwnedFd)> {
+    let (read, write) = pipe2(OFlag::O_CLOEXEC).context("coproc: failed to create a pipe")?;
+    // SAFETY: both descriptors were just created and nothing else owns them
+    Ok(unsafe { (OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)) })
+}
@@ -0,0 +1,193 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use crate::utils::path_utils;
+
+// Openings that mark a line as a request rather than a command
+const PHRASES: &[&str] = &[
+    "show me", "find all", "list all", "get all", "display", "create a",
+    "make a", "tell me", "give me", "use the", "how do", "what is", "where is",
+    "can you", "could you", "would you", "should I", "explain", "help me",
+    "search for", "look for", "find files", "count", "calculate", "summarize",
+    "who are", "what are", "which", "when", "why", "how many", "how much",
+    "get the", "list", "show", "find", "tell", "give", "display", "print",
+];
+
+// Outcomes looked at before the thresholds move, and how many are kept
+const TUNING_WINDOW: usize = 10;
+const RECENT_OUTCOMES: usize = 20;
+
+// Why a line was taken for natural language
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    // It starts with a phrase such as "show me"
+    Pattern,
+    // It has enough words
+    WordCount,
+}
+
+// Translations run or declined after one trigger
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Outcomes {
+    pub accepted: u64,
+    pub rejected: u64,
+    // Latest last; true when accepted
+    #[serde(default)]
+    recent: VecDeque<bool>,
+}
+
+impl Outcomes {
+    fn record(&mut self, accepted: bool) {
+        if accepted {
+            self.accepted += 1;
+        } else {
+            self.rejected += 1;
+        }
+        self.recent.push_back(accepted);
+        if self.recent.len() > RECENT_OUTCOMES {
+            self.recent.pop_front();
+        }
+    }
+
+    fn recent_rejections(&self) -> usize {
+        self.recent.iter().filter(|accepted| !**accepted).count()
+    }
+}
+
+// How eagerly input is taken for natural language, adjusted to how often
+// the user accepts what each trigger produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Detection {
+    // Words a line starting with a phrase needs
+    pub pattern_words: usize,
+    // Words that make any line a request
+    pub min_words: usize,
+    #[serde(default)]
+    pub pattern: Outcomes,
+    #[serde(default)]
+    pub word_count: Outcomes,
+}
+
+impl Default for Detection {
+    fn default() -> Self {
+        Detection { pattern_words: 1, min_words: 4, pattern: Outcomes::default(), word_count: Outcomes::default() }
+    }
+}
+
+impl Trigger {
+    // The range each threshold is tuned within
+    fn limits(self) -> (usize, usize) {
+        match self {
+            Trigger::Pattern => (1, 5),
+            Trigger::WordCount => (3, 8),
+        }
+    }
+}
+
+impl Detection {
+    pub fn load() -> Self {
+        path_utils::load_json(FILE)
+    }
+
+    fn save(&self) -> Result<()> {
+        path_utils::save_json(FILE, self)
+    }
+
+    // Whether the whole line reads as a request, and why
+    pub fn detect(&self, input: &str) -> Option<Trigger> {
+        let words = input.split_whitespace().count();
+        let lower = input.to_lowercase();
+        if words >= self.pattern_words && PHRASES.iter().any(|phrase| lower.starts_with(phrase)) {
+            Some(Trigger::Pattern)
+        } else if words >= self.min_words {
+            Some(Trigger::WordCount)
+        } else {
+            None
+        }
+    }
+
+    pub fn threshold(&self, trigger: Trigger) -> usize {
+        match trigger {
+            Trigger::Pattern => self.pattern_words,
+            Trigger::WordCount => self.min_words,
+        }
+    }
+
+    pub fn outcomes(&self, trigger: Trigger) -> &Outcomes {
+        match trigger {
+            Trigger::Pattern => &self.pattern,
+            Trigger::WordCount => &self.word_count,
+        }
+    }
+
+    // Counts a translation as run or declined, and moves the trigger's
+    // threshold when recent outcomes call for it. Returns a note about the
+    // change, if any.
+    pub fn record(&mut self, trigger: Trigger, accepted: bool) -> Option<String> {
+        let change = self.tune(trigger, accepted);
+        if let Err(e) = self.save() {
+            log::debug!("Failed to save detection thresholds: {}", e);
+        }
+        change
+    }
+
+    fn tune(&mut self, trigger: Trigger, accepted: bool) -> Option<String> {
+        let (low, high) = trigger.limits();
+        let (outcomes, threshold) = match trigger {
+            Trigger::Pattern => (&mut self.pattern, &mut self.pattern_words),
+            Trigger::WordCount => (&mut self.word_count, &mut self.min_words),
+        };
+        outcomes.record(accepted);
+        let window = outcomes.recent.len();
+        let rejected = outcomes.recent_rejections();
+        let reason = if window >= TUNING_WINDOW && rejected * 10 >= window * 4 && *threshold < high {
+            // Four in ten declined: detect less eagerly
+            *threshold += 1;
+            format!("{} of the last {} were declined", rejected, window)
+        } else if window >= RECENT_OUTCOMES && rejected == 0 && *threshold > low {
+            *threshold -= 1;
+            format!("the last {} were all accepted", window)
+        } else {
+            return None;
+        };
+        outcomes.recent.clear();
+        let what = match trigger {
+            Trigger::Pattern => "lines starting with a phrase like \"show me\" now need",
+            Trigger::WordCount => "other lines are taken for natural language from",
+        };
+        Some(format!("Natural-language detection: {} {} words ({})", what, threshold, reason))
+    }
+}
+
+// Stored in $XDG_STATE_HOME/llm-shell
+const FILE: &str = "detection.json";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tune() {
+        let mut detection = Detection::default();
+        assert_eq!(detection.detect("show disk usage"), Some(Trigger::Pattern));
+        assert_eq!(detection.detect("git commit -m wip"), Some(Trigger::WordCount));
+        assert_eq!(detection.detect("git status"), None);
+
+        for i in 0..9 {
+            assert_eq!(detection.tune(Trigger::WordCount, i % 2 == 0), None);
+        }
+        assert!(detection.tune(Trigger::WordCount, false).unwrap().contains("5 words"));
+        assert_eq!(detection.detect("git commit -m wip"), None);
+        assert_eq!((detection.word_count.accepted, detection.word_count.rejected), (5, 5));
+
+        for _ in 0..19 {
+            assert_eq!(detection.tune(Trigger::Pattern, true), None);
+        }
+        // Already as eager as it gets
+        assert_eq!(detection.tune(Trigger::Pattern, true), None);
+        for _ in 0..20 {
+            detection.tune(Trigger::WordCount, true);
+        }
+        assert_eq!(detection.min_words, 4);
+    }
+}
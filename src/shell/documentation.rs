@@ -25,6 +25,19 @@ impl Documentation {
         Ok(explanation)
     }
 
+    // Parts of a script are cached by their text, so explaining a script
+    // again only asks about the parts that changed
+    pub async fn get_script_help(&mut self, name: &str, outline: &str, chunk: &str) -> Result<String> {
+        let key = format!("script:{}", chunk);
+        if let Some(cached) = self.cache.get(&key) {
+            return Ok(cached.clone());
+        }
+
+        let explanation = self.llm_client.explain_script(name, outline, chunk).await?;
+        self.cache.insert(key, explanation.clone());
+        Ok(explanation)
+    }
+
     pub fn clear_cache(&mut self) {
         self.cache.clear();
     }
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde_json::json;
+use crate::shell::variables::Value;
+
+// Variables the shell keeps up to date itself, which would be wrong in another shell
+pub const MANAGED: &[&str] = &["SHELL", "PWD", "OLDPWD", "SHLVL", "_"];
+
+lazy_static! {
+    static ref POSITIONAL_RE: Regex = Regex::new(r"\$(?:([1-9@*#])|\{([1-9@*#])\})").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Bash,
+    Fish,
+    Json,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format> {
+        match name {
+            "bash" | "sh" | "zsh" => Ok(Format::Bash),
+            "fish" => Ok(Format::Fish),
+            "json" => Ok(Format::Json),
+            other => Err(anyhow::anyhow!("unknown format '{}' (use bash, fish or json)", other)),
+        }
+    }
+}
+
+// What the session has set up, sorted by name
+#[derive(Debug, Clone, Default)]
+pub struct Export {
+    // Shell variables and arrays that commands don't see
+    pub variables: Vec<(String, Value)>,
+    // Environment variables set or changed during the session
+    pub environment: Vec<(String, String)>,
+    pub aliases: Vec<(String, String)>,
+    // Aliases that take their arguments like functions ($1, $@, ...)
+    pub functions: Vec<(String, String)>,
+}
+
+pub fn render(export: &Export, format: Format) -> String {
+    match format {
+        Format::Bash => bash(export),
+        Format::Fish => fish(export),
+        Format::Json => json(export),
+    }
+}
+
+fn bash(export: &Export) -> String {
+    let mut script = String::from("# Exported from llm-shell; source it in bash or zsh\n");
+    for (name, value) in &export.environment {
+        script.push_str(&format!("export {}={}\n", name, quote(value)));
+    }
+    for (name, value) in &export.variables {
+        match value {
+            Value::Scalar(value) => script.push_str(&format!("{}={}\n", name, quote(value))),
+            Value::Array(values) => {
+                let values: Vec<String> = values.iter().map(|value| quote(value)).collect();
+                script.push_str(&format!("{}=({})\n", name, values.join(" ")));
+            }
+        }
+    }
+    for (name, value) in &export.aliases {
+        script.push_str(&format!("alias {}={}\n", name, quote(value)));
+    }
+    for (name, body) in &export.functions {
+        script.push_str(&format!("{}() {{ {}; }}\n", name, body));
+    }
+    script
+}
+
+fn fish(export: &Export) -> String {
+    let mut script = String::from("# Exported from llm-shell; source it in fish\n");
+    // fish keeps PATH-like variables as lists
+    let list = |name: &str, value: &str| -> Vec<String> {
+        if name.ends_with("PATH") {
+            value.split(':').map(fish_quote).collect()
+        } else {
+            vec![fish_quote(value)]
+        }
+    };
+    for (name, value) in &export.environment {
+        script.push_str(&format!("set -gx {} {}\n", name, list(name, value).join(" ")));
+    }
+    for (name, value) in &export.variables {
+        let values = match value {
+            Value::Scalar(value) => vec![fish_quote(value)],
+            Value::Array(values) => values.iter().map(|value| fish_quote(value)).collect(),
+        };
+        script.push_str(&format!("set -g {} {}\n", name, values.join(" ")));
+    }
+    for (name, value) in &export.aliases {
+        script.push_str(&format!("alias {} {}\n", name, fish_quote(value)));
+    }
+    for (name, body) in &export.functions {
+        script.push_str(&format!("function {}\n    {}\nend\n", name, fish_arguments(body)));
+    }
+    script
+}
+
+fn json(export: &Export) -> String {
+    let variables: serde_json::Map<String, serde_json::Value> = export
+        .variables
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::Scalar(value) => json!(value),
+                Value::Array(values) => json!(values),
+            };
+            (name.clone(), value)
+        })
+        .collect();
+    let pairs = |pairs: &[(String, String)]| -> serde_json::Map<String, serde_json::Value> {
+        pairs.iter().map(|(name, value)| (name.clone(), json!(value))).collect()
+    };
+    let document = json!({
+        "environment": pairs(&export.environment),
+        "variables": variables,
+        "aliases": pairs(&export.aliases),
+        "functions": pairs(&export.functions),
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_default() + "\n"
+}
+
+// $1, $@ and $# as fish has them: $argv[1], $argv and (count $argv)
+fn fish_arguments(body: &str) -> String {
+    POSITIONAL_RE
+        .replace_all(body, |captures: &Captures| {
+            match captures.get(1).or_else(|| captures.get(2)).map(|name| name.as_str()) {
+                Some("@" | "*") => "$argv".to_string(),
+                Some("#") => "(count $argv)".to_string(),
+                Some(n) => format!("$argv[{}]", n),
+                None => captures[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// fish single quotes only escape `\` and `'`
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export() -> Export {
+        Export {
+            variables: vec![("greeting".into(), Value::Scalar("it's".into())), ("hosts".into(), Value::Array(vec!["a".into(), "b c".into()]))],
+            environment: vec![("EDITOR".into(), "vim".into()), ("PATH".into(), "/opt/bin:/usr/bin".into())],
+            aliases: vec![("ll".into(), "ls -l".into())],
+            functions: vec![("gco".into(), "git checkout \"$1\" && echo $# ${@}".into())],
+        }
+    }
+
+    #[test]
+    fn test_bash() {
+        assert_eq!(
+            render(&export(), Format::Bash),
+            "# Exported from llm-shell; source it in bash or zsh\n\
+             export EDITOR='vim'\n\
+             export PATH='/opt/bin:/usr/bin'\n\
+             greeting='it'\\''s'\n\
+             hosts=('a' 'b c')\n\
+             alias ll='ls -l'\n\
+             gco() { git checkout \"$1\" && echo $# ${@}; }\n"
+        );
+    }
+
+    #[test]
+    fn test_fish() {
+        assert_eq!(
+            render(&export(), Format::Fish),
+            "# Exported from llm-shell; source it in fish\n\
+             set -gx EDITOR 'vim'\n\
+             set -gx PATH '/opt/bin' '/usr/bin'\n\
+             set -g greeting 'it\\'s'\n\
+             set -g hosts 'a' 'b c'\n\
+             alias ll 'ls -l'\n\
+             function gco\n    git checkout \"$argv[1]\" && echo (count $argv) $argv\nend\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let document: serde_json::Value = serde_json::from_str(&render(&export(), Format::Json)).unwrap();
+        assert_eq!(document["variables"]["hosts"], json!(["a", "b c"]));
+        assert_eq!(document["environment"]["EDITOR"], "vim");
+        assert_eq!(document["functions"]["gco"], "git checkout \"$1\" && echo $# ${@}");
+        assert!(Format::parse("csh").is_err());
+    }
+}
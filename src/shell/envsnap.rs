@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use crate::utils::path_utils;
+
+pub type Snapshot = BTreeMap<String, String>;
+
+// A difference between two snapshots
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added(String, String),
+    Removed(String, String),
+    Changed(String, String, String),
+}
+
+// Snapshots are NUL-separated NAME=VALUE lists, the format of `env -0`, in
+// $XDG_STATE_HOME/llm-shell/envsnap/NAME
+fn dir() -> Result<PathBuf> {
+    Ok(path_utils::state_dir()?.join("envsnap"))
+}
+
+fn file(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(anyhow::anyhow!("invalid snapshot name '{}'", name));
+    }
+    Ok(dir()?.join(name))
+}
+
+// The environment commands are started with
+pub fn current() -> Snapshot {
+    std::env::vars().collect()
+}
+
+pub fn save(name: &str, snapshot: &Snapshot) -> Result<()> {
+    let path = file(name)?;
+    fs::create_dir_all(dir()?)?;
+    let content: String = snapshot.iter().map(|(key, value)| format!("{}={}\0", key, value)).collect();
+    fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn load(name: &str) -> Result<Snapshot> {
+    let path = file(name)?;
+    let content = fs::read_to_string(&path).with_context(|| format!("no snapshot named '{}'", name))?;
+    Ok(parse(&content))
+}
+
+fn parse(content: &str) -> Snapshot {
+    content
+        .split('\0')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = dir()
+        .ok()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .map(|entries| entries.flatten().map(|entry| entry.file_name().to_string_lossy().to_string()).collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+pub fn diff(before: &Snapshot, after: &Snapshot) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (key, old) in before {
+        match after.get(key) {
+            None => changes.push(Change::Removed(key.clone(), old.clone())),
+            Some(new) if new != old => changes.push(Change::Changed(key.clone(), old.clone(), new.clone())),
+            Some(_) => {}
+        }
+    }
+    for (key, new) in after {
+        if !before.contains_key(key) {
+            changes.push(Change::Added(key.clone(), new.clone()));
+        }
+    }
+    changes.sort_by(|a, b| name(a).cmp(name(b)));
+    changes
+}
+
+fn name(change: &Change) -> &str {
+    match change {
+        Change::Added(key, _) | Change::Removed(key, _) | Change::Changed(key, _, _) => key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envsnap_diff() {
+        let before = parse("PATH=/bin\0EDITOR=vi\0GOPATH=/go\0");
+        let after = parse("PATH=/opt/bin:/bin\0GOPATH=/go\0VIRTUAL_ENV=/src/.venv\0");
+        assert_eq!(
+            diff(&before, &after),
+            vec![
+                Change::Removed("EDITOR".to_string(), "vi".to_string()),
+                Change::Changed("PATH".to_string(), "/bin".to_string(), "/opt/bin:/bin".to_string()),
+                Change::Added("VIRTUAL_ENV".to_string(), "/src/.venv".to_string()),
+            ]
+        );
+        assert!(file("../x").is_err());
+    }
+}
@@ -1,22 +1,103 @@
 // src/shell/executor.rs
 use anyhow::{Result, Context};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use crate::shell::command_parser::{Pipeline, SimpleCommand, Redirection};
+use crate::shell::script::{Redirect, RedirectTarget};
+use crate::shell::policy::ResourceLimits;
 use crate::utils::path_utils;
+use crate::utils::duration::format_duration;
+use crate::utils::performance::{Execution, PERFORMANCE_MONITOR};
+use crate::system::tty;
+use crate::shell::fifo::BlockedHint;
+
+// Exit status used by timeout(1) when the command had to be stopped
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Default)]
+pub struct ExecOptions {
+    pub timeout: Option<Duration>,
+    pub limits: ResourceLimits,
+    // `set -o noclobber`: `>` fails on existing files, `>|` still overwrites
+    pub noclobber: bool,
+}
 
 pub struct Executor;
 
+// What a redirection does to a descriptor in the child
+enum FdAction {
+    // Point the descriptor at the opened file
+    Open(File, i32),
+    // Make the second descriptor a copy of the first
+    Duplicate(i32, i32),
+    Close(i32),
+}
+
+// Restores the shell's own descriptors when a group redirection goes out of scope
+pub struct RedirectGuard {
+    saved: Vec<(i32, i32)>,
+}
+
+impl Drop for RedirectGuard {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+        for (fd, copy) in self.saved.drain(..).rev() {
+            if copy == -1 {
+                let _ = nix::unistd::close(fd);
+                continue;
+            }
+            let _ = nix::unistd::dup2(copy, fd);
+            let _ = nix::unistd::close(copy);
+        }
+    }
+}
+
 impl Executor {
-    pub fn execute(pipeline: &Pipeline) -> Result<i32> {
+    // Every foreground command passes through here, so this is where its
+    // start, duration and exit status are recorded
+    pub fn execute_with(pipeline: &Pipeline, options: &ExecOptions) -> Result<i32> {
         if pipeline.commands.is_empty() {
             return Ok(0);
         }
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+        let start = Instant::now();
+        let result = Self::run_pipeline(pipeline, options);
+        if !pipeline.background {
+            tty::check_after_command(*result.as_ref().unwrap_or(&0));
+            PERFORMANCE_MONITOR.lock().unwrap().record_execution(Execution {
+                command: Self::describe(pipeline),
+                started,
+                duration: start.elapsed(),
+                // Commands that couldn't be started count as status 1, as in the shell
+                status: *result.as_ref().unwrap_or(&1),
+            });
+        }
+        result
+    }
+
+    // The command line as it was run, after expansion
+    fn describe(pipeline: &Pipeline) -> String {
+        pipeline.commands
+            .iter()
+            .map(|cmd| std::iter::once(&cmd.program).chain(&cmd.args).map(String::as_str).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    fn run_pipeline(pipeline: &Pipeline, options: &ExecOptions) -> Result<i32> {
+        let _hint = BlockedHint::watch(pipeline);
         
         // Single command without pipes
         if pipeline.commands.len() == 1 && !pipeline.commands[0].redirections.contains(&Redirection::Pipe) {
-            return Self::execute_simple_command(&pipeline.commands[0], pipeline.background);
+            return Self::execute_simple_command(&pipeline.commands[0], pipeline.background, options);
         }
         
         // Pipeline with multiple commands
@@ -40,17 +121,7 @@ impl Executor {
                 Stdio::piped()
             };
             
-            // Create the command
-            let mut command = Self::create_command(cmd)?;
-            command.stdin(stdin);
-            command.stdout(stdout);
-            
-            // Apply redirections
-            Self::apply_redirections(&mut command, cmd)?;
-            
-            // Spawn the command
-            let mut child = command.spawn()
-                .with_context(|| format!("Failed to spawn command: {}", cmd.program))?;
+            let mut child = Self::spawn_stage(cmd, stdin, stdout, options)?;
             
             // Save stdout for the next command if not the last command
             if !is_last {
@@ -61,25 +132,33 @@ impl Executor {
             children.push(child);
         }
         
-        // Wait for all children to complete
-        let mut exit_code = 0;
-        for mut child in children {
-            let status = child.wait()
-                .with_context(|| "Failed to wait for child process")?;
-            if !status.success() {
-                exit_code = status.code().unwrap_or(1);
-            }
-        }
-        
-        Ok(exit_code)
+        Self::wait_for_children(children, options.timeout)
+    }
+    
+    // Starts one stage of a pipeline with the given ends of the pipes around it
+    pub fn spawn_stage(cmd: &SimpleCommand, stdin: Stdio, stdout: Stdio, options: &ExecOptions) -> Result<Child> {
+        let mut command = Self::create_command(cmd, &options.limits)?;
+        command.stdin(stdin);
+        command.stdout(stdout);
+        Self::apply_redirections(&mut command, cmd, options.noclobber)?;
+        command.spawn()
+            .with_context(|| format!("Failed to spawn command: {}", cmd.program))
+    }
+
+    // Starts a command without waiting for it so job control can track it
+    pub fn spawn_background(cmd: &SimpleCommand, options: &ExecOptions) -> Result<Child> {
+        let mut command = Self::create_command(cmd, &options.limits)?;
+        Self::apply_redirections(&mut command, cmd, options.noclobber)?;
+        command.spawn()
+            .with_context(|| format!("Failed to spawn command: {}", cmd.program))
     }
     
-    fn execute_simple_command(cmd: &SimpleCommand, background: bool) -> Result<i32> {
+    fn execute_simple_command(cmd: &SimpleCommand, background: bool, options: &ExecOptions) -> Result<i32> {
         // Create the command
-        let mut command = Self::create_command(cmd)?;
+        let mut command = Self::create_command(cmd, &options.limits)?;
         
         // Apply redirections
-        Self::apply_redirections(&mut command, cmd)?;
+        Self::apply_redirections(&mut command, cmd, options.noclobber)?;
         
         if background {
             // Run in background
@@ -87,15 +166,94 @@ impl Executor {
                 .with_context(|| format!("Failed to spawn command: {}", cmd.program))?;
             println!("[{}] {}", child.id(), cmd.program);
             Ok(0)
+        } else if options.timeout.is_some() {
+            let child = command.spawn()
+                .with_context(|| format!("Failed to execute command: {}", cmd.program))?;
+            Self::wait_for_children(vec![child], options.timeout)
         } else {
             // Run in foreground
             let status = command.status()
                 .with_context(|| format!("Failed to execute command: {}", cmd.program))?;
-            Ok(status.code().unwrap_or(0))
+            Ok(Self::exit_code(status))
+        }
+    }
+
+    // Like other shells, 128 plus the signal for programs killed by one
+    fn exit_code(status: ExitStatus) -> i32 {
+        status.code().or_else(|| status.signal().map(|signal| 128 + signal)).unwrap_or(1)
+    }
+    
+    pub fn wait_for_children(mut children: Vec<Child>, timeout: Option<Duration>) -> Result<i32> {
+        if let Some(timeout) = timeout {
+            let deadline = Instant::now() + timeout;
+            
+            // Poll until everything exits or the deadline passes
+            while Instant::now() < deadline {
+                let mut all_done = true;
+                for child in children.iter_mut() {
+                    if child.try_wait()?.is_none() {
+                        all_done = false;
+                    }
+                }
+                if all_done {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            
+            if Self::any_running(&mut children)? {
+                Self::terminate(&mut children)?;
+                eprintln!("Command timed out after {}", format_duration(timeout));
+                return Ok(TIMEOUT_EXIT_CODE);
+            }
+        }
+        
+        // Wait for all children to complete
+        let mut exit_code = 0;
+        for mut child in children {
+            let status = child.wait()
+                .with_context(|| "Failed to wait for child process")?;
+            if !status.success() {
+                exit_code = Self::exit_code(status);
+            }
         }
+        
+        Ok(exit_code)
     }
     
-    fn create_command(cmd: &SimpleCommand) -> Result<Command> {
+    fn any_running(children: &mut [Child]) -> Result<bool> {
+        for child in children.iter_mut() {
+            if child.try_wait()?.is_none() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+    
+    // SIGTERM first, then SIGKILL whatever is still alive after the grace period
+    fn terminate(children: &mut [Child]) -> Result<()> {
+        for child in children.iter_mut() {
+            if child.try_wait()?.is_none() {
+                let _ = signal::kill(Pid::from_raw(child.id() as i32), Signal::SIGTERM);
+            }
+        }
+        
+        let grace_deadline = Instant::now() + KILL_GRACE_PERIOD;
+        while Instant::now() < grace_deadline && Self::any_running(children)? {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        
+        for child in children.iter_mut() {
+            if child.try_wait()?.is_none() {
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+        }
+        
+        Ok(())
+    }
+    
+    fn create_command(cmd: &SimpleCommand, limits: &ResourceLimits) -> Result<Command> {
         // Find the executable
         let executable = path_utils::find_executable(&cmd.program)
             .with_context(|| format!("Command not found: {}", cmd.program))?;
@@ -105,52 +263,242 @@ impl Executor {
         
         // Add arguments
         command.args(&cmd.args);
+        command.envs(cmd.assignments.iter().map(|(name, value)| (name, value)));
+        
+        // Apply resource limits in the child before exec
+        if !limits.is_empty() {
+            let limits = *limits;
+            unsafe {
+                command.pre_exec(move || Self::apply_limits(&limits));
+            }
+        }
         
         Ok(command)
     }
     
-    fn apply_redirections(command: &mut Command, cmd: &SimpleCommand) -> Result<()> {
+    // Runs between fork and exec, so it must stick to async-signal-safe calls
+    fn apply_limits(limits: &ResourceLimits) -> std::io::Result<()> {
+        if let Some(cpu) = limits.cpu_seconds {
+            let rlim = libc::rlimit { rlim_cur: cpu, rlim_max: cpu };
+            if unsafe { libc::setrlimit(libc::RLIMIT_CPU, &rlim) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        
+        if let Some(size) = limits.file_size {
+            let rlim = libc::rlimit { rlim_cur: size, rlim_max: size };
+            if unsafe { libc::setrlimit(libc::RLIMIT_FSIZE, &rlim) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        
+        if let Some(nice) = limits.nice {
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Points the shell's own descriptors at the targets so builtins and children of a group both
+    // see them; the previous descriptors come back when the guard is dropped
+    pub fn redirect_shell(redirects: &[Redirect], noclobber: bool) -> Result<RedirectGuard> {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+
+        let mut guard = RedirectGuard { saved: Vec::new() };
+        for redirect in redirects {
+            if !guard.saved.iter().any(|(fd, _)| *fd == redirect.fd) {
+                // A descriptor that wasn't open is closed again afterwards
+                let copy = nix::unistd::dup(redirect.fd).unwrap_or(-1);
+                guard.saved.push((redirect.fd, copy));
+            }
+
+            let file = match &redirect.target {
+                RedirectTarget::Read(filename) => File::open(filename)
+                    .with_context(|| format!("Failed to open file for input: {}", filename))?,
+                RedirectTarget::Write(filename) => Self::create(filename, noclobber)?,
+                RedirectTarget::Clobber(filename) => Self::create(filename, false)?,
+                RedirectTarget::Append(filename) => OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(filename)
+                    .with_context(|| format!("Failed to open file for append: {}", filename))?,
+                RedirectTarget::Duplicate(source) => {
+                    source.parse::<i32>().ok().and_then(|source| nix::unistd::dup2(source, redirect.fd).ok())
+                        .with_context(|| format!("Bad file descriptor: {}", source))?;
+                    continue;
+                }
+            };
+            nix::unistd::dup2(file.as_raw_fd(), redirect.fd)
+                .with_context(|| format!("Failed to redirect file descriptor {}", redirect.fd))?;
+        }
+        Ok(guard)
+    }
+
+    // Applies a builtin's redirections to the shell's own descriptors, as for
+    // a group; `<&-` style closes are left out, since the shell needs them
+    pub fn redirect_builtin(redirections: &[Redirection], noclobber: bool) -> Result<RedirectGuard> {
+        let redirect = |fd, target| Redirect { fd, target };
+        let both = |target| vec![redirect(1, target), redirect(2, RedirectTarget::Duplicate("1".into()))];
+        let redirects: Vec<Redirect> = redirections
+            .iter()
+            .flat_map(|redirection| match redirection {
+                Redirection::Input(filename) => vec![redirect(0, RedirectTarget::Read(filename.clone()))],
+                Redirection::FdInput(fd, filename) => vec![redirect(*fd, RedirectTarget::Read(filename.clone()))],
+                Redirection::Output(filename) => vec![redirect(1, RedirectTarget::Write(filename.clone()))],
+                Redirection::Clobber(filename) => vec![redirect(1, RedirectTarget::Clobber(filename.clone()))],
+                Redirection::Append(filename) => vec![redirect(1, RedirectTarget::Append(filename.clone()))],
+                Redirection::ErrorOutput(filename) => vec![redirect(2, RedirectTarget::Write(filename.clone()))],
+                Redirection::ErrorAppend(filename) => vec![redirect(2, RedirectTarget::Append(filename.clone()))],
+                Redirection::FdOutput(fd, filename) => vec![redirect(*fd, RedirectTarget::Write(filename.clone()))],
+                Redirection::FdAppend(fd, filename) => vec![redirect(*fd, RedirectTarget::Append(filename.clone()))],
+                Redirection::Both(filename) => both(RedirectTarget::Write(filename.clone())),
+                Redirection::BothAppend(filename) => both(RedirectTarget::Append(filename.clone())),
+                Redirection::Duplicate(fd, source) => vec![redirect(*fd, RedirectTarget::Duplicate(source.to_string()))],
+                Redirection::Close(_) | Redirection::Pipe => Vec::new(),
+            })
+            .collect();
+        Self::redirect_shell(&redirects, noclobber)
+    }
+
+    // Opens the files here, so errors name them, and points the descriptors
+    // at them in the child in the order written: `> log 2>&1` sends both
+    // streams to the log, `2>&1 > log` leaves stderr where stdout was
+    fn apply_redirections(command: &mut Command, cmd: &SimpleCommand, noclobber: bool) -> Result<()> {
+        let mut actions = Vec::new();
+        // Descriptors a duplicate can copy: the standard ones and those opened before it
+        let mut open = vec![0, 1, 2];
         for redirection in &cmd.redirections {
-            match redirection {
-                Redirection::Input(filename) => {
-                    let file = File::open(filename)
-                        .with_context(|| format!("Failed to open file for input: {}", filename))?;
-                    command.stdin(Stdio::from(file));
-                },
-                Redirection::Output(filename) => {
-                    let file = File::create(filename)
-                        .with_context(|| format!("Failed to create file for output: {}", filename))?;
-                    command.stdout(Stdio::from(file));
-                },
-                Redirection::Append(filename) => {
-                    let file = OpenOptions::new()
-                        .write(true)
-                        .append(true)
-                        .create(true)
-                        .open(filename)
-                        .with_context(|| format!("Failed to open file for append: {}", filename))?;
-                    command.stdout(Stdio::from(file));
-                },
-                Redirection::ErrorOutput(filename) => {
-                    let file = File::create(filename)
-                        .with_context(|| format!("Failed to create file for error output: {}", filename))?;
-                    command.stderr(Stdio::from(file));
+            let action = match redirection {
+                Redirection::Pipe => continue, // Pipes are handled separately
+                Redirection::Input(filename) => FdAction::Open(Self::open_input(filename)?, 0),
+                Redirection::FdInput(fd, filename) => FdAction::Open(Self::open_input(filename)?, *fd),
+                Redirection::Output(filename) => FdAction::Open(Self::create(filename, noclobber)?, 1),
+                Redirection::Clobber(filename) => FdAction::Open(Self::create(filename, false)?, 1),
+                Redirection::Append(filename) => FdAction::Open(Self::open_append(filename)?, 1),
+                Redirection::ErrorOutput(filename) => FdAction::Open(Self::create(filename, noclobber)?, 2),
+                Redirection::ErrorAppend(filename) => FdAction::Open(Self::open_append(filename)?, 2),
+                Redirection::FdOutput(fd, filename) => FdAction::Open(Self::create(filename, noclobber)?, *fd),
+                Redirection::FdAppend(fd, filename) => FdAction::Open(Self::open_append(filename)?, *fd),
+                Redirection::Both(filename) | Redirection::BothAppend(filename) => {
+                    let file = match redirection {
+                        Redirection::Both(_) => Self::create(filename, noclobber)?,
+                        _ => Self::open_append(filename)?,
+                    };
+                    actions.push(FdAction::Open(file, 1));
+                    FdAction::Duplicate(1, 2)
                 },
-                Redirection::ErrorAppend(filename) => {
-                    let file = OpenOptions::new()
-                        .write(true)
-                        .append(true)
-                        .create(true)
-                        .open(filename)
-                        .with_context(|| format!("Failed to open file for error append: {}", filename))?;
-                    command.stderr(Stdio::from(file));
-                },
-                Redirection::Pipe => {
-                    // Pipes are handled separately
+                Redirection::Duplicate(_, source) if !open.contains(source) => {
+                    return Err(anyhow::anyhow!("{}: Bad file descriptor", source));
                 },
+                Redirection::Duplicate(fd, source) => FdAction::Duplicate(*source, *fd),
+                Redirection::Close(fd) => FdAction::Close(*fd),
+            };
+            match &action {
+                FdAction::Open(_, fd) | FdAction::Duplicate(_, fd) => open.push(*fd),
+                FdAction::Close(fd) => open.retain(|open| open != fd),
             }
+            actions.push(action);
+        }
+        if actions.is_empty() {
+            return Ok(());
+        }
+
+        // Runs after the pipes are set up on 0-2, so these apply on top of them
+        unsafe {
+            command.pre_exec(move || {
+                for action in &actions {
+                    let result = match action {
+                        FdAction::Open(file, fd) => Self::copy_fd(file.as_raw_fd(), *fd),
+                        FdAction::Duplicate(source, fd) => Self::copy_fd(*source, *fd),
+                        FdAction::Close(fd) => {
+                            libc::close(*fd);
+                            0
+                        }
+                    };
+                    if result == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
         }
-        
         Ok(())
     }
-}
\ No newline at end of file
+
+    // dup2, except that a descriptor copied onto itself is kept open across exec
+    unsafe fn copy_fd(source: i32, fd: i32) -> i32 {
+        if source == fd {
+            libc::fcntl(fd, libc::F_SETFD, 0)
+        } else {
+            libc::dup2(source, fd)
+        }
+    }
+
+    fn open_input(filename: &str) -> Result<File> {
+        let file = File::open(filename)
+            .with_context(|| format!("Failed to open file for input: {}", filename))?;
+        Self::above_targets(file)
+    }
+
+    // With noclobber an existing file is refused rather than truncated
+    fn create(filename: &str, noclobber: bool) -> Result<File> {
+        if noclobber && std::path::Path::new(filename).is_file() {
+            return Err(anyhow::anyhow!("{}: cannot overwrite existing file (use >| to force)", filename));
+        }
+        let file = File::create(filename)
+            .with_context(|| format!("Failed to create file for output: {}", filename))?;
+        Self::above_targets(file)
+    }
+
+    fn open_append(filename: &str) -> Result<File> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(filename)
+            .with_context(|| format!("Failed to open file for append: {}", filename))?;
+        Self::above_targets(file)
+    }
+
+    // Moves an opened file to descriptor 10 or above, out of the way of the
+    // small numbers redirections point at, as other shells do
+    fn above_targets(file: File) -> Result<File> {
+        let fd = nix::fcntl::fcntl(file.as_raw_fd(), nix::fcntl::FcntlArg::F_DUPFD_CLOEXEC(10))?;
+        // SAFETY: the descriptor was just created and nothing else owns it
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noclobber() {
+        let dir = std::env::temp_dir().join(format!("llm-shell-noclobber-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("existing");
+        std::fs::write(&file, "kept").unwrap();
+        let name = file.to_string_lossy().to_string();
+
+        for redirection in [
+            Redirection::Output(name.clone()),
+            Redirection::ErrorOutput(name.clone()),
+            Redirection::FdOutput(3, name.clone()),
+            Redirection::Both(name.clone()),
+        ] {
+            let cmd = SimpleCommand { program: "true".into(), args: Vec::new(), redirections: vec![redirection], assignments: Vec::new() };
+            assert!(Executor::apply_redirections(&mut Command::new("true"), &cmd, true).is_err());
+        }
+        let redirect = Redirect { fd: 2, target: RedirectTarget::Write(name.clone()) };
+        assert!(Executor::redirect_shell(&[redirect], true).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "kept");
+
+        let cmd = SimpleCommand { program: "true".into(), args: Vec::new(), redirections: vec![Redirection::Clobber(name.clone())], assignments: Vec::new() };
+        assert!(Executor::apply_redirections(&mut Command::new("true"), &cmd, true).is_ok());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::shell::policy;
+
+// Parts end at a blank line once they are this long, and are cut at the
+// next top-level line past the maximum
+const MIN_CHUNK_LINES: usize = 8;
+const MAX_CHUNK_LINES: usize = 40;
+
+lazy_static! {
+    static ref NETWORK_RE: Regex = Regex::new(
+        r"(^|[;&|(`\s])(curl|wget|ssh|scp|sftp|rsync|nc|ncat|telnet|ftp)\s|git\s+(clone|push|pull|fetch)\b|(apt-get|apt|yum|dnf|pip3?|npm|cargo|gem)\s+install\b|docker\s+(pull|push)\b"
+    ).unwrap();
+    static ref DELETION_RE: Regex = Regex::new(
+        r"(^|[;&|(`\s])(rm|rmdir|shred|unlink|truncate|dd|wipefs|mkfs(\.\w+)?)\s|\s-delete\b|git\s+(clean|reset\s+--hard)\b"
+    ).unwrap();
+    static ref FUNCTION_RE: Regex = Regex::new(r"^\s*(?:function\s+([\w-]+)|([\w-]+)\s*\(\s*\))").unwrap();
+    static ref HEREDOC_RE: Regex = Regex::new(r#"<<-?\s*['"]?(\w+)['"]?"#).unwrap();
+}
+
+// A run of whole lines of a script; `first_line` counts from 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub first_line: usize,
+    pub lines: Vec<String>,
+}
+
+impl Chunk {
+    pub fn last_line(&self) -> usize {
+        self.first_line + self.lines.len() - 1
+    }
+
+    // The lines prefixed with their numbers, as sent to the model
+    pub fn numbered(&self) -> String {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| format!("{:>4}| {}", self.first_line + i, line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Risk {
+    Network,
+    Deletion,
+    Privilege,
+}
+
+impl Risk {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Risk::Network => "network",
+            Risk::Deletion => "deletes",
+            Risk::Privilege => "privileges",
+        }
+    }
+}
+
+// Splits a script into parts to explain one at a time, only between
+// top-level commands so if/fi, loops, case and function bodies stay whole
+// (unless a single one is longer than the maximum)
+pub fn chunks(script: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut current = Chunk { first_line: 1, lines: Vec::new() };
+    let mut depth: i32 = 0;
+    let mut heredoc: Option<String> = None;
+    for (i, line) in script.lines().enumerate() {
+        if current.lines.is_empty() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            current.first_line = i + 1;
+        }
+        current.lines.push(line.to_string());
+
+        match &heredoc {
+            Some(end) if line.trim() == end => heredoc = None,
+            Some(_) => continue,
+            None => {
+                depth += nesting(line);
+                heredoc = HEREDOC_RE.captures(line).map(|captures| captures[1].to_string());
+            }
+        }
+        let at_top = depth <= 0 && heredoc.is_none();
+        let blank_next = script.lines().nth(i + 1).map(|next| next.trim().is_empty()).unwrap_or(true);
+        let full = current.lines.len() >= MAX_CHUNK_LINES || (blank_next && current.lines.len() >= MIN_CHUNK_LINES);
+        if (at_top && full) || current.lines.len() >= MAX_CHUNK_LINES * 2 {
+            depth = depth.max(0);
+            chunks.push(std::mem::replace(&mut current, Chunk { first_line: i + 2, lines: Vec::new() }));
+        }
+    }
+    while current.lines.last().map(|line| line.trim().is_empty()).unwrap_or(false) {
+        current.lines.pop();
+    }
+    if !current.lines.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+// How much a line opens (if, loops, case, `{`) or closes (fi, done, esac, `}`)
+fn nesting(line: &str) -> i32 {
+    let mut change = 0;
+    for word in code(line).split(|c: char| c.is_whitespace() || c == ';' || c == '&' || c == '|') {
+        match word {
+            "if" | "for" | "while" | "until" | "case" | "select" | "{" => change += 1,
+            "fi" | "done" | "esac" | "}" => change -= 1,
+            _ if word.ends_with('{') && !word.contains('$') => change += 1,
+            _ => {}
+        }
+    }
+    change
+}
+
+// The line without its comment and quoted text
+fn code(line: &str) -> String {
+    let mut code = String::new();
+    let mut quote = None;
+    let mut previous = ' ';
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => break,
+            None => code.push(c),
+        }
+        previous = c;
+    }
+    code
+}
+
+// The script's shebang and functions, so each part can be placed in the whole
+pub fn outline(script: &str) -> String {
+    let lines: Vec<&str> = script.lines().collect();
+    let mut outline = vec![format!("{} lines", lines.len())];
+    if let Some(shebang) = lines.first().filter(|line| line.starts_with("#!")) {
+        outline.push(format!("interpreter: {}", shebang.trim_start_matches("#!").trim()));
+    }
+    let functions: Vec<String> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let captures = FUNCTION_RE.captures(line)?;
+            let name = captures.get(1).or(captures.get(2))?.as_str();
+            Some(format!("{} (line {})", name, i + 1))
+        })
+        .collect();
+    if !functions.is_empty() {
+        outline.push(format!("functions: {}", functions.join(", ")));
+    }
+    outline.join("\n")
+}
+
+// Lines that reach the network, delete data or escalate privileges, found
+// without the model so the summary can be relied on
+pub fn risks(script: &str) -> Vec<(usize, Risk, String)> {
+    let mut risks = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        let code = code(line);
+        if code.trim().is_empty() {
+            continue;
+        }
+        let found = [
+            (Risk::Privilege, policy::privilege_escalation(code.trim()).is_some()),
+            (Risk::Deletion, DELETION_RE.is_match(&code)),
+            (Risk::Network, NETWORK_RE.is_match(&code)),
+        ];
+        for (risk, _) in found.iter().filter(|(_, found)| *found) {
+            risks.push((i + 1, *risk, line.trim().to_string()));
+        }
+    }
+    risks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "#!/bin/bash\nset -e\n\ndeploy() {\n  rsync -a build/ web:/srv\n\n  ssh web 'sudo systemctl restart app'\n}\n\nif [ \"$1\" = clean ]; then\n  rm -rf build # start over\nfi\necho '# not a comment { '\ncat <<EOF\nif this were code\nEOF\nsudo apt-get install -y jq\n";
+
+    #[test]
+    fn test_chunks() {
+        let text: String = (1..=30).map(|n| format!("echo {}\n", n)).collect::<String>()
+            + "\nfor f in *; do\n"
+            + &(1..=20).map(|n| format!("  echo {}\n", n)).collect::<String>()
+            + "done\necho end\n";
+        let chunks = chunks(&text);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].first_line, chunks[0].last_line()), (1, 30));
+        assert_eq!((chunks[1].first_line, chunks[1].last_line()), (32, 54));
+        assert!(chunks[1].numbered().starts_with("  32| for f in *; do"));
+
+        // Blank lines inside a function don't end a part
+        let chunks: Vec<(usize, usize)> = super::chunks(SCRIPT).iter().map(|chunk| (chunk.first_line, chunk.last_line())).collect();
+        assert_eq!(chunks, vec![(1, 8), (10, 17)]);
+        assert_eq!(nesting("if [ -f x ]; then echo '{'; fi"), 0);
+        assert_eq!(nesting("main() {"), 1);
+    }
+
+    #[test]
+    fn test_outline_and_risks() {
+        assert_eq!(outline(SCRIPT), "17 lines\ninterpreter: /bin/bash\nfunctions: deploy (line 4)");
+        let risks: Vec<(usize, Risk)> = risks(SCRIPT).into_iter().map(|(line, risk, _)| (line, risk)).collect();
+        assert_eq!(risks, vec![
+            (5, Risk::Network),
+            (7, Risk::Network),
+            (11, Risk::Deletion),
+            (17, Risk::Privilege),
+            (17, Risk::Network),
+        ]);
+    }
+}
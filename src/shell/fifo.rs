@@ -0,0 +1,155 @@
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use nix::sys::stat::Mode;
+use std::ffi::{CString, OsString};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::shell::command_parser::{Pipeline, Redirection};
+
+// How long a command may sit on a FIFO before the hint is shown
+const BLOCKED_AFTER: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    // FIFOs created with `mkfifo -t` and their directory, removed when the shell exits
+    static ref TEMPORARY: Mutex<Temporary> = Mutex::new(Temporary::default());
+}
+
+#[derive(Default)]
+struct Temporary {
+    dir: Option<PathBuf>,
+    fifos: Vec<PathBuf>,
+}
+
+pub fn is_fifo(path: &Path) -> bool {
+    std::fs::metadata(path).map(|meta| meta.file_type().is_fifo()).unwrap_or(false)
+}
+
+pub fn create(path: &Path, mode: u32) -> Result<()> {
+    nix::unistd::mkfifo(path, Mode::from_bits_truncate(mode))
+        .with_context(|| format!("mkfifo: cannot create fifo '{}'", path.display()))
+}
+
+// A new FIFO in a private directory under $TMPDIR, cleaned up by `remove_temporary`.
+// mkdtemp picks an unused name and creates the directory with mode 0700.
+pub fn create_temporary() -> Result<PathBuf> {
+    let mut temporary = TEMPORARY.lock().unwrap();
+    let dir = match &temporary.dir {
+        Some(dir) => dir.clone(),
+        None => {
+            let dir = make_private_dir()
+                .with_context(|| format!("mkfifo: cannot create directory in '{}'", std::env::temp_dir().display()))?;
+            temporary.dir = Some(dir.clone());
+            dir
+        }
+    };
+    let path = dir.join(format!("fifo{}", temporary.fifos.len()));
+    create(&path, 0o600)?;
+    temporary.fifos.push(path.clone());
+    Ok(path)
+}
+
+fn make_private_dir() -> std::io::Result<PathBuf> {
+    let template = std::env::temp_dir().join("llm-shell-XXXXXX");
+    let mut template = CString::new(template.into_os_string().into_vec())?.into_bytes_with_nul();
+    if unsafe { libc::mkdtemp(template.as_mut_ptr() as *mut libc::c_char) }.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    template.pop();
+    Ok(PathBuf::from(OsString::from_vec(template)))
+}
+
+pub fn remove_temporary() {
+    let mut temporary = TEMPORARY.lock().unwrap();
+    for path in temporary.fifos.drain(..) {
+        let _ = std::fs::remove_file(&path);
+    }
+    if let Some(dir) = temporary.dir.take() {
+        let _ = std::fs::remove_dir(dir);
+    }
+}
+
+// FIFOs a pipeline opens, with the end another process has to open for it
+// to go on. Arguments could be opened either way.
+fn fifos(pipeline: &Pipeline) -> Vec<(String, &'static str)> {
+    let mut fifos = Vec::new();
+    for cmd in &pipeline.commands {
+        for redirection in &cmd.redirections {
+            let (path, other_end) = match redirection {
+                Redirection::Input(path) | Redirection::FdInput(_, path) => (path, "writing"),
+                Redirection::Output(path)
+                | Redirection::Clobber(path)
+                | Redirection::Append(path)
+                | Redirection::ErrorOutput(path)
+                | Redirection::ErrorAppend(path)
+                | Redirection::Both(path)
+                | Redirection::BothAppend(path)
+                | Redirection::FdOutput(_, path)
+                | Redirection::FdAppend(_, path) => (path, "reading"),
+                Redirection::Duplicate(..) | Redirection::Close(_) | Redirection::Pipe => continue,
+            };
+            if is_fifo(Path::new(path)) {
+                fifos.push((path.clone(), other_end));
+            }
+        }
+        for arg in cmd.args.iter().filter(|arg| is_fifo(Path::new(arg))) {
+            fifos.push((arg.clone(), "reading or writing"));
+        }
+    }
+    fifos
+}
+
+// Prints a hint when a pipeline with a FIFO is stuck opening it, which waits
+// until another process opens the other end. Stops watching when dropped.
+pub struct BlockedHint {
+    done: Arc<AtomicBool>,
+}
+
+impl BlockedHint {
+    pub fn watch(pipeline: &Pipeline) -> Option<Self> {
+        let fifos = fifos(pipeline);
+        if fifos.is_empty() {
+            return None;
+        }
+        let done = Arc::new(AtomicBool::new(false));
+        let finished = done.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(BLOCKED_AFTER);
+            if finished.load(Ordering::SeqCst) || !waiting_for_partner() {
+                return;
+            }
+            for (path, other_end) in fifos {
+                eprintln!(
+                    "hint: '{}' is a FIFO and opening it waits until another process opens it for {}; \
+                     start one in another terminal or in the background, or press Ctrl+C",
+                    path, other_end
+                );
+            }
+        });
+        Some(BlockedHint { done })
+    }
+}
+
+// Whether the shell (opening a redirection) or one of its children is blocked
+// opening a FIFO, which Linux shows as the wait channel `wait_for_partner`
+fn waiting_for_partner() -> bool {
+    let waiting = |wchan: PathBuf| std::fs::read_to_string(wchan).map(|name| name.trim() == "wait_for_partner").unwrap_or(false);
+    let shell = std::process::id().to_string();
+    let threads = std::fs::read_dir("/proc/self/task").into_iter().flatten().flatten().map(|task| task.path().join("wchan"));
+    let children = std::fs::read_dir("/proc").into_iter().flatten().flatten().filter_map(|process| {
+        let stat = std::fs::read_to_string(process.path().join("stat")).ok()?;
+        // The parent pid is the second field after the parenthesized command name
+        let parent = stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.to_string();
+        (parent == shell).then(|| process.path().join("wchan"))
+    });
+    threads.chain(children).any(waiting)
+}
+
+impl Drop for BlockedHint {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use crate::utils::path_utils::lookup;
+
+// What keeps the script from being one to hand over: no shebang or safety
+// flags, syntax errors, and shellcheck findings when it is installed
+pub fn problems(script: &str) -> Vec<String> {
+    let mut problems = missing_safety(script);
+    problems.extend(check_with("bash", &["-n"], script));
+    if lookup("shellcheck").is_some() {
+        problems.extend(check_with("shellcheck", &["-f", "gcc", "-"], script));
+    }
+    problems
+}
+
+fn missing_safety(script: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    if !script.starts_with("#!") {
+        problems.push("the first line is not a shebang".to_string());
+    }
+    // Flags may be spread over several `set` lines
+    let set_words: Vec<&str> = script
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("set "))
+        .flat_map(str::split_whitespace)
+        .collect();
+    let short_flag = |flag: char| set_words.iter().any(|word| word.starts_with('-') && !word.starts_with("--") && word.contains(flag));
+    for (flag, name) in [('e', "errexit"), ('u', "nounset")] {
+        if !short_flag(flag) && !set_words.contains(&name) {
+            problems.push(format!("missing `set -{}`", flag));
+        }
+    }
+    if !set_words.contains(&"pipefail") {
+        problems.push("missing `set -o pipefail`".to_string());
+    }
+    problems
+}
+
+// Runs a checker on the script and returns what it reported, one finding
+// per line, or nothing if it passed or couldn't be run
+fn check_with(program: &str, args: &[&str], script: &str) -> Vec<String> {
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let Ok(mut child) = child else {
+        return Vec::new();
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(script.as_bytes());
+    }
+    let Ok(output) = child.wait_with_output() else {
+        return Vec::new();
+    };
+    if output.status.success() {
+        return Vec::new();
+    }
+    let report = String::from_utf8_lossy(&output.stdout).to_string() + &String::from_utf8_lossy(&output.stderr);
+    // `-:12:3: warning: ...` from shellcheck, `bash: line 12: ...` from bash
+    report
+        .lines()
+        .map(|line| match line.strip_prefix("-:").and_then(|rest| rest.split_once(':')) {
+            Some((number, rest)) => format!("line {}:{}", number, rest.split_once(':').map(|(_, rest)| rest).unwrap_or(rest)),
+            None => line.trim_start_matches("bash: ").to_string(),
+        })
+        .filter(|line| !line.trim().is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_safety() {
+        assert!(missing_safety("#!/usr/bin/env bash\nset -euo pipefail\necho hi\n").is_empty());
+        assert!(missing_safety("#!/bin/bash\nset -e\nset -o nounset -o pipefail\n").is_empty());
+        assert_eq!(missing_safety("echo hi\n"), vec![
+            "the first line is not a shebang",
+            "missing `set -e`",
+            "missing `set -u`",
+            "missing `set -o pipefail`",
+        ]);
+    }
+}
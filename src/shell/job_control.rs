@@ -2,10 +2,14 @@ use anyhow::{Result, Context};
 use std::process::{Command, Stdio, Child};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use nix::sys::signal::{self, Signal};
+use nix::sys::signal::{self, kill, Signal};
 use nix::unistd::Pid;
 use std::time::SystemTime;
 use libc;
+use crate::config::CONFIG;
+use crate::system::notify::Notifier;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct Job {
@@ -21,6 +25,29 @@ pub enum JobStatus {
     Stopped,
     Completed(i32),
     Failed(i32),
+    // An adopted job that ended while another process was its parent, so its
+    // exit status can't be known
+    Ended,
+}
+
+// A running job as kept in the session file, so the next instance can adopt it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedJob {
+    pub id: u32,
+    pub pid: u32,
+    pub pgid: i32,
+    pub command: String,
+    // When the process started, in clock ticks after boot, so a reused pid
+    // isn't mistaken for the job
+    pub start_ticks: Option<u64>,
+}
+
+// The parent pid and start time of a process, from /proc/PID/stat
+fn process_info(pid: u32) -> Option<(u32, u64)> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Fields after the parenthesized command name start with the state (field 3)
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    Some((fields.get(1)?.parse().ok()?, fields.get(19)?.parse().ok()?))
 }
 
 #[derive(Default)]
@@ -29,6 +56,8 @@ pub struct JobControl {
     last_job_id: u32,
     foreground_job: Option<u32>,
     job_mutex: Arc<Mutex<()>>,
+    // Filled in by monitor threads, reported before the next prompt
+    finished: Arc<Mutex<Vec<(u32, JobStatus)>>>,
 }
 
 impl JobControl {
@@ -38,6 +67,7 @@ impl JobControl {
             last_job_id: 0,
             foreground_job: None,
             job_mutex: Arc::new(Mutex::new(())),
+            finished: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -110,7 +140,7 @@ impl JobControl {
 
         if background {
             println!("[{}] {} {}", job_id, child.id(), exec_command);
-            self.monitor_background_job(job_id, child);
+            self.monitor_background_job(job_id, exec_command.to_string(), child);
         } else {
             self.foreground_job = Some(job_id);
             self.wait_for_foreground_job(child)?;
@@ -119,24 +149,132 @@ impl JobControl {
         Ok(())
     }
 
-    fn monitor_background_job(&self, job_id: u32, mut child: Child) {
+    pub fn add_background(&mut self, command: &str, child: Child) {
+        self.last_job_id += 1;
+        let job_id = self.last_job_id;
+        self.jobs.insert(job_id, Job {
+            pid: child.id(),
+            command: command.to_string(),
+            status: JobStatus::Running,
+            start_time: SystemTime::now(),
+        });
+
+        println!("[{}] {}", job_id, child.id());
+        self.monitor_background_job(job_id, command.to_string(), child);
+    }
+
+    fn monitor_background_job(&self, job_id: u32, command: String, mut child: Child) {
         let job_mutex = self.job_mutex.clone();
+        let finished = self.finished.clone();
         std::thread::spawn(move || {
-            let status = child.wait();
+            let status = match child.wait() {
+                Ok(status) if status.success() => JobStatus::Completed(0),
+                Ok(status) => JobStatus::Failed(status.code().unwrap_or(-1)),
+                Err(_) => JobStatus::Failed(-1),
+            };
             let _lock = job_mutex.lock().unwrap();
-            
-            if let Ok(status) = status {
-                if let Some(code) = status.code() {
-                    if status.success() {
-                        println!("[{}] Done {}", job_id, code);
-                    } else {
-                        println!("[{}] Exit {}", job_id, code);
-                    }
-                }
+
+            // Notify right away; the Done line waits for the next prompt
+            if CONFIG.desktop_notifications {
+                let (title, detail) = match status {
+                    JobStatus::Completed(_) => (format!("Job [{}] done", job_id), "exit 0".to_string()),
+                    JobStatus::Failed(code) => (format!("Job [{}] failed", job_id), format!("exit {}", code)),
+                    _ => unreachable!(),
+                };
+                let _ = Notifier::notify(&title, &format!("{} ({})", command, detail));
+            }
+
+            finished.lock().unwrap().push((job_id, status));
+        });
+    }
+
+    // Running and stopped jobs, for the session file
+    pub fn saved(&self) -> Vec<SavedJob> {
+        let mut saved: Vec<SavedJob> = self.jobs
+            .iter()
+            .filter(|(_, job)| matches!(job.status, JobStatus::Running | JobStatus::Stopped))
+            .map(|(id, job)| SavedJob {
+                id: *id,
+                pid: job.pid,
+                pgid: nix::unistd::getpgid(Some(Pid::from_raw(job.pid as i32))).map(|pgid| pgid.as_raw()).unwrap_or(0),
+                command: job.command.clone(),
+                start_ticks: process_info(job.pid).map(|(_, ticks)| ticks),
+            })
+            .collect();
+        saved.sort_by_key(|job| job.id);
+        saved
+    }
+
+    // Takes over jobs a previous instance saved: its children after an exec,
+    // which are still ours to wait for, or orphans after a crash, which are
+    // polled until they go away. Returns the jobs adopted and those already gone.
+    pub fn adopt(&mut self, saved: &[SavedJob]) -> (usize, usize) {
+        let (mut adopted, mut gone) = (0, 0);
+        for job in saved {
+            let info = process_info(job.pid);
+            let alive = kill(Pid::from_raw(job.pid as i32), None).is_ok()
+                && (job.start_ticks.is_none() || info.map(|(_, ticks)| ticks) == job.start_ticks);
+            if !alive {
+                gone += 1;
+                continue;
             }
+            let job_id = if self.jobs.contains_key(&job.id) { self.last_job_id + 1 } else { job.id };
+            self.last_job_id = self.last_job_id.max(job_id);
+            self.jobs.insert(job_id, Job {
+                pid: job.pid,
+                command: job.command.clone(),
+                status: JobStatus::Running,
+                start_time: SystemTime::now(),
+            });
+            let ours = info.map(|(parent, _)| parent == std::process::id()).unwrap_or(false);
+            self.monitor_adopted_job(job_id, job.clone(), ours);
+            adopted += 1;
+        }
+        (adopted, gone)
+    }
+
+    fn monitor_adopted_job(&self, job_id: u32, job: SavedJob, ours: bool) {
+        let finished = self.finished.clone();
+        std::thread::spawn(move || {
+            let pid = Pid::from_raw(job.pid as i32);
+            let status = if ours {
+                match nix::sys::wait::waitpid(pid, None) {
+                    Ok(nix::sys::wait::WaitStatus::Exited(_, 0)) => JobStatus::Completed(0),
+                    Ok(nix::sys::wait::WaitStatus::Exited(_, code)) => JobStatus::Failed(code),
+                    Ok(nix::sys::wait::WaitStatus::Signaled(_, signal, _)) => JobStatus::Failed(128 + signal as i32),
+                    _ => JobStatus::Ended,
+                }
+            } else {
+                while kill(pid, None).is_ok() && process_info(job.pid).map(|(_, ticks)| ticks) == job.start_ticks {
+                    std::thread::sleep(Duration::from_secs(1));
+                }
+                JobStatus::Ended
+            };
+            finished.lock().unwrap().push((job_id, status));
         });
     }
 
+    // Jobs still running or stopped
+    pub fn active_count(&self) -> usize {
+        self.jobs.values().filter(|job| matches!(job.status, JobStatus::Running | JobStatus::Stopped)).count()
+    }
+
+    // Prints bash-style "[1]+  Done" lines for jobs that finished since the last prompt
+    pub fn report_finished(&mut self) {
+        let finished: Vec<(u32, JobStatus)> = self.finished.lock().unwrap().drain(..).collect();
+        for (job_id, status) in finished {
+            let Some(job) = self.jobs.get_mut(&job_id) else {
+                continue;
+            };
+            let label = match status {
+                JobStatus::Failed(code) => format!("Exit {}", code),
+                _ => "Done".to_string(),
+            };
+            println!("[{}]+  {:<22}  {}", job_id, label, job.command);
+            job.status = status;
+        }
+    }
+
     fn wait_for_foreground_job(&mut self, mut child: Child) -> Result<()> {
         let status = child.wait()
             .with_context(|| "Failed to wait for foreground process")?;
@@ -167,7 +305,7 @@ impl JobControl {
             let status = match job.status {
                 JobStatus::Running => "Running",
                 JobStatus::Stopped => "Stopped",
-                JobStatus::Completed(_) => "Done",
+                JobStatus::Completed(_) | JobStatus::Ended => "Done",
                 JobStatus::Failed(_) => "Failed",
             };
 
@@ -0,0 +1,281 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// What an importer found in another shell's configuration
+#[derive(Debug, Default, PartialEq)]
+pub struct Import {
+    pub aliases: Vec<(String, String)>,
+    // Definitions that couldn't be imported, and why
+    pub skipped: Vec<(String, &'static str)>,
+}
+
+impl Import {
+    fn extend(&mut self, other: Import) {
+        self.aliases.extend(other.aliases);
+        self.skipped.extend(other.skipped);
+    }
+}
+
+pub type Importer = fn(&str) -> Import;
+
+const FUNCTIONS_UNSUPPORTED: &str = "only one-line functions can be imported";
+
+// zsh: `alias [-g|-s] NAME=VALUE...` lines and function definitions
+pub fn import_zsh(content: &str) -> Import {
+    let mut import = Import::default();
+    for line in content.lines().map(str::trim) {
+        if is_zsh_function(line) {
+            // `name() { BODY; }` becomes an alias using its arguments
+            match one_line_function(line) {
+                Some(alias) => import.aliases.push(alias),
+                None => import.skipped.push((line.to_string(), FUNCTIONS_UNSUPPORTED)),
+            }
+            continue;
+        }
+        if !line.starts_with("alias ") {
+            continue;
+        }
+        let words = match shellwords::split(line) {
+            Ok(words) => words,
+            Err(_) => {
+                import.skipped.push((line.to_string(), "unbalanced quotes"));
+                continue;
+            }
+        };
+        let mut words = words[1..].iter().peekable();
+        let (mut global, mut suffix) = (false, false);
+        while let Some(flag) = words.peek().filter(|word| word.starts_with('-')) {
+            global |= flag.as_str() == "-g";
+            suffix |= flag.as_str() == "-s";
+            words.next();
+        }
+        // `alias NAME` without a value only prints the alias
+        for (name, value) in words.filter_map(|word| word.split_once('=')) {
+            if suffix {
+                import.skipped.push((line.to_string(), "suffix aliases (-s) aren't supported"));
+                continue;
+            }
+            // Imported anyway, since they're mostly used as commands too
+            if global {
+                import.skipped.push((line.to_string(), "global aliases (-g) only expand as the first word here"));
+            }
+            import.aliases.push((name.to_string(), value.to_string()));
+        }
+    }
+    import
+}
+
+fn is_zsh_function(line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["function", name, ..] => !name.starts_with('-'),
+        [first, ..] => first.ends_with("()") || words.get(1).map(|next| next.starts_with("()")).unwrap_or(false),
+        [] => false,
+    }
+}
+
+fn one_line_function(line: &str) -> Option<(String, String)> {
+    let (head, body) = line.split_once('{')?;
+    let body = body.trim().strip_suffix('}')?.trim().trim_end_matches(';').trim();
+    let name = head.trim().trim_start_matches("function").trim().trim_end_matches("()").trim();
+    if name.is_empty() || body.is_empty() || name.contains(char::is_whitespace) || body.contains('{') || body.contains("local ") {
+        return None;
+    }
+    Some((name.to_string(), body.to_string()))
+}
+
+// fish: `alias`, `abbr` and `function` lines from config.fish and conf.d
+pub fn import_fish(content: &str) -> Import {
+    let mut import = Import::default();
+    for line in content.lines().map(str::trim) {
+        let words = match shellwords::split(line) {
+            Ok(words) => words,
+            Err(_) => continue,
+        };
+        match words.first().map(String::as_str) {
+            Some("alias") => {
+                let args: Vec<&String> = words[1..].iter().filter(|word| !word.starts_with('-')).collect();
+                match args.as_slice() {
+                    [definition] => {
+                        if let Some((name, value)) = definition.split_once('=') {
+                            import.aliases.push((name.to_string(), value.to_string()));
+                        }
+                    }
+                    [name, value @ ..] if !value.is_empty() => {
+                        let value: Vec<&str> = value.iter().map(|word| word.as_str()).collect();
+                        import.aliases.push((name.to_string(), value.join(" ")));
+                    }
+                    _ => {}
+                }
+            }
+            Some("abbr") => import.extend(fish_abbr(line, &words[1..])),
+            // `alias --save` writes a function whose description holds the alias
+            Some("function") => match fish_saved_alias(&words) {
+                Some(alias) => import.aliases.push(alias),
+                None => import.skipped.push((line.to_string(), FUNCTIONS_UNSUPPORTED)),
+            },
+            _ => {}
+        }
+    }
+    import
+}
+
+fn fish_abbr(line: &str, args: &[String]) -> Import {
+    let mut import = Import::default();
+    let mut operands = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-a" | "--add" | "-g" | "--global" | "-U" | "--universal" => {}
+            "--position" | "-p" => {
+                if args.next().map(String::as_str) == Some("anywhere") {
+                    import.skipped.push((line.to_string(), "abbreviations expanded anywhere aren't supported"));
+                    return import;
+                }
+            }
+            "--function" | "-f" | "--regex" | "-r" | "--set-cursor" => {
+                import.skipped.push((line.to_string(), "abbreviations with --function, --regex or --set-cursor aren't supported"));
+                return import;
+            }
+            // Erasing, listing and querying don't define anything
+            flag if flag.starts_with('-') => return import,
+            operand => operands.push(operand),
+        }
+    }
+    if let [name, expansion @ ..] = operands.as_slice() {
+        if !expansion.is_empty() {
+            import.aliases.push((name.to_string(), expansion.join(" ")));
+        }
+    }
+    import
+}
+
+// `function ll --wraps 'ls -la' --description 'alias ll=ls -la'`
+fn fish_saved_alias(words: &[String]) -> Option<(String, String)> {
+    let position = words.iter().position(|word| word == "--description" || word == "-d")?;
+    let description = words.get(position + 1)?.strip_prefix("alias ")?;
+    let (name, value) = description.split_once('=').or_else(|| description.split_once(' '))?;
+    Some((name.trim().to_string(), value.trim().to_string()))
+}
+
+// Universal abbreviations in fish_variables: `SETUVAR _fish_abbr_NAME:VALUE`,
+// with `_XX_` escapes in the name and `\xXX` escapes in the value
+pub fn import_fish_variables(content: &str) -> Import {
+    let mut import = Import::default();
+    for line in content.lines() {
+        let Some(rest) = line.strip_prefix("SETUVAR _fish_abbr_").or_else(|| line.strip_prefix("SETUVAR --export _fish_abbr_")) else {
+            continue;
+        };
+        if let Some((name, value)) = rest.split_once(':') {
+            import.aliases.push((unescape_name(name), unescape_value(value)));
+        }
+    }
+    import
+}
+
+fn unescape_name(name: &str) -> String {
+    let mut result = String::new();
+    let mut rest = name;
+    while let Some(start) = rest.find('_') {
+        result.push_str(&rest[..start]);
+        let escape = rest.get(start + 1..start + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) if rest.get(start + 3..start + 4) == Some("_") => {
+                result.push(byte as char);
+                rest = &rest[start + 4..];
+            }
+            _ => {
+                result.push('_');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn unescape_value(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("\\x") {
+        result.push_str(&rest[..start]);
+        match rest.get(start + 2..start + 4).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                result.push(byte as char);
+                rest = &rest[start + 4..];
+            }
+            None => {
+                result.push_str("\\x");
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// The config files of a shell that exist, each with its importer
+pub fn sources(shell: &str) -> Vec<(PathBuf, Importer)> {
+    let home = match dirs::home_dir() {
+        Some(home) => home,
+        None => return Vec::new(),
+    };
+    let mut files: Vec<(PathBuf, Importer)> = Vec::new();
+    match shell {
+        "zsh" => {
+            let zdotdir = std::env::var_os("ZDOTDIR").map(PathBuf::from).unwrap_or_else(|| home.clone());
+            for name in [".zshrc", ".zsh_aliases"] {
+                files.push((zdotdir.join(name), import_zsh));
+            }
+        }
+        "fish" => {
+            let config = dirs::config_dir().unwrap_or_else(|| home.join(".config")).join("fish");
+            files.push((config.join("config.fish"), import_fish));
+            for dir in ["conf.d", "functions"] {
+                files.extend(fish_files(&config.join(dir)).into_iter().map(|path| (path, import_fish as Importer)));
+            }
+            files.push((config.join("fish_variables"), import_fish_variables));
+        }
+        _ => {}
+    }
+    files.retain(|(path, _)| path.is_file());
+    files
+}
+
+fn fish_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).filter(|path| path.extension().map(|ext| ext == "fish").unwrap_or(false)).collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(import: &Import) -> Vec<(&str, &str)> {
+        import.aliases.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect()
+    }
+
+    #[test]
+    fn test_import_zsh() {
+        let import = import_zsh("alias gs='git status' gd=\"git diff\"\nalias -g G='| grep'\nalias -s md=vim\nmkcd() { mkdir -p \"$1\" && cd \"$1\"; }\nup() {\nexport X=1\n");
+        assert_eq!(aliases(&import), vec![("gs", "git status"), ("gd", "git diff"), ("G", "| grep"), ("mkcd", "mkdir -p \"$1\" && cd \"$1\"")]);
+        assert_eq!(import.skipped.len(), 3);
+        assert_eq!(import.skipped[2].1, FUNCTIONS_UNSUPPORTED);
+    }
+
+    #[test]
+    fn test_import_fish() {
+        let import = import_fish(
+            "alias ll 'ls -la'\nalias gco=\"git checkout\"\nabbr -a -g gp git push\nabbr --add --position anywhere L '| less'\nabbr -e old\n\
+             function ll --wraps='ls -lh' --description 'alias ll=ls -lh'\nfunction greet\n",
+        );
+        assert_eq!(aliases(&import), vec![("ll", "ls -la"), ("gco", "git checkout"), ("gp", "git push"), ("ll", "ls -lh")]);
+        assert_eq!(import.skipped.len(), 2);
+
+        let universal = import_fish_variables("# fish\nSETUVAR _fish_abbr_g_5F_st:git\\x20status\nSETUVAR fish_color_normal:normal\n");
+        assert_eq!(aliases(&universal), vec![("g_st", "git status")]);
+    }
+}
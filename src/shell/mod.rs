@@ -7,20 +7,123 @@ mod alias;
 mod signal_handler;
 mod command_parser;
 mod executor;
+mod policy;
+mod undo;
+mod prefetch;
+mod session;
+mod workspace;
+mod script;
+mod variables;
+mod arithmetic;
+mod options;
+mod builtins;
+mod wizard;
+mod search;
+mod pipe_builder;
+mod explain;
+mod genscript;
+mod regex_builder;
+mod page;
+mod structure;
+mod preview;
+mod backup;
+mod envsnap;
+mod envexport;
+mod migrate;
+mod coproc;
+mod fifo;
+mod detection;
+pub mod rescue;
 
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::os::unix::io::FromRawFd;
 use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use colored::*;
 use anyhow::{Result, Context};
-use crate::llm::LLMClient;
-use crate::terminal::Terminal;
-use crate::llm::context_manager::ContextManager;
+use crate::llm::{Cancelled, LLMClient};
+use crate::llm::budget;
+use crate::llm::{examples, feedback};
+use crate::terminal::{self, LlmStatus, PromptInfo, Terminal};
+use crate::llm::context_manager::{ContextManager, ContextScope};
 use crate::shell::suggestions::SuggestionEngine;
 use crate::shell::documentation::Documentation;
-use crate::utils::performance::PERFORMANCE_MONITOR;
+use crate::utils::performance::{self, PERFORMANCE_MONITOR};
+use crate::utils::path_utils;
+use crate::utils::telemetry;
+use crate::system::tmux::{split_pane_flag, Tmux};
+use crate::system::ssh::SshSession;
+use crate::system::cloud::CloudContext;
+use crate::system::container::ContainerInfo;
+use crate::system::remote::RemoteTarget;
+use crate::system::scheduler::{self, Scheduler, When};
+use crate::system::notify::Notifier;
+use crate::system::wsl;
+use crate::system::host;
+use crate::system::tty;
+use crate::shell::script::{LoopControl, Node};
+use crate::utils::pattern::{self, glob_match};
+use crate::utils::i18n::{tr, tr_args};
+use async_recursion::async_recursion;
+use crate::shell::policy::CommandOrigin;
+use crate::shell::detection::Trigger;
+use crate::shell::executor::ExecOptions;
+use crate::shell::command_parser::CommandParser;
+use crate::config::{CONFIG, FILE_CONFIG, TranslationMode};
 use log::debug;
 
+// Programs that are expected to run until the user quits them
+const INTERACTIVE_PROGRAMS: &[&str] = &[
+    "vim", "vi", "nvim", "nano", "emacs", "less", "more", "man", "top", "htop",
+    "ssh", "tmux", "screen", "watch", "python", "python3", "node", "psql", "mysql",
+];
+
+// How long to wait after a failure before probing the LLM endpoint again
+const LLM_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// Programs looked up at startup, from the most used ones in the timings
+const FREQUENT_PROGRAMS: usize = 20;
+
+// Started by login(1) or sshd, which put a '-' in front of argv[0], or with -l/--login
+pub fn is_login_shell() -> bool {
+    shell_env::Startup::current().login
+}
+
+// Started as `sh` (or `-sh`) through a symlink, by scripts and programs
+// that expect a POSIX shell rather than an interactive assistant
+pub fn invoked_as_sh() -> bool {
+    std::env::args()
+        .next()
+        .map(|arg| std::path::Path::new(arg.trim_start_matches('-')).file_name().is_some_and(|name| name == "sh"))
+        .unwrap_or(false)
+}
+
+// Library examples closest to a translation request
+fn library_examples(request: &str) -> Vec<(String, String)> {
+    if CONFIG.library_examples == 0 {
+        return Vec::new();
+    }
+    examples::relevant(&examples::library(), request, CONFIG.library_examples)
+        .into_iter()
+        .map(|example| (example.request, example.command))
+        .collect()
+}
+
+// Errors loading the resource limit and confirmation settings, as the shell
+// would warn about them at startup
+pub fn policy_problems() -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Err(e) = policy::ResourcePolicy::new().initialize() {
+        problems.push(format!("resource limits: {}", e));
+    }
+    if let Err(e) = policy::ConfirmationPolicy::new().initialize() {
+        problems.push(format!("confirmation policy: {}", e));
+    }
+    problems
+}
+
 pub struct Shell {
     terminal: Terminal,
     command_processor: command_processor::CommandProcessor,
@@ -32,109 +135,291 @@ pub struct Shell {
     context_manager: ContextManager,
     environment: shell_env::Environment,
     alias_manager: alias::AliasManager,
+    tmux_pane: Option<String>,
+    remote_target: Option<RemoteTarget>,
+    resource_policy: policy::ResourcePolicy,
+    confirmation_policy: policy::ConfirmationPolicy,
+    undo_stack: undo::UndoStack,
+    backups: backup::Backups,
+    options: options::ShellOptions,
+    // Set when the LLM endpoint last failed; cleared by the next successful health check
+    llm_degraded_at: Option<Instant>,
+    prefetcher: prefetch::SuggestionPrefetcher,
+    session: session::SessionManager,
+    dir_stack: Vec<PathBuf>,
+    workspaces: workspace::WorkspaceManager,
+    // Policy from the environment, which workspaces are layered on top of
+    base_confirmation_policy: policy::ConfirmationPolicy,
+    // Number of enclosing loops, and a pending `break`/`continue` for them
+    loop_depth: u32,
+    loop_control: Option<LoopControl>,
+    // Set while the interpreter runs a translation, whose commands keep the
+    // model's origin and timeout
+    in_translation: bool,
+    variables: variables::Variables,
+    builtins: builtins::Registry,
+    // Status of the last command, as $?
+    last_status: i32,
+    // Set by `exit`; the prompt loop and scripts stop once it is
+    exit_requested: bool,
+    // Request and command of the last translation, for `good` and `bad`
+    last_translation: Option<(String, String)>,
+    // The last `find!` command, read by a next line that starts with `|`
+    last_search: Option<String>,
+    // What the last command whose output the shell captured (`| copy`,
+    // `page`, `as-json`, `as-table`) printed, for `copy`
+    last_output: Option<String>,
+    // Natural-language detection thresholds, tuned by accepted and declined translations
+    detection: detection::Detection,
+    // Running coprocesses, closed and forgotten once they exit
+    coprocs: Vec<coproc::Coproc>,
+    // Context for Ctrl+G translations, refreshed before each prompt
+    translation_context: Arc<Mutex<String>>,
 }
 
 impl Shell {
     pub fn new() -> Self {
         let llm_client = LLMClient::new();
         
+        // Saved before line editing changes anything, for exec and fixterm
+        tty::save();
+        
         // Initialize signal handler
         signal_handler::SignalHandler::initialize().unwrap_or_else(|e| {
             eprintln!("Warning: Failed to initialize signal handlers: {}", e);
         });
         
-        // Determine if this is a login shell
-        let is_login_shell = std::env::args()
-            .next()
-            .map(|arg| arg.starts_with('-'))
-            .unwrap_or(false);
-            
-        // Create environment manager
-        let mut environment = shell_env::Environment::new(is_login_shell);
+        // Create environment manager, which reads the startup files
+        let mut environment = shell_env::Environment::new(shell_env::Startup::current());
         environment.initialize().unwrap_or_else(|e| {
             eprintln!("Warning: Failed to initialize environment: {}", e);
         });
         
+        // Windows-style PATH entries imported by WSL, fixed before anything searches PATH
+        if wsl::is_active() {
+            if let Ok(path) = std::env::var("PATH") {
+                std::env::set_var("PATH", wsl::normalize_path_var(&path));
+            }
+        }
+        
         // Create alias manager
         let mut alias_manager = alias::AliasManager::new();
         alias_manager.initialize().unwrap_or_else(|e| {
             eprintln!("Warning: Failed to initialize aliases: {}", e);
         });
         
+        for error in &FILE_CONFIG.errors {
+            eprintln!("Warning: Ignoring config file setting: {}", error);
+        }
+
+        // Load resource limit rules
+        let mut resource_policy = policy::ResourcePolicy::new();
+        resource_policy.initialize().unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to load resource limits: {}", e);
+        });
+        
+        let mut confirmation_policy = policy::ConfirmationPolicy::new();
+        confirmation_policy.initialize().unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to load confirmation policy: {}", e);
+        });
+        
+        let mut workspaces = workspace::WorkspaceManager::new();
+        workspaces.initialize().unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to load workspaces: {}", e);
+        });
+        
+        // As sh, nothing is set up for the model, so startup stays fast
+        let as_sh = invoked_as_sh();
+
+        // Let the LLM know when it is driving a remote machine or a container
+        let mut context_manager = ContextManager::new();
+        if !as_sh {
+            if let Some(session) = SshSession::detect() {
+                context_manager.set_system_info("ssh", &session.describe());
+            }
+            if wsl::is_active() {
+                context_manager.set_system_info("wsl", &wsl::describe());
+            }
+            if let Some(container) = ContainerInfo::detect() {
+                context_manager.set_system_info("container", &container.describe());
+            }
+        }
+        
+        // Suggestions are fetched in the background while the user types
+        let builtins = builtins::Registry::new();
+        let mut terminal = Terminal::new();
+        terminal.add_completion_commands(&builtins.names());
+        // User rules from the config come last so they can override the builtins'
+        for spec in builtins.completion_specs().iter().chain(&CONFIG.completion_specs) {
+            if let Err(e) = terminal.add_completion_rule(spec) {
+                eprintln!("Warning: {}", e);
+            }
+        }
+        // An `if` or `case` that isn't closed yet continues on the next line
+        terminal.set_continuation(script::is_incomplete);
+        if !as_sh {
+            Self::warm_up(terminal.commands_completing_hosts());
+        }
+        // Pipes and CI get plain output and no background suggestions
+        if !terminal.is_interactive() {
+            colored::control::set_override(false);
+        }
+        let throttle = prefetch::CadenceThrottle {
+            debounce: CONFIG.prefetch_debounce,
+            builtins: builtins.names().iter().map(|name| name.to_string()).collect(),
+        };
+        let prefetcher = prefetch::SuggestionPrefetcher::new(llm_client.clone(), Arc::new(throttle));
+        if CONFIG.prefetch_suggestions && terminal.is_interactive() && !as_sh {
+            let listener = prefetcher.clone();
+            terminal.set_input_listener(Arc::new(move |line: &str| listener.on_input(line)));
+        }
+        let translation_context = Arc::new(Mutex::new(String::new()));
+        let mut options = options::ShellOptions::new(CONFIG.demo_mode || std::env::args().any(|arg| arg == "--demo"));
+        options.posix = as_sh || std::env::args().any(|arg| arg == "--posix");
+        llm_client.set_enabled(!options.posix);
+        if terminal.is_interactive() && !as_sh {
+            let llm_client = llm_client.clone();
+            let context = Arc::clone(&translation_context);
+            let runtime = tokio::runtime::Handle::current();
+            terminal.set_translator(Arc::new(move |line: &str| {
+                if budget::check().is_err() {
+                    return None;
+                }
+                telemetry::count("translation.requested");
+                telemetry::count("translation.keybinding");
+                let context = context.lock().unwrap().clone();
+                let examples = library_examples(line);
+                // The editor waits for the key's result, so this blocks like Enter would
+                let translated = tokio::task::block_in_place(|| {
+                    runtime.block_on(llm_client.translate_command(line, &context, &examples))
+                });
+                translated
+                    .map_err(|e| {
+                        telemetry::count("translation.failed");
+                        debug!("Translating the buffer failed: {}", e);
+                    })
+                    .ok()
+            }));
+        }
+        
         Shell {
-            terminal: Terminal::new(),
+            terminal,
             command_processor: command_processor::CommandProcessor::new(),
             job_control: job_control::JobControl::new(),
             suggestion_engine: SuggestionEngine::new(),
             documentation: Documentation::new(llm_client.clone()),
-            context_manager: ContextManager::new(),
+            context_manager,
             llm_client,
             working_dir: std::env::current_dir().unwrap_or_default(),
             environment,
             alias_manager,
+            tmux_pane: CONFIG.tmux_target_pane.clone(),
+            remote_target: None,
+            resource_policy,
+            base_confirmation_policy: confirmation_policy.clone(),
+            confirmation_policy,
+            undo_stack: undo::UndoStack::new(),
+            backups: backup::Backups::new(),
+            options,
+            llm_degraded_at: None,
+            prefetcher,
+            session: session::SessionManager::new(),
+            dir_stack: Vec::new(),
+            workspaces,
+            loop_depth: 0,
+            loop_control: None,
+            in_translation: false,
+            variables: variables::Variables::new(),
+            builtins,
+            last_status: 0,
+            exit_requested: false,
+            last_translation: None,
+            last_search: None,
+            last_output: None,
+            detection: if as_sh { detection::Detection::default() } else { detection::Detection::load() },
+            coprocs: Vec::new(),
+            translation_context,
         }
     }
 
-    fn expand_env_vars(&self, value: &str) -> String {
-        let mut result = value.to_string();
-        let mut i = 0;
-        
-        while i < result.len() {
-            if result[i..].starts_with('$') {
-                let var_start = i;
-                i += 1; // Skip the $
-                
-                // Handle ${VAR} format
-                if i < result.len() && result[i..].starts_with('{') {
-                    i += 1; // Skip the {
-                    let var_name_start = i;
-                    
-                    // Find closing brace
-                    while i < result.len() && !result[i..].starts_with('}') {
-                        i += 1;
-                    }
-                    
-                    if i < result.len() {
-                        let var_name = &result[var_name_start..i];
-                        i += 1; // Skip the }
-                        
-                        if let Ok(value) = std::env::var(var_name) {
-                            result.replace_range(var_start..i, &value);
-                            i = var_start + value.len();
-                        }
-                    }
-                } 
-                // Handle $VAR format
-                else {
-                    let var_name_start = i;
-                    
-                    // Find end of variable name (alphanumeric or _)
-                    while i < result.len() && (result[i..].chars().next().unwrap().is_alphanumeric() || result[i..].starts_with('_')) {
-                        i += 1;
-                    }
-                    
-                    if i > var_name_start {
-                        let var_name = &result[var_name_start..i];
-                        
-                        if let Ok(value) = std::env::var(var_name) {
-                            result.replace_range(var_start..i, &value);
-                            i = var_start + value.len();
-                        }
-                    }
-                }
-            } else {
-                i += 1;
-            }
+    fn lookup_variable(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.last_status.to_string()),
+            "$" => Some(std::process::id().to_string()),
+            name => self.variables.get(name),
         }
-        
-        result
+    }
+
+    fn expand_env_vars(&self, value: &str) -> Result<String> {
+        variables::expand(value, |name| self.lookup_variable(name), self.options.nounset)
     }
     
-    pub async fn run(&mut self) -> Result<()> {
-        self.initialize()?;
+    // Runs the `$(...)` and backtick substitutions in text about to be used.
+    // Commands are run by sh in the shell's directory and environment, and
+    // their exit status is ignored.
+    fn substitute_commands(&self, text: &str, insert: variables::Insert) -> Result<String> {
+        variables::substitute(text, insert, |command| {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .stdin(std::process::Stdio::inherit())
+                .stderr(std::process::Stdio::inherit())
+                .output()
+                .with_context(|| format!("failed to run `{}`", command))?;
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        })
+    }
+
+    // Returns the status the shell should exit with
+    // Call `initialize` first
+    pub async fn run(&mut self) -> Result<i32> {
+        if let Some(name) = &CONFIG.workspace {
+            if let Err(e) = self.use_workspace(Some(name)) {
+                eprintln!("Warning: {}", e);
+            }
+        }
+        // As sh there is no session to pick up and no scheduler to start
+        if !invoked_as_sh() {
+            self.resume_session();
+            if let Err(e) = Scheduler::resume() {
+                debug!("Failed to resume scheduler: {}", e);
+            }
+        }
         
+        // Consecutive Ctrl+D presses, for ignoreeof
+        let mut eof_count = 0;
         loop {
-            let (input, show_suggestions) = self.terminal.read_line()?;
+            self.job_control.report_finished();
+            
+            // Saved before every prompt so a crash loses at most the command in flight
+            self.save_session(false);
+            let context = self.context_manager.get_context();
+            self.prefetcher.reset(&context, self.llm_degraded_at.is_none() && !self.options.posix);
+            *self.translation_context.lock().unwrap() = context;
+            if self.llm_degraded_at.is_none() && !self.options.posix {
+                self.context_manager.summarize_if_needed(&self.llm_client);
+            }
+            let aliases = self.alias_manager.list_aliases().into_iter().map(|(name, _)| name).collect();
+            self.terminal.set_completion_aliases(aliases);
+            self.terminal.set_prompt_info(PromptInfo {
+                status: self.last_status,
+                jobs: self.job_control.active_count(),
+                model: self.llm_client.model(),
+                llm: self.llm_status(),
+                in_flight: self.llm_client.in_flight(),
+            });
+            let (input, show_suggestions) = match self.terminal.read_line()? {
+                Some(line) => line,
+                None => {
+                    eof_count += 1;
+                    if eof_count < self.ignore_eof_limit() {
+                        eprintln!("{}", tr_args("eof.hint", &[if self.is_login_shell() { "logout" } else { "exit" }]));
+                        continue;
+                    }
+                    break;
+                }
+            };
+            eof_count = 0;
             let input = input.trim();
             
             // Check for interrupt
@@ -150,850 +435,1655 @@ impl Shell {
                 break;
             }
 
-            // Handle built-in commands
-            if let Some(result) = self.handle_builtin_command(input) {
-                match result {
-                    Ok(should_exit) => {
-                        if should_exit {
-                            break;
-                        }
-                        continue;
-                    }
-                    Err(e) => {
+            // Right after `find!`, a line starting with `|` reads its files.
+            // It runs as typed: a line starting with `find` would otherwise
+            // be taken for a request.
+            if let Some(search) = self.last_search.take().filter(|_| input.starts_with('|')) {
+                let piped = format!("{} {}", search, input);
+                println!("{} {}", "❯".bright_purple(), piped);
+                self.context_manager.add_command(&piped);
+                let result = self.execute_with_undo(&piped, false).await;
+                if let Err(e) = self.record_status(result) {
+                    eprintln!("Error: {}", e);
+                }
+                continue;
+            }
+
+            // Handle suggestions, for the whole line typed before `??`
+            let first_word = input.split_whitespace().next();
+            if show_suggestions && !self.options.posix && !first_word.map(|name| self.is_builtin(name)).unwrap_or(false) {
+                if let Ok(suggestions) = self.suggestions(Some(input)).await {
+                    telemetry::count("suggestions");
+                    if let Err(e) = self.show_suggestions(&suggestions).await {
                         eprintln!("Error: {}", e);
-                        continue;
                     }
+                    continue;
                 }
             }
 
-            // Handle suggestions
-            if show_suggestions {
-                let command_prefix = input.split_whitespace().next();
-                if let Ok(suggestions) = self.show_suggestions(command_prefix).await {
-                    println!("{}", suggestions);
-                    continue;
+            if let Err(e) = self.execute_line(input).await {
+                eprintln!("Error: {}", e);
+            }
+            if self.exit_requested {
+                break;
+            }
+        }
+
+        self.save_session(true);
+        fifo::remove_temporary();
+        if !invoked_as_sh() {
+            telemetry::upload_if_due().await;
+        }
+        Ok(self.last_status)
+    }
+
+    // How many Ctrl+D presses in a row end the session. Like bash, setting
+    // IGNOREEOF also turns it on, and a value that isn't a number means 10.
+    fn ignore_eof_limit(&self) -> u32 {
+        if !self.terminal.is_interactive() {
+            return 1;
+        }
+        match self.variables.get("IGNOREEOF") {
+            Some(value) => value.parse::<u32>().unwrap_or(10) + 1,
+            None if self.options.ignoreeof => 11,
+            None => 1,
+        }
+    }
+
+    // Runs one line the way the prompt would: builtins, aliases, then
+    // translation and execution. Returns the exit status, also kept as $?.
+    #[async_recursion(?Send)]
+    async fn execute_line(&mut self, input: &str) -> Result<i32> {
+        // In `a; b && c || d` each command is a line of its own, run or
+        // skipped on the status of the one before
+        if !script::is_compound(input) {
+            let list = CommandParser::parse_list(input)?;
+            if list.len() > 1 {
+                let mut status = 0;
+                for (connector, command) in list {
+                    if !connector.runs_after(status) {
+                        continue;
+                    }
+                    status = match self.execute_line(&command).await {
+                        Ok(status) => status,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            1
+                        }
+                    };
+                    if self.exit_requested {
+                        break;
+                    }
                 }
+                return self.record_status(Ok(status));
             }
+        }
 
-            // Expand aliases
-            let expanded_input = self.alias_manager.expand(input);
+        // `COMMAND | page` opens the output in the pager
+        let paged_input;
+        let input = match page::piped_into(input, "page").filter(|_| !self.options.posix) {
+            Some(command) => {
+                paged_input = format!("page {}", command);
+                paged_input.as_str()
+            }
+            None => input,
+        };
 
-            // Update context
-            self.context_manager.update_directory(&self.working_dir.to_string_lossy());
-            self.context_manager.add_command(&expanded_input);
-            
-            let start_time = std::time::Instant::now();
-            
-            // Process the input
-            self.process_input(&expanded_input).await?;
-            
-            // Record execution time
-            let duration = start_time.elapsed();
-            PERFORMANCE_MONITOR.lock().unwrap().record_execution(&expanded_input, duration);
-            
-            // Update working directory
-            if let Ok(dir) = std::env::current_dir() {
-                self.working_dir = dir;
+        // `COMMAND | copy` copies what the command prints
+        if let Some(command) = page::piped_into(input, "copy").filter(|_| !self.options.posix) {
+            let result = self.copy_output(command).await;
+            return self.record_status(result);
+        }
+
+        // `COMMAND | as-json [| REST]` structures the output of the command
+        // and passes it on to the rest of the pipeline
+        let structured_input;
+        let input = match structure::post_processor(input).filter(|_| !self.options.posix) {
+            Some((name, command, None)) => {
+                structured_input = format!("{} {}", name, command);
+                structured_input.as_str()
+            }
+            Some((name, command, Some(rest))) => {
+                let result = self.pipe_structured(name, command, rest).await;
+                return self.record_status(result);
+            }
+            None => input,
+        };
+
+        // With autocd, a directory name on its own changes into it
+        let autocd_input;
+        let input = if self.options.autocd && !self.options.posix && self.is_autocd_target(input) {
+            autocd_input = format!("cd {}", input);
+            autocd_input.as_str()
+        } else {
+            input
+        };
+
+        // In demo mode, builtins that change state are only described
+        if self.options.observemode {
+            let name = input.split_whitespace().next().unwrap_or("");
+            if self.is_builtin(name) && !self.is_demo_safe_builtin(name) {
+                println!("{} builtin not executed: {}", "[demo]".bright_yellow(), input);
+                return Ok(0);
             }
-            
-            // Clean up any completed background jobs
-            self.job_control.cleanup_completed_jobs();
         }
 
+        // Handle built-in commands
+        if let Some(result) = self.handle_builtin_command(input).await {
+            return self.record_status(result);
+        }
+
+        // Expand aliases
+        let expanded_input = self.alias_manager.expand(input);
+
+        // Update context
+        self.context_manager.update_directory(&self.working_dir.to_string_lossy());
+        self.context_manager.add_command(&expanded_input);
+        self.suggestion_engine.add_command(&expanded_input);
+        self.update_cloud_context();
+
+        let mark = PERFORMANCE_MONITOR.lock().unwrap().recorded();
+
+        let result = self.process_input(&expanded_input).await;
+
+        // Only the time spent in commands counts, not waiting for the model
+        let duration = PERFORMANCE_MONITOR.lock().unwrap().since(mark).map(|execution| execution.duration).sum();
+        self.report_long_command(&expanded_input, duration, result.is_ok());
+
+        // Update working directory
+        if let Ok(dir) = std::env::current_dir() {
+            self.working_dir = dir;
+        }
+
+        // Clean up any completed background jobs
+        self.job_control.cleanup_completed_jobs();
+        self.reap_coprocs();
+        self.record_status(result)
+    }
+
+    // Runs the as-json or as-table builtin on the command and feeds what
+    // it printed to the rest of the pipeline through a temporary file
+    async fn pipe_structured(&mut self, name: &str, command: &str, rest: &str) -> Result<i32> {
+        let line = format!("{} {}", name, command);
+        if self.options.observemode {
+            println!("{} builtin not executed: {}", "[demo]".bright_yellow(), line);
+            return Ok(0);
+        }
+        let Some(builtin) = self.builtins.find(name) else {
+            return Err(anyhow::anyhow!("{}: not found", name));
+        };
+        let mut output = Vec::new();
+        let mut invocation = builtins::Invocation::new(&line);
+        invocation.stdout = Box::new(&mut output);
+        let status = builtin.run(self, &mut invocation).await?;
+        drop(invocation);
+        if status != 0 {
+            return Ok(status);
+        }
+        // mkstemp creates a new file only this user can read, so nothing can be
+        // planted at the path beforehand
+        let template = std::env::temp_dir().join(format!("llm-shell-{}-XXXXXX", name));
+        let (fd, path) = nix::unistd::mkstemp(&template)
+            .with_context(|| format!("failed to create a temporary file in {}", std::env::temp_dir().display()))?;
+        let mut file = unsafe { std::fs::File::from_raw_fd(fd) };
+        if let Err(e) = file.write_all(&output) {
+            let _ = std::fs::remove_file(&path);
+            return Err(e).with_context(|| format!("failed to write {}", path.display()));
+        }
+        drop(file);
+        let result = self.run_script_command(&format!("cat {} | {}", regex_builder::quote(&path.to_string_lossy()), rest)).await;
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    // Runs the command, shows what it printed on stdout and keeps it for
+    // `copy`, which then copies it
+    async fn copy_output(&mut self, command: &str) -> Result<i32> {
+        if self.options.observemode {
+            println!("{} builtin not executed: {} | copy", "[demo]".bright_yellow(), command);
+            return Ok(0);
+        }
+        let (output, status) = structure::capture(command).await?;
+        print!("{}", output);
+        self.last_output = Some(output);
+        if status != 0 {
+            return Ok(status);
+        }
+        self.handle_builtin_command("copy").await.unwrap_or(Ok(1))
+    }
+
+    // POSIX mode: the model is cut off for every part of the shell, not just
+    // for lines typed at the prompt
+    fn set_posix(&mut self, on: bool) {
+        self.options.posix = on;
+        self.llm_client.set_enabled(!on);
+    }
+
+    // Errors count as status 1
+    fn record_status(&mut self, result: Result<i32>) -> Result<i32> {
+        self.last_status = *result.as_ref().unwrap_or(&1);
+        result
+    }
+
+    // Lets the user know a slow command is done in case they switched away
+    fn report_long_command(&self, command: &str, duration: std::time::Duration, succeeded: bool) {
+        let threshold = match CONFIG.long_command_threshold {
+            Some(threshold) if duration >= threshold => threshold,
+            _ => return,
+        };
+        
+        // Interactive programs run as long as the user keeps them open
+        let program = command.split_whitespace().next().unwrap_or("");
+        if INTERACTIVE_PROGRAMS.contains(&program) {
+            return;
+        }
+        debug!("'{}' exceeded the {:?} threshold", command, threshold);
+        
+        let elapsed = crate::utils::duration::format_duration(duration);
+        println!("{}", format!("Finished in {}", elapsed).bright_black());
+        Notifier::bell();
+        
+        if CONFIG.desktop_notifications {
+            let title = if succeeded { "Command finished" } else { "Command failed" };
+            if let Err(e) = Notifier::notify(title, &format!("{} ({})", command, elapsed)) {
+                debug!("Failed to send notification: {}", e);
+            }
+        }
+    }
+
+    // Looks up the most used programs and loads what their completions need in
+    // the background, most used first, so on slow filesystems they are ready
+    // by the time they're typed
+    fn warm_up(hosts_commands: Vec<String>) {
+        std::thread::spawn(move || {
+            let frequent = performance::frequent_programs(FREQUENT_PROGRAMS);
+            for program in &frequent {
+                path_utils::hash(program);
+            }
+            if frequent.iter().any(|program| hosts_commands.contains(program)) {
+                terminal::known_hosts();
+            }
+        });
+    }
+
+    fn resume_session(&mut self) {
+        let args: Vec<String> = std::env::args().collect();
+        if let Some(pos) = args.iter().position(|arg| arg == "--resume") {
+            let id = args.get(pos + 1).filter(|arg| !arg.starts_with('-')).cloned().unwrap_or_default();
+            if let Err(e) = self.restore_session(&id) {
+                eprintln!("Warning: Failed to resume session: {}", e);
+            }
+        } else if let Some(previous) = self.session.predecessor() {
+            // This process exec'd a new shell; its background jobs are still our children
+            self.adopt_jobs(&previous.jobs);
+        } else if let Some(crashed) = self.session.last_crashed() {
+            println!(
+                "{} session {} in {} ended unexpectedly; use `session restore {}` or start with --resume to pick up where it left off.",
+                "Note:".bright_yellow(),
+                crashed.id,
+                crashed.cwd,
+                crashed.id
+            );
+        }
+        
+        if let Err(e) = self.session.prune() {
+            debug!("Failed to prune old sessions: {}", e);
+        }
+    }
+
+    fn restore_session(&mut self, id: &str) -> Result<()> {
+        let state = self.session.find(id)?;
+        
+        for (key, value) in &state.variables {
+            std::env::set_var(key, value);
+        }
+        self.change_directory(&state.cwd)?;
+        self.dir_stack = state.dir_stack.iter().map(PathBuf::from).collect();
+        if let Some(name) = &state.workspace {
+            if let Err(e) = self.use_workspace(Some(name)) {
+                eprintln!("Warning: {}", e);
+            }
+        }
+        for (name, value) in &state.shell_variables {
+            if let Err(e) = self.variables.assign(name, value.clone()) {
+                eprintln!("Warning: {}", e);
+            }
+        }
+        self.context_manager.restore(state.context, &state.recent_commands);
+        if !state.model.is_empty() {
+            self.llm_client.set_model(&state.model);
+        }
+        
+        println!("Restored session {} in {} (model {})", state.id.bright_cyan(), state.cwd.bright_yellow(), state.model);
+        self.adopt_jobs(&state.jobs);
         Ok(())
     }
 
-    fn handle_builtin_command(&mut self, input: &str) -> Option<Result<bool>> {
+    fn adopt_jobs(&mut self, jobs: &[session::SavedJob]) {
+        if jobs.is_empty() {
+            return;
+        }
+        let (adopted, gone) = self.job_control.adopt(jobs);
+        if adopted > 0 {
+            println!("Re-adopted {} background job{}; see `jobs`", adopted, if adopted == 1 { "" } else { "s" });
+        }
+        if gone > 0 {
+            println!("{} saved job{} finished in the meantime", gone, if gone == 1 { "" } else { "s" });
+        }
+    }
+
+    fn save_session(&self, clean_exit: bool) {
+        if invoked_as_sh() {
+            return;
+        }
+        let mut state = self.session.capture(
+            &self.working_dir.to_string_lossy(),
+            &self.dir_stack,
+            self.context_manager.recent_commands(),
+            &self.llm_client.model(),
+            self.workspaces.active().map(|w| w.name.as_str()),
+            clean_exit,
+        );
+        state.jobs = self.job_control.saved();
+        state.shell_variables = self.variables.unexported();
+        state.context = self.context_manager.saved();
+        if let Err(e) = self.session.save(&state) {
+            debug!("Failed to save session state: {}", e);
+        }
+        telemetry::flush();
+    }
+
+    fn list_sessions(&self) -> Result<()> {
+        let sessions = session::SessionManager::list()?;
+        if sessions.is_empty() {
+            println!("No saved sessions");
+        }
+        for state in sessions {
+            let age = std::time::Duration::from_secs(session::now().saturating_sub(state.updated));
+            let marker = if state.id == self.session.id() { "*" } else { " " };
+            println!(
+                "{} {}  {:<8} {:>10} ago  {}",
+                marker,
+                state.id,
+                state.status(),
+                crate::utils::duration::format_duration(age),
+                state.cwd
+            );
+        }
+        Ok(())
+    }
+
+    fn use_workspace(&mut self, name: Option<&str>) -> Result<()> {
+        self.workspaces.set_active(name)?;
+        let workspace = self.workspaces.active().cloned().unwrap_or_default();
+        
+        // Start from the base settings so nothing leaks over from the previous workspace
+        let mut confirmation_policy = self.base_confirmation_policy.clone();
+        if let Some(level) = workspace.confirm {
+            confirmation_policy.level = level;
+        }
+        for pattern in &workspace.allow {
+            confirmation_policy.allow(pattern)?;
+        }
+        
+        self.terminal.set_workspace(name.map(|n| n.to_string()), workspace.theme.as_deref())?;
+        self.terminal.set_completion_bookmarks(workspace.bookmarks.iter().map(|(name, _)| name.clone()).collect());
+        self.confirmation_policy = confirmation_policy;
+        self.llm_client.set_model(workspace.model.as_deref().unwrap_or(&CONFIG.llm_model));
+        self.context_manager.scope = workspace.context_scope.unwrap_or(ContextScope::Full);
+        Ok(())
+    }
+
+    fn list_workspaces(&self) {
+        if self.workspaces.workspaces().is_empty() {
+            println!("No workspaces defined in ~/.llm_shell_workspaces");
+        }
+        let active = self.workspaces.active().map(|w| w.name.clone());
+        for workspace in self.workspaces.workspaces() {
+            let marker = if active.as_deref() == Some(workspace.name.as_str()) { "*" } else { " " };
+            let mut details = Vec::new();
+            if let Some(model) = &workspace.model {
+                details.push(format!("model={}", model));
+            }
+            if let Some(level) = workspace.confirm {
+                details.push(format!("confirm={}", level.as_str()));
+            }
+            if let Some(scope) = workspace.context_scope {
+                details.push(format!("context={}", scope.as_str()));
+            }
+            if !workspace.bookmarks.is_empty() {
+                let names: Vec<String> = workspace.bookmarks.iter().map(|(name, _)| format!("@{}", name)).collect();
+                details.push(format!("bookmarks={}", names.join(",")));
+            }
+            println!("{} {:<12} {}", marker, workspace.name, details.join(" "));
+        }
+    }
+
+    fn add_scheduled(&self, when: When, command: &str) -> Result<()> {
+        let job = Scheduler::add(when, command)?;
+        let delay = std::time::Duration::from_secs(job.next_run.saturating_sub(scheduler::now()));
+        println!(
+            "Scheduled job {}: {} (next run in {})",
+            job.id,
+            job.command.bright_cyan(),
+            crate::utils::duration::format_duration(delay)
+        );
+        Ok(())
+    }
+
+    fn list_scheduled(&self) -> Result<()> {
+        let jobs = Scheduler::list()?;
+        if jobs.is_empty() {
+            println!("No scheduled jobs");
+        }
+        for job in jobs {
+            let when = match &job.when {
+                When::Cron(spec) => spec.clone(),
+                When::Once(_) => "once".to_string(),
+            };
+            let delay = std::time::Duration::from_secs(job.next_run.saturating_sub(scheduler::now()));
+            println!(
+                "{:>3}  {:<16} in {:<10} {}",
+                job.id,
+                when,
+                crate::utils::duration::format_duration(delay),
+                job.command
+            );
+        }
+        Ok(())
+    }
+
+    fn change_directory(&mut self, dir: &str) -> Result<()> {
+        std::env::set_current_dir(dir).map_err(|e| anyhow::anyhow!("cd: {}: {}", dir, e))?;
+        if let Ok(new_dir) = std::env::current_dir() {
+            self.working_dir = new_dir;
+            self.context_manager.update_directory(&self.working_dir.to_string_lossy());
+        }
+        Ok(())
+    }
+
+    async fn handle_builtin_command(&mut self, input: &str) -> Option<Result<i32>> {
         let parts: Vec<&str> = input.split_whitespace().collect();
         if parts.is_empty() {
             return None;
         }
     
-        match parts[0] {
-            // Directory navigation
-            "cd" => {
-                let dir_to_use = if parts.len() > 1 {
-                    parts[1].to_string()
-                } else {
-                    // Default to home directory
-                    dirs::home_dir()
-                        .and_then(|p| p.to_str().map(|s| s.to_string()))
-                        .unwrap_or_else(|| ".".to_string())
-                };
-                
-                // Handle ~ expansion
-                let expanded_dir = if dir_to_use.starts_with('~') {
-                    if let Some(home) = dirs::home_dir() {
-                        if dir_to_use.len() == 1 {
-                            home.to_string_lossy().to_string()
-                        } else {
-                            home.join(&dir_to_use[2..]).to_string_lossy().to_string()
+        // `VAR=value` on its own assigns a shell variable
+        if variables::parse_assignment(parts[0]).is_some() {
+            let input = variables::expand_tildes(input, path_utils::tilde_directory);
+            let input = match self.substitute_commands(&input, variables::Insert::Word) {
+                Ok(input) => input,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Ok(words) = shellwords::split(&input) {
+                let assignments: Option<Vec<(String, variables::Value)>> = variables::parse_arguments(&words)
+                    .into_iter()
+                    .map(|(name, value)| value.map(|value| (name, value)))
+                    .collect();
+                if let Some(assignments) = assignments {
+                    for (name, value) in assignments {
+                        if let Err(e) = self.assign_variable(&name, value) {
+                            return Some(Err(e));
                         }
-                    } else {
-                        dir_to_use
-                    }
-                } else {
-                    dir_to_use
-                };
-                
-                match std::env::set_current_dir(&expanded_dir) {
-                    Ok(_) => {
-                        if let Ok(new_dir) = std::env::current_dir() {
-                            self.working_dir = new_dir;
-                            self.context_manager.update_directory(&self.working_dir.to_string_lossy());
-                        }
-                        Some(Ok(false))
-                    }
-                    Err(e) => Some(Err(anyhow::anyhow!("cd: {}: {}", expanded_dir, e))),
-                }
-            },
-            
-            "pwd" => {
-                println!("{}", self.working_dir.display());
-                Some(Ok(false))
-            },
-            
-            // Environment variables
-            "export" => {
-                if parts.len() == 1 {
-                    // Just 'export' - list all environment variables
-                    for (key, value) in std::env::vars() {
-                        println!("{}={}", key, value);
-                    }
-                } else {
-                    // Handle export VAR=VALUE
-                    let export_str = input["export ".len()..].trim();
-                    if let Some(equals_pos) = export_str.find('=') {
-                        let name = export_str[..equals_pos].trim();
-                        let value = export_str[equals_pos + 1..].trim();
-                        
-                        // Remove quotes if present
-                        let clean_value = value.trim_matches('"').trim_matches('\'');
-                        
-                        // Expand variables in the value
-                        let expanded_value = self.expand_env_vars(clean_value);
-                        
-                        // Set the environment variable
-                        std::env::set_var(name, expanded_value);
-                    } else {
-                        eprintln!("Invalid export format. Use: export VAR=VALUE");
-                    }
-                }
-                Some(Ok(false))
-            },
-            
-            "unset" => {
-                if parts.len() > 1 {
-                    for var in &parts[1..] {
-                        std::env::remove_var(var);
-                    }
-                } else {
-                    eprintln!("unset: missing variable name");
-                }
-                Some(Ok(false))
-            },
-            
-            "set" => {
-                if parts.len() == 1 {
-                    // Just 'set' - list all environment variables
-                    for (key, value) in std::env::vars() {
-                        println!("{}={}", key, value);
-                    }
-                } else {
-                    // Handle shell options (simplified)
-                    // In a real shell, this would handle options like -e, -x, etc.
-                    eprintln!("Note: shell options not fully implemented");
-                }
-                Some(Ok(false))
-            },
-            
-            // Output and redirection
-            "echo" => {
-                if parts.len() > 1 {
-                    // Check for -n option (no newline)
-                    let no_newline = parts[1] == "-n";
-                    let start_idx = if no_newline { 2 } else { 1 };
-                    
-                    // Join all arguments and expand variables
-                    let echo_str = parts[start_idx..].join(" ");
-                    let expanded = self.expand_env_vars(&echo_str);
-                    
-                    if no_newline {
-                        print!("{}", expanded);
-                        std::io::stdout().flush().unwrap_or(());
-                    } else {
-                        println!("{}", expanded);
                     }
-                } else {
-                    // Just echo a newline
-                    println!();
+                    return Some(Ok(0));
                 }
-                Some(Ok(false))
-            },
-            
-            "printf" => {
-                if parts.len() > 1 {
-                    // Very simplified printf implementation
-                    let format_str = self.expand_env_vars(parts[1]);
-                    let args: Vec<String> = parts[2..].iter()
-                        .map(|arg| self.expand_env_vars(arg))
-                        .collect();
-                    
-                    // Basic % substitution (simplified)
-                    let mut result = format_str.clone();
-                    for arg in args {
-                        if let Some(pos) = result.find('%') {
-                            let end = pos + 2.min(result.len() - pos);
-                            result.replace_range(pos..end, &arg);
-                        }
+            }
+        }
+    
+        let builtin = self.builtins.find(parts[0]);
+        if let Some(builtin) = builtin.as_ref().filter(|builtin| builtin.spec().raw) {
+            telemetry::count(&format!("builtin.{}", builtin.spec().name));
+            return Some(builtin.run(self, &mut builtins::Invocation::new(input)).await);
+        }
+        // Other builtins are parsed like any command, so they can be
+        // redirected and piped, also as a later stage of a pipeline. Text
+        // the parser rejects, like an unbalanced quote, goes to the builtin as typed.
+        match CommandParser::parse(input) {
+            Ok(pipeline) if builtin.is_some() || pipeline.commands.iter().any(|cmd| self.builtin_stage(cmd).is_some()) => {
+                Some(self.run_with_builtins(input).await)
+            }
+            Ok(_) => None,
+            Err(_) => {
+                let builtin = builtin?;
+                telemetry::count(&format!("builtin.{}", builtin.spec().name));
+                Some(builtin.run(self, &mut builtins::Invocation::new(input)).await)
+            }
+        }
+    }
+
+    // The builtin a pipeline stage runs; with `NAME=value` words in front
+    // it is a program, since builtins don't take a temporary environment
+    fn builtin_stage(&self, cmd: &command_parser::SimpleCommand) -> Option<Arc<dyn builtins::Builtin>> {
+        if !cmd.assignments.is_empty() {
+            return None;
+        }
+        self.builtins.find(&cmd.program).filter(|builtin| !builtin.spec().raw)
+    }
+
+    // Runs a pipeline in which some stages are builtins. A builtin reads
+    // from the stage before it and, when it isn't last, its output is
+    // collected and then fed to the next stage, so two builtins in a row
+    // can't block each other. Redirections apply to the shell's own
+    // descriptors while it runs. The status is the last stage's.
+    async fn run_with_builtins(&mut self, input: &str) -> Result<i32> {
+        let pipeline = self.parse_expanded(input)?;
+        let external = pipeline.commands.iter().any(|cmd| self.builtin_stage(cmd).is_none());
+        let redirected = pipeline.commands.iter().any(|cmd| cmd.redirections.iter().any(|r| *r != command_parser::Redirection::Pipe));
+        if self.options.observemode && (external || redirected) {
+            println!("{} not executed: {}", "[demo]".bright_yellow(), input);
+            return Ok(0);
+        }
+        let options = ExecOptions {
+            timeout: None,
+            limits: self.resource_policy.limits_for(input, CommandOrigin::User),
+            noclobber: self.options.noclobber,
+        };
+
+        enum Output {
+            Inherit,
+            Pipe(std::process::ChildStdout),
+            Collected(Vec<u8>),
+        }
+        let mut children = Vec::new();
+        let mut previous = Output::Inherit;
+        let mut result = Ok(0);
+        let last = pipeline.commands.len() - 1;
+        for (i, cmd) in pipeline.commands.iter().enumerate() {
+            let Some(builtin) = self.builtin_stage(cmd) else {
+                let stdout = if i < last { std::process::Stdio::piped() } else { std::process::Stdio::inherit() };
+                let (stdin, feed) = match std::mem::replace(&mut previous, Output::Inherit) {
+                    Output::Inherit => (std::process::Stdio::inherit(), None),
+                    Output::Pipe(pipe) => (std::process::Stdio::from(pipe), None),
+                    Output::Collected(bytes) => (std::process::Stdio::piped(), Some(bytes)),
+                };
+                let mut child = match executor::Executor::spawn_stage(cmd, stdin, stdout, &options) {
+                    Ok(child) => child,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
                     }
-                    
-                    print!("{}", result);
-                    std::io::stdout().flush().unwrap_or(());
-                } else {
-                    eprintln!("printf: missing format string");
-                }
-                Some(Ok(false))
-            },
-            
-            // Job control
-            "jobs" => {
-                match self.job_control.list_jobs() {
-                    Ok(_) => {},
-                    Err(e) => eprintln!("Error listing jobs: {}", e),
-                }
-                Some(Ok(false))
-            },
-            
-            "fg" => {
-                let args = parts.iter().map(|s| s.to_string()).collect::<Vec<_>>();
-                match self.job_control.bring_to_foreground(&args) {
-                    Ok(_) => {},
-                    Err(e) => eprintln!("Error bringing job to foreground: {}", e),
+                };
+                // Written from a thread, in case the program writes a lot before it reads
+                if let (Some(bytes), Some(mut stdin)) = (feed, child.stdin.take()) {
+                    std::thread::spawn(move || stdin.write_all(&bytes));
                 }
-                Some(Ok(false))
-            },
-            
-            "bg" => {
-                let args = parts.iter().map(|s| s.to_string()).collect::<Vec<_>>();
-                match self.job_control.continue_in_background(&args) {
-                    Ok(_) => {},
-                    Err(e) => eprintln!("Error continuing job in background: {}", e),
+                previous = child.stdout.take().map(Output::Pipe).unwrap_or(Output::Inherit);
+                children.push(child);
+                result = Ok(0);
+                continue;
+            };
+
+            telemetry::count(&format!("builtin.{}", builtin.spec().name));
+            let words: Vec<&str> = std::iter::once(&cmd.program).chain(&cmd.args).map(String::as_str).collect();
+            let mut output = Vec::new();
+            let mut invocation = builtins::Invocation::with_words(input, words);
+            let _redirected = match executor::Executor::redirect_builtin(&cmd.redirections, self.options.noclobber) {
+                Ok(guard) => guard,
+                Err(e) => {
+                    result = Err(e);
+                    break;
                 }
-                Some(Ok(false))
-            },
-            
-            "kill" => {
-                if parts.len() < 2 {
-                    eprintln!("kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]");
-                    return Some(Ok(false));
+            };
+            invocation.stdin = match std::mem::replace(&mut previous, Output::Inherit) {
+                // A copy of descriptor 0, so nothing is left behind in the
+                // buffer of the process's stdin when `<` pointed it at a file
+                Output::Inherit => match nix::unistd::dup(0) {
+                    Ok(fd) => Box::new(unsafe { std::fs::File::from_raw_fd(fd) }),
+                    Err(_) => Box::new(std::io::empty()),
+                },
+                Output::Pipe(pipe) => Box::new(pipe),
+                Output::Collected(bytes) => Box::new(std::io::Cursor::new(bytes)),
+            };
+            if i < last {
+                invocation.stdout = Box::new(&mut output);
+            }
+            result = builtin.run(self, &mut invocation).await;
+            drop(invocation);
+            if i < last {
+                if let Err(e) = &result {
+                    eprintln!("{}: {}", cmd.program, e);
                 }
-                
-                // Handle -l option to list signals
-                if parts[1] == "-l" {
-                    println!("HUP INT QUIT ILL TRAP ABRT BUS FPE KILL USR1 SEGV USR2 PIPE ALRM TERM STKFLT CHLD CONT STOP TSTP TTIN TTOU URG XCPU XFSZ VTALRM PROF WINCH POLL PWR SYS");
-                    return Some(Ok(false));
+                previous = Output::Collected(output);
+            }
+        }
+        let status = executor::Executor::wait_for_children(children, None)?;
+        match pipeline.commands.last().and_then(|cmd| self.builtin_stage(cmd)) {
+            Some(_) => result,
+            None => result.map(|_| status),
+        }
+    }
+
+    fn is_builtin(&self, name: &str) -> bool {
+        self.builtins.find(name).is_some()
+    }
+
+    // Builtins that only inspect or navigate, so they stay usable in demo mode
+    fn is_demo_safe_builtin(&self, name: &str) -> bool {
+        self.builtins.find(name).map(|builtin| builtin.spec().demo_safe).unwrap_or(false)
+    }
+
+    // shopt [-s|-u] [-p] [--save] [NAME...]
+    fn shopt(&mut self, input: &str) -> Result<()> {
+        let mut value = None;
+        let mut print = false;
+        let mut save = false;
+        let mut names = Vec::new();
+        for word in input.split_whitespace().skip(1) {
+            match word {
+                "-s" => value = Some(true),
+                "-u" => value = Some(false),
+                "-p" => print = true,
+                "--save" => save = true,
+                flag if flag.starts_with('-') => return Err(anyhow::anyhow!("shopt: {}: invalid option", flag)),
+                name => names.push(name),
+            }
+        }
+        
+        if let (Some(value), false) = (value, names.is_empty()) {
+            for name in &names {
+                self.options.set(name, value)?;
+                if save {
+                    options::ShellOptions::save(name, value)?;
                 }
-                
-                // Parse signal if provided
-                let mut signal = 15; // Default to SIGTERM
-                let mut arg_start = 1;
-                
-                if parts[1].starts_with('-') {
-                    if let Ok(sig) = parts[1][1..].parse::<i32>() {
-                        signal = sig;
-                        arg_start = 2;
-                    } else if parts[1] == "-KILL" || parts[1] == "-9" {
-                        signal = 9;
-                        arg_start = 2;
-                    } else if parts[1] == "-HUP" || parts[1] == "-1" {
-                        signal = 1;
-                        arg_start = 2;
-                    } else if parts[1] == "-INT" || parts[1] == "-2" {
-                        signal = 2;
-                        arg_start = 2;
-                    } else if parts[1] == "-TERM" || parts[1] == "-15" {
-                        signal = 15;
-                        arg_start = 2;
-                    }
+            }
+            self.terminal.set_history_append(self.options.histappend);
+            return Ok(());
+        }
+        if save {
+            return Err(anyhow::anyhow!("shopt: --save needs -s or -u and an option name"));
+        }
+        
+        // Listing: the named options, or all of them (only those set/unset with -s/-u)
+        let listed: Vec<&str> = if names.is_empty() { options::OPTION_NAMES.to_vec() } else { names };
+        for name in listed {
+            let enabled = self.options
+                .get(name)
+                .with_context(|| format!("shopt: {}: invalid shell option name", name))?;
+            if value.map(|v| v != enabled).unwrap_or(false) {
+                continue;
+            }
+            if print {
+                println!("shopt {} {}", if enabled { "-s" } else { "-u" }, name);
+            } else {
+                println!("{:<15}\t{}", name, if enabled { "on" } else { "off" });
+            }
+        }
+        Ok(())
+    }
+
+    fn is_autocd_target(&self, input: &str) -> bool {
+        if input.split_whitespace().count() != 1 || self.is_builtin(input) {
+            return false;
+        }
+        let is_command = crate::utils::path_utils::find_executable(input)
+            .map(|path| path.is_file())
+            .unwrap_or(false);
+        !is_command && PathBuf::from(path_utils::expand_tilde(input)).is_dir()
+    }
+
+    fn assign_variable(&mut self, name: &str, value: variables::Value) -> Result<()> {
+        let value = match value {
+            variables::Value::Scalar(value) => variables::Value::Scalar(self.expand_env_vars(&value)?),
+            variables::Value::Array(items) => {
+                variables::Value::Array(items.iter().map(|item| self.expand_env_vars(item)).collect::<Result<_>>()?)
+            }
+        };
+        self.variables.assign(name, value)
+    }
+
+    // declare/typeset [-airxp] [NAME[=VALUE]...] and readonly [-p] [NAME[=VALUE]...]
+    fn declare(&mut self, input: &str) -> Result<()> {
+        let words = shellwords::split(input)?;
+        let command = words[0].as_str();
+        let arguments = variables::parse_arguments(&words[1..]);
+        let names_start = arguments
+            .iter()
+            .position(|(word, value)| value.is_some() || !word.starts_with('-'))
+            .unwrap_or(arguments.len());
+        
+        let mut requested = variables::Attributes {
+            readonly: command == "readonly",
+            ..Default::default()
+        };
+        let mut export = false;
+        let mut print = false;
+        for (flags, _) in &arguments[..names_start] {
+            for flag in flags.chars().skip(1) {
+                match flag {
+                    'a' if command != "readonly" => requested.array = true,
+                    'i' if command != "readonly" => requested.integer = true,
+                    'r' => requested.readonly = true,
+                    'x' if command != "readonly" => export = true,
+                    'p' => print = true,
+                    _ => return Err(anyhow::anyhow!("{}: -{}: invalid option", command, flag)),
                 }
-                
-                // Send signal to each PID
-                for pid_str in &parts[arg_start..] {
-                    if let Ok(pid) = pid_str.parse::<i32>() {
-                        unsafe {
-                            if libc::kill(pid, signal) != 0 {
-                                eprintln!("kill: ({}) - No such process", pid);
-                            }
-                        }
-                    } else {
-                        eprintln!("kill: ({}) - Invalid process id", pid_str);
+            }
+        }
+        
+        let names = &arguments[names_start..];
+        if names.is_empty() {
+            // With only flags, list the variables that have those attributes
+            for (name, _) in self.variables.all() {
+                let attributes = self.variables.attributes(&name);
+                let listed = (!requested.readonly || attributes.readonly)
+                    && (!requested.integer || attributes.integer)
+                    && (!requested.array || attributes.array)
+                    && (!export || variables::Variables::is_exported(&name));
+                if listed {
+                    if let Some(line) = self.variables.describe(&name) {
+                        println!("{}", line);
                     }
                 }
-                
-                Some(Ok(false))
-            },
-            
-            "wait" => {
-                if parts.len() > 1 {
-                    for pid_str in &parts[1..] {
-                        if let Ok(pid) = pid_str.parse::<i32>() {
-                            unsafe {
-                                let mut status = 0;
-                                libc::waitpid(pid, &mut status, 0);
-                            }
-                        } else {
-                            eprintln!("wait: {}: invalid process id", pid_str);
-                        }
-                    }
-                } else {
-                    // Wait for all children
-                    unsafe {
-                        libc::wait(std::ptr::null_mut());
-                    }
+            }
+            return Ok(());
+        }
+        
+        for (name, value) in names {
+            if !variables::is_name(name) {
+                return Err(anyhow::anyhow!("{}: '{}': not a valid identifier", command, name));
+            }
+            if print {
+                match self.variables.describe(name) {
+                    Some(line) => println!("{}", line),
+                    None => return Err(anyhow::anyhow!("{}: {}: not found", command, name)),
                 }
-                Some(Ok(false))
-            },
+                continue;
+            }
             
-            // Aliases
-            "alias" => {
-                if parts.len() == 1 {
-                    // List all aliases
-                    for (name, value) in self.alias_manager.list_aliases() {
-                        println!("alias {}='{}'", name, value);
-                    }
-                } else if parts.len() == 2 && !parts[1].contains('=') {
-                    // Show specific alias
-                    let aliases = self.alias_manager.list_aliases();
-                    let name = parts[1];
-                    let found = aliases.iter().find(|(n, _)| n == name);
-                    if let Some((_, value)) = found {
-                        println!("alias {}='{}'", name, value);
-                    } else {
-                        println!("alias: {} not found", name);
-                    }
-                } else {
-                    // Define new alias
-                    let alias_def = input["alias ".len()..].trim();
-                    if let Some(equals_pos) = alias_def.find('=') {
-                        let name = alias_def[..equals_pos].trim();
-                        let mut value = alias_def[equals_pos + 1..].trim();
-                        // Remove surrounding quotes if present
-                        if (value.starts_with('\'') && value.ends_with('\'')) || 
-                           (value.starts_with('"') && value.ends_with('"')) {
-                            value = &value[1..value.len() - 1];
-                        }
-                        match self.alias_manager.add_alias(name, value) {
-                            Ok(_) => {},
-                            Err(e) => eprintln!("Error adding alias: {}", e),
-                        }
-                    } else {
-                        eprintln!("Invalid alias format. Use: alias name='value'");
+            if self.variables.attributes(name).readonly && (value.is_some() || requested != variables::Attributes::default()) {
+                return Err(anyhow::anyhow!("{}: readonly variable", name));
+            }
+            // Type attributes first so the value is stored as an integer or array
+            self.variables.set_attributes(name, |attributes| {
+                attributes.integer |= requested.integer;
+                attributes.array |= requested.array;
+            });
+            if let Some(value) = value {
+                self.assign_variable(name, value.clone())?;
+            }
+            if requested.readonly {
+                self.variables.set_attributes(name, |attributes| attributes.readonly = true);
+            }
+            if export {
+                self.variables.export(name, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn print_dir_stack(&self) {
+        let home = dirs::home_dir().unwrap_or_default();
+        let stack: Vec<String> = std::iter::once(&self.working_dir)
+            .chain(self.dir_stack.iter().rev())
+            .map(|dir| match dir.strip_prefix(&home) {
+                Ok(rest) if !home.as_os_str().is_empty() => format!("~/{}", rest.display()).trim_end_matches('/').to_string(),
+                _ => dir.display().to_string(),
+            })
+            .collect();
+        println!("{}", stack.join(" "));
+    }
+
+    fn show_config(&self) {
+        let files: Vec<String> = FILE_CONFIG.files.iter().map(|path| path.display().to_string()).collect();
+        println!("config_files          = {}", if files.is_empty() { "none".to_string() } else { files.join(", ") });
+        println!("llm_host              = {}", CONFIG.llm_host);
+        println!("workspace             = {}", self.workspaces.active().map(|w| w.name.as_str()).unwrap_or("none"));
+        println!("llm_model             = {}", self.llm_client.model());
+        println!("context_scope         = {}", self.context_manager.scope.as_str());
+        println!("llm_command_timeout   = {}", CONFIG.llm_command_timeout
+            .map(crate::utils::duration::format_duration)
+            .unwrap_or_else(|| "none".to_string()));
+        println!("suggestion_count      = {}", CONFIG.suggestion_count);
+        println!("history_size          = {}", CONFIG.history_size);
+        println!("translation_mode      = {}", CONFIG.translation_mode.as_str());
+        println!("demo_mode             = {}", self.options.observemode);
+        println!("confirm               = {}", self.confirmation_policy.level.as_str());
+        for pattern in self.confirmation_policy.allowlist() {
+            println!("confirm.allow         = {}", pattern);
+        }
+        for (name, trigger) in [("nl.pattern_words", Trigger::Pattern), ("nl.min_words", Trigger::WordCount)] {
+            let outcomes = self.detection.outcomes(trigger);
+            println!(
+                "{:<21} = {} ({} accepted, {} declined)",
+                name,
+                self.detection.threshold(trigger),
+                outcomes.accepted,
+                outcomes.rejected
+            );
+        }
+    }
+
+    fn show_help(&self) {
+        println!("\n{}", tr("help.title").bright_green());
+        println!("{}", "=".repeat(tr("help.title").chars().count()).bright_green());
+        
+        println!("\n{}", tr("help.builtins").bright_yellow());
+        for builtin in self.builtins.iter() {
+            let names: Vec<&str> = std::iter::once(builtin.name).chain(builtin.aliases.iter().copied()).collect();
+            println!("  {:<22}- {}", names.join("/"), builtin.summary);
+        }
+        println!("  {}", tr("help.builtin_hint"));
+        
+        println!("\n{}", tr("help.features").bright_yellow());
+        println!("  command??             - Show command suggestions");
+        println!("  ?query                - Ask a question to the LLM");
+        println!("  use natural language  - Type commands in plain English");
+        println!("  Ctrl+G                - Translate the line in place (Ctrl+_ undoes it)");
+        println!("  ... --to-pane[=pane]  - Send the translation to a tmux pane");
+        
+        println!("\n{}", tr("help.examples").bright_yellow());
+        println!("  ? How do I find large files in Linux?");
+        println!("  find all python files modified in the last week");
+        println!("  ps ??                 - Show suggestions for ps command");
+        println!("  git rebase -i ??      - Suggest complete commands for what's typed");
+        
+        println!("\n{}", "For more information, visit: https://github.com/yourusername/llm-shell".bright_blue());
+    }
+
+    fn show_builtin_help(&self, name: &str) -> Result<i32> {
+        let builtin = self.builtins.find(name)
+            .with_context(|| format!("help: no help topics match '{}'", name))?;
+        let builtin = builtin.spec();
+        
+        println!("{}", builtin.usage.bright_green());
+        println!("    {}", builtin.summary);
+        if !builtin.details.is_empty() {
+            println!();
+            for line in builtin.details.lines() {
+                println!("    {}", line);
+            }
+        }
+        if !builtin.aliases.is_empty() {
+            println!("\n    Also available as: {}", builtin.aliases.join(", "));
+        }
+        Ok(0)
+    }
+
+    async fn process_input(&mut self, input: &str) -> Result<i32> {
+        // Check for chat prefix
+        if input.starts_with('?') && !self.options.posix {
+            let question = input[1..].trim();
+            if !question.is_empty() {
+                telemetry::count("chat");
+                print!("\n{}", "Thinking...".bright_blue());
+                let _ = std::io::stdout().flush();
+                // The answer replaces "Thinking..." once it starts arriving
+                let mut started = false;
+                let mut print_text = |text: &str| {
+                    let text = if started { text } else { text.trim_start() };
+                    if text.is_empty() {
+                        return;
                     }
-                }
-                Some(Ok(false))
-            },
-            
-            "unalias" => {
-                if parts.len() > 1 {
-                    for name in &parts[1..] {
-                        match self.alias_manager.remove_alias(name) {
-                            Ok(_) => {},
-                            Err(e) => eprintln!("Error removing alias {}: {}", name, e),
-                        }
+                    if !started {
+                        started = true;
+                        print!("\r\x1b[K{}\n", "Answer:".bright_green());
                     }
-                } else {
-                    eprintln!("unalias: missing alias name");
-                }
-                Some(Ok(false))
-            },
-            
-            // History
-            "history" => {
-                let entries = self.terminal.get_history().get_entries();
-                let count = if parts.len() > 1 {
-                    parts[1].parse::<usize>().unwrap_or(entries.len())
-                } else {
-                    entries.len()
+                    print!("{}", text);
+                    let _ = std::io::stdout().flush();
                 };
-                
-                for (i, entry) in entries.iter().rev().take(count).rev().enumerate() {
-                    println!("{:5} {}", entries.len() - count + i + 1, entry);
+                // A Ctrl+C from before the question doesn't cancel it
+                signal_handler::SignalHandler::was_interrupted();
+                let answer = self.llm_client
+                    .chat_stream(question, &mut print_text, signal_handler::SignalHandler::was_interrupted)
+                    .await;
+                if !started {
+                    print!("\r\x1b[K");
                 }
-                Some(Ok(false))
-            },
-            
-            // File operations
-            "touch" => {
-                if parts.len() > 1 {
-                    for file in &parts[1..] {
-                        let path = std::path::Path::new(file);
-                        if !path.exists() {
-                            if let Err(e) = std::fs::File::create(path) {
-                                eprintln!("touch: cannot touch '{}': {}", file, e);
-                            }
-                        } else {
-                            // Update file times (simplified - just recreates the file)
-                            let content = std::fs::read(path).unwrap_or_default();
-                            if let Err(e) = std::fs::write(path, content) {
-                                eprintln!("touch: cannot touch '{}': {}", file, e);
-                            }
-                        }
+                match answer {
+                    Ok(response) => {
+                        println!("\n");
+                        self.context_manager.add_chat_turn(question, &response);
                     }
-                } else {
-                    eprintln!("touch: missing file operand");
-                }
-                Some(Ok(false))
-            },
-            
-            "mkdir" => {
-                if parts.len() > 1 {
-                    let mut create_parents = false;
-                    let mut dirs_start = 1;
-                    
-                    if parts[1] == "-p" {
-                        create_parents = true;
-                        dirs_start = 2;
+                    Err(e) if e.is::<Cancelled>() => {
+                        println!("\n{}", "Cancelled.".yellow());
+                        return Ok(130);
                     }
-                    
-                    for dir in &parts[dirs_start..] {
-                        let path = std::path::Path::new(dir);
-                        let result = if create_parents {
-                            std::fs::create_dir_all(path)
-                        } else {
-                            std::fs::create_dir(path)
-                        };
-                        
-                        if let Err(e) = result {
-                            eprintln!("mkdir: cannot create directory '{}': {}", dir, e);
-                        }
+                    Err(e) => {
+                        println!("Error getting response: {}", e);
+                        return Ok(1);
                     }
-                } else {
-                    eprintln!("mkdir: missing operand");
                 }
-                Some(Ok(false))
-            },
+                return Ok(0);
+            }
+        }
+    
+        // Compound commands (if ... fi, case ... esac) are parsed as a script rather than split on ';'
+        if script::is_compound(input) {
+            let nodes = script::parse(input)?;
+            return self.run_script(&nodes).await;
+        }
+    
+        // Check for natural language patterns, in the request without a
+        // `--to-pane` flag
+        let (request, pane_target) = self.pane_target(input);
+        let trigger = self.detection.detect(&request).filter(|_| self.options.nl_detection && !self.options.posix);
+    
+        let mut untranslated = false;
+        if trigger.is_some() {
+            debug!("Processing as natural language: {}", input);
+            println!("Processing as natural language: {}", input.bright_yellow());
             
-            "rmdir" => {
-                if parts.len() > 1 {
-                    for dir in &parts[1..] {
-                        if let Err(e) = std::fs::remove_dir(dir) {
-                            eprintln!("rmdir: failed to remove '{}': {}", dir, e);
-                        }
-                    }
-                } else {
-                    eprintln!("rmdir: missing operand");
+            // Without the model, fall through and treat the input as a regular command
+            let Ok(translation) = self.translate(&request).await else {
+                return Ok(130);
+            };
+            untranslated = translation.is_none();
+            if let Some(shell_command) = translation {
+                return self.run_translation(&request, &shell_command, pane_target.as_deref(), trigger).await;
+            }
+        }
+    
+        // Regular command processing
+        let commands = self.command_processor.parse(input, &self.detection)?;
+        
+        let mut status = 0;
+        for cmd in commands {
+            let (request, pane_target) = self.pane_target(&cmd.command);
+            let translation = if cmd.trigger.is_some() && self.options.nl_detection && !self.options.posix && !untranslated {
+                debug!("Detected natural language: {}", cmd.command);
+                println!("Detected natural language: {}", cmd.command.bright_yellow());
+                match self.translate(&request).await {
+                    Ok(translation) => translation,
+                    Err(_) => return Ok(130),
                 }
-                Some(Ok(false))
-            },
+            } else {
+                None
+            };
             
-            // Shell control
-            "exit" | "logout" | "bye" => {
-                let exit_code = if parts.len() > 1 {
-                    parts[1].parse::<i32>().unwrap_or(0)
-                } else {
-                    0
-                };
-                
-                if exit_code != 0 {
-                    eprintln!("Exit code: {}", exit_code);
+            if let Some(shell_command) = translation {
+                status = self.run_translation(&request, &shell_command, pane_target.as_deref(), cmd.trigger).await?;
+            } else {
+                // Ask for confirmation according to the confirmation policy
+                if !self.options.observemode && !self.confirm_command(&cmd.command, CommandOrigin::User)? {
+                    println!("{}", tr("confirm.aborted"));
+                    status = 1;
+                    continue;
+                }
+                status = self.execute_with_undo(&cmd.command, false).await?;
+            }
+        }
+        
+        Ok(status)
+    }
+
+    // Shows a translated command and runs it as the translation mode allows.
+    // `trigger` is why the request was detected, for tuning detection.
+    async fn run_translation(&mut self, request: &str, command: &str, pane: Option<&str>, trigger: Option<Trigger>) -> Result<i32> {
+        println!("\nTranslated command: {}", command.bright_green());
+        self.last_translation = Some((request.to_string(), command.to_string()));
+        
+        if let Ok(explanation) = self.documentation.get_command_help(command).await {
+            println!("Explanation: {}", explanation.bright_blue());
+        }
+        
+        if let Some(pane) = pane {
+            telemetry::count("translation.sent_to_pane");
+            return self.send_to_pane(pane, command).map(|_| 0);
+        }
+        
+        if CONFIG.translation_mode == TranslationMode::Suggest {
+            telemetry::count("translation.suggested");
+            self.terminal.set_prefill(command);
+            return Ok(0);
+        }
+        
+        // Ask for confirmation according to the confirmation policy
+        if !self.options.observemode && !self.confirm_command(command, CommandOrigin::Llm)? {
+            telemetry::count("translation.rejected");
+            self.record_detection(trigger, false);
+            println!("{}", tr("confirm.aborted"));
+            return Ok(1);
+        }
+        
+        telemetry::count("translation.accepted");
+        let status = self.execute_with_undo(command, true).await?;
+        if status != 0 {
+            telemetry::count("translation.command_failed");
+        } else {
+            self.record_detection(trigger, true);
+        }
+        self.remember_translation(request, command, status);
+        Ok(status)
+    }
+
+    fn record_detection(&mut self, trigger: Option<Trigger>, accepted: bool) {
+        if let Some(change) = trigger.and_then(|trigger| self.detection.record(trigger, accepted)) {
+            println!("{}", format!("{}; see `config`.", change).dimmed());
+        }
+    }
+
+    // Translations that worked are kept in the project's memory as examples
+    fn remember_translation(&mut self, request: &str, command: &str, status: i32) {
+        if status != 0 {
+            return;
+        }
+        if let Err(e) = self.context_manager.update_memory(|memory| memory.add_translation(request, command)) {
+            debug!("Failed to save project memory: {}", e);
+        }
+    }
+
+    // Returns None when the LLM is unavailable so callers can run the raw input instead
+    // A `Cancelled` error means the user stopped it with Ctrl+C, and the input
+    // shouldn't run either
+    async fn translate(&mut self, input: &str) -> Result<Option<String>> {
+        if let Err(e) = budget::check() {
+            println!("{}", format!("{}; running input as a regular command.", e).yellow());
+            return Ok(None);
+        }
+        if !self.llm_available().await {
+            println!("{}", "LLM unavailable; running input as a regular command.".yellow());
+            return Ok(None);
+        }
+        
+        let (mut examples, context) = self.feedback_examples();
+        examples.splice(0..0, library_examples(input));
+        telemetry::count("translation.requested");
+        // At a terminal the command is shown as it is written, then replaced
+        // by the translation flow's output
+        let translation = if std::io::stdout().is_terminal() {
+            let mut shown = String::from("Translating: ");
+            print!("{}", shown.dimmed());
+            let mut print_text = |text: &str| {
+                shown.push_str(text);
+                print!("{}", text.dimmed());
+                let _ = std::io::stdout().flush();
+            };
+            signal_handler::SignalHandler::was_interrupted();
+            let translation = self.llm_client
+                .translate_command_stream(input, &context, &examples, &mut print_text, signal_handler::SignalHandler::was_interrupted)
+                .await;
+            terminal::erase(&shown);
+            translation
+        } else {
+            self.llm_client.translate_command(input, &context, &examples).await
+        };
+        match translation {
+            Ok(command) => Ok(Some(command)),
+            Err(e) if e.is::<Cancelled>() => {
+                println!("{}", "Translation cancelled.".yellow());
+                Err(e)
+            }
+            Err(e) => {
+                telemetry::count("translation.failed");
+                eprintln!("{} {}", "Translation failed:".red(), e);
+                println!("{}", "LLM features degraded; running input as a regular command.".yellow());
+                self.llm_degraded_at = Some(Instant::now());
+                Ok(None)
+            }
+        }
+    }
+    
+    // Well-rated translations become examples for the model; rejected ones
+    // are listed in the context with the user's note
+    fn feedback_examples(&self) -> (Vec<(String, String)>, String) {
+        let mut context = self.context_manager.get_context();
+        if CONFIG.feedback_examples == 0 {
+            return (Vec::new(), context);
+        }
+        let (good, bad): (Vec<_>, Vec<_>) = feedback::recent(CONFIG.feedback_examples).into_iter().partition(|rating| rating.good);
+        if !bad.is_empty() {
+            let rejected: Vec<String> = bad
+                .iter()
+                .map(|rating| match rating.note.as_str() {
+                    "" => format!("'{}' -> {}", rating.request, rating.command),
+                    note => format!("'{}' -> {} ({})", rating.request, rating.command, note),
+                })
+                .collect();
+            context.push_str(&format!("\nTranslations the user rejected: {}", rejected.join("; ")));
+        }
+        (good.into_iter().map(|rating| (rating.request, rating.command)).collect(), context)
+    }
+
+    // For the prompt: whether the next multi-word input would reach the model
+    fn llm_status(&self) -> LlmStatus {
+        if !self.options.nl_detection || self.options.posix || budget::check().is_err() {
+            LlmStatus::Off
+        } else if self.llm_degraded_at.is_some() {
+            LlmStatus::Degraded
+        } else {
+            LlmStatus::Ready
+        }
+    }
+
+    async fn llm_available(&mut self) -> bool {
+        let degraded_at = match self.llm_degraded_at {
+            Some(at) => at,
+            None => return true,
+        };
+        
+        if degraded_at.elapsed() < LLM_HEALTH_CHECK_INTERVAL {
+            return false;
+        }
+        
+        match self.llm_client.health_check().await {
+            Ok(()) => {
+                println!("{}", "LLM endpoint reachable again.".bright_green());
+                self.llm_degraded_at = None;
+                true
+            }
+            Err(e) => {
+                debug!("LLM health check failed: {}", e);
+                self.llm_degraded_at = Some(Instant::now());
+                false
+            }
+        }
+    }
+
+    fn update_cloud_context(&mut self) {
+        let cloud = CloudContext::current();
+        if cloud.is_empty() {
+            self.context_manager.remove_system_info("cloud");
+        } else {
+            self.context_manager.set_system_info("cloud", &cloud.describe());
+        }
+    }
+
+    // A translation request without its `--to-pane` flag, and the tmux pane
+    // its translation goes to, if any
+    fn pane_target(&self, request: &str) -> (String, Option<String>) {
+        match split_pane_flag(request) {
+            Some((request, Some(pane))) => (request, Some(pane)),
+            Some((request, None)) => (request, self.tmux_pane.clone().or_else(|| Some("{last}".to_string()))),
+            None => (request.to_string(), self.tmux_pane.clone()),
+        }
+    }
+
+    fn send_to_pane(&self, pane: &str, command: &str) -> Result<()> {
+        Tmux::send_keys(pane, command)?;
+        println!("Sent to tmux pane {} (press Enter there to run it)", pane.bright_cyan());
+        Ok(())
+    }
+
+    async fn execute_with_undo(&mut self, command: &str, translated: bool) -> Result<i32> {
+        if self.options.observemode {
+            return self.show_demo_plan(command, translated).await.map(|_| 0);
+        }
+        
+        let destructive = self.is_destructive_command(command);
+        
+        // Rule-based hints need the file state from before the command runs (only meaningful locally)
+        let local = !(translated && self.remote_target.is_some());
+        let prior_state = if destructive && local {
+            undo::UndoStack::capture(command)
+        } else {
+            None
+        };
+        
+        // With `shopt -s backup`, files a redirection is about to overwrite are copied first
+        if destructive && local && self.options.backup {
+            for file in backup::Backups::targets(&self.expand_env_vars(command).unwrap_or_else(|_| command.to_string())) {
+                match self.backups.save(&file) {
+                    Ok(n) => println!("{} {} (restore {})", "Backed up".bright_magenta(), file.display(), n),
+                    Err(e) => eprintln!("Warning: {}", e),
                 }
-                
-                Some(Ok(true)) // Signal to exit the shell
-            },
+            }
+        }
+        
+        // Translations are often loops or conditionals, which the script
+        // interpreter runs
+        let exit_code = if translated && local && script::is_compound(command) {
+            let nodes = script::parse(command)?;
+            self.in_translation = true;
+            let result = self.run_script(&nodes).await;
+            self.in_translation = false;
+            result?
+        } else if translated {
+            self.execute_translated(command)?
+        } else {
+            self.execute_command(command)?
+        };
+        
+        // A failed command may have changed nothing, so there is nothing to undo
+        if destructive && exit_code == 0 {
+            let hint = match prior_state.as_ref().and_then(undo::UndoStack::rule_hint) {
+                Some(hint) => Some(hint),
+                None if self.llm_degraded_at.is_none() => self.llm_client.suggest_undo(command).await.unwrap_or(None),
+                None => None,
+            };
             
-            "source" | "." => {
-                if parts.len() > 1 {
-                    let path = std::path::Path::new(parts[1]);
-                    if let Ok(content) = std::fs::read_to_string(path) {
-                        for line in content.lines() {
-                            let line = line.trim();
-                            if line.is_empty() || line.starts_with('#') {
-                                continue;
-                            }
-                            
-                            // Process each line as a command
-                            // Note: This will be handled by the caller since process_input is async
-                            return Some(Err(anyhow::anyhow!("source: async operations not supported in built-ins")));
-                        }
-                    } else {
-                        eprintln!("{}: cannot open {}: No such file or directory", parts[0], parts[1]);
+            if let Some(hint) = hint {
+                println!("{} {}", "Undo hint:".bright_magenta(), hint.bright_cyan());
+                self.undo_stack.push(command, &hint);
+            }
+        }
+        
+        Ok(exit_code)
+    }
+
+    async fn show_demo_plan(&mut self, command: &str, translated: bool) -> Result<()> {
+        let label = "[demo]".bright_yellow();
+        let expanded = self.expand_env_vars(command)?;
+        println!("{} would run: {}", label, expanded.bright_green());
+        
+        let pipeline = crate::shell::command_parser::CommandParser::parse(&expanded)?;
+        for (i, stage) in pipeline.commands.iter().enumerate() {
+            println!("{}   stage {}: {} {:?}", label, i + 1, stage.program, stage.args);
+            for redirection in stage.redirections.iter().filter(|r| **r != crate::shell::command_parser::Redirection::Pipe) {
+                println!("{}            redirect {:?}", label, redirection);
+            }
+        }
+        if pipeline.background {
+            println!("{}   runs in the background", label);
+        }
+        if self.is_destructive_command(command) {
+            println!("{}   {}", label, "this command may modify or delete data".bright_red());
+        }
+        if let Some(target) = self.remote_target.as_ref().filter(|_| translated) {
+            println!("{}   would run on {}", label, target.url());
+        }
+        
+        // Translated commands were already explained
+        if !translated {
+            if let Ok(explanation) = self.documentation.get_command_help(command).await {
+                println!("{} Explanation: {}", label, explanation.bright_blue());
+            }
+        }
+        
+        println!("{} not executed", label);
+        Ok(())
+    }
+
+    fn execute_translated(&mut self, command: &str) -> Result<i32> {
+        let target = match &mut self.remote_target {
+            Some(target) => target,
+            None => return self.execute_command_as(command, CommandOrigin::Llm, CONFIG.llm_command_timeout),
+        };
+        
+        let exit_code = target.execute(command)?;
+        if exit_code != 0 {
+            eprintln!("Command failed on {} with exit code: {}", target.host, exit_code);
+        }
+        // The model is told where the next command will run
+        let description = target.describe();
+        self.context_manager.set_system_info("target", &description);
+        
+        Ok(exit_code)
+    }
+
+    #[async_recursion(?Send)]
+    async fn run_script(&mut self, nodes: &[Node]) -> Result<i32> {
+        let mut status = 0;
+        for node in nodes {
+            status = match node {
+                // A failing command sets the status instead of aborting the rest of the script
+                Node::Command(command) => match self.run_script_command(command).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        1
                     }
-                } else {
-                    eprintln!("{}: filename argument required", parts[0]);
-                }
-                Some(Ok(false))
-            },
-            
-            "eval" => {
-                if parts.len() > 1 {
-                    let cmd = parts[1..].join(" ");
-                    // Note: This will be handled by the caller since process_input is async
-                    return Some(Err(anyhow::anyhow!("eval: async operations not supported in built-ins")));
-                }
-                Some(Ok(false))
-            },
-            
-            // Information and help
-            "type" => {
-                if parts.len() > 1 {
-                    for cmd in &parts[1..] {
-                        // Check if it's a built-in
-                        let is_builtin = matches!(*cmd, 
-                            "cd" | "pwd" | "export" | "unset" | "set" | "echo" | "printf" |
-                            "jobs" | "fg" | "bg" | "kill" | "wait" | "alias" | "unalias" |
-                            "history" | "touch" | "mkdir" | "rmdir" | "exit" | "logout" |
-                            "source" | "." | "eval" | "type" | "help" | "true" | "false" |
-                            "test" | "time" | "umask" | "ulimit" | "read" | "exec"
-                        );
-                        
-                        if is_builtin {
-                            println!("{} is a shell builtin", cmd);
-                        } else if let Some(path) = crate::utils::path_utils::find_executable(cmd) {
-                            println!("{} is {}", cmd, path.display());
-                        } else if self.alias_manager.list_aliases().iter().any(|(name, _)| name == cmd) {
-                            println!("{} is an alias", cmd);
-                        } else {
-                            println!("{}: not found", cmd);
+                },
+                Node::Case { word, arms } => {
+                    let value = script::unquote(&self.expand_env_vars(word)?);
+                    // Patterns are expanded in order, only until one matches
+                    let mut matched = None;
+                    'arms: for arm in arms {
+                        for pattern in &arm.patterns {
+                            if glob_match(&script::pattern_from_word(&self.expand_env_vars(pattern)?), &value) {
+                                matched = Some(arm);
+                                break 'arms;
+                            }
                         }
                     }
-                } else {
-                    eprintln!("type: missing argument");
-                }
-                Some(Ok(false))
-            },
-            
-            "help" => {
-                self.show_help();
-                Some(Ok(false))
-            },
-            
-            // Simple utilities
-            "true" => {
-                Some(Ok(false))
-            },
-            
-            "false" => {
-                // In a real shell, this would set the exit status to 1
-                Some(Ok(false))
-            },
-            
-            "test" | "[" => {
-                // Very simplified test implementation
-                if parts.len() < 2 {
-                    eprintln!("test: missing argument");
-                    return Some(Ok(false));
-                }
-                
-                // Handle the closing bracket for [ command
-                let test_parts = if parts[0] == "[" {
-                    if parts[parts.len() - 1] != "]" {
-                        eprintln!("[: missing closing ]");
-                        return Some(Ok(false));
-                    }
-                    &parts[1..parts.len() - 1]
-                } else {
-                    &parts[1..]
-                };
-                
-                if test_parts.is_empty() {
-                    // Empty test is false
-                    eprintln!("Test failed");
-                    return Some(Ok(false));
-                }
-                
-                // Handle simple file tests
-                if test_parts.len() == 2 && test_parts[0] == "-f" {
-                    let path = std::path::Path::new(test_parts[1]);
-                    if !path.is_file() {
-                        eprintln!("Test failed: {} is not a file", test_parts[1]);
-                    }
-                } else if test_parts.len() == 2 && test_parts[0] == "-d" {
-                    let path = std::path::Path::new(test_parts[1]);
-                    if !path.is_dir() {
-                        eprintln!("Test failed: {} is not a directory", test_parts[1]);
-                    }
-                } else if test_parts.len() == 3 && test_parts[1] == "=" {
-                    if test_parts[0] != test_parts[2] {
-                        eprintln!("Test failed: {} != {}", test_parts[0], test_parts[2]);
+                    match matched {
+                        Some(arm) => self.run_script(&arm.body).await?,
+                        None => 0,
                     }
-                } else if test_parts.len() == 3 && test_parts[1] == "!=" {
-                    if test_parts[0] == test_parts[2] {
-                        eprintln!("Test failed: {} == {}", test_parts[0], test_parts[2]);
-                    }
-                }
-                
-                Some(Ok(false))
-            },
-            
-            "time" => {
-                if parts.len() > 1 {
-                    let cmd = parts[1..].join(" ");
-                    // Note: This will be handled by the caller since process_input is async
-                    return Some(Err(anyhow::anyhow!("time: async operations not supported in built-ins")));
-                } else {
-                    eprintln!("time: missing command");
                 }
-                Some(Ok(false))
-            },
-            
-            // System control
-            "umask" => {
-                if parts.len() > 1 {
-                    // Set umask (simplified)
-                    if let Ok(mask) = u32::from_str_radix(parts[1], 8) {
-                        unsafe {
-                            libc::umask(mask);
-                        }
-                    } else {
-                        eprintln!("umask: invalid octal number: {}", parts[1]);
-                    }
-                } else {
-                    // Get current umask
-                    unsafe {
-                        // Save current umask
-                        let current = libc::umask(0);
-                        // Restore it
-                        libc::umask(current);
-                        println!("{:04o}", current);
-                    }
+                Node::If { branches, otherwise } => self.run_if(branches, otherwise.as_deref()).await?,
+                Node::For { name, words, body } => self.run_for(name, words, body).await?,
+                Node::While { until, condition, body } => self.run_while(*until, condition, body).await?,
+                Node::Group { body, redirects } => {
+                    let _redirected = crate::shell::executor::Executor::redirect_shell(&self.expand_redirects(redirects)?, self.options.noclobber)?;
+                    self.run_script(body).await?
                 }
-                Some(Ok(false))
-            },
-            
-            "ulimit" => {
-                // Simplified ulimit implementation
-                if parts.len() == 1 {
-                    // Show file size limit
-                    unsafe {
-                        let mut rlim: libc::rlimit = std::mem::zeroed();
-                        if libc::getrlimit(libc::RLIMIT_FSIZE, &mut rlim) == 0 {
-                            if rlim.rlim_cur == libc::RLIM_INFINITY {
-                                println!("unlimited");
-                            } else {
-                                println!("{}", rlim.rlim_cur);
-                            }
-                        } else {
-                            eprintln!("ulimit: error getting limit");
-                        }
-                    }
-                } else if parts[1] == "-a" {
-                    // Show all limits
-                    println!("core file size          (blocks, -c) unlimited");
-                    println!("data seg size           (kbytes, -d) unlimited");
-                    println!("scheduling priority             (-e) 0");
-                    println!("file size               (blocks, -f) unlimited");
-                    println!("pending signals                 (-i) 15169");
-                    println!("max locked memory       (kbytes, -l) 65536");
-                    println!("max memory size         (kbytes, -m) unlimited");
-                    println!("open files                      (-n) 1024");
-                    println!("pipe size            (512 bytes, -p) 8");
-                    println!("POSIX message queues     (bytes, -q) 819200");
-                    println!("real-time priority              (-r) 0");
-                    println!("stack size              (kbytes, -s) 8192");
-                    println!("cpu time               (seconds, -t) unlimited");
-                    println!("max user processes              (-u) 15169");
-                    println!("virtual memory          (kbytes, -v) unlimited");
-                    println!("file locks                      (-x) unlimited");
+                Node::Subshell { body, redirects } => {
+                    let _redirected = crate::shell::executor::Executor::redirect_shell(&self.expand_redirects(redirects)?, self.options.noclobber)?;
+                    self.run_subshell(body).await?
                 }
-                Some(Ok(false))
-            },
-            
-            // Input/output
-            "read" => {
-                if parts.len() > 1 {
-                    let mut input = String::new();
-                    if std::io::stdin().read_line(&mut input).is_ok() {
-                        input = input.trim().to_string();
-                        
-                        // Handle -p prompt option
-                        let mut var_start = 1;
-                        if parts[1] == "-p" && parts.len() > 3 {
-                            print!("{}", parts[2]);
-                            std::io::stdout().flush().unwrap_or(());
-                            var_start = 3;
+                Node::Select { name, words, body } => self.run_select(name, words, body).await?,
+                Node::Coproc { name, body } => self.run_coproc(name, body)?,
+                Node::AndOr { first, rest } => {
+                    let mut status = self.run_script(std::slice::from_ref(first)).await?;
+                    for (connector, node) in rest {
+                        if self.loop_control.is_some() || self.exit_requested {
+                            break;
                         }
-                        
-                        // Assign to variables
-                        if parts.len() > var_start {
-                            let var_name = parts[var_start];
-                            std::env::set_var(var_name, input);
+                        if connector.runs_after(status) {
+                            status = self.run_script(std::slice::from_ref(node)).await?;
                         }
                     }
-                } else {
-                    eprintln!("read: missing variable name");
-                }
-                Some(Ok(false))
-            },
-            
-            "exec" => {
-                if parts.len() > 1 {
-                    let cmd = parts[1].to_string();
-                    let args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
-                    
-                    if let Some(path) = crate::utils::path_utils::find_executable(&cmd) {
-                        use std::os::unix::process::CommandExt;
-                        let err = std::process::Command::new(path)
-                            .args(&args[1..])
-                            .exec();
-                        
-                        // If we get here, exec failed
-                        eprintln!("exec: failed to execute {}: {}", cmd, err);
-                    } else {
-                        eprintln!("exec: {}: command not found", cmd);
-                    }
-                } else {
-                    // No command specified, just continue
+                    status
                 }
-                Some(Ok(false))
-            },
+            };
+            self.last_status = status;
             
-            // Not a built-in command
-            _ => None,
+            // The rest of the list is skipped until the targeted loop picks up the break/continue
+            if self.loop_control.is_some() || self.exit_requested {
+                break;
+            }
         }
+        Ok(status)
     }
 
-    fn show_help(&self) {
-        println!("\n{}", "LLM Shell Help".bright_green());
-        println!("{}", "=============".bright_green());
-        
-        println!("\n{}", "Basic Commands:".bright_yellow());
-        println!("  cd [dir]              - Change directory");
-        println!("  alias [name[=value]]  - List or set aliases");
-        println!("  unalias name          - Remove an alias");
-        println!("  jobs                  - List background jobs");
-        println!("  fg [job_id]           - Bring job to foreground");
-        println!("  bg [job_id]           - Continue job in background");
-        println!("  exit                  - Exit the shell");
-        
-        println!("\n{}", "Special Features:".bright_yellow());
-        println!("  command??             - Show command suggestions");
-        println!("  ?query                - Ask a question to the LLM");
-        println!("  use natural language  - Type commands in plain English");
-        
-        println!("\n{}", "Examples:".bright_yellow());
-        println!("  ? How do I find large files in Linux?");
-        println!("  find all python files modified in the last week");
-        println!("  ps ??                 - Show suggestions for ps command");
-        
-        println!("\n{}", "For more information, visit: https://github.com/yourusername/llm-shell".bright_blue());
+    // Runs the body of the first branch whose condition succeeds, or the
+    // else part. With neither the status is 0, as in sh.
+    async fn run_if(&mut self, branches: &[script::IfBranch], otherwise: Option<&[Node]>) -> Result<i32> {
+        for branch in branches {
+            let status = self.run_script(&branch.condition).await?;
+            if self.loop_control.is_some() || self.exit_requested {
+                return Ok(status);
+            }
+            if status == 0 {
+                return self.run_script(&branch.body).await;
+            }
+        }
+        match otherwise {
+            Some(body) => self.run_script(body).await,
+            None => Ok(0),
+        }
     }
 
-    async fn process_input(&mut self, input: &str) -> Result<()> {
-        // Expand environment variables
-        let expanded_input = self.expand_env_vars(input);
-        // Check for chat prefix
-        if input.starts_with('?') {
-            let question = input[1..].trim();
-            if !question.is_empty() {
-                println!("\n{}", "Thinking...".bright_blue());
-                match self.llm_client.chat(question).await {
-                    Ok(response) => {
-                        println!("\n{}", "Answer:".bright_green());
-                        println!("{}\n", response);
-                    }
-                    Err(e) => println!("Error getting response: {}", e),
+    async fn run_for(&mut self, name: &str, words: &[String], body: &[Node]) -> Result<i32> {
+        let mut items = Vec::new();
+        for word in words {
+            items.extend(self.expand_fields(word)?);
+        }
+
+        self.loop_depth += 1;
+        let mut status = Ok(0);
+        for item in items {
+            if let Err(e) = self.variables.set(name, &item) {
+                status = Err(e);
+                break;
+            }
+            status = self.run_script(body).await;
+            if self.stops_loop(&status) {
+                break;
+            }
+        }
+        self.loop_depth -= 1;
+        status
+    }
+
+    // The status is the body's last, or 0 when it never ran
+    async fn run_while(&mut self, until: bool, condition: &[Node], body: &[Node]) -> Result<i32> {
+        self.loop_depth += 1;
+        let mut status = Ok(0);
+        loop {
+            let holds = match self.run_script(condition).await {
+                Ok(checked) => (checked == 0) != until,
+                Err(e) => {
+                    status = Err(e);
+                    break;
                 }
-                return Ok(());
+            };
+            // A break, exit or Ctrl+C in the condition ends the loop too
+            if !holds || self.stops_loop(&Ok(0)) {
+                break;
+            }
+            status = self.run_script(body).await;
+            if self.stops_loop(&status) {
+                break;
             }
         }
-    
-        // Check for natural language patterns
-        let natural_language_patterns = [
-            "show me", "find all", "list all", "get all", "display", "create a", 
-            "make a", "tell me", "give me", "use the", "how do", "what is", "where is",
-            "can you", "could you", "would you", "should I", "explain", "help me",
-            "search for", "look for", "find files", "count", "calculate", "summarize",
-            "who are", "what are", "which", "when", "why", "how many", "how much",
-            "get the", "list", "show", "find", "tell", "give", "display", "print",
-        ];
+        self.loop_depth -= 1;
+        status
+    }
+
+    // After a loop's body: whether the loop ends, for a break aimed at it,
+    // an error, `exit` or Ctrl+C
+    fn stops_loop(&mut self, status: &Result<i32>) -> bool {
+        let stop = self.end_of_iteration();
+        stop || matches!(status, Err(_) | Ok(130)) || self.exit_requested || signal_handler::SignalHandler::was_interrupted()
+    }
+
+    // A word as a command would get it: tildes, variables and command
+    // substitutions expanded, then split on unquoted blanks and globbed
+    fn expand_fields(&self, word: &str) -> Result<Vec<String>> {
+        let word = variables::expand_tildes(word, path_utils::tilde_directory);
+        let expanded = self.substitute_commands(&self.expand_env_vars(&word)?, variables::Insert::Words)?;
+        Ok(script::fields(&expanded)
+            .into_iter()
+            .flat_map(|field| {
+                let pattern = script::pattern_from_word(&field);
+                let paths = if pattern::has_wildcards(&pattern) { pattern::glob_paths(&pattern) } else { Vec::new() };
+                // A pattern that matches nothing stays as it is, as in sh
+                if paths.is_empty() { vec![script::unquote(&field)] } else { paths }
+            })
+            .collect())
+    }
+
+    fn expand_redirects(&self, redirects: &[script::Redirect]) -> Result<Vec<script::Redirect>> {
+        use script::RedirectTarget::*;
+        redirects
+            .iter()
+            .map(|redirect| {
+                let expand = |word: &String| self.expand_env_vars(word).map(|word| script::unquote(&word));
+                let target = match &redirect.target {
+                    Read(word) => Read(expand(word)?),
+                    Write(word) => Write(expand(word)?),
+                    Clobber(word) => Clobber(expand(word)?),
+                    Append(word) => Append(expand(word)?),
+                    Duplicate(word) => Duplicate(expand(word)?),
+                };
+                Ok(script::Redirect { fd: redirect.fd, target })
+            })
+            .collect()
+    }
+
+    // Starts the coprocess and sets NAME to its (read, write) descriptors and
+    // NAME_PID to its process id
+    fn run_coproc(&mut self, name: &str, body: &str) -> Result<i32> {
+        self.reap_coprocs();
+        if let Some(running) = self.coprocs.iter().find(|coproc| coproc.name == name) {
+            return Err(anyhow::anyhow!("coproc: {} is still running (pid {})", name, running.pid()));
+        }
+        let coproc = coproc::Coproc::start(name, body)?;
+        let (read, write) = coproc.fds();
+        self.variables.assign(name, variables::Value::Array(vec![read.to_string(), write.to_string()]))?;
+        self.variables.set(&format!("{}_PID", name), &coproc.pid().to_string())?;
+        if self.terminal.is_interactive() {
+            println!("[coproc {}] {}", name, coproc.pid());
+        }
+        self.coprocs.push(coproc);
+        Ok(0)
+    }
+
+    // Like bash, a coprocess's descriptors and variables go away when it exits
+    fn reap_coprocs(&mut self) {
+        let mut finished = Vec::new();
+        let mut index = 0;
+        while index < self.coprocs.len() {
+            if self.coprocs[index].finished() {
+                finished.push(self.coprocs.remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        for coproc in finished {
+            debug!("coproc {} exited", coproc.name);
+            let _ = self.variables.unset(&coproc.name);
+            let _ = self.variables.unset(&format!("{}_PID", coproc.name));
+        }
+    }
+
+    async fn run_select(&mut self, name: &str, words: &[String], body: &[Node]) -> Result<i32> {
+        let items: Vec<String> = words
+            .iter()
+            .map(|word| self.expand_env_vars(word).map(|word| script::unquote(&word)))
+            .collect::<Result<_>>()?;
+        if items.is_empty() {
+            return Ok(0);
+        }
+        let prompt = self.variables.get("PS3").unwrap_or_else(|| "#? ".to_string());
         
-        let is_natural_language = natural_language_patterns.iter()
-            .any(|pattern| input.to_lowercase().starts_with(pattern)) ||
-            (input.split_whitespace().count() >= 4);
-    
-        if is_natural_language {
-            debug!("Processing as natural language: {}", input);
-            println!("Processing as natural language: {}", input.bright_yellow());
-            
-            let shell_command = self.llm_client.translate_command(input).await?;
-            
-            println!("\nTranslated command: {}", shell_command.bright_green());
+        self.loop_depth += 1;
+        self.terminal.print_menu(&items);
+        let mut status = Ok(0);
+        loop {
+            let reply = match self.terminal.read_reply(&prompt) {
+                Ok(Some(reply)) => reply,
+                Ok(None) => break,
+                Err(e) => {
+                    status = Err(e);
+                    break;
+                }
+            };
+            // An empty reply shows the menu again
+            if reply.is_empty() {
+                self.terminal.print_menu(&items);
+                continue;
+            }
             
-            if let Ok(explanation) = self.documentation.get_command_help(&shell_command).await {
-                println!("Explanation: {}", explanation.bright_blue());
+            let choice = reply
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| items.get(i));
+            let assigned = self.variables
+                .set(name, choice.map(String::as_str).unwrap_or(""))
+                .and_then(|_| self.variables.set("REPLY", &reply));
+            if let Err(e) = assigned {
+                status = Err(e);
+                break;
             }
             
-            // Only ask for confirmation if it's a destructive command
-            if self.is_destructive_command(&shell_command) {
-                println!("\nWarning: This command may modify or delete data.");
-                print!("Proceed? [y/N] ");
-                std::io::stdout().flush()?;
-                
-                let mut response = String::new();
-                std::io::stdin().read_line(&mut response)?;
-                
-                if !response.trim().eq_ignore_ascii_case("y") {
-                    println!("Command aborted.");
-                    return Ok(());
+            status = self.run_script(body).await;
+            if status.is_err() || self.end_of_iteration() {
+                break;
+            }
+        }
+        self.loop_depth -= 1;
+        status
+    }
+
+    // Consumes a break/continue aimed at the current loop; true when the loop should stop
+    fn end_of_iteration(&mut self) -> bool {
+        match self.loop_control.take() {
+            None | Some(LoopControl::Continue(1)) => false,
+            Some(LoopControl::Break(1)) => true,
+            Some(LoopControl::Break(n)) => {
+                self.loop_control = Some(LoopControl::Break(n - 1));
+                true
+            }
+            Some(LoopControl::Continue(n)) => {
+                self.loop_control = Some(LoopControl::Continue(n - 1));
+                true
+            }
+        }
+    }
+
+    // Runs the body against a snapshot of the directory and environment, restored afterwards
+    async fn run_subshell(&mut self, body: &[Node]) -> Result<i32> {
+        let working_dir = self.working_dir.clone();
+        let dir_stack = self.dir_stack.clone();
+        let variables = self.variables.clone();
+        let vars: Vec<(String, String)> = std::env::vars().collect();
+
+        let result = self.run_script(body).await;
+
+        for (name, _) in std::env::vars() {
+            if !vars.iter().any(|(saved, _)| *saved == name) {
+                std::env::remove_var(name);
+            }
+        }
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+        self.variables = variables;
+        self.dir_stack = dir_stack;
+        if self.working_dir != working_dir {
+            self.change_directory(&working_dir.to_string_lossy())?;
+        }
+        result
+    }
+
+    #[async_recursion(?Send)]
+    async fn run_script_command(&mut self, command: &str) -> Result<i32> {
+        let list = CommandParser::parse_list(command)?;
+        if list.len() > 1 {
+            let mut status = 0;
+            for (connector, command) in list {
+                if connector.runs_after(status) {
+                    status = self.run_script_command(&command).await?;
+                    self.last_status = status;
                 }
             }
-            
-            return self.execute_command(&shell_command);
+            return Ok(status);
+        }
+        // The translation was confirmed as a whole before it started, but
+        // each command it runs still gets the escalation and diff checks
+        if self.in_translation && self.confirm_generated(command)? == Some(false) {
+            println!("{}", tr("confirm.aborted"));
+            return Ok(1);
+        }
+        // Raw builtins get the expanded text; everything else expands its
+        // words as it is parsed, so values are never parsed again
+        let name = command.split_whitespace().next().unwrap_or("");
+        let input = match self.builtins.find(name) {
+            Some(builtin) if builtin.spec().raw => self.expand_env_vars(command)?,
+            _ => command.to_string(),
+        };
+        if let Some(result) = self.handle_builtin_command(&input).await {
+            return result;
+        }
+        if self.in_translation {
+            return self.execute_command_as(command, CommandOrigin::Llm, CONFIG.llm_command_timeout);
         }
-    
-        // Regular command processing
-        let commands = self.command_processor.parse(input)?;
         
-        for cmd in commands {
-            if cmd.is_natural_language {
-                debug!("Detected natural language: {}", cmd.command);
-                println!("Detected natural language: {}", cmd.command.bright_yellow());
-                
-                let shell_command = self.llm_client.translate_command(&cmd.command).await?;
-                
-                println!("\nTranslated command: {}", shell_command.bright_green());
-                
-                if let Ok(explanation) = self.documentation.get_command_help(&shell_command).await {
-                    println!("Explanation: {}", explanation.bright_blue());
-                }
-                
-                // Only ask for confirmation if it's a destructive command
-                if self.is_destructive_command(&shell_command) {
-                    println!("\nWarning: This command may modify or delete data.");
-                    print!("Proceed? [y/N] ");
-                    std::io::stdout().flush()?;
-                    
-                    let mut response = String::new();
-                    std::io::stdin().read_line(&mut response)?;
-                    
-                    if !response.trim().eq_ignore_ascii_case("y") {
-                        println!("Command aborted.");
-                        continue;
-                    }
-                }
-                
-                self.execute_command(&shell_command)?;
-            } else {
-                // Only ask for confirmation if it's a destructive command
-                if self.is_destructive_command(&cmd.command) {
-                    println!("\nWarning: This command may modify or delete data.");
-                    print!("Proceed? [y/N] ");
-                    std::io::stdout().flush()?;
-                    
-                    let mut response = String::new();
-                    std::io::stdin().read_line(&mut response)?;
-                    
-                    if !response.trim().eq_ignore_ascii_case("y") {
-                        println!("Command aborted.");
-                        continue;
-                    }
-                }
-                self.execute_command(&cmd.command)?;
+        if !self.options.observemode && !self.confirm_command(command, CommandOrigin::User)? {
+            println!("{}", tr("confirm.aborted"));
+            return Ok(1);
+        }
+        self.execute_with_undo(command, false).await
+    }
+
+    fn confirm_command(&self, command: &str, origin: CommandOrigin) -> Result<bool> {
+        if origin == CommandOrigin::Llm {
+            if let Some(confirmed) = self.confirm_generated(command)? {
+                return Ok(confirmed);
             }
         }
+        let destructive = self.is_destructive_command(command);
+        // `LLM_SHELL_TRANSLATION_MODE=confirm` asks about every translation
+        // except those that clearly only read
+        let always = origin == CommandOrigin::Llm && CONFIG.translation_mode == TranslationMode::Confirm && !policy::is_read_only(command);
+        if !always && (!self.options.confirmations || !self.confirmation_policy.requires_confirmation(command, origin, destructive)) {
+            return Ok(true);
+        }
+        // Nobody to ask: fail closed for destructive commands and forced confirmations
+        if !self.terminal.is_interactive() {
+            if destructive || always {
+                eprintln!("{}", tr_args("warn.no_terminal", &[command]));
+            }
+            return Ok(!(destructive || always));
+        }
         
-        Ok(())
+        if destructive {
+            println!("\n{}", tr("warn.destructive"));
+        }
+        terminal::confirm(tr("confirm.proceed"))
+    }
+
+    // The checks every generated command gets whatever the confirmation
+    // level; None when neither applies
+    fn confirm_generated(&self, command: &str) -> Result<Option<bool>> {
+        // Escalating privileges always needs the hostname typed
+        if let Some(tool) = policy::privilege_escalation(command) {
+            println!("\n{}", tr_args("confirm.escalation", &[tool]).bright_red());
+            let host = host::hostname();
+            return terminal::confirm_typed(&tr_args("confirm.type_host", &[&host]), &host).map(Some);
+        }
+        // In-place edits and overwriting redirects show their diff first
+        if self.remote_target.is_none() {
+            if let Some(preview) = preview::preview(command) {
+                preview.print();
+                return terminal::confirm(tr("confirm.apply")).map(Some);
+            }
+        }
+        Ok(None)
     }
 
     fn is_destructive_command(&self, command: &str) -> bool {
@@ -1036,113 +2126,132 @@ impl Shell {
         false
     }
 
-    async fn show_suggestions(&self, command_prefix: Option<&str>) -> Result<String> {
-        let suggestions = self.llm_client
-            .suggest_commands(&self.context_manager.get_context(), command_prefix)
-            .await?;
-            
+    async fn suggestions(&self, command_prefix: Option<&str>) -> Result<Vec<String>> {
+        let suggestions = match self.prefetcher.take(command_prefix).await {
+            Some(suggestions) => suggestions,
+            None => match self.llm_client
+                .suggest_commands(&self.context_manager.get_context(), command_prefix)
+                .await
+            {
+                Ok(suggestions) => suggestions,
+                // Over budget, suggestions come from history alone
+                Err(e) if budget::check().is_err() => {
+                    eprintln!("{}", e.to_string().yellow());
+                    self.suggestion_engine.get_suggestions(command_prefix.unwrap_or(""))
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        Ok(suggestions)
+    }
+
+    // At a terminal, suggestions are a menu: Enter puts the highlighted one
+    // at the prompt to edit, and pressing a number explains that one and
+    // offers to run it. Otherwise they're listed.
+    async fn show_suggestions(&mut self, suggestions: &[String]) -> Result<()> {
         if suggestions.is_empty() {
-            Ok("No suggestions available.".to_string())
-        } else {
-            Ok(format!("\nSuggested commands:\n{}", 
-                suggestions.iter()
-                    .map(|s| format!("  {}", s.bright_cyan()))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            ))
+            println!("No suggestions available.");
+            return Ok(());
         }
-    }
+        println!("\nSuggested commands:");
+        if !self.terminal.is_interactive() {
+            for (i, suggestion) in suggestions.iter().enumerate() {
+                println!("  {} {}", format!("{}.", i + 1).dimmed(), suggestion.bright_cyan());
+            }
+            return Ok(());
+        }
+
+        let hint = "↑/↓ move · Enter edit at the prompt · number explain · Esc dismiss";
+        let command = match terminal::select(suggestions, hint)? {
+            terminal::Selection::Chosen(i) => {
+                self.terminal.set_prefill(&suggestions[i]);
+                return Ok(());
+            }
+            terminal::Selection::Numbered(i) => &suggestions[i],
+            terminal::Selection::Cancelled => return Ok(()),
+        };
 
-    fn initialize(&mut self) -> Result<()> {
-        // Process login shell initialization if needed
-        if self.is_login_shell() {
-            self.process_profile_files()?;
+        println!("{}", command.bright_cyan());
+        match self.documentation.get_command_help(command).await {
+            Ok(explanation) => println!("{}", explanation.bright_blue()),
+            Err(e) => eprintln!("{} {}", "Could not explain it:".yellow(), e),
         }
-        
+        print!("{}", "Run it? [y]es, [e]dit first, any other key to skip ".dimmed());
+        std::io::Write::flush(&mut std::io::stdout())?;
+        let key = terminal::read_key()?;
+        println!();
+        match key {
+            Some(terminal::Key::Char('y' | 'Y')) => {
+                self.terminal.add_to_history(command)?;
+                let status = self.execute_line(command).await;
+                if let Err(e) = status {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            Some(terminal::Key::Char('e' | 'E')) => self.terminal.set_prefill(command),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn initialize(&mut self) -> Result<()> {
         // Set up environment
         self.setup_environment()?;
         
+        // Options saved with `shopt --save`
+        if let Err(e) = self.options.load_saved() {
+            eprintln!("Warning: {}", e);
+        }
+        self.terminal.set_history_append(self.options.histappend);
+        
+        // History-based suggestions for when the LLM can't be used
+        for entry in self.terminal.get_history().get_entries() {
+            self.suggestion_engine.add_command(entry);
+        }
+        
         // Handle SIGCHLD for job control
         self.job_control.handle_sigchld()?;
         
-        // Print welcome message
-        self.print_welcome_message();
+        // Print welcome message; it's about features POSIX mode turns off
+        if self.terminal.is_interactive() && !self.options.posix {
+            self.print_welcome_message();
+        }
         
         Ok(())
     }
 
     fn print_welcome_message(&self) {
-        println!("{}", "\n╭───────────────────────────────────────────╮".bright_blue());
-        println!("{}", "│           Welcome to LLM Shell            │".bright_green());
-        println!("{}", "│                                           │".bright_blue());
-        println!("{}", "│  • Use natural language for commands      │".bright_blue());
-        println!("{}", "│  • Type '??' after a command for help     │".bright_blue());
-        println!("{}", "│  • Start with '?' to ask a question       │".bright_blue());
-        println!("{}", "│  • Type 'help' for more information       │".bright_blue());
-        println!("{}", "╰───────────────────────────────────────────╯".bright_blue());
-        println!();
-    }
-
-    fn is_login_shell(&self) -> bool {
-        std::env::args()
-            .next()
-            .map(|arg| arg.starts_with('-'))
-            .unwrap_or(false)
-    }
-
-    fn process_profile_files(&self) -> Result<()> {
-        let home = dirs::home_dir().context("Could not determine home directory")?;
-        
-        // Process global profile
-        if let Ok(contents) = std::fs::read_to_string("/etc/profile") {
-            self.process_profile_content(&contents)?;
-        }
-
-        // Process user profile
-        let profile_path = home.join(".profile");
-        if let Ok(contents) = std::fs::read_to_string(profile_path) {
-            self.process_profile_content(&contents)?;
+        // The box grows to fit longer translations
+        let mut lines = vec![
+            format!("  • {}", tr("welcome.natural")),
+            format!("  • {}", tr("welcome.suggest")),
+            format!("  • {}", tr("welcome.ask")),
+            format!("  • {}", tr("welcome.help")),
+        ];
+        if self.options.observemode {
+            lines.push(format!("  • {}", tr("welcome.demo")));
         }
-
-        // Process .bash_profile or .bash_login if they exist
-        let bash_profile = home.join(".bash_profile");
-        let bash_login = home.join(".bash_login");
+        let title = tr("welcome.title");
+        let width = lines.iter().map(|line| line.chars().count() + 2).chain([43]).max().unwrap_or(43);
+        let boxed = |text: &str| format!("│{}{}│", text, " ".repeat(width - text.chars().count()));
+        let indent = (width - title.chars().count()) / 2;
         
-        if bash_profile.exists() {
-            if let Ok(contents) = std::fs::read_to_string(bash_profile) {
-                self.process_profile_content(&contents)?;
-            }
-        } else if bash_login.exists() {
-            if let Ok(contents) = std::fs::read_to_string(bash_login) {
-                self.process_profile_content(&contents)?;
+        println!("{}", format!("\n╭{}╮", "─".repeat(width)).bright_blue());
+        println!("{}", boxed(&format!("{}{}", " ".repeat(indent), title)).bright_green());
+        println!("{}", boxed("").bright_blue());
+        for (i, line) in lines.iter().enumerate() {
+            if self.options.observemode && i == lines.len() - 1 {
+                println!("{}", boxed(line).bright_yellow());
+            } else {
+                println!("{}", boxed(line).bright_blue());
             }
         }
-
-        Ok(())
+        println!("{}", format!("╰{}╯", "─".repeat(width)).bright_blue());
+        println!();
     }
 
-    fn process_profile_content(&self, content: &str) -> Result<()> {
-        for line in content.lines() {
-            let line = line.trim();
-            
-            // Skip comments and empty lines
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            
-            if line.starts_with("export ") {
-                let parts: Vec<&str> = line["export ".len()..].splitn(2, '=').collect();
-                if parts.len() == 2 {
-                    let key = parts[0].trim();
-                    let value = parts[1].trim().trim_matches('"').trim_matches('\'');
-                    
-                    // Handle variable expansion in values
-                    let expanded_value = self.expand_env_vars(value);
-                    std::env::set_var(key, expanded_value);
-                }
-            }
-        }
-        Ok(())
+    fn is_login_shell(&self) -> bool {
+        is_login_shell()
     }
 
     fn setup_environment(&self) -> Result<()> {
@@ -1170,17 +2279,43 @@ impl Shell {
         Ok(())
     }
 
-    fn execute_command(&mut self, command: &str) -> Result<()> {
-        // Parse the command
-        let pipeline = crate::shell::command_parser::CommandParser::parse(command)?;
+    fn execute_command(&mut self, command: &str) -> Result<i32> {
+        self.execute_command_as(command, CommandOrigin::User, None)
+    }
+
+    // Tildes and command substitutions are expanded first; variables are
+    // expanded word by word as the command is parsed
+    fn parse_expanded(&self, command: &str) -> Result<command_parser::Pipeline> {
+        let command = &variables::expand_tildes(command, path_utils::tilde_directory);
+        let command = &self.substitute_commands(command, variables::Insert::Words)?;
+        let lookup = |name: &str| self.lookup_variable(name);
+        let expand = |rest: &str| variables::expand_reference(rest, &lookup, self.options.nounset);
+        CommandParser::parse_expanding(command, &expand)
+    }
+
+    fn execute_command_as(&mut self, command: &str, origin: CommandOrigin, timeout: Option<std::time::Duration>) -> Result<i32> {
+        let pipeline = self.parse_expanded(command)?;
+        
+        let options = ExecOptions {
+            timeout,
+            limits: self.resource_policy.limits_for(command, origin),
+            noclobber: self.options.noclobber,
+        };
+        
+        // Background commands are handed to job control so their completion gets reported
+        if pipeline.background && pipeline.commands.len() == 1 {
+            let child = crate::shell::executor::Executor::spawn_background(&pipeline.commands[0], &options)?;
+            self.job_control.add_background(command.trim().trim_end_matches('&').trim(), child);
+            return Ok(0);
+        }
         
         // Execute the pipeline
-        let exit_code = crate::shell::executor::Executor::execute(&pipeline)?;
+        let exit_code = crate::shell::executor::Executor::execute_with(&pipeline, &options)?;
         
         if exit_code != 0 {
             eprintln!("Command failed with exit code: {}", exit_code);
         }
         
-        Ok(())
+        Ok(exit_code)
     }
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+use anyhow::{Result, Context};
+use std::fs;
+use std::path::PathBuf;
+
+pub const OPTION_NAMES: &[&str] = &["autocd", "backup", "confirmations", "histappend", "ignoreeof", "nl-detection", "observemode"];
+
+// Options set with `set -o NAME` rather than `shopt`
+pub const SET_OPTION_NAMES: &[&str] = &["noclobber", "nounset", "posix"];
+
+// Toggles controlled with `shopt`
+#[derive(Debug, Clone)]
+pub struct ShellOptions {
+    // Typing a directory name changes into it
+    pub autocd: bool,
+    // Copy files before a `>` redirection overwrites them, for `restore`
+    pub backup: bool,
+    // Ask before running commands according to the confirmation policy
+    pub confirmations: bool,
+    // Append this session's history to the file instead of rewriting it
+    pub histappend: bool,
+    // Ctrl+D is ignored $IGNOREEOF times in a row (10 by default) before it exits
+    pub ignoreeof: bool,
+    // Route plain-English input to the model for translation
+    pub nl_detection: bool,
+    // Explain commands without executing them (demo mode)
+    pub observemode: bool,
+    // `>` refuses to overwrite existing files; `>|` still does
+    pub noclobber: bool,
+    // Expanding an unset variable is an error instead of an empty string
+    pub nounset: bool,
+    // Lines are read as a POSIX shell would: no natural-language detection,
+    // model features, autocd or llm-shell's own pipe forms
+    pub posix: bool,
+}
+
+impl ShellOptions {
+    pub fn new(observemode: bool) -> Self {
+        ShellOptions {
+            autocd: false,
+            backup: false,
+            confirmations: true,
+            histappend: false,
+            ignoreeof: false,
+            nl_detection: true,
+            observemode,
+            noclobber: false,
+            nounset: false,
+            posix: false,
+        }
+    }
+
+    fn slot(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "autocd" => Some(&mut self.autocd),
+            "backup" => Some(&mut self.backup),
+            "confirmations" => Some(&mut self.confirmations),
+            "histappend" => Some(&mut self.histappend),
+            "ignoreeof" => Some(&mut self.ignoreeof),
+            "nl-detection" => Some(&mut self.nl_detection),
+            "observemode" => Some(&mut self.observemode),
+            "noclobber" => Some(&mut self.noclobber),
+            "nounset" => Some(&mut self.nounset),
+            "posix" => Some(&mut self.posix),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "autocd" => Some(self.autocd),
+            "backup" => Some(self.backup),
+            "confirmations" => Some(self.confirmations),
+            "histappend" => Some(self.histappend),
+            "ignoreeof" => Some(self.ignoreeof),
+            "nl-detection" => Some(self.nl_detection),
+            "observemode" => Some(self.observemode),
+            "noclobber" => Some(self.noclobber),
+            "nounset" => Some(self.nounset),
+            "posix" => Some(self.posix),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: bool) -> Result<()> {
+        let slot = self
+            .slot(name)
+            .with_context(|| format!("shopt: {}: invalid shell option name", name))?;
+        *slot = value;
+        Ok(())
+    }
+
+    fn rc_file() -> Result<PathBuf> {
+        Ok(dirs::home_dir().context("Could not determine home directory")?.join(".llm_shellrc"))
+    }
+
+    // Applies `shopt -s NAME` / `shopt -u NAME` lines saved in ~/.llm_shellrc
+    pub fn load_saved(&mut self) -> Result<()> {
+        let content = match fs::read_to_string(Self::rc_file()?) {
+            Ok(content) => content,
+            Err(_) => return Ok(()),
+        };
+        for (name, value) in Self::parse_saved(&content) {
+            self.set(&name, value)?;
+        }
+        Ok(())
+    }
+
+    fn parse_saved(content: &str) -> Vec<(String, bool)> {
+        content
+            .lines()
+            .filter_map(|line| {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                match words.as_slice() {
+                    ["shopt", "-s", names @ ..] => Some(names.iter().map(|n| (n.to_string(), true)).collect::<Vec<_>>()),
+                    ["shopt", "-u", names @ ..] => Some(names.iter().map(|n| (n.to_string(), false)).collect()),
+                    _ => None,
+                }
+            })
+            .flatten()
+            .collect()
+    }
+
+    // Records the option in ~/.llm_shellrc, replacing an earlier line for it
+    pub fn save(name: &str, value: bool) -> Result<()> {
+        let path = Self::rc_file()?;
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        let mut lines: Vec<String> = content
+            .lines()
+            .filter(|line| {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                !matches!(words.as_slice(), ["shopt", "-s" | "-u", saved] if *saved == name)
+            })
+            .map(|line| line.to_string())
+            .collect();
+        lines.push(format!("shopt {} {}", if value { "-s" } else { "-u" }, name));
+        fs::write(&path, lines.join("\n") + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let mut options = ShellOptions::new(false);
+        options.set("autocd", true).unwrap();
+        assert_eq!(options.get("autocd"), Some(true));
+        assert!(options.set("extglob", true).is_err());
+        options.set("posix", true).unwrap();
+        assert_eq!(options.get("posix"), Some(true));
+
+        let saved = ShellOptions::parse_saved("export A=1\nshopt -s autocd histappend\nshopt -u nl-detection\n");
+        assert_eq!(
+            saved,
+            vec![("autocd".to_string(), true), ("histappend".to_string(), true), ("nl-detection".to_string(), false)]
+        );
+    }
+}
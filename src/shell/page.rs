@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use tokio::process::Command;
+
+// What the model is asked to do with the text for each pager action
+pub const SUMMARIZE: &str = "Summarize this part of its output in a few short bullets: what it shows and anything that needs attention.";
+pub const EXPLAIN: &str = "Explain the line marked with >>> in this part of its output: what it means, the likely cause and how to fix it. Be brief.";
+pub const TABLE: &str = "Extract the table or list of records in this part of its output as CSV with a header row, one record per row. Respond ONLY with the CSV.";
+
+// Runs a command with `sh -c` and returns what it printed on stdout and
+// stderr, interleaved as it was written, and its exit status
+pub async fn capture(command: &str) -> Result<(String, i32)> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("exec 2>&1\n{}", command))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("failed to start sh")?;
+    Ok((String::from_utf8_lossy(&output.stdout).to_string(), output.status.code().unwrap_or(1)))
+}
+
+// The command of `COMMAND | NAME`, like `COMMAND | page`, which is run as
+// `page COMMAND`
+pub fn piped_into<'a>(input: &'a str, name: &str) -> Option<&'a str> {
+    let mut quote = None;
+    let mut last_pipe = None;
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if q == c => quote = None,
+            (Some('"') | None, '\\') => {
+                chars.next();
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '|') if chars.peek().map(|(_, next)| *next) == Some('|') => {
+                chars.next();
+            }
+            (None, '|') => last_pipe = Some(i),
+            _ => {}
+        }
+    }
+    let pipe = last_pipe?;
+    let command = input[..pipe].trim();
+    (input[pipe + 1..].trim() == name && !command.is_empty()).then_some(command)
+}
+
+// The CSV in the model's answer, checked to have a header and rows with
+// the same number of fields, and how many rows it has below the header
+pub fn csv_table(answer: &str) -> Result<(String, usize)> {
+    let lines: Vec<&str> = answer
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```") && !line.trim().is_empty())
+        .collect();
+    let [header, rows @ ..] = lines.as_slice() else {
+        return Err(anyhow::anyhow!("the answer has no CSV"));
+    };
+    if rows.is_empty() {
+        return Err(anyhow::anyhow!("the CSV has a header but no rows"));
+    }
+    let columns = fields(header)?.len();
+    if columns < 2 {
+        return Err(anyhow::anyhow!("the CSV has only one column"));
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let count = fields(row)?.len();
+        if count != columns {
+            return Err(anyhow::anyhow!("row {} has {} fields but the header has {}", i + 1, count, columns));
+        }
+    }
+    Ok((lines.join("\n") + "\n", rows.len()))
+}
+
+// The fields of a CSV line, where quoted fields can hold commas and `""`
+// is a quote
+pub fn fields(line: &str) -> Result<Vec<String>> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow::anyhow!("unbalanced quotes in `{}`", line));
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_piped_into() {
+        assert_eq!(piped_into("journalctl -b | page", "page"), Some("journalctl -b"));
+        assert_eq!(piped_into("dmesg | grep usb |page ", "page"), Some("dmesg | grep usb"));
+        assert_eq!(piped_into("echo 'a | page'", "page"), None);
+        assert_eq!(piped_into("make || page", "page"), None);
+        assert_eq!(piped_into("ls | pager", "page"), None);
+        assert_eq!(piped_into("| page", "page"), None);
+        assert_eq!(piped_into("git diff | copy", "copy"), Some("git diff"));
+    }
+
+    #[test]
+    fn test_csv_table() {
+        let (csv, rows) = csv_table("```csv\nname,size\n\"a, b\",1\nc,\"2\"\"\"\n```").unwrap();
+        assert_eq!((csv.as_str(), rows), ("name,size\n\"a, b\",1\nc,\"2\"\"\"\n", 2));
+        assert!(csv_table("name,size\na,1,extra\n").is_err());
+        assert!(csv_table("name,size\n").is_err());
+        assert!(csv_table("- one\n- two\n").is_err());
+        assert!(csv_table("name,\"size\na,1\n").is_err());
+        assert_eq!(fields("\"a, b\",\"2\"\"\",").unwrap(), vec!["a, b", "2\"", ""]);
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use crate::shell::command_parser::{CommandParser, Redirection, SimpleCommand};
+use crate::shell::policy;
+use crate::shell::wizard::quotes_balanced;
+
+pub const TEMPLATE: &str = "You build shell pipelines one stage at a time. You are given a goal, the pipeline so far and the first lines of its output. Respond with ONLY the next stage: a single command, without a leading `|`, that reads the previous stage's output from standard input (the first stage produces the data instead). Use jq for JSON and POSIX awk, sed, sort, grep, cut, uniq, head and wc for text. Never write, delete or run anything: no redirections, no `sed -i`, no awk system() or print to files. If the pipeline already meets the goal, respond with DONE.";
+
+// Lines of each stage's output shown and sent back to the model
+pub const SAMPLE_LINES: usize = 10;
+const SAMPLE_WIDTH: usize = 200;
+// A stage still running after this (`tail -f`, a huge input) is stopped
+const RUN_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    // awk that runs commands or writes files: system(), `print > "f"`, `"cmd" | getline`
+    static ref AWK_WRITES_RE: Regex = Regex::new(r#"system\s*\(|>\s*"|\|\s*"|"\s*\|"#).unwrap();
+    // sed's w (write file) and e (execute) commands and s///w flag
+    static ref SED_WRITES_RE: Regex = Regex::new(r"(^|[;{}/\s])[wWe](\s|$|;)").unwrap();
+}
+
+// The request for the next stage
+pub fn request(goal: &str, stages: &[String], sample: &str, steer: Option<&str>) -> String {
+    let mut lines = vec![format!("Goal: {}", goal)];
+    if stages.is_empty() {
+        lines.push("There is no pipeline yet; write its first stage.".to_string());
+    } else {
+        lines.push(format!("Pipeline so far: {}", stages.join(" | ")));
+        lines.push(format!("Its output starts:\n{}", sample));
+    }
+    if let Some(steer) = steer {
+        lines.push(format!("The user's instruction for this stage: {}", steer));
+    }
+    lines.join("\n")
+}
+
+// Checks a proposed stage is one command that only reads: a read-only
+// program, or a text filter that doesn't write files or run commands
+pub fn validate(stage: &str) -> Result<()> {
+    if !quotes_balanced(stage) {
+        return Err(anyhow::anyhow!("unbalanced quotes"));
+    }
+    // Quoted text is a filter's program, where `>` and `|` are operators
+    let unquoted = unquoted(stage);
+    if unquoted.contains(['>', '<', ';', '&', '`']) || unquoted.contains("$(") {
+        return Err(anyhow::anyhow!("a stage can't redirect, substitute or run more than one command"));
+    }
+    let pipeline = CommandParser::parse(stage)?;
+    let [command] = pipeline.commands.as_slice() else {
+        return Err(anyhow::anyhow!("write one stage at a time, without `|`"));
+    };
+    if policy::is_read_only(stage) || is_filter(command) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("{} can change things; use a read-only command", command.program))
+    }
+}
+
+// The stage with single-quoted text left out; double quotes still expand
+// `$(...)` and backticks, so their text is kept
+fn unquoted(stage: &str) -> String {
+    let mut in_quote = false;
+    stage
+        .chars()
+        .filter(|&c| {
+            if c == '\'' {
+                in_quote = !in_quote;
+                return false;
+            }
+            !in_quote
+        })
+        .collect()
+}
+
+fn is_filter(command: &SimpleCommand) -> bool {
+    if command.redirections.iter().any(|r| *r != Redirection::Pipe) {
+        return false;
+    }
+    let args = &command.args;
+    match command.program.as_str() {
+        "jq" | "column" | "paste" | "fold" | "rev" | "tac" | "expand" => true,
+        // A second operand is a file uniq writes to
+        "uniq" => args.iter().filter(|arg| !arg.starts_with('-')).count() <= 1,
+        "sort" => !args.iter().any(|arg| arg.starts_with("-o") || arg.starts_with("--output")),
+        "sed" => !args.iter().any(|arg| arg.starts_with("-i") || arg.starts_with("--in-place") || SED_WRITES_RE.is_match(arg)),
+        "awk" | "gawk" | "mawk" => !args.iter().any(|arg| AWK_WRITES_RE.is_match(arg)),
+        _ => false,
+    }
+}
+
+// Runs the pipeline and returns its output, or why it failed
+pub async fn run(stages: &[String]) -> Result<String> {
+    let pipeline = stages.join(" | ");
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(&pipeline)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("failed to start sh")?;
+    let output = tokio::time::timeout(RUN_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow::anyhow!("still running after {}s", RUN_TIMEOUT.as_secs()))??;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        let error = error.lines().next().unwrap_or("").trim();
+        return Err(anyhow::anyhow!("exit status {}{}", output.status.code().unwrap_or(-1), if error.is_empty() { String::new() } else { format!(": {}", error) }));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// The first lines of the output, long ones cut, with a count of the rest
+pub fn sample(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut sample: Vec<String> = lines
+        .iter()
+        .take(SAMPLE_LINES)
+        .map(|line| match line.char_indices().nth(SAMPLE_WIDTH) {
+            Some((end, _)) => format!("{}…", &line[..end]),
+            None => line.to_string(),
+        })
+        .collect();
+    if lines.is_empty() {
+        sample.push("(no output)".to_string());
+    } else if lines.len() > SAMPLE_LINES {
+        sample.push(format!("… {} more lines", lines.len() - SAMPLE_LINES));
+    }
+    sample.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("cat access.log").is_ok());
+        assert!(validate("jq -r '.items[] | .name'").is_ok());
+        assert!(validate("awk '$3 > 100 { print $1 }'").is_ok());
+        assert!(validate("sed -n 's/^user: //p'").is_ok());
+        assert!(validate("sort | uniq -c").is_err());
+        assert!(validate("sed -i 's/a/b/' file").is_err());
+        assert!(validate("sed 's/a/b/w out'").is_err());
+        assert!(validate("awk '{ print > \"out\" }'").is_err());
+        assert!(validate("awk '{ system(\"rm \" $1) }'").is_err());
+        assert!(validate("uniq -c counts.txt").is_ok());
+        assert!(validate("uniq -c in.txt out.txt").is_err());
+        assert!(validate("xargs rm").is_err());
+        assert!(validate("grep x > y").is_err());
+    }
+
+    #[test]
+    fn test_sample() {
+        let output: String = (1..=15).map(|n| format!("{}\n", n)).collect();
+        assert_eq!(sample(&output), "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n… 5 more lines");
+        assert_eq!(sample(""), "(no output)");
+    }
+}
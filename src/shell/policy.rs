@@ -0,0 +1,514 @@
+use anyhow::{Result, Context};
+use regex::Regex;
+use std::fs;
+use log::debug;
+use crate::config;
+use crate::shell::command_parser::{CommandParser, Redirection};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandOrigin {
+    User,
+    Llm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfirmationLevel {
+    Never,
+    LlmGenerated,
+    Destructive,
+    Always,
+}
+
+impl ConfirmationLevel {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "never" => Ok(ConfirmationLevel::Never),
+            "llm" | "llm-generated" => Ok(ConfirmationLevel::LlmGenerated),
+            "destructive" => Ok(ConfirmationLevel::Destructive),
+            "always" => Ok(ConfirmationLevel::Always),
+            _ => Err(anyhow::anyhow!("invalid confirmation level '{}' (use never, llm, destructive or always)", value)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfirmationLevel::Never => "never",
+            ConfirmationLevel::LlmGenerated => "llm",
+            ConfirmationLevel::Destructive => "destructive",
+            ConfirmationLevel::Always => "always",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfirmationPolicy {
+    pub level: ConfirmationLevel,
+    allowlist: Vec<Regex>,
+}
+
+impl ConfirmationPolicy {
+    pub fn new() -> Self {
+        ConfirmationPolicy {
+            level: ConfirmationLevel::Destructive,
+            allowlist: Vec::new(),
+        }
+    }
+
+    pub fn initialize(&mut self) -> Result<()> {
+        if let Some(level) = config::env_opt("LLM_SHELL_CONFIRM") {
+            self.level = ConfirmationLevel::parse(level.trim())?;
+        }
+
+        // Allowlist patterns are separated by newlines or ";;" since regexes may contain ';'
+        if let Some(patterns) = config::env_opt("LLM_SHELL_CONFIRM_ALLOW") {
+            for pattern in patterns.split(['\n']).flat_map(|p| p.split(";;")) {
+                if !pattern.trim().is_empty() {
+                    self.allow(pattern.trim())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn allow(&mut self, pattern: &str) -> Result<()> {
+        if self.allowlist.iter().any(|re| re.as_str() == pattern) {
+            return Ok(());
+        }
+        let regex = Regex::new(pattern).with_context(|| format!("invalid allowlist pattern: {}", pattern))?;
+        self.allowlist.push(regex);
+        Ok(())
+    }
+
+    pub fn disallow(&mut self, pattern: &str) -> bool {
+        let before = self.allowlist.len();
+        self.allowlist.retain(|re| re.as_str() != pattern);
+        self.allowlist.len() != before
+    }
+
+    pub fn allowlist(&self) -> Vec<&str> {
+        self.allowlist.iter().map(|re| re.as_str()).collect()
+    }
+
+    pub fn requires_confirmation(&self, command: &str, origin: CommandOrigin, destructive: bool) -> bool {
+        if self.allowlist.iter().any(|re| re.is_match(command)) {
+            return false;
+        }
+
+        match self.level {
+            ConfirmationLevel::Never => false,
+            ConfirmationLevel::LlmGenerated => origin == CommandOrigin::Llm,
+            ConfirmationLevel::Destructive => destructive,
+            ConfirmationLevel::Always => true,
+        }
+    }
+}
+
+const ESCALATION_TOOLS: &[&str] = &["sudo", "doas", "pkexec", "run0", "su"];
+
+// Words after which the next word is still a command: keywords and `!`
+const COMMAND_PREFIXES: &[&str] = &["if", "then", "else", "elif", "do", "while", "until", "!", "{"];
+
+// Programs that run the command they are given, after their own options
+const WRAPPERS: &[&str] = &[
+    "xargs", "env", "nohup", "time", "nice", "exec", "command", "builtin", "stdbuf", "timeout", "setsid", "ionice",
+    "chrt", "taskset",
+];
+
+// The escalation tool a command uses, if any. LLM-generated commands that use
+// one need typed confirmation whatever the confirmation level.
+pub fn privilege_escalation(command: &str) -> Option<&'static str> {
+    command_words(command).into_iter().find_map(|word| {
+        let program = word.rsplit('/').next().unwrap_or(&word);
+        ESCALATION_TOOLS.iter().find(|tool| **tool == program).copied()
+    })
+}
+
+// The words of a command that are run as programs: the first word of each
+// simple command, after separators, pipes, `(`, `$(` and backquotes, after
+// keywords like `do` and `then`, and after wrappers like `xargs` or
+// `env NAME=value`, whose options are skipped. Quoted text isn't split.
+fn command_words(command: &str) -> Vec<String> {
+    #[derive(PartialEq)]
+    enum Position {
+        Command,
+        // After a wrapper, until the program it runs
+        Wrapped,
+        Argument,
+    }
+
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut position = Position::Command;
+    let mut quote: Option<char> = None;
+    // The quote each open `(` or `$(` was inside, restored at its `)`
+    let mut parens: Vec<Option<char>> = Vec::new();
+    let mut backquote: Option<Option<char>> = None;
+
+    let end_word = |word: &mut String, position: &mut Position, words: &mut Vec<String>| {
+        if word.is_empty() {
+            return;
+        }
+        let text = std::mem::take(word);
+        let assignment = text.split_once('=').is_some_and(|(name, _)| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        *position = match position {
+            Position::Command if assignment || COMMAND_PREFIXES.contains(&text.as_str()) => Position::Command,
+            // Options, `NAME=value`, `{}` and numbers like `timeout 10` or `nice -n 5`
+            Position::Wrapped if text.starts_with(['-', '{']) || assignment || text.starts_with(|c: char| c.is_ascii_digit()) => {
+                Position::Wrapped
+            }
+            Position::Command | Position::Wrapped => {
+                let program = text.rsplit('/').next().unwrap_or(&text);
+                let wrapped = WRAPPERS.contains(&program);
+                words.push(text);
+                if wrapped { Position::Wrapped } else { Position::Argument }
+            }
+            Position::Argument => Position::Argument,
+        };
+    };
+
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), c) => word.push(c),
+            (_, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+            }
+            (Some('"'), '"') => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None | Some('"'), '$') if chars.peek() == Some(&'(') => {
+                chars.next();
+                parens.push(quote);
+                quote = None;
+                end_word(&mut word, &mut position, &mut words);
+                position = Position::Command;
+            }
+            (None | Some('"'), '`') if backquote.is_none() => {
+                backquote = Some(quote);
+                quote = None;
+                end_word(&mut word, &mut position, &mut words);
+                position = Position::Command;
+            }
+            (None, '`') => {
+                end_word(&mut word, &mut position, &mut words);
+                quote = backquote.take().flatten();
+                position = Position::Argument;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '(') => {
+                parens.push(None);
+                end_word(&mut word, &mut position, &mut words);
+                position = Position::Command;
+            }
+            (None, ')') => {
+                end_word(&mut word, &mut position, &mut words);
+                quote = parens.pop().flatten();
+                // `case` patterns end with `)` and the command follows
+                position = Position::Command;
+            }
+            (None, ';' | '&' | '|' | '\n') => {
+                end_word(&mut word, &mut position, &mut words);
+                position = Position::Command;
+            }
+            (None, c) if c.is_whitespace() => end_word(&mut word, &mut position, &mut words),
+            (None, c) => word.push(c),
+        }
+    }
+    end_word(&mut word, &mut position, &mut words);
+    words
+}
+
+// Programs that only read, whatever their arguments
+const READ_ONLY: &[&str] = &[
+    "ls", "pwd", "df", "du", "free", "ps", "uptime", "whoami", "id", "groups", "uname", "cal",
+    "cat", "head", "tail", "wc", "grep", "egrep", "fgrep", "file", "stat", "which", "type",
+    "whereis", "lsblk", "lscpu", "lsusb", "lspci", "nproc", "printenv", "echo", "basename",
+    "dirname", "realpath", "readlink", "cut", "tr", "nl", "diff", "cmp", "md5sum", "sha1sum",
+    "sha256sum", "jobs", "last", "w", "who", "locale",
+];
+
+// git subcommands that only read. Some write with an option (`git log
+// --output=FILE`), listed as rejected prefixes; others change things with an
+// operand (`git branch x`), so only the options that keep them listing pass.
+enum GitArgs {
+    Except(&'static [&'static str]),
+    Only(&'static [&'static str]),
+}
+
+const GIT_READ_ONLY: &[(&str, GitArgs)] = &[
+    ("status", GitArgs::Except(&[])),
+    ("log", GitArgs::Except(&["--output", "--ext-diff"])),
+    ("diff", GitArgs::Except(&["--output", "--ext-diff"])),
+    ("show", GitArgs::Except(&["--output", "--ext-diff"])),
+    ("blame", GitArgs::Except(&[])), ("shortlog", GitArgs::Except(&[])), ("describe", GitArgs::Except(&[])),
+    ("ls-files", GitArgs::Except(&[])), ("rev-parse", GitArgs::Except(&[])),
+    ("branch", GitArgs::Only(&["-a", "-r", "-v", "-vv", "--all", "--remotes", "--list", "--show-current"])),
+    ("remote", GitArgs::Only(&["-v", "--verbose"])),
+    ("tag", GitArgs::Only(&["-l", "--list"])),
+    ("stash", GitArgs::Only(&["list"])),
+];
+
+// Whether a command clearly only reads: a pipeline of known read-only
+// programs, with no redirections, substitutions, lists or background jobs.
+// Anything unknown counts as not read-only.
+pub fn is_read_only(command: &str) -> bool {
+    if command.contains(['>', '<', ';', '&', '`', '\n']) || command.contains("$(") {
+        return false;
+    }
+    let Ok(pipeline) = CommandParser::parse(command) else {
+        return false;
+    };
+    !pipeline.commands.is_empty()
+        && pipeline.commands.iter().all(|stage| {
+            stage.assignments.is_empty()
+                && stage.redirections.iter().all(|r| *r == Redirection::Pipe)
+                && stage_is_read_only(&stage.program, &stage.args)
+        })
+}
+
+fn stage_is_read_only(program: &str, args: &[String]) -> bool {
+    match program {
+        // find can delete, run and write files
+        "find" => !args.iter().any(|arg| {
+            matches!(arg.as_str(), "-delete" | "-exec" | "-execdir" | "-ok" | "-okdir") || arg.starts_with("-fprint") || arg == "-fls"
+        }),
+        // sort -o writes its output to a file
+        "sort" => !args.iter().any(|arg| arg.starts_with("-o") || arg.starts_with("--output")),
+        // Without arguments these print; with them they set
+        "hostname" => args.is_empty(),
+        "git" => {
+            let Some((subcommand, rest)) = args.split_first() else {
+                return false;
+            };
+            match GIT_READ_ONLY.iter().find(|(name, _)| name == subcommand) {
+                Some((_, GitArgs::Except(rejected))) => !rest.iter().any(|arg| rejected.iter().any(|option| arg.starts_with(option))),
+                Some((_, GitArgs::Only(listing))) => rest.iter().all(|arg| listing.contains(&arg.as_str())),
+                None => false,
+            }
+        }
+        program => READ_ONLY.contains(&program),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceLimits {
+    pub cpu_seconds: Option<u64>,
+    pub file_size: Option<u64>,
+    pub nice: Option<i32>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == ResourceLimits::default()
+    }
+
+    // Later rules only fill in limits that are still unset
+    fn merge(&mut self, other: &ResourceLimits) {
+        self.cpu_seconds = self.cpu_seconds.or(other.cpu_seconds);
+        self.file_size = self.file_size.or(other.file_size);
+        self.nice = self.nice.or(other.nice);
+    }
+
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut limits = ResourceLimits::default();
+        for item in spec.split([',', ' ']).filter(|s| !s.is_empty()) {
+            let (key, value) = item
+                .split_once('=')
+                .with_context(|| format!("invalid limit '{}', expected key=value", item))?;
+            match key {
+                "cpu" => limits.cpu_seconds = Some(value.parse().with_context(|| format!("invalid cpu limit: {}", value))?),
+                "fsize" => limits.file_size = Some(parse_size(value)?),
+                "nice" => limits.nice = Some(value.parse().with_context(|| format!("invalid nice value: {}", value))?),
+                _ => return Err(anyhow::anyhow!("unknown limit '{}' (use cpu, fsize or nice)", key)),
+            }
+        }
+        Ok(limits)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum RuleMatch {
+    Origin(CommandOrigin),
+    Pattern(Regex),
+}
+
+#[derive(Debug, Clone)]
+struct ResourceRule {
+    matcher: RuleMatch,
+    limits: ResourceLimits,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResourcePolicy {
+    rules: Vec<ResourceRule>,
+}
+
+impl ResourcePolicy {
+    pub fn new() -> Self {
+        ResourcePolicy { rules: Vec::new() }
+    }
+
+    pub fn initialize(&mut self) -> Result<()> {
+        // LLM-generated commands can be limited straight from the environment
+        if let Some(spec) = config::env_opt("LLM_SHELL_LLM_LIMITS") {
+            self.add_rule("llm", &spec)?;
+        }
+
+        if let Some(home) = dirs::home_dir() {
+            let limits_file = home.join(".llm_shell_limits");
+            if let Ok(content) = fs::read_to_string(limits_file) {
+                self.parse_rules(&content)?;
+            }
+        }
+
+        debug!("Loaded {} resource limit rules", self.rules.len());
+        Ok(())
+    }
+
+    // Each line is "<llm|user|regex> <key=value>..." e.g. `^find\s cpu=30 nice=10`
+    fn parse_rules(&mut self, content: &str) -> Result<()> {
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once(char::is_whitespace) {
+                Some((selector, spec)) => self.add_rule(selector, spec.trim())?,
+                None => return Err(anyhow::anyhow!("invalid limit rule: {}", line)),
+            }
+        }
+        Ok(())
+    }
+
+    fn add_rule(&mut self, selector: &str, spec: &str) -> Result<()> {
+        let matcher = match selector {
+            "llm" => RuleMatch::Origin(CommandOrigin::Llm),
+            "user" => RuleMatch::Origin(CommandOrigin::User),
+            pattern => RuleMatch::Pattern(
+                Regex::new(pattern).with_context(|| format!("invalid pattern in limit rule: {}", pattern))?,
+            ),
+        };
+
+        self.rules.push(ResourceRule {
+            matcher,
+            limits: ResourceLimits::parse(spec)?,
+        });
+        Ok(())
+    }
+
+    pub fn limits_for(&self, command: &str, origin: CommandOrigin) -> ResourceLimits {
+        let mut limits = ResourceLimits::default();
+        for rule in &self.rules {
+            let matches = match &rule.matcher {
+                RuleMatch::Origin(o) => *o == origin,
+                RuleMatch::Pattern(re) => re.is_match(command),
+            };
+            if matches {
+                limits.merge(&rule.limits);
+            }
+        }
+        limits
+    }
+}
+
+fn parse_size(value: &str) -> Result<u64> {
+    let (number, multiplier) = match value.chars().last() {
+        Some('K') | Some('k') => (&value[..value.len() - 1], 1024),
+        Some('M') | Some('m') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G') | Some('g') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    let number: u64 = number.parse().with_context(|| format!("invalid size: {}", value))?;
+    Ok(number * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        let limits = ResourceLimits::parse("cpu=60,fsize=1G nice=10").unwrap();
+        assert_eq!(limits.cpu_seconds, Some(60));
+        assert_eq!(limits.file_size, Some(1024 * 1024 * 1024));
+        assert_eq!(limits.nice, Some(10));
+        assert!(ResourceLimits::parse("mem=1G").is_err());
+    }
+
+    #[test]
+    fn test_privilege_escalation() {
+        assert_eq!(privilege_escalation("sudo apt-get install jq"), Some("sudo"));
+        assert_eq!(privilege_escalation("cat x | doas tee /etc/y"), Some("doas"));
+        assert_eq!(privilege_escalation("echo $(pkexec id)"), Some("pkexec"));
+        assert_eq!(privilege_escalation("ls && su -c reboot"), Some("su"));
+        assert_eq!(privilege_escalation("grep sudo /var/log/auth.log"), None);
+        assert_eq!(privilege_escalation("visudo-check"), None);
+        assert_eq!(privilege_escalation("for f in *; do sudo rm $f; done"), Some("sudo"));
+        assert_eq!(privilege_escalation("if x; then sudo reboot; fi"), Some("sudo"));
+        assert_eq!(privilege_escalation("{ sudo id; }"), Some("sudo"));
+        assert_eq!(privilege_escalation("find . | xargs sudo rm"), Some("sudo"));
+        assert_eq!(privilege_escalation("env LANG=C sudo id"), Some("sudo"));
+        assert_eq!(privilege_escalation("nice -n 5 /usr/bin/doas id"), Some("doas"));
+        assert_eq!(privilege_escalation("echo \"$(sudo id)\""), Some("sudo"));
+        assert_eq!(privilege_escalation("echo 'x; sudo y' | xargs grep sudo"), None);
+    }
+
+    #[test]
+    fn test_read_only() {
+        assert!(is_read_only("ls -la"));
+        assert!(is_read_only("ps aux | grep nginx | head -5"));
+        assert!(is_read_only("git status"));
+        assert!(is_read_only("git branch -a"));
+        assert!(is_read_only("find . -name '*.rs' -mtime -7"));
+        assert!(!is_read_only("git branch feature"));
+        assert!(!is_read_only("git push"));
+        assert!(is_read_only("git log --oneline"));
+        assert!(!is_read_only("git log --output=log.txt"));
+        assert!(!is_read_only("git diff --output=/tmp/x HEAD"));
+        assert!(!is_read_only("find . -name '*.tmp' -delete"));
+        assert!(!is_read_only("sort -o out.txt in.txt"));
+        assert!(!is_read_only("ls > files.txt"));
+        assert!(!is_read_only("ls; rm -rf x"));
+        assert!(!is_read_only("echo $(rm x)"));
+        assert!(!is_read_only("cat x | sh"));
+        assert!(!is_read_only("hostname newname"));
+    }
+
+    #[test]
+    fn test_confirmation_levels_and_allowlist() {
+        let mut policy = ConfirmationPolicy::new();
+        assert!(policy.requires_confirmation("rm -rf build", CommandOrigin::User, true));
+        assert!(!policy.requires_confirmation("ls", CommandOrigin::Llm, false));
+
+        policy.level = ConfirmationLevel::LlmGenerated;
+        assert!(policy.requires_confirmation("ls", CommandOrigin::Llm, false));
+        assert!(!policy.requires_confirmation("rm x", CommandOrigin::User, true));
+
+        policy.level = ConfirmationLevel::Always;
+        policy.allow("^git push").unwrap();
+        assert!(!policy.requires_confirmation("git push origin main", CommandOrigin::Llm, false));
+        assert!(policy.requires_confirmation("git status", CommandOrigin::User, false));
+
+        assert!(policy.disallow("^git push"));
+        assert!(policy.requires_confirmation("git push", CommandOrigin::User, false));
+    }
+
+    #[test]
+    fn test_rules_by_origin_and_pattern() {
+        let mut policy = ResourcePolicy::new();
+        policy.parse_rules("# comment\n^find\\s cpu=30\nllm cpu=60 nice=5\n").unwrap();
+
+        let limits = policy.limits_for("find / -name x", CommandOrigin::Llm);
+        assert_eq!(limits.cpu_seconds, Some(30));
+        assert_eq!(limits.nice, Some(5));
+
+        assert!(policy.limits_for("ls", CommandOrigin::User).is_empty());
+        assert_eq!(policy.limits_for("ls", CommandOrigin::Llm).cpu_seconds, Some(60));
+    }
+}
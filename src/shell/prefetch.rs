@@ -0,0 +1,187 @@
+use crate::llm::LLMClient;
+use crate::utils::path_utils;
+use log::debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+// Decides when the line being typed is worth a suggestion request
+pub trait Throttle: Send + Sync {
+    // How long typing has to pause before a request is sent
+    fn debounce(&self) -> Duration;
+    // Whether the line, as it stands once typing pauses, should be fetched for
+    fn wants(&self, line: &str) -> bool;
+}
+
+// Waits for a pause in typing and skips lines that don't start with a
+// program or builtin, such as plain-English requests
+pub struct CadenceThrottle {
+    pub debounce: Duration,
+    pub builtins: Vec<String>,
+}
+
+impl Throttle for CadenceThrottle {
+    fn debounce(&self) -> Duration {
+        self.debounce
+    }
+
+    fn wants(&self, line: &str) -> bool {
+        let command = match line.split_whitespace().next() {
+            Some(command) => command,
+            None => return false,
+        };
+        command.contains('/')
+            || self.builtins.iter().any(|name| name == command)
+            || path_utils::find_executable(command).map(|path| path.is_absolute()).unwrap_or(false)
+    }
+}
+
+#[derive(Default)]
+struct PrefetchState {
+    enabled: bool,
+    context: String,
+    prefix: Option<String>,
+    task: Option<JoinHandle<()>>,
+    suggestions: Option<(String, Vec<String>)>,
+    // The line as of the last keystroke, and when that was
+    line: String,
+    last_input: Option<Instant>,
+}
+
+// Fetches LLM suggestions for the command being typed so `??` doesn't block on the model
+#[derive(Clone)]
+pub struct SuggestionPrefetcher {
+    llm_client: LLMClient,
+    runtime: Handle,
+    state: Arc<Mutex<PrefetchState>>,
+    throttle: Arc<dyn Throttle>,
+}
+
+impl SuggestionPrefetcher {
+    pub fn new(llm_client: LLMClient, throttle: Arc<dyn Throttle>) -> Self {
+        SuggestionPrefetcher {
+            llm_client,
+            runtime: Handle::current(),
+            state: Arc::new(Mutex::new(PrefetchState::default())),
+            throttle,
+        }
+    }
+
+    // Called before each prompt: results from the previous command's context are stale
+    pub fn reset(&self, context: &str, enabled: bool) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(task) = state.task.take() {
+            task.abort();
+        }
+        state.enabled = enabled;
+        state.context = context.to_string();
+        state.prefix = None;
+        state.suggestions = None;
+    }
+
+    // Keeps a request in flight for the line as typed so far and returns an
+    // inline hint when a cached suggestion continues it
+    pub fn on_input(&self, line: &str) -> Option<String> {
+        let prefix = line.trim_end_matches('?').trim().to_string();
+        if prefix.is_empty() || line.trim_end().ends_with("??") {
+            return None;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if !state.enabled {
+            return None;
+        }
+        state.line = line.to_string();
+        state.last_input = Some(Instant::now());
+
+        if state.prefix.as_deref() != Some(prefix.as_str()) {
+            if let Some(task) = state.task.take() {
+                task.abort();
+            }
+            state.prefix = Some(prefix.clone());
+            state.task = Some(self.spawn_fetch(prefix.clone(), state.context.clone()));
+        }
+
+        // Suggestions for an earlier, shorter line still apply while they match
+        match &state.suggestions {
+            Some((_, suggestions)) => suggestions
+                .iter()
+                .find(|s| s.len() > line.len() && s.starts_with(line))
+                .map(|s| s[line.len()..].to_string()),
+            _ => None,
+        }
+    }
+
+    fn spawn_fetch(&self, prefix: String, context: String) -> JoinHandle<()> {
+        let llm_client = self.llm_client.clone();
+        let state = Arc::clone(&self.state);
+        let throttle = Arc::clone(&self.throttle);
+        self.runtime.spawn(async move {
+            // Keep waiting while keystrokes arrive, then check the line as it stands
+            let debounce = throttle.debounce();
+            let mut wait = debounce;
+            loop {
+                tokio::time::sleep(wait).await;
+                let state = state.lock().unwrap();
+                let idle = state.last_input.map(|at| at.elapsed()).unwrap_or(debounce);
+                if idle < debounce {
+                    wait = debounce - idle;
+                    continue;
+                }
+                if !throttle.wants(&state.line) {
+                    return;
+                }
+                break;
+            }
+            match llm_client.suggest_commands(&context, Some(&prefix)).await {
+                Ok(suggestions) => {
+                    let mut state = state.lock().unwrap();
+                    if state.prefix.as_deref() == Some(prefix.as_str()) {
+                        state.suggestions = Some((prefix, suggestions));
+                    }
+                }
+                Err(e) => debug!("Suggestion prefetch for '{}' failed: {}", prefix, e),
+            }
+        })
+    }
+
+    // Returns prefetched suggestions for the prefix, waiting for an in-flight request if needed
+    pub async fn take(&self, prefix: Option<&str>) -> Option<Vec<String>> {
+        let prefix = prefix?;
+        let task = {
+            let mut state = self.state.lock().unwrap();
+            if state.prefix.as_deref() != Some(prefix) {
+                return None;
+            }
+            if let Some((cached, suggestions)) = &state.suggestions {
+                if cached == prefix {
+                    return Some(suggestions.clone());
+                }
+            }
+            state.task.take()?
+        };
+
+        let _ = task.await;
+        let state = self.state.lock().unwrap();
+        match &state.suggestions {
+            Some((cached, suggestions)) if cached == prefix => Some(suggestions.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cadence_throttle() {
+        let throttle = CadenceThrottle { debounce: Duration::from_millis(300), builtins: vec!["cd".to_string()] };
+        assert!(throttle.wants("cd /tmp"));
+        assert!(throttle.wants("./build.sh --release"));
+        assert!(throttle.wants("sh -c true"));
+        assert!(!throttle.wants("please show me the largest files"));
+        assert!(!throttle.wants(""));
+    }
+}
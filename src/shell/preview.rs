@@ -0,0 +1,408 @@
+use anyhow::Result;
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Filters that only read their input, so a pipeline of them can be run
+// early to see what a `> FILE` redirect would write; `inert` rules out
+// the options of some of them that write files or never finish
+const READ_ONLY_FILTERS: &[&str] = &[
+    "cat", "sort", "uniq", "grep", "egrep", "fgrep", "cut", "tr", "head", "tail", "jq", "column",
+    "fmt", "fold", "tac", "nl", "paste", "rev", "echo", "printf", "wc", "expand", "unexpand", "base64",
+];
+
+// How a command would change files
+#[derive(Debug, PartialEq)]
+enum Change {
+    // `sed -i`: the words of the command and the positions of the files it
+    // edits
+    InPlace { words: Vec<String>, files: Vec<usize> },
+    // A read-only pipeline whose output replaces FILE
+    Overwrite { pipeline: String, file: PathBuf },
+}
+
+// What a file-modifying command would change, as a unified diff
+pub struct Preview {
+    pub diff: String,
+    // Something the diff alone doesn't make obvious
+    pub note: Option<&'static str>,
+}
+
+impl Preview {
+    pub fn print(&self) {
+        if let Some(note) = self.note {
+            println!("{}", format!("Note: {}", note).yellow());
+        }
+        if self.diff.is_empty() {
+            println!("{}", "Preview: no file would change.".bright_blue());
+            return;
+        }
+        println!("{}", "Preview of the changes:".bright_blue());
+        for line in self.diff.lines() {
+            let line = if line.starts_with("+++") || line.starts_with("---") {
+                line.bold()
+            } else if line.starts_with('+') {
+                line.green()
+            } else if line.starts_with('-') {
+                line.red()
+            } else if line.starts_with("@@") {
+                line.cyan()
+            } else {
+                line.normal()
+            };
+            println!("{}", line);
+        }
+    }
+}
+
+// Runs the command against temporary copies of the files it would modify
+// and diffs the result; None when the command doesn't modify files in a way
+// that can be previewed
+pub fn preview(command: &str) -> Option<Preview> {
+    let change = plan(command)?;
+    let dir = std::env::temp_dir().join(format!("llm-shell-preview-{}", std::process::id()));
+    let result = run(&change, &dir);
+    let _ = fs::remove_dir_all(&dir);
+    let note = match &change {
+        Change::Overwrite { pipeline, file } if reads(pipeline, file) => {
+            Some("the shell empties the file before the pipeline reads it")
+        }
+        _ => None,
+    };
+    match result {
+        Ok(diff) => Some(Preview { diff, note }),
+        Err(e) => {
+            log::debug!("Preview of '{}' failed: {}", command, e);
+            None
+        }
+    }
+}
+
+fn run(change: &Change, dir: &Path) -> Result<String> {
+    fs::create_dir_all(dir)?;
+    let mut diff = String::new();
+    match change {
+        Change::InPlace { words, files } => {
+            let mut words = words.clone();
+            let mut copies = Vec::new();
+            for (n, &i) in files.iter().enumerate() {
+                let original = PathBuf::from(&words[i]);
+                let copy = dir.join(n.to_string());
+                fs::copy(&original, &copy)?;
+                words[i] = copy.to_string_lossy().to_string();
+                copies.push((original, copy));
+            }
+            let status = Command::new(&words[0])
+                .args(&words[1..])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()?;
+            if !status.success() {
+                return Err(anyhow::anyhow!("{} exited with {}", words[0], status));
+            }
+            for (original, copy) in copies {
+                diff.push_str(&unified_diff(&original, &copy)?);
+            }
+        }
+        Change::Overwrite { pipeline, file } => {
+            let output = if reads(pipeline, file) {
+                Vec::new()
+            } else {
+                Command::new("sh").arg("-c").arg(pipeline).stdin(Stdio::null()).stderr(Stdio::null()).output()?.stdout
+            };
+            let copy = dir.join("output");
+            fs::write(&copy, output)?;
+            diff.push_str(&unified_diff(file, &copy)?);
+        }
+    }
+    Ok(diff)
+}
+
+// Whether the pipeline reads the file it is redirected to
+fn reads(pipeline: &str, file: &Path) -> bool {
+    shellwords::split(&pipeline.replace('|', " "))
+        .map(|words| words.iter().any(|word| Path::new(word) == file))
+        .unwrap_or(false)
+}
+
+fn unified_diff(original: &Path, changed: &Path) -> Result<String> {
+    let name = original.to_string_lossy();
+    let output = Command::new("diff")
+        .arg("-u")
+        .args(["--label", &format!("a/{}", name), "--label", &format!("b/{}", name)])
+        .arg(original)
+        .arg(changed)
+        .output()?;
+    // 0 means identical, 1 that they differ
+    match output.status.code() {
+        Some(0) | Some(1) => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        _ => Err(anyhow::anyhow!("diff failed: {}", String::from_utf8_lossy(&output.stderr).trim())),
+    }
+}
+
+// Positions of the shell operators in a command, outside quotes
+fn operators(command: &str) -> Vec<(usize, char)> {
+    let mut found = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in command.char_indices() {
+        match quote {
+            _ if escaped => escaped = false,
+            Some(q) if c == q => quote = None,
+            Some('"') if c == '\\' => escaped = true,
+            Some(_) => {}
+            None => match c {
+                '\\' => escaped = true,
+                '\'' | '"' => quote = Some(c),
+                '|' | ';' | '&' | '<' | '>' | '`' | '$' | '(' | ')' => found.push((i, c)),
+                _ => {}
+            },
+        }
+    }
+    found
+}
+
+fn plan(command: &str) -> Option<Change> {
+    let ops = operators(command);
+    if ops.is_empty() {
+        return in_place(command);
+    }
+    // PIPELINE > FILE, with `|` the only other operator
+    let (at, _) = *ops.iter().find(|(_, c)| *c == '>')?;
+    if ops.iter().any(|(i, c)| *c != '|' && *i != at) || command[..at].ends_with('2') {
+        return None;
+    }
+    let pipeline = command[..at].trim();
+    let file = shellwords::split(&command[at + 1..]).ok()?;
+    let file = match file.as_slice() {
+        [file] if Path::new(file).is_file() => PathBuf::from(file),
+        _ => return None,
+    };
+    let mut stages = Vec::new();
+    let mut start = 0;
+    for (i, _) in ops.iter().filter(|(i, _)| *i < at) {
+        stages.push(&command[start..*i]);
+        start = i + 1;
+    }
+    stages.push(&command[start..at]);
+    let read_only = stages.iter().all(|stage| {
+        let words = shellwords::split(stage).unwrap_or_default();
+        match words.split_first() {
+            Some((program, args)) => inert(program, args),
+            None => false,
+        }
+    });
+    if !read_only {
+        return None;
+    }
+    Some(Change::Overwrite { pipeline: pipeline.to_string(), file })
+}
+
+// Whether a filter only writes to stdout and finishes on its own
+fn inert(program: &str, args: &[String]) -> bool {
+    let short = |letter: char| args.iter().any(|arg| arg.starts_with('-') && !arg.starts_with("--") && arg[1..].contains(letter));
+    let long = |name: &str| args.iter().any(|arg| arg.starts_with(name));
+    match program {
+        "sed" => !args.iter().any(|arg| is_sed_in_place(arg)) && sed_is_inert(args),
+        "sort" => !short('o') && !long("--output") && !long("--compress-program"),
+        "tail" => !short('f') && !short('F') && !long("--follow"),
+        // A second operand is the output file
+        "uniq" => args.iter().filter(|arg| !arg.starts_with('-')).count() <= 1,
+        _ => READ_ONLY_FILTERS.contains(&program),
+    }
+}
+
+// Whether the sed scripts given by the arguments can be seen and none of
+// them runs a program or touches a file other than the input
+fn sed_is_inert(args: &[String]) -> bool {
+    match sed_scripts(args) {
+        Some(scripts) => !scripts.is_empty() && scripts.iter().all(|script| !sed_touches_files(script)),
+        None => false,
+    }
+}
+
+// The scripts of a sed command; None for a script read from a file
+fn sed_scripts(args: &[String]) -> Option<Vec<&str>> {
+    let mut scripts = Vec::new();
+    let mut operands = Vec::new();
+    let mut words = args.iter();
+    while let Some(word) = words.next() {
+        if word == "--" {
+            operands.extend(words.by_ref().map(String::as_str));
+        } else if let Some(long) = word.strip_prefix("--") {
+            if long.starts_with("file") {
+                return None;
+            } else if let Some(script) = long.strip_prefix("expression=") {
+                scripts.push(script);
+            } else if long == "expression" {
+                scripts.push(words.next()?.as_str());
+            } else if long == "line-length" {
+                words.next();
+            }
+        } else if word.len() > 1 && word.starts_with('-') {
+            // In a group of short options, -e, -f and -l take the rest of
+            // the word or the next one, -i the rest as a backup suffix
+            for (i, c) in word.char_indices().skip(1) {
+                match c {
+                    'i' => break,
+                    'f' => return None,
+                    'e' | 'l' => {
+                        let rest = &word[i + 1..];
+                        let value = if rest.is_empty() { words.next()?.as_str() } else { rest };
+                        if c == 'e' {
+                            scripts.push(value);
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        } else {
+            operands.push(word.as_str());
+        }
+    }
+    if scripts.is_empty() {
+        scripts.extend(operands.first());
+    }
+    Some(scripts)
+}
+
+// Whether a sed script may run a program or read or write another file:
+// the e, r, R, w and W commands and the e and w flags of s. Anything not
+// understood counts as touching files.
+fn sed_touches_files(script: &str) -> bool {
+    let chars: Vec<char> = script.chars().collect();
+    // From an opening delimiter to just past the matching closing one
+    let skip_delimited = |mut i: usize| -> Option<usize> {
+        let delimiter = *chars.get(i)?;
+        i += 1;
+        while *chars.get(i)? != delimiter {
+            i += if chars[i] == '\\' { 2 } else { 1 };
+        }
+        Some(i + 1)
+    };
+    let end_of_command = |mut i: usize| {
+        while i < chars.len() && !matches!(chars[i], ';' | '\n' | '}') {
+            i += 1;
+        }
+        i
+    };
+    let end_of_line = |mut i: usize| {
+        while i < chars.len() && chars[i] != '\n' {
+            i += if chars[i] == '\\' { 2 } else { 1 };
+        }
+        i
+    };
+    let mut i = 0;
+    while i < chars.len() {
+        i = match chars[i] {
+            ' ' | '\t' | '\n' | ';' | '{' | '}' | '!' | ',' | '$' | '~' | '+' | 'I' | 'M' | '0'..='9' => i + 1,
+            '/' => match skip_delimited(i) {
+                Some(next) => next,
+                None => return true,
+            },
+            '\\' => match skip_delimited(i + 1) {
+                Some(next) => next,
+                None => return true,
+            },
+            's' | 'y' => {
+                let Some(next) = skip_delimited(i + 1).and_then(|end| skip_delimited(end - 1)) else {
+                    return true;
+                };
+                let end = end_of_command(next);
+                if chars[i] == 's' && chars[next..end].iter().any(|c| matches!(c, 'e' | 'w')) {
+                    return true;
+                }
+                end
+            }
+            'a' | 'i' | 'c' | '#' => end_of_line(i + 1),
+            ':' | 'b' | 't' | 'T' | 'v' => end_of_command(i + 1),
+            'q' | 'Q' | 'l' | 'L' | '=' | 'd' | 'D' | 'g' | 'G' | 'h' | 'H' | 'n' | 'N' | 'p' | 'P' | 'x' | 'z' | 'F' => i + 1,
+            _ => return true,
+        };
+    }
+    false
+}
+
+fn is_sed_in_place(arg: &str) -> bool {
+    arg.starts_with("--in-place") || (arg.starts_with("-i") && !arg.starts_with("--"))
+}
+
+fn in_place(command: &str) -> Option<Change> {
+    let words = shellwords::split(command).ok()?;
+    let (program, args) = words.split_first()?;
+    // Only sed scripts can be checked for side effects; awk programs can do
+    // anything, so `gawk -i inplace` is never run early
+    if !matches!(program.as_str(), "sed" | "gsed") || !args.iter().any(|arg| is_sed_in_place(arg)) || !sed_is_inert(args) {
+        return None;
+    }
+    // Options that take the next word as their value, and whether one of
+    // them supplies the script
+    let takes_value = ["-e", "-f", "-l", "--expression", "--file"];
+    let script_options = ["-e", "-f", "--expression", "--file"];
+    let mut operands = Vec::new();
+    let mut script_given = false;
+    let mut i = 1;
+    while i < words.len() {
+        let word = &words[i];
+        if takes_value.contains(&word.as_str()) {
+            script_given |= script_options.contains(&word.as_str());
+            i += 2;
+            continue;
+        }
+        if !word.starts_with('-') || word == "-" {
+            operands.push(i);
+        }
+        i += 1;
+    }
+    // The first operand is the script unless an option gave it
+    let files: Vec<usize> = operands.into_iter().skip(if script_given { 0 } else { 1 }).collect();
+    if files.is_empty() || !files.iter().all(|&i| Path::new(&words[i]).is_file()) {
+        return None;
+    }
+    Some(Change::InPlace { words, files })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview() {
+        let dir = std::env::temp_dir().join(format!("llm-shell-preview-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        fs::write(&file, "alpha\nbeta\n").unwrap();
+        let path = file.to_string_lossy();
+
+        let command = format!("sed -i 's/beta/gamma/' {}", path);
+        assert_eq!(plan(&command), Some(Change::InPlace { words: shellwords::split(&command).unwrap(), files: vec![3] }));
+        let diff = preview(&command).unwrap().diff;
+        assert!(diff.contains("-beta\n+gamma"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "alpha\nbeta\n");
+
+        let command = format!("sort -r {0} > {0}", path);
+        assert!(matches!(plan(&command), Some(Change::Overwrite { .. })));
+        let truncated = preview(&command).unwrap();
+        assert!(truncated.note.is_some());
+        assert!(truncated.diff.contains("-alpha\n-beta"));
+        assert!(plan(&format!("sort {} >> {}", path, path)).is_none());
+        assert!(plan(&format!("curl example.com > {}", path)).is_none());
+        assert!(matches!(plan(&format!("grep -v 'a|b' {0}.bak | sort > {0}", path)), Some(Change::Overwrite { .. })));
+        assert!(plan(&format!("sed 's/a/b/' {}", path)).is_none());
+        assert!(plan("sed -i 's/a/b/' missing.txt").is_none());
+
+        // Nothing that can run programs or write other files is run early
+        assert!(plan(&format!("sed -i 's/a/b/w {0}.out' {0}", path)).is_none());
+        assert!(plan(&format!("sed -i -e 's/a/b/' -e '1e touch x' {}", path)).is_none());
+        assert!(plan(&format!("sed -i -f script.sed {}", path)).is_none());
+        assert!(plan(&format!("gawk -i inplace '{{ print }}' {}", path)).is_none());
+        assert!(plan(&format!("sort -o ~/.bashrc {0} > {0}", path)).is_none());
+        assert!(plan(&format!("cat {0}.bak | sed 'r /etc/passwd' > {0}", path)).is_none());
+        assert!(plan(&format!("sed -n '/x/,$ {{ s/[0-9]\\+/N/g; p }}' {0}.bak > {0}", path)).is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
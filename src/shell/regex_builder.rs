@@ -0,0 +1,132 @@
+use anyhow::Result;
+use regex::Regex;
+
+// Each answer needs at least this many examples of each kind to be checked
+const MIN_EXAMPLES: usize = 2;
+
+// What the model answered: the pattern and the whole strings it gave that
+// the pattern should and shouldn't match
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answer {
+    pub pattern: String,
+    pub matches: Vec<String>,
+    pub non_matches: Vec<String>,
+}
+
+// Reads the `REGEX:`, `MATCH:` and `NO MATCH:` lines of an answer
+pub fn parse(answer: &str) -> Result<Answer> {
+    let mut pattern = None;
+    let mut matches = Vec::new();
+    let mut non_matches = Vec::new();
+    for line in answer.lines().map(str::trim) {
+        // Checked before MATCH:, which it ends with
+        if let Some(example) = line.strip_prefix("NO MATCH:") {
+            non_matches.push(example.trim().to_string());
+        } else if let Some(example) = line.strip_prefix("MATCH:") {
+            matches.push(example.trim().to_string());
+        } else if let Some(found) = line.strip_prefix("REGEX:") {
+            pattern = Some(found.trim().to_string());
+        }
+    }
+    match pattern.filter(|pattern| !pattern.is_empty()) {
+        Some(pattern) => Ok(Answer { pattern, matches, non_matches }),
+        None => Err(anyhow::anyhow!("the answer has no `REGEX:` line")),
+    }
+}
+
+// What is wrong with an answer: a pattern that doesn't compile or won't
+// work in grep -E and sed -E, too few examples, or examples it gets wrong.
+// Examples are whole strings, so the pattern is tried anchored at both ends.
+pub fn problems(answer: &Answer) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(feature) = non_posix(&answer.pattern) {
+        problems.push(format!("it uses {}, which grep -E and sed -E don't support", feature));
+    }
+    let anchored = match Regex::new(&format!("^(?:{})$", answer.pattern)) {
+        Ok(anchored) => anchored,
+        Err(e) => {
+            let error = e.to_string();
+            problems.push(format!("it doesn't compile: {}", error.lines().last().unwrap_or(&error).trim()));
+            return problems;
+        }
+    };
+    if answer.matches.len() < MIN_EXAMPLES || answer.non_matches.len() < MIN_EXAMPLES {
+        problems.push(format!("give at least {} MATCH and {} NO MATCH examples", MIN_EXAMPLES, MIN_EXAMPLES));
+    }
+    for example in answer.matches.iter().filter(|example| !anchored.is_match(example)) {
+        problems.push(format!("it doesn't match `{}`, which it should", example));
+    }
+    for example in answer.non_matches.iter().filter(|example| anchored.is_match(example)) {
+        problems.push(format!("it matches `{}`, which it shouldn't", example));
+    }
+    problems
+}
+
+// The first Perl-style feature in a pattern that POSIX extended regexes lack
+fn non_posix(pattern: &str) -> Option<&'static str> {
+    let mut chars = pattern.chars().peekable();
+    let mut previous = None;
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('d' | 'D')) => return Some("`\\d` (use [0-9])"),
+            ('\\', Some(_)) => {
+                chars.next();
+                previous = None;
+                continue;
+            }
+            ('(', Some('?')) => return Some("`(?...)` groups"),
+            ('?', _) if matches!(previous, Some('*' | '+' | '?' | '}')) => return Some("lazy quantifiers"),
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    None
+}
+
+// Single-quotes text for the shell
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+// `sed -E 's/PATTERN/REPLACEMENT/g'`, with a delimiter the pattern doesn't use
+pub fn sed_command(pattern: &str) -> String {
+    let delimiter = ['/', '#', ',', '@', '%', '~'].into_iter().find(|d| !pattern.contains(*d)).unwrap_or('/');
+    let pattern = if pattern.contains(delimiter) { pattern.replace(delimiter, &format!("\\{}", delimiter)) } else { pattern.to_string() };
+    format!("sed -E {} FILE", quote(&format!("s{d}{}{d}REPLACEMENT{d}g", pattern, d = delimiter)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWER: &str = "REGEX: ([0-9]{1,3}\\.){3}[0-9]{1,3}\nMATCH: 192.168.0.1\nMATCH: 8.8.8.8\nNO MATCH: 1.2.3\nNO MATCH: a.b.c.d\n";
+
+    #[test]
+    fn test_parse() {
+        let answer = parse(&format!("```\n{}```", ANSWER)).unwrap();
+        assert_eq!(answer.pattern, "([0-9]{1,3}\\.){3}[0-9]{1,3}");
+        assert_eq!(answer.matches, vec!["192.168.0.1", "8.8.8.8"]);
+        assert_eq!(answer.non_matches, vec!["1.2.3", "a.b.c.d"]);
+        assert!(parse("MATCH: x").is_err());
+    }
+
+    #[test]
+    fn test_problems() {
+        assert!(problems(&parse(ANSWER).unwrap()).is_empty());
+        let wrong = parse(&format!("{}NO MATCH: 999.1.1.1\n", ANSWER)).unwrap();
+        assert_eq!(problems(&wrong), vec!["it matches `999.1.1.1`, which it shouldn't"]);
+        let answer = |pattern: &str| Answer { pattern: pattern.to_string(), matches: vec!["1".into(), "22".into()], non_matches: vec!["a".into(), "".into()] };
+        assert!(problems(&answer("[0-9]+")).is_empty());
+        assert_eq!(problems(&answer("\\d+")), vec!["it uses `\\d` (use [0-9]), which grep -E and sed -E don't support"]);
+        assert_eq!(problems(&answer("[0-9]+?")), vec!["it uses lazy quantifiers, which grep -E and sed -E don't support"]);
+        assert!(problems(&answer("\\\\d|[0-9]?")).iter().all(|problem| !problem.contains("grep -E")));
+        assert!(problems(&answer("[0-9")).iter().any(|problem| problem.starts_with("it doesn't compile: ")));
+    }
+
+    #[test]
+    fn test_sed_command() {
+        assert_eq!(sed_command("[0-9]+"), "sed -E 's/[0-9]+/REPLACEMENT/g' FILE");
+        assert_eq!(sed_command("https?://[^ ]+"), "sed -E 's#https?://[^ ]+#REPLACEMENT#g' FILE");
+        assert_eq!(sed_command("it's"), "sed -E 's/it'\\''s/REPLACEMENT/g' FILE");
+    }
+}
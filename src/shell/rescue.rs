@@ -0,0 +1,166 @@
+use colored::*;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use crate::system::{doctor, tty};
+use crate::utils::path_utils;
+use super::signal_handler::SignalHandler;
+
+const HELP: &str = "\
+Rescue mode: llm-shell failed to start, so only these builtins are available.
+
+  cd [DIR]              change directory
+  pwd                   print the current directory
+  ls [DIR]              list a directory
+  cat FILE              show a file
+  mv FROM TO            rename a file, e.g. to move a broken config aside
+  echo [TEXT...]        print text
+  env                   list environment variables
+  export NAME=VALUE     set an environment variable
+  unset NAME            remove an environment variable
+  doctor                check the setup and suggest fixes
+  retry                 start llm-shell again, with the current environment
+  exec PROGRAM [ARG...] replace this shell, e.g. `exec /bin/sh` for a full shell
+  exit [STATUS]         leave";
+
+// A line-at-a-time REPL with no LLM, line editing, profile or config, for
+// login shells that can't start otherwise. Returns the exit status.
+pub async fn run(error: &str) -> i32 {
+    let _ = tty::restore();
+    eprintln!("{} {}", "llm-shell failed to start:".bright_red(), error);
+    eprintln!("Starting a rescue shell instead; type `help` for what it can do, or `exec /bin/sh` for a full shell.");
+
+    let stdin = io::stdin();
+    let mut status = 0;
+    loop {
+        let cwd = std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+        print!("{} {}$ ", "rescue".bright_red(), cwd);
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return status,
+            Ok(_) => {}
+        }
+        let words = match shellwords::split(line.trim()) {
+            Ok(words) => words.iter().map(|word| path_utils::expand_tilde(word)).collect::<Vec<_>>(),
+            Err(_) => {
+                eprintln!("rescue: unmatched quote");
+                status = 2;
+                continue;
+            }
+        };
+        let Some(command) = words.first() else {
+            continue;
+        };
+        let args = &words[1..];
+        status = match command.as_str() {
+            "exit" | "logout" => return args.first().and_then(|code| code.parse().ok()).unwrap_or(status),
+            "doctor" => doctor::run(&[]).await.unwrap_or(1),
+            "retry" => {
+                let args: Vec<String> = std::env::args().collect();
+                match std::env::current_exe() {
+                    Ok(exe) => exec(&exe.to_string_lossy(), &args[1..], args.first().map(String::as_str)),
+                    Err(e) => report(command, e),
+                }
+            }
+            "exec" => match args.split_first() {
+                Some((program, rest)) => exec(program, rest, None),
+                None => usage("exec PROGRAM [ARG...]"),
+            },
+            _ => builtin(command, args),
+        };
+    }
+}
+
+fn builtin(command: &str, args: &[String]) -> i32 {
+    let result = match (command, args) {
+        ("help", _) => {
+            println!("{}", HELP);
+            Ok(())
+        }
+        ("cd", []) => std::env::set_current_dir(dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"))),
+        ("cd", [dir]) => std::env::set_current_dir(dir),
+        ("pwd", []) => std::env::current_dir().map(|dir| println!("{}", dir.display())),
+        ("ls", []) => list("."),
+        ("ls", [dir]) => list(dir),
+        ("cat", [file]) => fs::read(file).and_then(|content| io::stdout().write_all(&content)),
+        ("mv", [from, to]) => fs::rename(from, to),
+        ("echo", words) => {
+            println!("{}", words.join(" "));
+            Ok(())
+        }
+        ("env", []) => {
+            for (key, value) in std::env::vars() {
+                println!("{}={}", key, value);
+            }
+            Ok(())
+        }
+        ("export", assignments) if !assignments.is_empty() => {
+            for assignment in assignments {
+                match assignment.split_once('=') {
+                    Some((name, value)) => std::env::set_var(name, value),
+                    None => return usage("export NAME=VALUE..."),
+                }
+            }
+            Ok(())
+        }
+        ("unset", names) if !names.is_empty() => {
+            for name in names {
+                std::env::remove_var(name);
+            }
+            Ok(())
+        }
+        ("cd" | "pwd" | "ls" | "cat" | "mv" | "env" | "export" | "unset", _) => {
+            eprintln!("rescue: {}: wrong number of arguments; see `help`", command);
+            return 2;
+        }
+        _ => {
+            eprintln!("rescue: {}: not available in rescue mode; see `help`, or `exec /bin/sh`", command);
+            return 127;
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => report(command, e),
+    }
+}
+
+fn list(dir: &str) -> io::Result<()> {
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) { format!("{}/", name) } else { name }
+        })
+        .collect();
+    names.sort();
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+// Only returns if the program couldn't be started
+fn exec(program: &str, args: &[String], arg0: Option<&str>) -> i32 {
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+    if let Some(arg0) = arg0 {
+        command.arg0(arg0);
+    }
+    // Handlers set up before startup failed would otherwise leak into the program
+    let _ = SignalHandler::reset();
+    let e = command.exec();
+    report(program, e)
+}
+
+fn usage(usage: &str) -> i32 {
+    eprintln!("usage: {}", usage);
+    2
+}
+
+fn report(command: &str, e: io::Error) -> i32 {
+    eprintln!("rescue: {}: {}", command, e);
+    1
+}
@@ -0,0 +1,837 @@
+use anyhow::Result;
+use crate::shell::command_parser::Connector;
+use crate::shell::variables;
+
+// Words that start a compound command and route the input through this parser
+const COMPOUND_KEYWORDS: &[&str] = &["case", "select", "coproc", "{"];
+
+// Words the parser reads as the start of a compound command
+const COMPOUND_STARTS: &[&str] = &["case", "if", "for", "while", "until", "select", "coproc", "{"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    // A simple command (possibly a pipeline), kept as source text for the regular execution path
+    Command(String),
+    Case { word: String, arms: Vec<CaseArm> },
+    // `if LIST; then LIST; [elif LIST; then LIST;]... [else LIST;] fi`
+    If { branches: Vec<IfBranch>, otherwise: Option<Vec<Node>> },
+    // `for NAME [in WORD...]; do LIST; done`; without `in` the words are the
+    // positional parameters, which the shell has none of
+    For { name: String, words: Vec<String>, body: Vec<Node> },
+    // `while LIST; do LIST; done`, or `until` to loop while the condition fails
+    While { until: bool, condition: Vec<Node>, body: Vec<Node> },
+    // `{ ...; }` runs in the current shell, `( ... )` in a copy of its environment
+    Group { body: Vec<Node>, redirects: Vec<Redirect> },
+    Subshell { body: Vec<Node>, redirects: Vec<Redirect> },
+    Select { name: String, words: Vec<String>, body: Vec<Node> },
+    // `coproc [NAME] { ...; }` or `coproc COMMAND`, with the body kept as source for sh
+    Coproc { name: String, body: String },
+    // `A && B || C` where one of them is compound; each runs on the status
+    // of the one before
+    AndOr { first: Box<Node>, rest: Vec<(Connector, Node)> },
+}
+
+// Set by `break`/`continue` and consumed by the enclosing loop(s)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopControl {
+    Break(u32),
+    Continue(u32),
+}
+
+// A redirection applied to a whole group, e.g. the `> log 2>&1` in `( make ) > log 2>&1`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub fd: i32,
+    pub target: RedirectTarget,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedirectTarget {
+    Read(String),
+    Write(String),
+    // `>|`, which overwrites even with noclobber
+    Clobber(String),
+    Append(String),
+    // A descriptor number, or a word expanding to one like `${COPROC[1]}`
+    Duplicate(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseArm {
+    pub patterns: Vec<String>,
+    pub body: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfBranch {
+    pub condition: Vec<Node>,
+    pub body: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word,
+    Op,
+    Newline,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+const OPERATORS: &[&str] = &[";;", "&&", "||", ";", "|", "&", "(", ")"];
+
+pub fn is_compound(input: &str) -> bool {
+    if input.trim_start().starts_with('(') {
+        return true;
+    }
+    let Ok(tokens) = tokenize(input) else {
+        return input.split_whitespace().next().is_some_and(|word| COMPOUND_KEYWORDS.contains(&word));
+    };
+    // Any command of a list can be one, as in `N=0; while ...` or `cd x && for ...`
+    (0..tokens.len()).filter(|&i| starts_command(&tokens, i)).any(|i| match tokens[i].text.as_str() {
+        // Only when the condition is a test or `then`/`do` starts a command,
+        // so a request like "if the disk is full, clean up /tmp" or "for each
+        // log, count its lines" still reaches the model
+        "if" => is_statement(&tokens[i..], "then"),
+        "for" | "while" | "until" => is_statement(&tokens[i..], "do"),
+        word => COMPOUND_KEYWORDS.contains(&word),
+    })
+}
+
+fn starts_command(tokens: &[Token], i: usize) -> bool {
+    tokens[i].kind == TokenKind::Word && (i == 0 || matches!(tokens[i - 1].kind, TokenKind::Newline | TokenKind::Op))
+}
+
+fn is_statement(tokens: &[Token], keyword: &str) -> bool {
+    if tokens.get(1).is_some_and(|token| matches!(token.text.as_str(), "[" | "[[" | "test" | "!")) {
+        return true;
+    }
+    tokens.windows(2).any(|pair| {
+        let separator = pair[0].kind == TokenKind::Newline || (pair[0].kind == TokenKind::Op && pair[0].text == ";");
+        separator && pair[1].kind == TokenKind::Word && pair[1].text == keyword
+    })
+}
+
+// Whether the input opens a compound command it doesn't close, so reading
+// goes on with the next line
+pub fn is_incomplete(input: &str) -> bool {
+    is_compound(input) && matches!(parse(input), Err(e) if e.to_string().contains("unexpected end of input"))
+}
+
+pub fn parse(input: &str) -> Result<Vec<Node>> {
+    let mut parser = Parser {
+        input,
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    let nodes = parser.parse_list(&[])?;
+    match parser.peek() {
+        Some(token) => Err(anyhow::anyhow!("syntax error near unexpected token '{}'", token.text)),
+        None => Ok(nodes),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<(usize, char)> = input.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (start, c) = chars[i];
+
+        if c == '\n' {
+            tokens.push(Token { kind: TokenKind::Newline, text: "\n".to_string(), start, end: start + 1 });
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            while i < chars.len() && chars[i].1 != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        if let Some(op) = OPERATORS.iter().find(|op| input[start..].starts_with(**op)) {
+            tokens.push(Token { kind: TokenKind::Op, text: op.to_string(), start, end: start + op.len() });
+            i += op.chars().count();
+            continue;
+        }
+
+        // A word runs until unquoted whitespace or an operator
+        let mut quote: Option<char> = None;
+        let mut depth = 0;
+        while i < chars.len() {
+            let (offset, c) = chars[i];
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some('"') if c == '\\' => i += 1,
+                Some(_) => {}
+                None => {
+                    if c == '\'' || c == '"' {
+                        quote = Some(c);
+                    } else if c == '\\' {
+                        i += 1;
+                    } else if c == '$' && chars.get(i + 1).map(|(_, n)| *n) == Some('(') {
+                        // Command substitution keeps its parentheses inside the word
+                        depth += 1;
+                        i += 1;
+                    } else if depth > 0 && c == '(' {
+                        // As in `$((N + 1))` or `$(f (x))`
+                        depth += 1;
+                    } else if depth > 0 && c == ')' {
+                        depth -= 1;
+                    } else if c == '&' && i > 0 && matches!(chars[i - 1].1, '>' | '<') {
+                        // `2>&1` is a single redirection word, not a background operator
+                    } else if c == '|' && i > 0 && chars[i - 1].1 == '>' {
+                        // Nor is the `|` of `>|` a pipe
+                    } else if depth == 0
+                        && (c.is_whitespace() || OPERATORS.iter().any(|op| input[offset..].starts_with(*op)))
+                    {
+                        break;
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        if quote.is_some() {
+            return Err(anyhow::anyhow!("syntax error: unterminated quote"));
+        }
+        let end = chars.get(i).map(|(offset, _)| *offset).unwrap_or(input.len());
+        tokens.push(Token { kind: TokenKind::Word, text: input[start..end].to_string(), start, end });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_is(&self, kind: TokenKind, text: &str) -> bool {
+        self.peek().map(|t| t.kind == kind && t.text == text).unwrap_or(false)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind, text: Option<&str>) -> Result<Token> {
+        match self.next() {
+            Some(token) if token.kind == kind && text.map(|t| t == token.text).unwrap_or(true) => Ok(token),
+            Some(token) => Err(anyhow::anyhow!(
+                "syntax error near unexpected token '{}'{}",
+                token.text.escape_default(),
+                text.map(|t| format!(", expected '{}'", t)).unwrap_or_default()
+            )),
+            None => Err(anyhow::anyhow!(
+                "syntax error: unexpected end of input{}",
+                text.map(|t| format!(", expected '{}'", t)).unwrap_or_default()
+            )),
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(token) = self.peek() {
+            if token.kind == TokenKind::Newline || (token.kind == TokenKind::Op && token.text == ";") {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek().map(|t| t.kind == TokenKind::Newline).unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+
+    // Parses commands until a terminator keyword or operator (left unconsumed) or the end of input
+    fn parse_list(&mut self, terminators: &[&str]) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_separators();
+            match self.peek() {
+                None => break,
+                Some(token) if terminators.contains(&token.text.as_str()) => break,
+                Some(_) => {}
+            }
+
+            let node = self.parse_and_or()?;
+            let compound = !matches!(node, Node::Command(_));
+            nodes.push(node);
+
+            // A compound command must be followed by a separator
+            if compound {
+                if let Some(token) = self.peek() {
+                    let separator = token.kind == TokenKind::Newline
+                        || (token.kind == TokenKind::Op && matches!(token.text.as_str(), ";" | ";;"));
+                    if !separator && !terminators.contains(&token.text.as_str()) {
+                        return Err(anyhow::anyhow!("syntax error near unexpected token '{}'", token.text));
+                    }
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    // Commands joined by `&&` and `||`. Simple commands keep theirs in their
+    // text; only a compound one on either side makes this a node of its own.
+    fn parse_and_or(&mut self) -> Result<Node> {
+        let first = self.parse_command()?;
+        let mut rest = Vec::new();
+        while let Some(token) = self.peek().filter(|t| t.kind == TokenKind::Op && matches!(t.text.as_str(), "&&" | "||")) {
+            let connector = if token.text == "&&" { Connector::And } else { Connector::Or };
+            self.pos += 1;
+            self.skip_newlines();
+            rest.push((connector, self.parse_command()?));
+        }
+        if rest.is_empty() {
+            return Ok(first);
+        }
+        Ok(Node::AndOr { first: Box::new(first), rest })
+    }
+
+    fn parse_command(&mut self) -> Result<Node> {
+        if self.peek_is(TokenKind::Word, "case") {
+            return self.parse_case();
+        }
+        if self.peek_is(TokenKind::Word, "if") {
+            return self.parse_if();
+        }
+        if self.peek_is(TokenKind::Word, "for") {
+            return self.parse_for();
+        }
+        if self.peek_is(TokenKind::Word, "while") || self.peek_is(TokenKind::Word, "until") {
+            return self.parse_while();
+        }
+        if self.peek_is(TokenKind::Word, "select") {
+            return self.parse_select();
+        }
+        if self.peek_is(TokenKind::Word, "coproc") {
+            return self.parse_coproc();
+        }
+        if self.peek_is(TokenKind::Word, "{") {
+            self.pos += 1;
+            let body = self.parse_block(&["}"])?;
+            self.expect(TokenKind::Word, Some("}"))?;
+            let redirects = self.parse_redirects()?;
+            return Ok(Node::Group { body, redirects });
+        }
+        if self.peek_is(TokenKind::Op, "(") {
+            self.pos += 1;
+            let body = self.parse_block(&[")"])?;
+            self.expect(TokenKind::Op, Some(")"))?;
+            let redirects = self.parse_redirects()?;
+            return Ok(Node::Subshell { body, redirects });
+        }
+
+        let start = self.peek().map(|t| t.start).unwrap_or(0);
+        let mut end = start;
+        while let Some(token) = self.peek() {
+            let separator = token.kind == TokenKind::Newline
+                || (token.kind == TokenKind::Op && matches!(token.text.as_str(), ";" | ";;" | ")"));
+            // `&&` and `||` stay part of the text unless a compound command follows
+            let before_compound = token.kind == TokenKind::Op
+                && matches!(token.text.as_str(), "&&" | "||")
+                && self.tokens.get(self.pos + 1).is_some_and(|next| next.kind == TokenKind::Word && COMPOUND_STARTS.contains(&next.text.as_str()));
+            if separator || before_compound {
+                break;
+            }
+            end = token.end;
+            self.pos += 1;
+        }
+
+        if end == start {
+            let token = self.next().map(|t| t.text).unwrap_or_default();
+            return Err(anyhow::anyhow!("syntax error near unexpected token '{}'", token));
+        }
+        Ok(Node::Command(self.input[start..end].to_string()))
+    }
+
+    // The body of a group or clause, which may not be empty
+    fn parse_block(&mut self, close: &[&str]) -> Result<Vec<Node>> {
+        let body = self.parse_list(close)?;
+        if body.is_empty() {
+            return Err(match self.peek() {
+                Some(token) => anyhow::anyhow!("syntax error near unexpected token '{}'", token.text),
+                None => anyhow::anyhow!("syntax error: unexpected end of input"),
+            });
+        }
+        Ok(body)
+    }
+
+    // Redirection words following a closing `}` or `)`
+    fn parse_redirects(&mut self) -> Result<Vec<Redirect>> {
+        let mut redirects = Vec::new();
+        while let Some(token) = self.peek().filter(|t| t.kind == TokenKind::Word) {
+            let Some((fd, operator, rest)) = split_redirect(&token.text) else {
+                break;
+            };
+            let (fd, operator, rest) = (fd, operator.to_string(), rest.to_string());
+            self.pos += 1;
+
+            // Targets are expanded and unquoted when the group runs
+            let target = if rest.is_empty() { self.expect(TokenKind::Word, None)?.text } else { rest };
+            let target = match operator.as_str() {
+                ">&" | "<&" if target.parse::<i32>().is_ok() || target.starts_with('$') => RedirectTarget::Duplicate(target),
+                ">&" | "<&" => return Err(anyhow::anyhow!("syntax error: bad file descriptor '{}'", target)),
+                "<" => RedirectTarget::Read(target),
+                ">>" => RedirectTarget::Append(target),
+                ">|" => RedirectTarget::Clobber(target),
+                _ => RedirectTarget::Write(target),
+            };
+            redirects.push(Redirect { fd, target });
+        }
+        Ok(redirects)
+    }
+
+    // for NAME [in WORD...] ; do LIST done
+    fn parse_for(&mut self) -> Result<Node> {
+        self.expect(TokenKind::Word, Some("for"))?;
+        let name = self.expect(TokenKind::Word, None)?.text;
+        if !variables::is_name(&name) {
+            return Err(anyhow::anyhow!("for: '{}': not a valid identifier", name));
+        }
+
+        let mut words = Vec::new();
+        self.skip_newlines();
+        if self.peek_is(TokenKind::Word, "in") {
+            self.pos += 1;
+            while let Some(token) = self.peek().filter(|t| t.kind == TokenKind::Word) {
+                words.push(token.text.clone());
+                self.pos += 1;
+            }
+        }
+        self.skip_separators();
+
+        let body = self.parse_do_group()?;
+        Ok(Node::For { name, words, body })
+    }
+
+    // while LIST; do LIST done, and the same with until
+    fn parse_while(&mut self) -> Result<Node> {
+        let until = self.next().is_some_and(|token| token.text == "until");
+        let condition = self.parse_block(&["do"])?;
+        let body = self.parse_do_group()?;
+        Ok(Node::While { until, condition, body })
+    }
+
+    // select NAME [in WORD...] ; do LIST done
+    fn parse_select(&mut self) -> Result<Node> {
+        self.expect(TokenKind::Word, Some("select"))?;
+        let name = self.expect(TokenKind::Word, None)?.text;
+        if !variables::is_name(&name) {
+            return Err(anyhow::anyhow!("select: '{}': not a valid identifier", name));
+        }
+
+        let mut words = Vec::new();
+        self.skip_newlines();
+        if self.peek_is(TokenKind::Word, "in") {
+            self.pos += 1;
+            while let Some(token) = self.peek().filter(|t| t.kind == TokenKind::Word) {
+                words.push(token.text.clone());
+                self.pos += 1;
+            }
+        }
+        self.skip_separators();
+
+        let body = self.parse_do_group()?;
+        Ok(Node::Select { name, words, body })
+    }
+
+    // coproc [NAME] { LIST; } or coproc COMMAND; as in bash, a name is only
+    // recognized before a group and otherwise it is COPROC
+    fn parse_coproc(&mut self) -> Result<Node> {
+        self.expect(TokenKind::Word, Some("coproc"))?;
+        let named = self.tokens.get(self.pos + 1).map(|t| t.kind == TokenKind::Word && t.text == "{").unwrap_or(false);
+        let name = if named { self.expect(TokenKind::Word, None)?.text } else { "COPROC".to_string() };
+        if !variables::is_name(&name) {
+            return Err(anyhow::anyhow!("coproc: '{}': not a valid identifier", name));
+        }
+
+        if !self.peek_is(TokenKind::Word, "{") {
+            return match self.parse_command()? {
+                Node::Command(body) => Ok(Node::Coproc { name, body }),
+                _ => Err(anyhow::anyhow!("coproc: only a command or a {{ ...; }} group can be run as a coprocess")),
+            };
+        }
+        let open = self.expect(TokenKind::Word, Some("{"))?;
+        self.parse_block(&["}"])?;
+        let close = self.expect(TokenKind::Word, Some("}"))?;
+        let body = self.input[open.end..close.start].trim().to_string();
+        Ok(Node::Coproc { name, body })
+    }
+
+    // do LIST done
+    fn parse_do_group(&mut self) -> Result<Vec<Node>> {
+        self.expect(TokenKind::Word, Some("do"))?;
+        let body = self.parse_block(&["done"])?;
+        self.expect(TokenKind::Word, Some("done"))?;
+        Ok(body)
+    }
+
+    // if LIST; then LIST; [elif LIST; then LIST;]... [else LIST;] fi
+    fn parse_if(&mut self) -> Result<Node> {
+        self.expect(TokenKind::Word, Some("if"))?;
+        let mut branches = Vec::new();
+        loop {
+            let condition = self.parse_block(&["then"])?;
+            self.expect(TokenKind::Word, Some("then"))?;
+            let body = self.parse_block(&["elif", "else", "fi"])?;
+            branches.push(IfBranch { condition, body });
+            if !self.peek_is(TokenKind::Word, "elif") {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        let otherwise = if self.peek_is(TokenKind::Word, "else") {
+            self.pos += 1;
+            Some(self.parse_block(&["fi"])?)
+        } else {
+            None
+        };
+        self.expect(TokenKind::Word, Some("fi"))?;
+        Ok(Node::If { branches, otherwise })
+    }
+
+    // case WORD in [(]PATTERN[|PATTERN]...) LIST ;; ... esac
+    fn parse_case(&mut self) -> Result<Node> {
+        self.expect(TokenKind::Word, Some("case"))?;
+        let word = self.expect(TokenKind::Word, None)?.text;
+        self.skip_newlines();
+        self.expect(TokenKind::Word, Some("in"))?;
+
+        let mut arms = Vec::new();
+        loop {
+            self.skip_separators();
+            if self.peek_is(TokenKind::Word, "esac") {
+                self.pos += 1;
+                break;
+            }
+            if self.peek_is(TokenKind::Op, "(") {
+                self.pos += 1;
+            }
+
+            let mut patterns = vec![self.expect(TokenKind::Word, None)?.text];
+            while self.peek_is(TokenKind::Op, "|") {
+                self.pos += 1;
+                patterns.push(self.expect(TokenKind::Word, None)?.text);
+            }
+            self.expect(TokenKind::Op, Some(")"))?;
+
+            let body = self.parse_list(&["esac", ";;"])?;
+            arms.push(CaseArm { patterns, body });
+
+            if self.peek_is(TokenKind::Op, ";;") {
+                self.pos += 1;
+            } else if self.peek().is_none() {
+                return Err(anyhow::anyhow!("syntax error: unexpected end of input, expected 'esac'"));
+            }
+        }
+
+        Ok(Node::Case { word, arms })
+    }
+}
+
+// Splits a word like `2>>file` into its descriptor, operator and (possibly empty) target
+fn split_redirect(word: &str) -> Option<(i32, &str, &str)> {
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = &word[digits..];
+    let operator = [">>", ">&", "<&", ">|", ">", "<"].into_iter().find(|op| rest.starts_with(op))?;
+    let fd = match &word[..digits] {
+        "" if operator.starts_with('<') => 0,
+        "" => 1,
+        digits => digits.parse().ok()?,
+    };
+    Some((fd, operator, &rest[operator.len()..]))
+}
+
+// Splits text on blanks outside quotes, keeping the quotes in the fields
+pub fn fields(text: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                fields.extend(field.take());
+                continue;
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                let field = field.get_or_insert_with(String::new);
+                field.push(c);
+                field.extend(chars.next());
+                continue;
+            }
+            _ => {}
+        }
+        field.get_or_insert_with(String::new).push(c);
+    }
+    fields.extend(field);
+    fields
+}
+
+// Removes shell quoting from a word
+pub fn unquote(word: &str) -> String {
+    let mut result = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some('\''), c) => result.push(c),
+            (_, '\\') => {
+                if let Some(next) = chars.next() {
+                    result.push(next);
+                }
+            }
+            (_, c) => result.push(c),
+        }
+    }
+    result
+}
+
+// Turns a case pattern word into a glob, escaping metacharacters that were quoted
+pub fn pattern_from_word(word: &str) -> String {
+    let mut result = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    result.push('\\');
+                    result.push(next);
+                }
+            }
+            (Some(_), c) if "*?[]\\".contains(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            (_, c) => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_case() {
+        let nodes = parse("case $1 in start|up) echo starting; run;; 'stop') echo stop;; *) echo usage;; esac").unwrap();
+        assert_eq!(nodes.len(), 1);
+
+        let Node::Case { word, arms } = &nodes[0] else {
+            panic!("expected a case node");
+        };
+        assert_eq!(word, "$1");
+        assert_eq!(arms.len(), 3);
+        assert_eq!(arms[0].patterns, vec!["start", "up"]);
+        assert_eq!(arms[0].body, vec![Node::Command("echo starting".into()), Node::Command("run".into())]);
+        assert_eq!(arms[1].patterns, vec!["'stop'"]);
+        assert_eq!(arms[2].body, vec![Node::Command("echo usage".into())]);
+    }
+
+    #[test]
+    fn test_parse_multiline_case() {
+        let script = "case \"$OS\" in\n  (linux)\n    echo apt\n    ;;\n  darwin) echo brew\nesac\necho done";
+        let nodes = parse(script).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1], Node::Command("echo done".into()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("case x in a) echo a;;").is_err());
+        assert!(parse("case x a) echo a;; esac").is_err());
+        assert!(parse("case x in a) echo a;; esac echo").is_err());
+        assert!(parse("echo 'unterminated").is_err());
+    }
+
+    #[test]
+    fn test_parse_if() {
+        let nodes = parse("if [ -f Cargo.toml ]; then cargo build; fi").unwrap();
+        assert_eq!(
+            nodes[0],
+            Node::If {
+                branches: vec![IfBranch {
+                    condition: vec![Node::Command("[ -f Cargo.toml ]".into())],
+                    body: vec![Node::Command("cargo build".into())],
+                }],
+                otherwise: None,
+            }
+        );
+
+        let script = "if [ \"$1\" = a ]\nthen\n  echo a\nelif [ \"$1\" = b ]; then echo b\nelse\n  if true; then echo nested; fi\nfi\necho done";
+        let nodes = parse(script).unwrap();
+        assert_eq!(nodes.len(), 2);
+        let Node::If { branches, otherwise: Some(otherwise) } = &nodes[0] else {
+            panic!("expected an if node with an else");
+        };
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[1].body, vec![Node::Command("echo b".into())]);
+        assert!(matches!(otherwise[0], Node::If { .. }));
+
+        assert!(parse("if true; then fi").is_err());
+        assert!(parse("if true; echo a; fi").is_err());
+        assert!(parse("if true; then echo a; fi echo").is_err());
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(is_incomplete("if [ -d .git ]; then"));
+        assert!(is_incomplete("if [ -d .git ]; then\n  git pull\nelse"));
+        assert!(!is_incomplete("if [ -d .git ]; then git pull; fi"));
+        assert!(is_incomplete("case $1 in"));
+        assert!(is_incomplete("{ echo a"));
+        assert!(!is_incomplete("{ echo a; } > log"));
+        assert!(is_incomplete("if [ -d .git ]"));
+        assert!(!is_compound("if the disk is full, clean up /tmp"));
+        assert!(!is_incomplete("echo 'if"));
+    }
+
+    #[test]
+    fn test_parse_loops() {
+        assert_eq!(parse("N=$((N + 1)); echo").unwrap()[0], Node::Command("N=$((N + 1))".into()));
+        let nodes = parse("for f in *.log \"a b\"; do gzip $f; continue; done\nwhile [ -f lock ]\ndo\n  sleep 1\ndone").unwrap();
+        assert_eq!(
+            nodes[0],
+            Node::For {
+                name: "f".into(),
+                words: vec!["*.log".into(), "\"a b\"".into()],
+                body: vec![Node::Command("gzip $f".into()), Node::Command("continue".into())],
+            }
+        );
+        assert_eq!(
+            nodes[1],
+            Node::While {
+                until: false,
+                condition: vec![Node::Command("[ -f lock ]".into())],
+                body: vec![Node::Command("sleep 1".into())],
+            }
+        );
+        let Node::For { words, .. } = &parse("for arg\ndo echo $arg; done").unwrap()[0] else {
+            panic!("expected a for node");
+        };
+        assert!(words.is_empty());
+        assert!(matches!(parse("until false; do break; done").unwrap()[0], Node::While { until: true, .. }));
+
+        assert!(parse("for 1 in a; do echo; done").is_err());
+        assert!(parse("for x in a; echo; done").is_err());
+        assert!(parse("while true; do done").is_err());
+        assert!(is_incomplete("for f in *.txt; do"));
+        assert!(is_incomplete("while [ -f lock ]"));
+        assert!(!is_compound("for each log, count its lines"));
+
+        // Compound commands later in a list
+        assert!(is_compound("N=0; while [ $N -lt 3 ]; do N=1; done"));
+        assert!(!is_compound("make && echo done; ls"));
+        assert_eq!(
+            parse("cd x && for f in a; do echo $f; done || echo failed").unwrap(),
+            vec![Node::AndOr {
+                first: Box::new(Node::Command("cd x".into())),
+                rest: vec![
+                    (Connector::And, Node::For { name: "f".into(), words: vec!["a".into()], body: vec![Node::Command("echo $f".into())] }),
+                    (Connector::Or, Node::Command("echo failed".into())),
+                ],
+            }]
+        );
+        assert_eq!(parse("make && make install").unwrap(), vec![Node::Command("make && make install".into())]);
+    }
+
+    #[test]
+    fn test_parse_groups() {
+        let nodes = parse("( cd build; make ) > log 2>&1; { echo a; echo b; } >>out").unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(
+            nodes[0],
+            Node::Subshell {
+                body: vec![Node::Command("cd build".into()), Node::Command("make".into())],
+                redirects: vec![
+                    Redirect { fd: 1, target: RedirectTarget::Write("log".into()) },
+                    Redirect { fd: 2, target: RedirectTarget::Duplicate("1".into()) },
+                ],
+            }
+        );
+        let Node::Group { body, redirects } = &nodes[1] else {
+            panic!("expected a group node");
+        };
+        assert_eq!(body.len(), 2);
+        assert_eq!(redirects, &vec![Redirect { fd: 1, target: RedirectTarget::Append("out".into()) }]);
+        let Node::Group { redirects, .. } = &parse("{ echo a; } >| out").unwrap()[0] else {
+            panic!("expected a group node");
+        };
+        assert_eq!(redirects, &vec![Redirect { fd: 1, target: RedirectTarget::Clobber("out".into()) }]);
+
+        assert!(parse("{ echo a }").is_err());
+        assert!(parse("( )").is_err());
+        assert!(parse("(echo a) echo b").is_err());
+    }
+
+    #[test]
+    fn test_parse_select() {
+        let nodes = parse("select env in dev 'prod eu'\ndo\n  echo $env; break\ndone").unwrap();
+        assert_eq!(
+            nodes[0],
+            Node::Select {
+                name: "env".into(),
+                words: vec!["dev".into(), "'prod eu'".into()],
+                body: vec![Node::Command("echo $env".into()), Node::Command("break".into())],
+            }
+        );
+
+        assert!(parse("select 1x in a; do echo; done").is_err());
+        assert!(parse("select x in a; echo; done").is_err());
+        assert!(parse("select x in a; do echo").is_err());
+    }
+
+    #[test]
+    fn test_parse_coproc() {
+        let nodes = parse("coproc PY { python3 -iu; }; echo started").unwrap();
+        assert_eq!(nodes[0], Node::Coproc { name: "PY".into(), body: "python3 -iu;".into() });
+        assert_eq!(nodes[1], Node::Command("echo started".into()));
+        assert_eq!(parse("coproc bc -l").unwrap()[0], Node::Coproc { name: "COPROC".into(), body: "bc -l".into() });
+        assert_eq!(parse("coproc { cat; }").unwrap()[0], Node::Coproc { name: "COPROC".into(), body: "cat;".into() });
+
+        assert!(parse("coproc 1x { cat; }").is_err());
+        assert!(parse("coproc X { cat").is_err());
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(unquote("\"a b\"'c'\\d"), "a bcd");
+        assert_eq!(pattern_from_word("'*'.txt"), "\\*.txt");
+        assert_eq!(pattern_from_word("*.txt"), "*.txt");
+        assert_eq!(fields(" a  \"b c\"'d e'f\\ g "), vec!["a", "\"b c\"'d e'f\\ g"]);
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use crate::shell::command_parser::{CommandParser, Redirection, SimpleCommand};
+use crate::shell::wizard::quotes_balanced;
+use crate::utils::path_utils::lookup;
+
+const FIND_TEMPLATE: &str = "You turn descriptions of files into a single GNU find invocation. Start from `.` unless a directory is named, use -iname for name patterns, -mtime/-mmin for ages (-mtime -7 is the last week), -size with k, M or G suffixes (+1M is over 1MB) and -type f for files. Never delete, execute or write anything: no -delete, -exec, -ok or -fprint, no pipes and no redirections. Respond ONLY with the command.";
+
+const FD_TEMPLATE: &str = "You turn descriptions of files into a single fd invocation. Use -e for extensions, -t f for files, --changed-within for ages (1week, 2d), -S for sizes (+1m is over 1MB), -H to include hidden files and -g for glob patterns; the pattern, if any, comes before the directory. Never run or write anything: no -x, -X, no pipes and no redirections. Respond ONLY with the command.";
+
+// find options and how many values each takes. Actions that delete, run or
+// write files are left out, so the validation rejects them.
+const FIND_OPTIONS: &[(&str, usize)] = &[
+    ("-name", 1), ("-iname", 1), ("-path", 1), ("-ipath", 1), ("-wholename", 1), ("-iwholename", 1),
+    ("-regex", 1), ("-iregex", 1), ("-regextype", 1), ("-lname", 1), ("-ilname", 1),
+    ("-type", 1), ("-xtype", 1), ("-size", 1), ("-perm", 1), ("-user", 1), ("-group", 1),
+    ("-uid", 1), ("-gid", 1), ("-links", 1), ("-inum", 1), ("-samefile", 1), ("-fstype", 1),
+    ("-mtime", 1), ("-mmin", 1), ("-atime", 1), ("-amin", 1), ("-ctime", 1), ("-cmin", 1),
+    ("-newer", 1), ("-anewer", 1), ("-cnewer", 1), ("-newermt", 1), ("-newerat", 1), ("-newerct", 1),
+    ("-maxdepth", 1), ("-mindepth", 1), ("-printf", 1),
+    ("-empty", 0), ("-executable", 0), ("-readable", 0), ("-writable", 0), ("-nouser", 0), ("-nogroup", 0),
+    ("-daystart", 0), ("-depth", 0), ("-xdev", 0), ("-mount", 0), ("-follow", 0), ("-prune", 0),
+    ("-print", 0), ("-print0", 0), ("-ls", 0), ("-true", 0), ("-false", 0),
+    ("-not", 0), ("!", 0), ("-a", 0), ("-and", 0), ("-o", 0), ("-or", 0), ("(", 0), (")", 0),
+    ("-L", 0), ("-H", 0), ("-P", 0),
+];
+
+// fd options and how many values each takes; -x/-X (run a command) are left out
+const FD_OPTIONS: &[(&str, usize)] = &[
+    ("-e", 1), ("--extension", 1), ("-t", 1), ("--type", 1), ("-S", 1), ("--size", 1),
+    ("--changed-within", 1), ("--changed-before", 1), ("--newer", 1), ("--older", 1),
+    ("-d", 1), ("--max-depth", 1), ("--min-depth", 1), ("--exact-depth", 1),
+    ("-E", 1), ("--exclude", 1), ("-o", 1), ("--owner", 1), ("--max-results", 1),
+    ("-H", 0), ("--hidden", 0), ("-I", 0), ("--no-ignore", 0), ("-u", 0), ("--unrestricted", 0),
+    ("-s", 0), ("--case-sensitive", 0), ("-i", 0), ("--ignore-case", 0), ("-g", 0), ("--glob", 0),
+    ("-F", 0), ("--fixed-strings", 0), ("-a", 0), ("--absolute-path", 0), ("-l", 0), ("--list-details", 0),
+    ("-L", 0), ("--follow", 0), ("-p", 0), ("--full-path", 0), ("-0", 0), ("--print0", 0),
+    ("-1", 0), ("-q", 0), ("--quiet", 0), ("--prune", 0),
+];
+
+// The search tool to write commands for: fd when it is installed (Debian
+// names it fdfind), find otherwise
+pub fn tool() -> &'static str {
+    ["fd", "fdfind"].into_iter().find(|name| lookup(name).is_some()).unwrap_or("find")
+}
+
+pub fn template(tool: &str) -> &'static str {
+    if tool == "find" { FIND_TEMPLATE } else { FD_TEMPLATE }
+}
+
+// Checks the command is one find or fd invocation that only lists files,
+// with options the tool knows
+pub fn validate(command: &str) -> Result<()> {
+    if !quotes_balanced(command) {
+        return Err(anyhow::anyhow!("unbalanced quotes"));
+    }
+    let pipeline = CommandParser::parse(command)?;
+    let [stage] = pipeline.commands.as_slice() else {
+        return Err(anyhow::anyhow!("a search is one command, without pipes"));
+    };
+    if pipeline.background || stage.redirections.iter().any(|r| *r != Redirection::Pipe) {
+        return Err(anyhow::anyhow!("a search can't redirect or run in the background"));
+    }
+    match stage.program.as_str() {
+        "find" => check_options(stage, FIND_OPTIONS, |arg| arg.starts_with('-') || arg == "!" || arg == "(" || arg == ")"),
+        "fd" | "fdfind" => check_options(stage, FD_OPTIONS, |arg| arg.starts_with('-') && arg.len() > 1),
+        program => Err(anyhow::anyhow!("{} is not find or fd", program)),
+    }
+}
+
+fn check_options(stage: &SimpleCommand, known: &[(&str, usize)], is_option: impl Fn(&str) -> bool) -> Result<()> {
+    let mut args = stage.args.iter();
+    while let Some(arg) = args.next() {
+        // find's `\(` and `\!` escape them from the shell
+        let arg = arg.strip_prefix('\\').unwrap_or(arg);
+        if !is_option(arg) {
+            continue;
+        }
+        // fd also takes --option=value
+        let name = arg.split_once('=').map(|(name, _)| name).filter(|_| arg.starts_with("--")).unwrap_or(arg);
+        let (_, values) = known
+            .iter()
+            .find(|(option, _)| *option == name)
+            .with_context(|| format!("{} {} is not allowed in a search", stage.program, arg))?;
+        if name == arg {
+            for _ in 0..*values {
+                args.next().with_context(|| format!("{} needs a value", arg))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("find . -type f -iname '*.rs' -mtime -7 -size +1M").is_ok());
+        assert!(validate("find src \\( -name '*.rs' -o -name '*.toml' \\) -not -path './target/*'").is_ok());
+        assert!(validate("fd -e rs --changed-within 1week -S +1m . src").is_ok());
+        assert!(validate("fd --extension=rs -t f").is_ok());
+        assert!(validate("find . -name '*.tmp' -delete").is_err());
+        assert!(validate("find . -name x -exec rm {} ;").is_err());
+        assert!(validate("fd -e log -x rm").is_err());
+        assert!(validate("find . -name '*.rs' | xargs wc -l").is_err());
+        assert!(validate("find . -name '*.rs' > list").is_err());
+        assert!(validate("find . -mtime").is_err());
+        assert!(validate("ls -R").is_err());
+    }
+}
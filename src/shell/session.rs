@@ -0,0 +1,208 @@
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
+use crate::llm::context_manager::SavedContext;
+use crate::shell::variables::Value;
+use crate::utils::path_utils;
+pub use super::job_control::SavedJob;
+
+// Older sessions beyond this count are pruned when a new one starts
+const MAX_SESSIONS: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionState {
+    pub id: String,
+    pub pid: u32,
+    pub started: u64,
+    pub updated: u64,
+    pub cwd: String,
+    pub dir_stack: Vec<String>,
+    pub variables: Vec<(String, String)>,
+    pub model: String,
+    #[serde(default)]
+    pub workspace: Option<String>,
+    pub recent_commands: Vec<String>,
+    // Shell variables and arrays that aren't exported
+    #[serde(default)]
+    pub shell_variables: Vec<(String, Value)>,
+    // The conversation with the model so far
+    #[serde(default)]
+    pub context: SavedContext,
+    pub clean_exit: bool,
+    // Background jobs still running when the session was saved
+    #[serde(default)]
+    pub jobs: Vec<SavedJob>,
+}
+
+impl SessionState {
+    // A session that didn't exit cleanly and whose process is gone has crashed
+    pub fn crashed(&self) -> bool {
+        !self.clean_exit && !self.is_running()
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.clean_exit && kill(Pid::from_raw(self.pid as i32), None).is_ok()
+    }
+
+    pub fn status(&self) -> &'static str {
+        if self.clean_exit {
+            "exited"
+        } else if self.is_running() {
+            "running"
+        } else {
+            "crashed"
+        }
+    }
+}
+
+pub struct SessionManager {
+    id: String,
+    started: u64,
+    // Variables present at startup; only changes made during the session are saved
+    initial_env: HashMap<String, String>,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        let started = now();
+        SessionManager {
+            id: format!("{}-{}", started, std::process::id()),
+            started,
+            initial_env: std::env::vars().collect(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn sessions_dir() -> Result<PathBuf> {
+        Ok(path_utils::state_dir()?.join("sessions"))
+    }
+
+    // Environment variables set or changed since startup, sorted by name
+    pub fn changed_env(&self) -> Vec<(String, String)> {
+        let mut variables: Vec<(String, String)> = std::env::vars()
+            .filter(|(key, value)| self.initial_env.get(key) != Some(value))
+            .collect();
+        variables.sort();
+        variables
+    }
+
+    pub fn capture(&self, cwd: &str, dir_stack: &[PathBuf], recent_commands: &[String], model: &str, workspace: Option<&str>, clean_exit: bool) -> SessionState {
+        let variables = self.changed_env();
+
+        SessionState {
+            id: self.id.clone(),
+            pid: std::process::id(),
+            started: self.started,
+            updated: now(),
+            cwd: cwd.to_string(),
+            dir_stack: dir_stack.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+            variables,
+            model: model.to_string(),
+            workspace: workspace.map(|w| w.to_string()),
+            recent_commands: recent_commands.to_vec(),
+            shell_variables: Vec::new(),
+            context: SavedContext::default(),
+            clean_exit,
+            jobs: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, state: &SessionState) -> Result<()> {
+        let dir = Self::sessions_dir()?;
+        fs::create_dir_all(&dir)?;
+
+        // Write then rename so a crash mid-save never leaves a truncated file
+        let path = dir.join(format!("{}.json", state.id));
+        let tmp = dir.join(format!("{}.json.tmp", state.id));
+        // Only readable by you, as it holds variables and the conversation
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)?
+            .write_all(serde_json::to_string_pretty(state)?.as_bytes())?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    // Most recently updated first
+    pub fn list() -> Result<Vec<SessionState>> {
+        let dir = Self::sessions_dir()?;
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let mut sessions: Vec<SessionState> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().map(|ext| ext == "json").unwrap_or(false))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str(&content).ok())
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.updated));
+        Ok(sessions)
+    }
+
+    // An empty id selects the most recent session other than this one
+    pub fn find(&self, id: &str) -> Result<SessionState> {
+        Self::list()?
+            .into_iter()
+            .filter(|session| session.id != self.id)
+            .find(|session| id.is_empty() || session.id.starts_with(id))
+            .ok_or_else(|| match id {
+                "" => anyhow::anyhow!("no previous session to restore"),
+                _ => anyhow::anyhow!("no session matching '{}'", id),
+            })
+    }
+
+    // The session this process ran before exec'ing itself: same pid, another id
+    pub fn predecessor(&self) -> Option<SessionState> {
+        Self::list()
+            .ok()?
+            .into_iter()
+            .find(|session| session.id != self.id && session.pid == std::process::id())
+    }
+
+    // Only the most recent previous session is reported, so an old crash isn't nagged about forever
+    pub fn last_crashed(&self) -> Option<SessionState> {
+        Self::list()
+            .ok()?
+            .into_iter()
+            .find(|session| session.id != self.id)
+            .filter(|session| session.crashed())
+    }
+
+    pub fn prune(&self) -> Result<()> {
+        let dir = Self::sessions_dir()?;
+        for session in Self::list()?.iter().skip(MAX_SESSIONS) {
+            if !session.is_running() {
+                fs::remove_file(dir.join(format!("{}.json", session.id)))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let dir = Self::sessions_dir()?;
+        fs::remove_file(dir.join(format!("{}.json", id)))
+            .with_context(|| format!("Failed to remove session {}", id))
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -1,18 +1,67 @@
 use anyhow::{Result, Context};
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 use log::debug;
+use crate::shell::variables;
+use crate::utils::path_utils;
+
+// How the shell was started: as a login shell (a '-' in front of argv[0],
+// as login(1) and sshd do, or -l/--login) and which startup files to skip
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Startup {
+    pub login: bool,
+    // --norc skips the rc files, --noprofile the login profiles
+    pub norc: bool,
+    pub noprofile: bool,
+}
+
+impl Startup {
+    pub fn from_args<S: AsRef<str>>(args: &[S]) -> Self {
+        let arg0 = args.first().map(|arg| arg.as_ref()).unwrap_or("");
+        let has = |flags: &[&str]| args.iter().skip(1).any(|arg| flags.contains(&arg.as_ref()));
+        Startup {
+            login: arg0.starts_with('-') || has(&["-l", "--login"]),
+            norc: has(&["--norc"]),
+            noprofile: has(&["--noprofile"]),
+        }
+    }
+
+    pub fn current() -> Self {
+        Self::from_args(&env::args().collect::<Vec<_>>())
+    }
+
+    // The startup files in the order they are read: the login profiles
+    // (/etc/profile, ~/.profile, then ~/.bash_profile or else ~/.bash_login)
+    // for login shells, then the rc files (/etc/bashrc, ~/.bashrc and
+    // ~/.llm_shellrc) for every shell
+    pub fn files(&self, home: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        if self.login && !self.noprofile {
+            files.push(PathBuf::from("/etc/profile"));
+            files.push(home.join(".profile"));
+            let bash_profile = home.join(".bash_profile");
+            files.push(if bash_profile.exists() { bash_profile } else { home.join(".bash_login") });
+        }
+        if !self.norc {
+            files.push(PathBuf::from("/etc/bashrc"));
+            files.push(home.join(".bashrc"));
+            files.push(home.join(".llm_shellrc"));
+        }
+        files
+    }
+}
 
 pub struct Environment {
     env_vars: std::collections::HashMap<String, String>,
-    is_login_shell: bool,
+    startup: Startup,
 }
 
 impl Environment {
-    pub fn new(is_login_shell: bool) -> Self {
+    pub fn new(startup: Startup) -> Self {
         Environment {
             env_vars: std::collections::HashMap::new(),
-            is_login_shell,
+            startup,
         }
     }
     
@@ -20,13 +69,8 @@ impl Environment {
         // Set basic environment variables
         self.set_default_env_vars();
         
-        // Process profile files for login shells
-        if self.is_login_shell {
-            self.process_login_files()?;
-        }
-        
-        // Process rc files for all shells
-        self.process_rc_files()?;
+        // Profiles for login shells, then rc files
+        self.process_startup_files()?;
         
         // Apply all environment variables
         self.apply_env_vars();
@@ -62,61 +106,16 @@ impl Environment {
         self.env_vars.insert("TERM".to_string(), "xterm-256color".to_string());
     }
     
-    fn process_login_files(&mut self) -> Result<()> {
-        debug!("Processing login files");
-        
-        // Process /etc/profile
-        if let Ok(content) = fs::read_to_string("/etc/profile") {
-            self.parse_env_file(&content);
-        }
-        
-        // Process ~/.profile
-        let home = dirs::home_dir().context("Could not determine home directory")?;
-        let profile_path = home.join(".profile");
-        if let Ok(content) = fs::read_to_string(profile_path) {
-            self.parse_env_file(&content);
-        }
-        
-        // Process ~/.bash_profile or ~/.bash_login if they exist
-        let bash_profile = home.join(".bash_profile");
-        let bash_login = home.join(".bash_login");
-        
-        if bash_profile.exists() {
-            if let Ok(content) = fs::read_to_string(bash_profile) {
-                self.parse_env_file(&content);
-            }
-        } else if bash_login.exists() {
-            if let Ok(content) = fs::read_to_string(bash_login) {
-                self.parse_env_file(&content);
-            }
-        }
-        
-        Ok(())
-    }
-    
-    fn process_rc_files(&mut self) -> Result<()> {
-        debug!("Processing rc files");
-        
-        // Process /etc/bashrc
-        if let Ok(content) = fs::read_to_string("/etc/bashrc") {
-            self.parse_env_file(&content);
-        }
-        
-        // Process ~/.bashrc
+    // Reads the startup files in order, so a later file overrides what an
+    // earlier one sets
+    fn process_startup_files(&mut self) -> Result<()> {
         let home = dirs::home_dir().context("Could not determine home directory")?;
-        let bashrc_path = home.join(".bashrc");
-        if let Ok(content) = fs::read_to_string(bashrc_path) {
-            self.parse_env_file(&content);
-        }
-        
-        // Process ~/.llm_shellrc if it exists
-        let llm_shellrc = home.join(".llm_shellrc");
-        if llm_shellrc.exists() {
-            if let Ok(content) = fs::read_to_string(llm_shellrc) {
+        for path in self.startup.files(&home) {
+            if let Ok(content) = fs::read_to_string(&path) {
+                debug!("Processing {}", path.display());
                 self.parse_env_file(&content);
             }
         }
-        
         Ok(())
     }
     
@@ -134,16 +133,73 @@ impl Environment {
                 let parts: Vec<&str> = line["export ".len()..].splitn(2, '=').collect();
                 if parts.len() == 2 {
                     let key = parts[0].trim();
-                    let value = parts[1].trim().trim_matches('"').trim_matches('\'');
-                    self.env_vars.insert(key.to_string(), value.to_string());
+                    let value = parts[1].trim();
+                    // Single-quoted values are taken as they are
+                    let value = if value.starts_with('\'') {
+                        value.trim_matches('\'').to_string()
+                    } else {
+                        let value = path_utils::expand_tilde(value.trim_matches('"'));
+                        variables::expand(&value, |name| self.lookup(name), false).unwrap_or(value)
+                    };
+                    self.env_vars.insert(key.to_string(), value);
                 }
             }
         }
     }
     
+    // Variables in values come from what the files set so far or the environment
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.env_vars.get(name).cloned().or_else(|| env::var(name).ok())
+    }
+    
     fn apply_env_vars(&self) {
         for (key, value) in &self.env_vars {
             env::set_var(key, value);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startup_args() {
+        assert_eq!(Startup::from_args(&["-llm-shell"]), Startup { login: true, norc: false, noprofile: false });
+        assert_eq!(Startup::from_args(&["llm-shell", "--login", "--norc"]), Startup { login: true, norc: true, noprofile: false });
+        assert_eq!(Startup::from_args(&["llm-shell", "-l", "--noprofile"]), Startup { login: true, norc: false, noprofile: true });
+        assert_eq!(Startup::from_args(&["llm-shell"]), Startup::default());
+    }
+
+    #[test]
+    fn test_startup_files() {
+        let home = env::temp_dir().join(format!("llm-shell-startup-{}", std::process::id()));
+        fs::create_dir_all(&home).unwrap();
+        let names = |startup: Startup| -> Vec<String> {
+            startup.files(&home).iter().map(|path| path.to_string_lossy().replace(&*home.to_string_lossy(), "~")).collect()
+        };
+
+        let login = Startup { login: true, ..Startup::default() };
+        assert_eq!(
+            names(login),
+            ["/etc/profile", "~/.profile", "~/.bash_login", "/etc/bashrc", "~/.bashrc", "~/.llm_shellrc"]
+        );
+        fs::write(home.join(".bash_profile"), "").unwrap();
+        assert_eq!(names(login)[2], "~/.bash_profile");
+        assert_eq!(names(Startup::default()), ["/etc/bashrc", "~/.bashrc", "~/.llm_shellrc"]);
+        assert_eq!(names(Startup { noprofile: true, ..login }), ["/etc/bashrc", "~/.bashrc", "~/.llm_shellrc"]);
+        assert_eq!(names(Startup { norc: true, ..login })[..2], ["/etc/profile", "~/.profile"]);
+        assert!(names(Startup { norc: true, noprofile: true, ..login }).is_empty());
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_later_files_override() {
+        let mut environment = Environment::new(Startup::default());
+        environment.parse_env_file("export EDITOR=vi\nexport LLM_SHELL_TEST_BIN=/opt/bin");
+        environment.parse_env_file("# rc\nexport EDITOR=\"nvim\"\nexport LLM_SHELL_TEST_PATH=\"${LLM_SHELL_TEST_BIN}:$LLM_SHELL_TEST_BIN/x\"\nexport RAW='$HOME'");
+        assert_eq!(environment.env_vars["EDITOR"], "nvim");
+        assert_eq!(environment.env_vars["LLM_SHELL_TEST_PATH"], "/opt/bin:/opt/bin/x");
+        assert_eq!(environment.env_vars["RAW"], "$HOME");
+    }
+}
@@ -41,6 +41,16 @@ impl SignalHandler {
         Ok(())
     }
     
+    // Gives the signals handled above their default handling back and unblocks
+    // everything, for a program that replaces the shell with exec
+    pub fn reset() -> Result<(), nix::Error> {
+        let default = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+        for sig in [Signal::SIGINT, Signal::SIGTSTP, Signal::SIGCHLD] {
+            unsafe { signal::sigaction(sig, &default)? };
+        }
+        SigSet::all().thread_unblock()
+    }
+    
     extern "C" fn handle_sigint(_: i32) {
         INTERRUPT_RECEIVED.store(true, Ordering::SeqCst);
         // Print a newline to ensure the next prompt appears on a fresh line
@@ -0,0 +1,342 @@
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+use std::process::Stdio;
+use tokio::process::Command;
+use crate::shell::page;
+
+// Output longer than this isn't sent to the model when the layout isn't recognized
+pub const MAX_MODEL_LINES: usize = 200;
+
+// Keys longer than this many words are more likely a sentence with a colon
+const MAX_KEY_WORDS: usize = 5;
+
+lazy_static! {
+    // `Model name:   Intel...`, as lscpu, hostnamectl and friends print
+    static ref COLON_RE: Regex = Regex::new(r"^\s*([A-Za-z][\w .()/-]*?)\s*:(?:\s+(.*))?$").unwrap();
+    // `NAME=value`, as env and os-release
+    static ref EQUALS_RE: Regex = Regex::new(r"^\s*([A-Za-z_][\w.-]*)=(.*)$").unwrap();
+    static ref NUMBER_RE: Regex = Regex::new(r"^-?(0|[1-9][0-9]*)(\.[0-9]+)?$").unwrap();
+}
+
+// Command output with its layout worked out
+#[derive(Debug, Clone, PartialEq)]
+pub enum Structured {
+    // A table with a header row, like df or ps print
+    Records { columns: Vec<String>, rows: Vec<Vec<String>> },
+    // One value per key, like lscpu prints
+    Fields(Vec<(String, String)>),
+    // One value per line, like ls prints when piped
+    List(Vec<String>),
+}
+
+// Runs a command with `sh -c` and returns its stdout and exit status.
+// Errors go to the terminal rather than into the data.
+pub async fn capture(command: &str) -> Result<(String, i32)> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("failed to start sh")?;
+    Ok((String::from_utf8_lossy(&output.stdout).to_string(), output.status.code().unwrap_or(1)))
+}
+
+// `COMMAND | as-json [| REST]`: the post-processor, the command before it
+// and what its output is piped into after it, if anything
+pub fn post_processor(input: &str) -> Option<(&'static str, &str, Option<&str>)> {
+    let pipes = pipes(input);
+    for (n, &pipe) in pipes.iter().enumerate() {
+        let end = pipes.get(n + 1).copied().unwrap_or(input.len());
+        let Some(name) = ["as-json", "as-table"].into_iter().find(|name| input[pipe + 1..end].trim() == *name) else {
+            continue;
+        };
+        let command = input[..pipe].trim();
+        let rest = pipes.get(n + 1).map(|&next| input[next + 1..].trim());
+        if command.is_empty() || rest == Some("") {
+            return None;
+        }
+        return Some((name, command, rest));
+    }
+    None
+}
+
+// Where the unquoted `|`s are, leaving out `||`
+fn pipes(input: &str) -> Vec<usize> {
+    let mut quote = None;
+    let mut pipes = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if q == c => quote = None,
+            (Some('"') | None, '\\') => {
+                chars.next();
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '|') if chars.peek().map(|(_, next)| *next) == Some('|') => {
+                chars.next();
+            }
+            (None, '|') => pipes.push(i),
+            _ => {}
+        }
+    }
+    pipes
+}
+
+// Works out the layout of the output without the model: JSON, `key: value`
+// or `KEY=value` lines, a table of aligned columns, or one word per line.
+// None when it's none of those.
+pub fn detect(text: &str) -> Option<Structured> {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.is_empty() {
+        return None;
+    }
+    if let Ok(value) = serde_json::from_str::<Value>(text) {
+        return Some(from_json(value));
+    }
+    key_values(&lines)
+        .or_else(|| aligned_table(&lines))
+        .or_else(|| lines.iter().all(|line| line.split_whitespace().count() == 1).then(|| Structured::List(lines.iter().map(|line| line.trim().to_string()).collect())))
+}
+
+fn from_json(value: Value) -> Structured {
+    let text = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    match value {
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+            let mut columns: Vec<String> = Vec::new();
+            for item in &items {
+                for key in item.as_object().unwrap().keys() {
+                    if !columns.contains(key) {
+                        columns.push(key.clone());
+                    }
+                }
+            }
+            let rows = items
+                .iter()
+                .map(|item| columns.iter().map(|column| item.get(column).map(text).unwrap_or_default()).collect())
+                .collect();
+            Structured::Records { columns, rows }
+        }
+        Value::Array(items) => Structured::List(items.iter().map(text).collect()),
+        Value::Object(fields) => Structured::Fields(fields.iter().map(|(key, value)| (key.clone(), text(value))).collect()),
+        other => Structured::List(vec![text(&other)]),
+    }
+}
+
+fn key_values(lines: &[&str]) -> Option<Structured> {
+    for re in [&*COLON_RE, &*EQUALS_RE] {
+        let fields: Option<Vec<(String, String)>> = lines
+            .iter()
+            .map(|line| {
+                let captures = re.captures(line)?;
+                let key = captures[1].trim();
+                (key.split_whitespace().count() <= MAX_KEY_WORDS).then_some(())?;
+                let value = captures.get(2).map(|value| value.as_str().trim()).unwrap_or("");
+                Some((key.to_string(), unquote(value).to_string()))
+            })
+            .collect();
+        if let Some(fields) = fields {
+            return Some(Structured::Fields(fields));
+        }
+    }
+    None
+}
+
+// os-release style values are quoted
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+// A header line and rows whose columns line up: a column starts wherever
+// some line has text right after a position that is blank on every line.
+// A column without a header of its own or without any values belongs to
+// the one before it, as with "Mounted on" or a command with arguments.
+fn aligned_table(lines: &[&str]) -> Option<Structured> {
+    if lines.len() < 2 {
+        return None;
+    }
+    let lines: Vec<Vec<char>> = lines.iter().map(|line| line.trim_end().chars().collect()).collect();
+    let width = lines.iter().map(Vec::len).max().unwrap_or(0);
+    let blank = |position: usize| lines.iter().all(|line| line.get(position).is_none_or(|c| c.is_whitespace()));
+    let mut starts: Vec<usize> = (0..width).filter(|&position| !blank(position) && (position == 0 || blank(position - 1))).collect();
+
+    let cell = |line: &[char], starts: &[usize], column: usize| -> String {
+        let start = starts[column].min(line.len());
+        let end = starts.get(column + 1).copied().unwrap_or(line.len()).min(line.len());
+        line[start..end].iter().collect::<String>().trim().to_string()
+    };
+    let mut column = 1;
+    while column < starts.len() {
+        let headless = cell(&lines[0], &starts, column).is_empty();
+        let empty = lines[1..].iter().all(|line| cell(line, &starts, column).is_empty());
+        if headless || empty {
+            starts.remove(column);
+        } else {
+            column += 1;
+        }
+    }
+    if starts.len() < 2 {
+        return None;
+    }
+    let columns = (0..starts.len()).map(|column| cell(&lines[0], &starts, column)).collect();
+    let rows = lines[1..].iter().map(|line| (0..starts.len()).map(|column| cell(line, &starts, column)).collect()).collect();
+    Some(Structured::Records { columns, rows })
+}
+
+// The records in a CSV answer already checked with `page::csv_table`
+pub fn from_csv(csv: &str) -> Result<Structured> {
+    let mut lines = csv.lines();
+    let columns = page::fields(lines.next().unwrap_or(""))?;
+    let rows = lines.map(page::fields).collect::<Result<_>>()?;
+    Ok(Structured::Records { columns, rows })
+}
+
+// JSON for jq: an array with an object per row, an object of the fields or
+// an array of the values. Numbers become JSON numbers and keys become
+// lowercase identifiers, in the order the output had them.
+pub fn to_json(structured: &Structured) -> String {
+    let value = |text: &str| if NUMBER_RE.is_match(text) { text.to_string() } else { Value::String(text.to_string()).to_string() };
+    let object = |keys: &[String], values: &[String]| {
+        let fields: Vec<String> = keys.iter().zip(values).map(|(key, text)| format!("{}: {}", Value::String(key.clone()), value(text))).collect();
+        format!("{{{}}}", fields.join(", "))
+    };
+    match structured {
+        Structured::Records { columns, rows } => {
+            let keys = keys(columns);
+            let rows: Vec<String> = rows.iter().map(|row| format!("  {}", object(&keys, row))).collect();
+            format!("[\n{}\n]\n", rows.join(",\n"))
+        }
+        Structured::Fields(fields) => {
+            let (names, values): (Vec<String>, Vec<String>) = fields.iter().cloned().unzip();
+            let keys = keys(&names);
+            let fields: Vec<String> = keys.iter().zip(&values).map(|(key, text)| format!("  {}: {}", Value::String(key.clone()), value(text))).collect();
+            format!("{{\n{}\n}}\n", fields.join(",\n"))
+        }
+        Structured::List(items) => {
+            let items: Vec<String> = items.iter().map(|item| format!("  {}", value(item))).collect();
+            format!("[\n{}\n]\n", items.join(",\n"))
+        }
+    }
+}
+
+// Column names as JSON keys: "Mounted on" is mounted_on, "Use%" is use, and
+// a repeated or empty name gets a number
+fn keys(names: &[String]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        let words: Vec<String> = name.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(str::to_lowercase).collect();
+        let mut key = if words.is_empty() { format!("column{}", i + 1) } else { words.join("_") };
+        if keys.contains(&key) {
+            key = format!("{}_{}", key, i + 1);
+        }
+        keys.push(key);
+    }
+    keys
+}
+
+// Aligned columns two spaces apart, with the header when there is one
+pub fn to_table(structured: &Structured) -> String {
+    let rows: Vec<Vec<String>> = match structured {
+        Structured::Records { columns, rows } => std::iter::once(columns.clone()).chain(rows.iter().cloned()).collect(),
+        Structured::Fields(fields) => fields.iter().map(|(key, value)| vec![key.clone(), value.clone()]).collect(),
+        Structured::List(items) => items.iter().map(|item| vec![item.clone()]).collect(),
+    };
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| rows.iter().filter_map(|row| row.get(column)).map(|cell| cell.chars().count()).max().unwrap_or(0))
+        .collect();
+    let mut table = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, &width)| format!("{:width$}", cell, width = width)).collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DF: &str = "\
+Filesystem      Size  Used Avail Use% Mounted on
+overlay          20G  7.5G   12G  39% /
+tmpfs            64M     0   64M   0% /dev
+/dev/sda1       100G   50G   50G  50% /etc/hosts
+";
+
+    #[test]
+    fn test_post_processor() {
+        assert_eq!(post_processor("df -h | as-json"), Some(("as-json", "df -h", None)));
+        assert_eq!(post_processor("ps aux | head |as-table | less -S"), Some(("as-table", "ps aux | head", Some("less -S"))));
+        assert_eq!(post_processor("df | as-json | jq '.[] | .use'"), Some(("as-json", "df", Some("jq '.[] | .use'"))));
+        assert_eq!(post_processor("echo 'x | as-json'"), None);
+        assert_eq!(post_processor("df || as-json"), None);
+        assert_eq!(post_processor("| as-json"), None);
+        assert_eq!(post_processor("df | as-json |"), None);
+    }
+
+    #[test]
+    fn test_aligned_table() {
+        let Some(Structured::Records { columns, rows }) = detect(DF) else {
+            panic!("df output not recognized");
+        };
+        assert_eq!(columns, vec!["Filesystem", "Size", "Used", "Avail", "Use%", "Mounted on"]);
+        assert_eq!(rows[1], vec!["tmpfs", "64M", "0", "64M", "0%", "/dev"]);
+        let ps = "  PID TTY          TIME CMD\n    1 pts/0    00:00:00 bash -l\n   42 pts/0    00:00:01 vim notes.txt\n";
+        let Some(Structured::Records { columns, rows }) = detect(ps) else {
+            panic!("ps output not recognized");
+        };
+        assert_eq!(columns, vec!["PID", "TTY", "TIME", "CMD"]);
+        assert_eq!(rows[1], vec!["42", "pts/0", "00:00:01", "vim notes.txt"]);
+        assert_eq!(detect("hello world, this is\nsome text that wraps\n"), None);
+    }
+
+    #[test]
+    fn test_key_values() {
+        assert_eq!(
+            detect("Architecture:        x86_64\nCPU(s):              8\nModel name:          Intel Core\nFlags:\n"),
+            Some(Structured::Fields(vec![
+                ("Architecture".into(), "x86_64".into()),
+                ("CPU(s)".into(), "8".into()),
+                ("Model name".into(), "Intel Core".into()),
+                ("Flags".into(), "".into()),
+            ]))
+        );
+        assert_eq!(detect("NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\n"), Some(Structured::Fields(vec![("NAME".into(), "Ubuntu".into()), ("VERSION_ID".into(), "22.04".into())])));
+        assert!(!matches!(detect("drwxr-xr-x 2 root root 4096 Oct 16 12:30 bin\n-rw-r--r-- 1 root root 12 Oct 16 09:01 a\n"), Some(Structured::Fields(_))));
+        assert_eq!(detect("a.txt\nb.txt\n"), Some(Structured::List(vec!["a.txt".into(), "b.txt".into()])));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = to_json(&detect(DF).unwrap());
+        assert!(json.starts_with("[\n  {\"filesystem\": \"overlay\", \"size\": \"20G\", \"used\": \"7.5G\", \"avail\": \"12G\", \"use\": \"39%\", \"mounted_on\": \"/\"},\n"));
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value[1]["used"], 0);
+        assert_eq!(keys(&["Name".into(), "".into(), "name".into()]), vec!["name", "column2", "name_3"]);
+        assert_eq!(to_json(&Structured::List(vec!["1".into(), "01".into()])), "[\n  1,\n  \"01\"\n]\n");
+    }
+
+    #[test]
+    fn test_to_table() {
+        let structured = detect("[{\"name\": \"a\", \"size\": 10}, {\"name\": \"long\", \"extra\": null}]").unwrap();
+        assert_eq!(to_table(&structured), "name  size  extra\na     10\nlong\n");
+        assert_eq!(from_csv("name,size\n\"a, b\",1\n").unwrap(), Structured::Records { columns: vec!["name".into(), "size".into()], rows: vec![vec!["a, b".into(), "1".into()]] });
+    }
+}
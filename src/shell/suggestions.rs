@@ -1,6 +1,7 @@
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use std::collections::HashMap;
+use crate::config::CONFIG;
 
 pub struct SuggestionEngine {
     history: Vec<String>,
@@ -35,7 +36,7 @@ impl SuggestionEngine {
         matches.sort_by(|a, b| b.0.cmp(&a.0));
         matches.into_iter()
             .map(|(_, cmd)| cmd)
-            .take(3)
+            .take(CONFIG.suggestion_count)
             .collect()
     }
 }
\ No newline at end of file
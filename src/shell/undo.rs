@@ -0,0 +1,140 @@
+use anyhow::Result;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct UndoEntry {
+    pub command: String,
+    pub hint: String,
+}
+
+// File state captured before a destructive command runs, needed to build its inverse
+#[derive(Debug, Clone)]
+pub enum PriorState {
+    Modes(Vec<(String, u32)>),
+    Owners(Vec<(String, u32, u32)>),
+    Move { source: String, destination: String },
+}
+
+pub struct UndoStack {
+    entries: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    pub fn new() -> Self {
+        UndoStack { entries: Vec::new() }
+    }
+
+    pub fn capture(command: &str) -> Option<PriorState> {
+        let words = shellwords::split(command).ok()?;
+        let (program, args) = words.split_first()?;
+        let operands: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
+
+        match program.as_str() {
+            // Recursive changes can't be reverted file by file, so they get no rule-based hint
+            "chmod" if operands.len() >= 2 && !args.iter().any(|a| a == "-R") => {
+                let modes = operands[1..]
+                    .iter()
+                    .filter_map(|file| {
+                        fs::metadata(file.as_str())
+                            .ok()
+                            .map(|m| (file.to_string(), m.permissions().mode() & 0o7777))
+                    })
+                    .collect();
+                Some(PriorState::Modes(modes))
+            }
+            "chown" if operands.len() >= 2 && !args.iter().any(|a| a == "-R") => {
+                let owners = operands[1..]
+                    .iter()
+                    .filter_map(|file| {
+                        fs::metadata(file.as_str())
+                            .ok()
+                            .map(|m| (file.to_string(), m.uid(), m.gid()))
+                    })
+                    .collect();
+                Some(PriorState::Owners(owners))
+            }
+            "mv" if operands.len() == 2 && operands.len() == args.len() => {
+                let source = operands[0].to_string();
+                let destination = if Path::new(operands[1].as_str()).is_dir() {
+                    let name = Path::new(&source).file_name()?.to_string_lossy().to_string();
+                    Path::new(operands[1].as_str()).join(name).to_string_lossy().to_string()
+                } else {
+                    // Moving onto an existing file overwrites it, which can't be undone
+                    if Path::new(operands[1].as_str()).exists() {
+                        return None;
+                    }
+                    operands[1].to_string()
+                };
+                Some(PriorState::Move { source, destination })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn rule_hint(state: &PriorState) -> Option<String> {
+        let commands: Vec<String> = match state {
+            PriorState::Modes(modes) => modes
+                .iter()
+                .map(|(file, mode)| format!("chmod {:o} {}", mode, quote(file)))
+                .collect(),
+            PriorState::Owners(owners) => owners
+                .iter()
+                .map(|(file, uid, gid)| format!("chown {}:{} {}", uid, gid, quote(file)))
+                .collect(),
+            PriorState::Move { source, destination } => {
+                vec![format!("mv {} {}", quote(destination), quote(source))]
+            }
+        };
+
+        if commands.is_empty() {
+            None
+        } else {
+            Some(commands.join(" && "))
+        }
+    }
+
+    pub fn push(&mut self, command: &str, hint: &str) {
+        self.entries.push(UndoEntry {
+            command: command.to_string(),
+            hint: hint.to_string(),
+        });
+
+        if let Err(e) = Self::log(command, hint) {
+            log::warn!("Failed to write undo log: {}", e);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop()
+    }
+
+    pub fn entries(&self) -> &[UndoEntry] {
+        &self.entries
+    }
+
+    fn log(command: &str, hint: &str) -> Result<()> {
+        if let Some(home) = dirs::home_dir() {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(home.join(".llm_shell_undo.log"))?;
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            writeln!(file, "{}\t{}\t{}", timestamp, command, hint)?;
+        }
+        Ok(())
+    }
+}
+
+fn quote(value: &str) -> String {
+    if value.chars().all(|c| c.is_alphanumeric() || "/._-+,:@".contains(c)) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
@@ -0,0 +1,622 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use crate::shell::arithmetic;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Attributes {
+    pub readonly: bool,
+    pub integer: bool,
+    pub array: bool,
+}
+
+// Value of an assignment word: `x=1` or `x=(a b c)`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    Scalar(String),
+    Array(Vec<String>),
+}
+
+// Shell variables. Exported ones live in the process environment so children inherit them;
+// the rest (and all arrays) are only visible to the shell itself.
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    local: HashMap<String, String>,
+    arrays: HashMap<String, Vec<String>>,
+    attributes: HashMap<String, Attributes>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Variables {
+            local: HashMap::new(),
+            arrays: HashMap::new(),
+            attributes: HashMap::new(),
+        }
+    }
+
+    // Also resolves `name[index]`, `name[@]` and `name[*]` for arrays
+    pub fn get(&self, name: &str) -> Option<String> {
+        if let Some((array, index)) = name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+            let values = self.arrays.get(array)?;
+            return match index {
+                "@" | "*" => Some(values.join(" ")),
+                index => values.get(index.parse::<usize>().ok()?).cloned(),
+            };
+        }
+        if let Some(values) = self.arrays.get(name) {
+            return values.first().cloned();
+        }
+        self.local
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+    }
+
+    pub fn is_exported(name: &str) -> bool {
+        std::env::var_os(name).is_some()
+    }
+
+    pub fn attributes(&self, name: &str) -> Attributes {
+        self.attributes.get(name).copied().unwrap_or_default()
+    }
+
+    fn check_writable(&self, name: &str) -> Result<()> {
+        if self.attributes(name).readonly {
+            return Err(anyhow::anyhow!("{}: readonly variable", name));
+        }
+        Ok(())
+    }
+
+    // Assignment keeps the variable's export state, like `VAR=x` in bash
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        self.check_writable(name)?;
+        let attributes = self.attributes(name);
+        let value = if attributes.integer {
+            arithmetic::evaluate(value, &|name| self.get(name), false)?.to_string()
+        } else {
+            value.to_string()
+        };
+
+        if attributes.array {
+            // Assigning to an array without an index replaces its first element
+            let values = self.arrays.entry(name.to_string()).or_default();
+            match values.first_mut() {
+                Some(first) => *first = value,
+                None => values.push(value),
+            }
+        } else if Self::is_exported(name) {
+            std::env::set_var(name, value);
+        } else {
+            self.local.insert(name.to_string(), value);
+        }
+        Ok(())
+    }
+
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<()> {
+        match value {
+            Value::Scalar(value) => self.set(name, &value),
+            Value::Array(values) => {
+                self.check_writable(name)?;
+                self.local.remove(name);
+                std::env::remove_var(name);
+                self.attributes.entry(name.to_string()).or_default().array = true;
+                self.arrays.insert(name.to_string(), values);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn export(&mut self, name: &str, value: Option<&str>) -> Result<()> {
+        if self.arrays.contains_key(name) {
+            return Err(anyhow::anyhow!("export: {}: arrays cannot be exported", name));
+        }
+        if let Some(value) = value {
+            self.set(name, value)?;
+        }
+        let value = self.local.remove(name).or_else(|| std::env::var(name).ok());
+        std::env::set_var(name, value.unwrap_or_default());
+        Ok(())
+    }
+
+    // `export -n`: keep the value but stop passing it to children
+    pub fn unexport(&mut self, name: &str) {
+        if let Ok(value) = std::env::var(name) {
+            std::env::remove_var(name);
+            self.local.insert(name.to_string(), value);
+        }
+    }
+
+    pub fn unset(&mut self, name: &str) -> Result<()> {
+        self.check_writable(name)?;
+        self.local.remove(name);
+        self.arrays.remove(name);
+        self.attributes.remove(name);
+        std::env::remove_var(name);
+        Ok(())
+    }
+
+    pub fn set_attributes(&mut self, name: &str, update: impl FnOnce(&mut Attributes)) {
+        let mut attributes = self.attributes(name);
+        update(&mut attributes);
+        // `declare -a x` turns an existing scalar into a one-element array
+        if attributes.array && !self.arrays.contains_key(name) {
+            let value = self.local.remove(name).or_else(|| std::env::var(name).ok());
+            std::env::remove_var(name);
+            self.arrays.insert(name.to_string(), value.into_iter().collect());
+        }
+        if attributes == Attributes::default() {
+            self.attributes.remove(name);
+        } else {
+            self.attributes.insert(name.to_string(), attributes);
+        }
+    }
+
+    // Every variable, exported or not, sorted by name
+    pub fn all(&self) -> Vec<(String, String)> {
+        let mut vars: Vec<(String, String)> = std::env::vars()
+            .chain(self.local.iter().map(|(k, v)| (k.clone(), v.clone())))
+            .chain(self.arrays.iter().map(|(k, v)| (k.clone(), format!("({})", v.join(" ")))))
+            .collect();
+        vars.sort();
+        vars
+    }
+
+    // Variables only the shell sees, arrays included, sorted by name
+    pub fn unexported(&self) -> Vec<(String, Value)> {
+        let mut vars: Vec<(String, Value)> = self
+            .local
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::Scalar(v.clone())))
+            .chain(self.arrays.iter().map(|(k, v)| (k.clone(), Value::Array(v.clone()))))
+            .collect();
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+        vars
+    }
+
+    // A variable as `declare -p` prints it
+    pub fn describe(&self, name: &str) -> Option<String> {
+        let attributes = self.attributes(name);
+        let mut flags = String::new();
+        if attributes.array {
+            flags.push('a');
+        }
+        if attributes.integer {
+            flags.push('i');
+        }
+        if attributes.readonly {
+            flags.push('r');
+        }
+        if Self::is_exported(name) {
+            flags.push('x');
+        }
+        let flags = if flags.is_empty() { "--".to_string() } else { format!("-{}", flags) };
+
+        let value = match self.arrays.get(name) {
+            Some(values) => {
+                let items: Vec<String> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| format!("[{}]=\"{}\"", i, v))
+                    .collect();
+                format!("=({})", items.join(" "))
+            }
+            None => match self.get(name) {
+                Some(value) => format!("=\"{}\"", value.replace('"', "\\\"")),
+                None if self.attributes.contains_key(name) => String::new(),
+                None => return None,
+            },
+        };
+        Some(format!("declare {} {}{}", flags, name, value))
+    }
+}
+
+pub fn is_name(word: &str) -> bool {
+    let mut chars = word.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// `NAME=value` with a valid variable name
+pub fn parse_assignment(word: &str) -> Option<(&str, &str)> {
+    word.split_once('=').filter(|(name, _)| is_name(name))
+}
+
+// Groups already-split words into arguments, joining `name=(a b c)` back into one array value
+pub fn parse_arguments(words: &[String]) -> Vec<(String, Option<Value>)> {
+    let mut arguments = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let word = &words[i];
+        i += 1;
+        let Some((name, value)) = parse_assignment(word) else {
+            arguments.push((word.clone(), None));
+            continue;
+        };
+        let Some(first) = value.strip_prefix('(') else {
+            arguments.push((name.to_string(), Some(Value::Scalar(value.to_string()))));
+            continue;
+        };
+
+        let mut items = vec![first.to_string()];
+        while !items.last().map(|item| item.ends_with(')')).unwrap_or(false) && i < words.len() {
+            items.push(words[i].clone());
+            i += 1;
+        }
+        if let Some(last) = items.last_mut() {
+            if last.ends_with(')') {
+                last.pop();
+            }
+        }
+        items.retain(|item| !item.is_empty());
+        arguments.push((name.to_string(), Some(Value::Array(items))));
+    }
+    arguments
+}
+
+// Expands `~` and `~user` at the start of each unquoted word, and after the
+// `=` or a `:` of an assignment like `PATH=$PATH:~/bin`, in one pass over
+// shell text. `directory` answers for the name after the `~` ("" for the
+// user's own home); a name it doesn't know, or one with quotes in it, is
+// left as written. Directories with characters the parser would split on
+// are inserted single-quoted.
+pub fn expand_tildes(text: &str, directory: impl Fn(&str) -> Option<String>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut in_single = false;
+    let mut in_double = false;
+    // The unquoted word so far, to tell an assignment from other words
+    let mut word = String::new();
+    let mut assignment = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let quoted = in_single || in_double;
+        let prefix_start = word.is_empty() || (assignment && matches!(chars[i - 1], '=' | ':'));
+        if c == '~' && !quoted && prefix_start {
+            let end = (i + 1..chars.len())
+                .find(|&j| chars[j] == '/' || ends_word(chars[j]) || (assignment && chars[j] == ':'))
+                .unwrap_or(chars.len());
+            let name: String = chars[i + 1..end].iter().collect();
+            let plain = name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '+'));
+            if let Some(dir) = directory(&name).filter(|_| plain) {
+                if dir.chars().all(|c| c.is_alphanumeric() || "/._+-@%,:".contains(c)) {
+                    result.push_str(&dir);
+                } else {
+                    result.push_str(&quote(&dir));
+                }
+                word.push('~');
+                i = end;
+                continue;
+            }
+        }
+        match c {
+            '\\' if !in_single => {
+                result.push(c);
+                word.push(c);
+                if let Some(&next) = chars.get(i + 1) {
+                    result.push(next);
+                    word.push(next);
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            c if !quoted && ends_word(c) => {
+                word.clear();
+                assignment = false;
+                result.push(c);
+                i += 1;
+                continue;
+            }
+            '=' if !quoted && !assignment && is_name(&word) => assignment = true,
+            _ => {}
+        }
+        word.push(c);
+        result.push(c);
+        i += 1;
+    }
+    result
+}
+
+fn ends_word(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '|' | ';' | '&' | '<' | '>' | '(' | ')')
+}
+
+// Expands $NAME, ${NAME}, $?, $$ and $((...)) in one pass over shell text. Values are
+// inserted as they are and never rescanned; quotes and backslashes are left for
+// the parser, and nothing is expanded inside single quotes or after `\`.
+// `lookup` also answers for the special names "?" and "$". Unset variables
+// expand to nothing, or are an error with `nounset`.
+pub fn expand(text: &str, lookup: impl Fn(&str) -> Option<String>, nounset: bool) -> Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if !in_single => {
+                result.push(c);
+                if let Some((_, next)) = chars.next() {
+                    result.push(next);
+                }
+                continue;
+            }
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '$' if !in_single => {
+                if let Some((value, length)) = expand_reference(&text[i + 1..], &lookup, nounset)? {
+                    result.push_str(&value);
+                    while chars.peek().map(|(j, _)| *j <= i + length).unwrap_or(false) {
+                        chars.next();
+                    }
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        result.push(c);
+    }
+    Ok(result)
+}
+
+// The value of what a `$` refers to, given the text after it: $NAME, ${NAME},
+// $?, $$ or $((...)), with how many bytes of `rest` the reference takes. None
+// for a `$` that stays as written, like `$5`, `$(...)` or a lone `$`.
+pub fn expand_reference(rest: &str, lookup: &dyn Fn(&str) -> Option<String>, nounset: bool) -> Result<Option<(String, usize)>> {
+    if let Some(inner) = rest.strip_prefix("((") {
+        // The expression ends where the inner `(` closes, right before the outer one
+        let end = closing_paren(inner).filter(|&end| inner[end + 1..].starts_with(')'))
+            .ok_or_else(|| anyhow::anyhow!("unterminated arithmetic expansion: ${}", rest))?;
+        return Ok(Some((arithmetic::evaluate(&inner[..end], lookup, nounset)?.to_string(), end + 4)));
+    }
+    let reference = match rest.chars().next() {
+        Some('?') | Some('$') => Some((&rest[..1], 1)),
+        Some('{') => rest.find('}').map(|end| (&rest[1..end], end + 1)).filter(|(name, _)| is_reference(name)),
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            Some((&rest[..end], end))
+        }
+        _ => None,
+    };
+    let Some((name, length)) = reference else {
+        return Ok(None);
+    };
+    match lookup(name) {
+        Some(value) => Ok(Some((value, length))),
+        None if nounset => Err(anyhow::anyhow!("{}: unbound variable", name)),
+        None => Ok(Some((String::new(), length))),
+    }
+}
+
+// How a command substitution's output goes into text outside double quotes.
+// Inside them it is always inserted as is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Insert {
+    // Split into words, each quoted so the command parser takes it literally
+    Words,
+    // Quoted as a single word, for assignments, which aren't split
+    Word,
+}
+
+// Replaces `$(...)` and `` `...` `` outside single quotes with the output
+// `run` gives for the command inside, trailing newlines removed. Nested
+// substitutions are left to `run`, and `$((...))` to `expand`.
+pub fn substitute(text: &str, insert: Insert, mut run: impl FnMut(&str) -> Result<String>) -> Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let command = match c {
+            '\\' if !in_single => {
+                result.push(c);
+                result.extend(chars.next().map(|(_, next)| next));
+                continue;
+            }
+            '\'' if !in_double => {
+                in_single = !in_single;
+                None
+            }
+            '"' if !in_single => {
+                in_double = !in_double;
+                None
+            }
+            '$' if !in_single && text[i + 1..].starts_with('(') && !text[i + 1..].starts_with("((") => {
+                let end = closing_paren(&text[i + 2..]).map(|end| i + 2 + end)
+                    .ok_or_else(|| anyhow::anyhow!("unterminated command substitution: {}", &text[i..]))?;
+                Some((&text[i + 2..end], end))
+            }
+            '`' if !in_single => {
+                let end = text[i + 1..].find('`').map(|end| i + 1 + end)
+                    .ok_or_else(|| anyhow::anyhow!("unterminated command substitution: {}", &text[i..]))?;
+                Some((&text[i + 1..end], end))
+            }
+            _ => None,
+        };
+        let Some((command, end)) = command else {
+            result.push(c);
+            continue;
+        };
+        let output = run(command)?;
+        let output = output.trim_end_matches('\n');
+        match insert {
+            _ if in_double => result.push_str(output),
+            Insert::Words => result.push_str(&output.split_whitespace().map(quote).collect::<Vec<_>>().join(" ")),
+            Insert::Word => result.push_str(&quote(output)),
+        }
+        while chars.peek().map(|(j, _)| *j <= end).unwrap_or(false) {
+            chars.next();
+        }
+    }
+    Ok(result)
+}
+
+// Where the `)` closing a `$(` is, skipping quoted text and nested parentheses
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some('"'), '"') => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 0 => return Some(i),
+            (None, ')') => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Single-quotes a word, closing the quotes around any `'` it contains
+fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+// What `${...}` can hold: a name, an array element, or a special name
+fn is_reference(name: &str) -> bool {
+    if name == "?" || name == "$" {
+        return true;
+    }
+    match name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+        Some((array, index)) => is_name(array) && !index.is_empty(),
+        None => is_name(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_round_trip() {
+        let mut vars = Variables::new();
+        vars.set("LLMSH_TEST_LOCAL", "1").unwrap();
+        assert_eq!(vars.get("LLMSH_TEST_LOCAL").as_deref(), Some("1"));
+        assert!(!Variables::is_exported("LLMSH_TEST_LOCAL"));
+
+        vars.export("LLMSH_TEST_LOCAL", None).unwrap();
+        assert_eq!(std::env::var("LLMSH_TEST_LOCAL").as_deref(), Ok("1"));
+
+        vars.unexport("LLMSH_TEST_LOCAL");
+        assert!(!Variables::is_exported("LLMSH_TEST_LOCAL"));
+        assert_eq!(vars.get("LLMSH_TEST_LOCAL").as_deref(), Some("1"));
+
+        vars.unset("LLMSH_TEST_LOCAL").unwrap();
+        assert_eq!(vars.get("LLMSH_TEST_LOCAL"), None);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut vars = Variables::new();
+        vars.set("count", "4").unwrap();
+        vars.set_attributes("count", |a| a.integer = true);
+        vars.set("count", "count * 2 + 10 % 4 - -1").unwrap();
+        assert_eq!(vars.get("count").as_deref(), Some("11"));
+        assert!(vars.set("count", "1 / 0").is_err());
+        vars.set("count", "(1 + 2) * 3 == 9 && !0").unwrap();
+        assert_eq!(vars.get("count").as_deref(), Some("1"));
+
+        vars.set_attributes("count", |a| a.readonly = true);
+        assert!(vars.set("count", "1").is_err());
+        assert!(vars.unset("count").is_err());
+        assert_eq!(vars.describe("count").as_deref(), Some("declare -ir count=\"1\""));
+
+        vars.assign("list", Value::Array(vec!["a".into(), "b c".into()])).unwrap();
+        assert_eq!(vars.get("list").as_deref(), Some("a"));
+        assert_eq!(vars.get("list[1]").as_deref(), Some("b c"));
+        assert_eq!(vars.get("list[@]").as_deref(), Some("a b c"));
+        assert_eq!(vars.get("list[5]"), None);
+    }
+
+    // Expected values are what bash leaves for the parser for the same text
+    #[test]
+    fn test_expand_like_bash() {
+        let lookup = |name: &str| match name {
+            "VAR" => Some("short".to_string()),
+            "VAR_NAME" => Some("long".to_string()),
+            "DOLLAR" => Some("$VAR".to_string()),
+            "EMPTY" => Some(String::new()),
+            "xs[1]" => Some("b".to_string()),
+            "?" => Some("1".to_string()),
+            "$" => Some("4242".to_string()),
+            _ => None,
+        };
+        let cases = [
+            ("$VAR_NAME", "long"),
+            ("${VAR}_NAME", "short_NAME"),
+            ("$VAR.txt $VAR-x", "short.txt short-x"),
+            ("$DOLLAR", "$VAR"),
+            ("'$VAR' \"$VAR\"", "'$VAR' \"short\""),
+            ("\"it's $VAR\"", "\"it's short\""),
+            ("\\$VAR \\\\$VAR", "\\$VAR \\\\short"),
+            ("[$UNSET]", "[]"),
+            ("${EMPTY}x", "x"),
+            ("cost: $5, $ alone, $(date) and ${VAR:-x}", "cost: $5, $ alone, $(date) and ${VAR:-x}"),
+            ("$? $$ ${?}", "1 4242 1"),
+            ("${xs[1]} ${unterminated", "b ${unterminated"),
+            ("$VARé", "shorté"),
+            ("$((3 + 4)) \"$(( (1 + 2) * 2 ))\" '$((1))'", "7 \"6\" '$((1))'"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(expand(text, lookup, false).unwrap(), expected, "expanding {}", text);
+        }
+        assert_eq!(expand("$EMPTY '$UNSET'", lookup, true).unwrap(), " '$UNSET'");
+        assert_eq!(expand("a $UNSET b", lookup, true).unwrap_err().to_string(), "UNSET: unbound variable");
+    }
+
+    #[test]
+    fn test_substitute() {
+        let run = |command: &str| Ok(format!("<{}>\n\n", command));
+        assert_eq!(substitute("echo $(date) `id -u`", Insert::Word, run).unwrap(), "echo '<date>' '<id -u>'");
+        assert_eq!(substitute("ls $(echo a b) x", Insert::Words, run).unwrap(), "ls '<echo' 'a' 'b>' x");
+        assert_eq!(substitute("ls \"$(echo a b)\"", Insert::Words, run).unwrap(), "ls \"<echo a b>\"");
+        assert_eq!(substitute("X=$(echo a b)", Insert::Word, run).unwrap(), "X='<echo a b>'");
+        assert_eq!(substitute("X=$(whoami)", Insert::Word, |_| Ok("it's \"x\"".to_string())).unwrap(), "X='it'\\''s \"x\"'");
+        assert_eq!(substitute("echo $(dirname $(pwd)) '$(no)'", Insert::Word, run).unwrap(), "echo '<dirname $(pwd)>' '$(no)'");
+        assert_eq!(substitute("echo $(echo ')') $((1 + 2))", Insert::Word, run).unwrap(), "echo '<echo '\\'')'\\''>' $((1 + 2))");
+        assert!(substitute("echo $(date", Insert::Word, run).is_err());
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let words: Vec<String> = ["-a", "xs=(one", "two)", "n=3", "ys=()"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            parse_arguments(&words),
+            vec![
+                ("-a".to_string(), None),
+                ("xs".to_string(), Some(Value::Array(vec!["one".into(), "two".into()]))),
+                ("n".to_string(), Some(Value::Scalar("3".into()))),
+                ("ys".to_string(), Some(Value::Array(vec![]))),
+            ]
+        );
+        assert_eq!(parse_assignment("FOO=a=b"), Some(("FOO", "a=b")));
+        assert_eq!(parse_assignment("1X=2"), None);
+    }
+
+    #[test]
+    fn test_expand_tildes() {
+        let directory = |name: &str| match name {
+            "" => Some("/home/me".to_string()),
+            "bob" => Some("/home/bob".to_string()),
+            "odd" => Some("/home/my files".to_string()),
+            _ => None,
+        };
+        let expand = |text: &str| expand_tildes(text, directory);
+        assert_eq!(expand("cat ~/notes.txt ~"), "cat /home/me/notes.txt /home/me");
+        assert_eq!(expand("cp file ~bob/ && ls ~nobody"), "cp file /home/bob/ && ls ~nobody");
+        assert_eq!(expand("echo '~' \"~/x\" \\~ a~b"), "echo '~' \"~/x\" \\~ a~b");
+        assert_eq!(expand("ls >~/out|wc"), "ls >/home/me/out|wc");
+        assert_eq!(expand("PATH=$PATH:~/bin:~bob/bin cmd --dir=~"), "PATH=$PATH:/home/me/bin:/home/bob/bin cmd --dir=~");
+        assert_eq!(expand("cd ~odd/x"), "cd '/home/my files'/x");
+    }
+}
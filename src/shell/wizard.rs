@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+use crate::shell::command_parser::{CommandParser, SimpleCommand};
+use crate::utils::path_utils::find_executable;
+
+// A guided translation for a tool with a syntax small models often get wrong
+pub struct Wizard {
+    pub tool: &'static str,
+    // Asked in order; blank answers are left out of the request
+    pub questions: &'static [&'static str],
+    pub template: &'static str,
+}
+
+pub const WIZARDS: &[Wizard] = &[
+    Wizard {
+        tool: "ffmpeg",
+        questions: &["Input file", "Desired output (e.g. mp4 at 720p, audio only as mp3)", "Output file (blank to choose one)"],
+        template: "You write ffmpeg invocations. Use `-i` for every input, put output options after the inputs and end with the output file. Prefer stream copy (`-c copy`) when no re-encoding is needed. Respond ONLY with the command.",
+    },
+    Wizard {
+        tool: "jq",
+        questions: &["Input JSON file (blank for stdin)", "What to extract or change"],
+        template: "You write jq invocations. Quote the filter in single quotes, use `-r` when the output should be raw strings, and put the input file after the filter. Respond ONLY with the command.",
+    },
+    Wizard {
+        tool: "awk",
+        questions: &["Input file", "Field separator (blank for whitespace)", "What to print or compute"],
+        template: "You write POSIX awk invocations. Quote the program in single quotes, set the separator with -F, and use only POSIX awk features. Respond ONLY with the command.",
+    },
+];
+
+pub fn find(tool: &str) -> Option<&'static Wizard> {
+    WIZARDS.iter().find(|wizard| wizard.tool == tool)
+}
+
+impl Wizard {
+    // The answers as a request for the model
+    pub fn request(&self, answers: &[String]) -> String {
+        let mut lines = vec![format!("Write a command using {}.", self.tool)];
+        for (question, answer) in self.questions.iter().zip(answers) {
+            if !answer.is_empty() {
+                lines.push(format!("{}: {}", question, answer));
+            }
+        }
+        lines.join("\n")
+    }
+
+    // Checks the command runs the tool and that its arguments hang together;
+    // jq filters are also compiled when jq is installed
+    pub fn validate(&self, command: &str) -> Result<()> {
+        if !quotes_balanced(command) {
+            return Err(anyhow::anyhow!("unbalanced quotes"));
+        }
+        let pipeline = CommandParser::parse(command)?;
+        let stage = pipeline
+            .commands
+            .iter()
+            .find(|stage| stage.program == self.tool)
+            .with_context(|| format!("the command does not run {}", self.tool))?;
+        match self.tool {
+            "ffmpeg" => check_ffmpeg(stage),
+            "jq" => check_jq(stage),
+            "awk" => check_awk(stage),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn quotes_balanced(command: &str) -> bool {
+    let mut quote = None;
+    let mut escaped = false;
+    for c in command.chars() {
+        match quote {
+            _ if escaped => escaped = false,
+            Some('\'') if c == '\'' => quote = None,
+            Some('"') if c == '\\' => escaped = true,
+            Some('"') if c == '"' => quote = None,
+            Some(_) => {}
+            None if c == '\\' => escaped = true,
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None => {}
+        }
+    }
+    quote.is_none()
+}
+
+fn check_ffmpeg(stage: &SimpleCommand) -> Result<()> {
+    let inputs: Vec<&String> = stage
+        .args
+        .windows(2)
+        .filter(|pair| pair[0] == "-i")
+        .map(|pair| &pair[1])
+        .collect();
+    if inputs.is_empty() {
+        return Err(anyhow::anyhow!("ffmpeg needs an input given with -i"));
+    }
+    match stage.args.last() {
+        Some(output) if !output.starts_with('-') && !inputs.contains(&output) && stage.args.len() > 2 => Ok(()),
+        _ => Err(anyhow::anyhow!("ffmpeg needs an output file as its last argument")),
+    }
+}
+
+// The first argument that isn't an option or an option's value
+fn first_operand<'a>(args: &'a [String], takes_values: &[(&str, usize)]) -> Option<&'a String> {
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if let Some((_, count)) = takes_values.iter().find(|(option, _)| option == arg) {
+            i += count + 1;
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            i += 1;
+        } else {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+fn check_jq(stage: &SimpleCommand) -> Result<()> {
+    let options = [("--arg", 2), ("--argjson", 2), ("--slurpfile", 2), ("--rawfile", 2), ("--indent", 1), ("-f", 1), ("--from-file", 1)];
+    if stage.args.iter().any(|arg| arg == "-f" || arg == "--from-file") {
+        return Ok(());
+    }
+    let filter = first_operand(&stage.args, &options).context("jq needs a filter")?;
+    if find_executable("jq").is_none() {
+        return Ok(());
+    }
+    // Exit status 3 is a compile error; anything else is about the (null) input
+    let output = Command::new("jq")
+        .args(["-n", filter])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()?;
+    if output.status.code() == Some(3) {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("invalid jq filter: {}", error.lines().next().unwrap_or("").trim()));
+    }
+    Ok(())
+}
+
+fn check_awk(stage: &SimpleCommand) -> Result<()> {
+    if stage.args.iter().any(|arg| arg == "-f") {
+        return Ok(());
+    }
+    let program = first_operand(&stage.args, &[("-F", 1), ("-v", 1)]).context("awk needs a program")?;
+    // Braces and parentheses outside string literals must pair up
+    let mut depth = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in program.chars() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' => in_string = !in_string,
+            '{' | '(' if !in_string => depth.push(c),
+            '}' if !in_string && depth.pop() != Some('{') => return Err(anyhow::anyhow!("unbalanced braces in the awk program")),
+            ')' if !in_string && depth.pop() != Some('(') => return Err(anyhow::anyhow!("unbalanced parentheses in the awk program")),
+            _ => {}
+        }
+    }
+    if in_string || !depth.is_empty() {
+        return Err(anyhow::anyhow!("unterminated string or block in the awk program"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wizard_validation() {
+        let ffmpeg = find("ffmpeg").unwrap();
+        assert!(ffmpeg.validate("ffmpeg -i in.mov -c:v libx264 out.mp4").is_ok());
+        assert!(ffmpeg.validate("ffmpeg -i in.mov").is_err());
+        assert!(ffmpeg.validate("ffmpeg in.mov out.mp4").is_err());
+        assert!(ffmpeg.validate("sox in.wav out.mp3").is_err());
+
+        let awk = find("awk").unwrap();
+        assert!(awk.validate("awk -F, '{ sum += $3 } END { print sum }' data.csv").is_ok());
+        assert!(awk.validate("awk '{ print $1 ' data.csv").is_err());
+        assert!(awk.validate("awk '{ print \"}\" }' x").is_ok());
+        assert!(awk.validate("awk -F:").is_err());
+
+        let jq = find("jq").unwrap();
+        assert!(jq.validate("cat x.json | jq -r '.items[].name'").is_ok());
+        assert!(jq.validate("jq --arg n x").is_err());
+
+        assert_eq!(
+            awk.request(&["log.txt".to_string(), String::new(), "sum column 3".to_string()]),
+            "Write a command using awk.\nInput file: log.txt\nWhat to print or compute: sum column 3"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{Result, Context};
+use std::fs;
+use log::debug;
+use crate::llm::context_manager::ContextScope;
+use crate::shell::policy::ConfirmationLevel;
+
+// A named bundle of settings switched together with `workspace use`
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    pub name: String,
+    pub model: Option<String>,
+    pub confirm: Option<ConfirmationLevel>,
+    pub allow: Vec<String>,
+    pub theme: Option<String>,
+    pub bookmarks: Vec<(String, String)>,
+    pub context_scope: Option<ContextScope>,
+}
+
+impl Workspace {
+    pub fn bookmark(&self, name: &str) -> Option<&str> {
+        self.bookmarks
+            .iter()
+            .find(|(bookmark, _)| bookmark == name)
+            .map(|(_, path)| path.as_str())
+    }
+}
+
+pub struct WorkspaceManager {
+    workspaces: Vec<Workspace>,
+    active: Option<String>,
+}
+
+impl WorkspaceManager {
+    pub fn new() -> Self {
+        WorkspaceManager {
+            workspaces: Vec::new(),
+            active: None,
+        }
+    }
+
+    pub fn initialize(&mut self) -> Result<()> {
+        if let Some(home) = dirs::home_dir() {
+            if let Ok(content) = fs::read_to_string(home.join(".llm_shell_workspaces")) {
+                self.workspaces = Self::parse(&content)?;
+            }
+        }
+        debug!("Loaded {} workspaces", self.workspaces.len());
+        Ok(())
+    }
+
+    // Sections name workspaces; `bookmark NAME = PATH` lines add bookmarks, e.g.
+    //   [ops]
+    //   confirm = always
+    //   bookmark deploy = ~/src/deploy
+    fn parse(content: &str) -> Result<Vec<Workspace>> {
+        let mut workspaces: Vec<Workspace> = Vec::new();
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                workspaces.push(Workspace {
+                    name: name.trim().to_string(),
+                    ..Default::default()
+                });
+                continue;
+            }
+
+            let workspace = workspaces
+                .last_mut()
+                .with_context(|| format!("line {}: setting outside of a [workspace] section", number + 1))?;
+            let (key, value) = line
+                .split_once('=')
+                .with_context(|| format!("line {}: expected key = value", number + 1))?;
+            let (key, value) = (key.trim(), value.trim().to_string());
+
+            match key {
+                "model" => workspace.model = Some(value),
+                "confirm" => workspace.confirm = Some(ConfirmationLevel::parse(&value)?),
+                "allow" => workspace.allow.push(value),
+                "theme" => workspace.theme = Some(value),
+                "context" => workspace.context_scope = Some(ContextScope::parse(&value)?),
+                _ => match key.strip_prefix("bookmark ") {
+                    Some(name) => workspace.bookmarks.push((name.trim().to_string(), value)),
+                    None => return Err(anyhow::anyhow!("line {}: unknown workspace setting '{}'", number + 1, key)),
+                },
+            }
+        }
+
+        Ok(workspaces)
+    }
+
+    pub fn workspaces(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Workspace> {
+        self.workspaces.iter().find(|w| w.name == name)
+    }
+
+    pub fn active(&self) -> Option<&Workspace> {
+        self.active.as_deref().and_then(|name| self.get(name))
+    }
+
+    pub fn set_active(&mut self, name: Option<&str>) -> Result<()> {
+        if let Some(name) = name {
+            if self.get(name).is_none() {
+                return Err(anyhow::anyhow!("workspace: {}: not defined in ~/.llm_shell_workspaces", name));
+            }
+        }
+        self.active = name.map(|n| n.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_workspaces() {
+        let workspaces = WorkspaceManager::parse(
+            "# profiles\n[personal]\nconfirm = never\n\n[ops]\nmodel = llama3\nconfirm = always\nallow = ^kubectl get\ntheme = red\ncontext = directory\nbookmark deploy = ~/src/deploy\n",
+        )
+        .unwrap();
+
+        assert_eq!(workspaces.len(), 2);
+        assert_eq!(workspaces[0].confirm, Some(ConfirmationLevel::Never));
+
+        let ops = &workspaces[1];
+        assert_eq!(ops.model.as_deref(), Some("llama3"));
+        assert_eq!(ops.allow, vec!["^kubectl get".to_string()]);
+        assert_eq!(ops.context_scope, Some(ContextScope::Directory));
+        assert_eq!(ops.bookmark("deploy"), Some("~/src/deploy"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(WorkspaceManager::parse("confirm = always\n").is_err());
+        assert!(WorkspaceManager::parse("[ops]\ncolour = red\n").is_err());
+    }
+}
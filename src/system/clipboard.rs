@@ -0,0 +1,112 @@
+use anyhow::{Result, Context};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use crate::utils::path_utils;
+
+// Clipboard tools in order of preference, with the arguments needed to copy and paste
+const COPY_TOOLS: &[(&str, &[&str])] = &[
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    // WSL, through the Windows clipboard
+    ("clip.exe", &[]),
+];
+
+const PASTE_TOOLS: &[(&str, &[&str])] = &[
+    ("pbpaste", &[]),
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-o"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("powershell.exe", &["-NoProfile", "-Command", "Get-Clipboard"]),
+];
+
+pub struct Clipboard;
+
+impl Clipboard {
+    pub fn copy(text: &str) -> Result<()> {
+        for (tool, args) in COPY_TOOLS {
+            if !Self::is_available(tool) {
+                continue;
+            }
+
+            let mut child = Command::new(tool)
+                .args(*args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .with_context(|| format!("Failed to spawn {}", tool))?;
+
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(text.as_bytes())?;
+            }
+
+            if child.wait()?.success() {
+                return Ok(());
+            }
+        }
+
+        // No clipboard tool worked, fall back to the OSC 52 terminal escape
+        Self::copy_osc52(text)
+    }
+
+    pub fn paste() -> Result<String> {
+        for (tool, args) in PASTE_TOOLS {
+            if !Self::is_available(tool) {
+                continue;
+            }
+
+            let output = Command::new(tool)
+                .args(*args)
+                .stderr(Stdio::null())
+                .output()
+                .with_context(|| format!("Failed to run {}", tool))?;
+
+            if output.status.success() {
+                let text = String::from_utf8_lossy(&output.stdout).to_string();
+                // PowerShell prints Windows line endings and a trailing newline
+                if *tool == "powershell.exe" {
+                    return Ok(text.replace("\r\n", "\n").trim_end_matches('\n').to_string());
+                }
+                return Ok(text);
+            }
+        }
+
+        Err(anyhow::anyhow!("no clipboard tool found (install xclip, xsel or wl-clipboard)"))
+    }
+
+    fn copy_osc52(text: &str) -> Result<()> {
+        let mut stdout = std::io::stdout();
+        write!(stdout, "\x1b]52;c;{}\x07", Self::base64_encode(text.as_bytes()))?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn is_available(tool: &str) -> bool {
+        path_utils::find_executable(tool)
+            .map(|path| path.is_absolute())
+            .unwrap_or(false)
+    }
+
+    fn base64_encode(input: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut result = String::with_capacity(input.len().div_ceil(3) * 4);
+
+        for chunk in input.chunks(3) {
+            let b = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+            result.push(ALPHABET[(n >> 18) as usize & 63] as char);
+            result.push(ALPHABET[(n >> 12) as usize & 63] as char);
+            result.push(if chunk.len() > 1 { ALPHABET[(n >> 6) as usize & 63] as char } else { '=' });
+            result.push(if chunk.len() > 2 { ALPHABET[n as usize & 63] as char } else { '=' });
+        }
+
+        result
+    }
+}
@@ -0,0 +1,262 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use lazy_static::lazy_static;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CloudContext {
+    pub kube_context: Option<String>,
+    pub kube_namespace: Option<String>,
+    pub aws_profile: Option<String>,
+    pub gcloud_project: Option<String>,
+}
+
+struct CachedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Default)]
+struct Cache {
+    files: Vec<CachedFile>,
+    kube: (Option<String>, Option<String>),
+    gcloud_project: Option<String>,
+}
+
+lazy_static! {
+    static ref CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+}
+
+impl CloudContext {
+    pub fn current() -> Self {
+        let mut cache = CACHE.lock().unwrap();
+
+        // Only re-read the config files when one of them changed on disk
+        let files = Self::watched_files();
+        let stale = match cache.as_ref() {
+            Some(cached) => {
+                cached.files.len() != files.len()
+                    || cached.files.iter().zip(&files).any(|(a, b)| a.path != b.path || a.modified != b.modified)
+            }
+            None => true,
+        };
+
+        if stale {
+            *cache = Some(Cache {
+                kube: Self::read_kube_config(),
+                gcloud_project: Self::read_gcloud_project(),
+                files,
+            });
+        }
+
+        let cached = cache.as_ref().unwrap();
+        CloudContext {
+            kube_context: cached.kube.0.clone(),
+            kube_namespace: cached.kube.1.clone(),
+            aws_profile: env::var("AWS_PROFILE")
+                .or_else(|_| env::var("AWS_DEFAULT_PROFILE"))
+                .ok()
+                .filter(|p| !p.is_empty()),
+            gcloud_project: env::var("CLOUDSDK_CORE_PROJECT")
+                .ok()
+                .filter(|p| !p.is_empty())
+                .or_else(|| cached.gcloud_project.clone()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == CloudContext::default()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(ctx) = &self.kube_context {
+            parts.push(format!(
+                "kubectl context {} (namespace {})",
+                ctx,
+                self.kube_namespace.as_deref().unwrap_or("default")
+            ));
+        }
+        if let Some(profile) = &self.aws_profile {
+            parts.push(format!("AWS profile {}", profile));
+        }
+        if let Some(project) = &self.gcloud_project {
+            parts.push(format!("gcloud project {}", project));
+        }
+        format!("Cloud context: {}", parts.join(", "))
+    }
+
+    fn kube_config_path() -> Option<PathBuf> {
+        if let Ok(kubeconfig) = env::var("KUBECONFIG") {
+            // Only the first file of a KUBECONFIG list is consulted
+            if let Some(first) = kubeconfig.split(':').find(|p| !p.is_empty()) {
+                return Some(PathBuf::from(first));
+            }
+        }
+        dirs::home_dir().map(|home| home.join(".kube").join("config"))
+    }
+
+    fn gcloud_dir() -> Option<PathBuf> {
+        env::var("CLOUDSDK_CONFIG")
+            .ok()
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config").join("gcloud")))
+    }
+
+    fn watched_files() -> Vec<CachedFile> {
+        let mut paths = Vec::new();
+        paths.extend(Self::kube_config_path());
+        if let Some(dir) = Self::gcloud_dir() {
+            paths.push(dir.join("active_config"));
+            paths.push(dir.join("configurations").join(format!("config_{}", Self::gcloud_active_config(&dir))));
+        }
+
+        paths
+            .into_iter()
+            .map(|path| CachedFile {
+                modified: fs::metadata(&path).and_then(|m| m.modified()).ok(),
+                path,
+            })
+            .collect()
+    }
+
+    fn read_kube_config() -> (Option<String>, Option<String>) {
+        Self::kube_config_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| parse_kube_config(&content))
+            .unwrap_or((None, None))
+    }
+
+    fn gcloud_active_config(dir: &std::path::Path) -> String {
+        fs::read_to_string(dir.join("active_config"))
+            .map(|name| name.trim().to_string())
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "default".to_string())
+    }
+
+    fn read_gcloud_project() -> Option<String> {
+        let dir = Self::gcloud_dir()?;
+        let config = dir.join("configurations").join(format!("config_{}", Self::gcloud_active_config(&dir)));
+        let content = fs::read_to_string(config).ok()?;
+
+        let mut in_core = false;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                in_core = line == "[core]";
+            } else if in_core {
+                if let Some((key, value)) = line.split_once('=') {
+                    if key.trim() == "project" {
+                        return Some(value.trim().to_string());
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+// Extract the current context and its namespace from a kubeconfig file without a YAML parser
+fn parse_kube_config(content: &str) -> (Option<String>, Option<String>) {
+    let current = content
+        .lines()
+        .find_map(|line| line.strip_prefix("current-context:"))
+        .map(|value| value.trim().trim_matches('"').trim_matches('\'').to_string())
+        .filter(|value| !value.is_empty());
+
+    let current = match current {
+        Some(current) => current,
+        None => return (None, None),
+    };
+
+    let mut in_contexts = false;
+    let mut entry_name: Option<String> = None;
+    let mut entry_namespace: Option<String> = None;
+
+    for line in content.lines() {
+        if !line.starts_with(' ') && !line.starts_with('-') && !line.trim().is_empty() {
+            // A new top-level key ends the previous section
+            if in_contexts && entry_name.as_deref() == Some(current.as_str()) {
+                break;
+            }
+            in_contexts = line.trim_end() == "contexts:";
+            continue;
+        }
+
+        if !in_contexts {
+            continue;
+        }
+
+        if line.starts_with('-') {
+            if entry_name.as_deref() == Some(current.as_str()) {
+                break;
+            }
+            entry_name = None;
+            entry_namespace = None;
+        }
+
+        let trimmed = line.trim_start_matches(['-', ' ']);
+        if let Some(name) = trimmed.strip_prefix("name:") {
+            entry_name = Some(name.trim().trim_matches('"').to_string());
+        } else if let Some(namespace) = trimmed.strip_prefix("namespace:") {
+            entry_namespace = Some(namespace.trim().trim_matches('"').to_string());
+        }
+    }
+
+    let namespace = if entry_name.as_deref() == Some(current.as_str()) {
+        entry_namespace
+    } else {
+        None
+    };
+
+    (Some(current), namespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KUBECONFIG: &str = "apiVersion: v1
+clusters:
+- cluster:
+    server: https://prod.example.com
+  name: prod
+contexts:
+- context:
+    cluster: dev
+    namespace: sandbox
+    user: dev-user
+  name: dev
+- context:
+    cluster: prod
+    namespace: api
+    user: admin
+  name: prod
+current-context: prod
+kind: Config
+";
+
+    #[test]
+    fn test_kube_current_context_and_namespace() {
+        let (context, namespace) = parse_kube_config(KUBECONFIG);
+        assert_eq!(context.as_deref(), Some("prod"));
+        assert_eq!(namespace.as_deref(), Some("api"));
+    }
+
+    #[test]
+    fn test_kube_context_without_namespace() {
+        let config = KUBECONFIG.replace("    namespace: api\n", "");
+        let (context, namespace) = parse_kube_config(&config);
+        assert_eq!(context.as_deref(), Some("prod"));
+        assert_eq!(namespace, None);
+    }
+
+    #[test]
+    fn test_kube_missing_current_context() {
+        let config = KUBECONFIG.replace("current-context: prod\n", "");
+        assert_eq!(parse_kube_config(&config), (None, None));
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::Path;
+use crate::system::host;
+use crate::utils::path_utils;
+
+// Tools LLM translations commonly reach for that slim images often leave out
+const COMMON_TOOLS: &[&str] = &[
+    "curl", "wget", "git", "ps", "ip", "ss", "netstat", "less", "vim", "nano",
+    "sudo", "find", "xargs", "awk", "sed", "tar", "unzip", "python3", "jq",
+];
+
+const PACKAGE_MANAGERS: &[&str] = &[
+    "apt-get", "apk", "dnf", "microdnf", "yum", "pacman", "zypper",
+];
+
+#[derive(Debug, Clone)]
+pub struct ContainerInfo {
+    pub runtime: String,
+    pub distro: String,
+    pub package_manager: Option<String>,
+    pub missing_tools: Vec<String>,
+}
+
+impl ContainerInfo {
+    pub fn detect() -> Option<Self> {
+        let runtime = Self::detect_runtime()?;
+
+        let package_manager = PACKAGE_MANAGERS
+            .iter()
+            .find(|pm| Self::is_installed(pm))
+            .map(|pm| pm.to_string());
+
+        let missing_tools = COMMON_TOOLS
+            .iter()
+            .filter(|tool| !Self::is_installed(tool))
+            .map(|tool| tool.to_string())
+            .collect();
+
+        Some(ContainerInfo {
+            runtime,
+            distro: host::os_name(),
+            package_manager,
+            missing_tools,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let mut description = format!("Running inside a {} container ({})", self.runtime, self.distro);
+        match &self.package_manager {
+            Some(pm) => description.push_str(&format!(", package manager: {}", pm)),
+            None => description.push_str(", no package manager available"),
+        }
+        if !self.missing_tools.is_empty() {
+            description.push_str(&format!(
+                ". Not installed: {} (prefer installed alternatives)",
+                self.missing_tools.join(", ")
+            ));
+        }
+        description
+    }
+
+    fn detect_runtime() -> Option<String> {
+        if Path::new("/.dockerenv").exists() {
+            return Some("docker".to_string());
+        }
+        if Path::new("/run/.containerenv").exists() {
+            return Some("podman".to_string());
+        }
+        if let Ok(runtime) = std::env::var("container") {
+            if !runtime.is_empty() {
+                return Some(runtime);
+            }
+        }
+
+        let cgroup = fs::read_to_string("/proc/1/cgroup").unwrap_or_default();
+        for (marker, runtime) in [("kubepods", "kubernetes"), ("docker", "docker"), ("containerd", "containerd"), ("lxc", "lxc")] {
+            if cgroup.contains(marker) {
+                return Some(runtime.to_string());
+            }
+        }
+
+        None
+    }
+
+    fn is_installed(tool: &str) -> bool {
+        path_utils::find_executable(tool)
+            .map(|path| path.is_absolute())
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use crate::system::scheduler::{self, CronSchedule};
+use crate::utils::path_utils;
+
+pub const TEMPLATE: &str = "You turn schedules described in words into cron expressions. Respond with ONLY the five fields: minute, hour, day of month, month and day of week. Use numbers (Sunday is 0), `*`, ranges like 1-5, lists like 1,15 and steps like */10; no month or day names and no @daily style shortcuts.";
+
+const WEEKDAYS: [&str; 8] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const MONTHS: [&str; 13] = ["", "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+
+// Checks the model's answer is five fields the scheduler can parse
+pub fn validate(expression: &str) -> Result<()> {
+    CronSchedule::parse(expression).map(|_| ())
+}
+
+// The schedule in words, e.g. "at 09:00 on Monday through Friday". Fields
+// this can't put in words are quoted as they are.
+pub fn describe(expression: &str) -> String {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+        return expression.to_string();
+    };
+
+    let mut parts = Vec::new();
+    let times = match (numbers(minute), numbers(hour)) {
+        (Some(minutes), Some(hours)) if minutes.len() * hours.len() <= 4 => {
+            let times: Vec<String> = hours.iter().flat_map(|h| minutes.iter().map(move |m| format!("{:02}:{:02}", h, m))).collect();
+            Some(format!("at {}", join(&times)))
+        }
+        _ => None,
+    };
+    match &times {
+        Some(times) => parts.push(times.clone()),
+        None => {
+            parts.push(match minute {
+                "*" => "every minute".to_string(),
+                _ => match minute.strip_prefix("*/") {
+                    Some(step) => format!("every {} minutes", step),
+                    None => format!("at minute {}", minute),
+                },
+            });
+            if hour != "*" {
+                parts.push(match (hour.strip_prefix("*/"), hour.split_once('-')) {
+                    (Some(step), _) => format!("every {} hours", step),
+                    (None, Some((start, end))) if !hour.contains([',', '/']) => format!("from {:0>2}:00 to {:0>2}:59", start, end),
+                    _ => format!("during hour {}", hour),
+                });
+            }
+        }
+    }
+
+    let days = (day != "*").then(|| format!("on day {} of the month", day));
+    let weekdays = (weekday != "*").then(|| format!("on {}", names(weekday, &WEEKDAYS).unwrap_or_else(|| format!("weekday {}", weekday))));
+    match (days, weekdays) {
+        // Cron runs on either when both are given
+        (Some(days), Some(weekdays)) => parts.push(format!("{} or {}", days, weekdays)),
+        (Some(days), None) => parts.push(days),
+        (None, Some(weekdays)) => parts.push(weekdays),
+        (None, None) if times.is_some() => parts.push("every day".to_string()),
+        (None, None) => {}
+    }
+    if month != "*" {
+        parts.push(format!("in {}", names(month, &MONTHS).unwrap_or_else(|| format!("month {}", month))));
+    }
+    parts.join(" ")
+}
+
+// The values of a field that is only numbers and commas
+fn numbers(field: &str) -> Option<Vec<u32>> {
+    field.split(',').map(|value| value.parse().ok()).collect()
+}
+
+// A field of values and ranges with each value named: "Monday through Friday"
+fn names(field: &str, names: &[&str]) -> Option<String> {
+    let name = |value: &str| value.parse::<usize>().ok().and_then(|n| names.get(n)).filter(|name| !name.is_empty()).copied();
+    let parts: Option<Vec<String>> = field
+        .split(',')
+        .map(|part| match part.split_once('-') {
+            Some((start, end)) => Some(format!("{} through {}", name(start)?, name(end)?)),
+            None => name(part).map(str::to_string),
+        })
+        .collect();
+    Some(join(&parts?))
+}
+
+fn join(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+// The next times the schedule runs, in local time
+pub fn next_runs(expression: &str, count: usize) -> Vec<String> {
+    let Ok(schedule) = CronSchedule::parse(expression) else {
+        return Vec::new();
+    };
+    let mut runs = Vec::new();
+    let mut after = scheduler::now();
+    while runs.len() < count {
+        let Some(next) = schedule.next_after(after) else {
+            break;
+        };
+        let tm = scheduler::local_time(next);
+        runs.push(format!(
+            "{} {}-{:02}-{:02} {:02}:{:02}",
+            &WEEKDAYS[tm.tm_wday as usize][..3],
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min
+        ));
+        after = next;
+    }
+    runs
+}
+
+// The user's crontab; having none yet is an empty one
+pub fn current() -> Result<String> {
+    let output = Command::new("crontab").arg("-l").output().context("failed to run crontab")?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+    }
+    let error = String::from_utf8_lossy(&output.stderr);
+    if error.contains("no crontab") {
+        return Ok(String::new());
+    }
+    Err(anyhow::anyhow!("crontab -l: {}", error.trim()))
+}
+
+// Saves a copy of the crontab in $XDG_STATE_HOME/llm-shell/crontab before
+// it is replaced, and returns where
+pub fn backup(crontab: &str) -> Result<PathBuf> {
+    let dir = path_utils::state_dir()?.join("crontab");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("crontab.{}", scheduler::now()));
+    fs::write(&path, crontab).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+// Replaces the crontab with the given one through `crontab -`
+pub fn install(crontab: &str) -> Result<()> {
+    let mut child = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run crontab")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(crontab.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("crontab -: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe("0 9 * * 1-5"), "at 09:00 on Monday through Friday");
+        assert_eq!(describe("30 8,17 * * *"), "at 08:30 and 17:30 every day");
+        assert_eq!(describe("*/15 9-17 * * *"), "every 15 minutes from 09:00 to 17:59");
+        assert_eq!(describe("0 */2 * * *"), "at minute 0 every 2 hours");
+        assert_eq!(describe("0 0 1 1,7 *"), "at 00:00 on day 1 of the month in January and July");
+        assert_eq!(describe("0 3 15 * 0,6"), "at 03:00 on day 15 of the month or on Sunday and Saturday");
+        assert_eq!(describe("* * * * */2"), "every minute on weekday */2");
+        assert!(validate("0 9 * * 1-5").is_ok());
+        assert!(validate("0 9 * * MON-FRI").is_err());
+        assert!(validate("@daily").is_err());
+    }
+}
@@ -0,0 +1,336 @@
+use anyhow::Result;
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use nix::unistd::isatty;
+use crate::config::{self, CONFIG};
+use crate::llm::LLMClient;
+use crate::utils::crash;
+use crate::utils::path_utils;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Level {
+    Ok,
+    Warning,
+    Failure,
+}
+
+struct Check {
+    level: Level,
+    name: String,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &str, detail: impl Into<String>) -> Self {
+        Check { level: Level::Ok, name: name.to_string(), detail: detail.into(), fix: None }
+    }
+
+    fn warning(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check { level: Level::Warning, name: name.to_string(), detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn failure(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check { level: Level::Failure, name: name.to_string(), detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn print(&self) {
+        let mark = match self.level {
+            Level::Ok => "✔".bright_green(),
+            Level::Warning => "!".bright_yellow(),
+            Level::Failure => "✘".bright_red(),
+        };
+        println!("  {} {:<14} {}", mark, self.name, self.detail);
+        if let Some(fix) = &self.fix {
+            println!("    {} {}", "fix:".bright_cyan(), fix);
+        }
+    }
+}
+
+const USAGE: &str = "usage: llm-shell doctor";
+
+// `llm-shell doctor`: checks what the shell depends on and says how to fix
+// what's wrong. The exit status is 1 when something is broken.
+pub async fn run(args: &[String]) -> Result<i32> {
+    if let Some(arg) = args.first() {
+        println!("{}", USAGE);
+        return Ok(if arg == "-h" || arg == "--help" { 0 } else { 2 });
+    }
+
+    let sections = [
+        ("Configuration", configuration()),
+        ("LLM", llm().await),
+        ("Login shell", login_shell()),
+        ("Files", files()),
+        ("Terminal", terminal()),
+    ];
+
+    let mut warnings = 0;
+    let mut failures = 0;
+    for (title, checks) in &sections {
+        println!("{}", title.bold());
+        for check in checks {
+            check.print();
+            match check.level {
+                Level::Warning => warnings += 1,
+                Level::Failure => failures += 1,
+                Level::Ok => {}
+            }
+        }
+    }
+
+    println!();
+    if failures + warnings == 0 {
+        println!("{}", "Everything looks good.".bright_green());
+    } else {
+        println!("{} problem(s), {} warning(s). Include this output when reporting a bug.", failures, warnings);
+    }
+    Ok(if failures > 0 { 1 } else { 0 })
+}
+
+fn configuration() -> Vec<Check> {
+    let mut checks: Vec<Check> = config::FILE_CONFIG
+        .errors
+        .iter()
+        .map(|error| Check::failure("config file", error, "fix or remove the setting (see the README for the supported ones)"))
+        .collect();
+    checks.extend(config::problems(config::env_opt).into_iter().map(|(key, problem)| {
+        let fix = match config::FILE_SETTINGS.iter().find(|(_, env)| *env == key) {
+            Some((setting, _)) => format!("fix or unset {} or `{}` in the config file (see the README for its values)", key, setting),
+            None => format!("fix or unset {} (see the README for its values)", key),
+        };
+        Check::failure(key, problem, fix)
+    }));
+    checks.extend(crate::shell::policy_problems().into_iter().map(|problem| {
+        Check::failure("policy", problem, "fix LLM_SHELL_CONFIRM, LLM_SHELL_CONFIRM_ALLOW, LLM_SHELL_LLM_LIMITS or ~/.llm_shell_limits")
+    }));
+    if checks.is_empty() {
+        checks.push(Check::ok("settings", "config files, environment settings and ~/.llm_shell_limits are valid"));
+    }
+    checks
+}
+
+async fn llm() -> Vec<Check> {
+    let client = LLMClient::new();
+    let start = Instant::now();
+    let models = match client.models().await {
+        Ok(models) => models,
+        Err(e) => {
+            return vec![Check::failure(
+                "endpoint",
+                format!("{} is not reachable: {}", CONFIG.llm_host, e),
+                "start the server (e.g. `ollama serve`) or check the host address and any firewall in between",
+            )];
+        }
+    };
+    let mut checks = vec![Check::ok(
+        "endpoint",
+        format!("{} answered in {}ms", CONFIG.llm_host, start.elapsed().as_millis()),
+    )];
+    if has_model(&models, &CONFIG.llm_model) {
+        checks.push(Check::ok("model", format!("{} is available", CONFIG.llm_model)));
+    } else {
+        let available = if models.is_empty() { "none".to_string() } else { models.join(", ") };
+        checks.push(Check::failure(
+            "model",
+            format!("{} is not offered by the host (available: {})", CONFIG.llm_model, available),
+            format!("pull it with `ollama pull {}` or switch to an available model with `model NAME`", CONFIG.llm_model),
+        ));
+    }
+    checks
+}
+
+// Ollama lists untagged models as NAME:latest
+fn has_model(models: &[String], wanted: &str) -> bool {
+    models.iter().any(|model| model == wanted || model.strip_suffix(":latest") == Some(wanted))
+}
+
+fn login_shell() -> Vec<Check> {
+    let exe = std::env::current_exe().ok();
+    let shells = fs::read_to_string("/etc/shells").unwrap_or_default();
+    let listed = ["/usr/bin/llm-shell".to_string()]
+        .into_iter()
+        .chain(exe.iter().map(|exe| exe.to_string_lossy().into_owned()))
+        .find(|path| registered(&shells, path));
+    let mut checks = vec![match listed {
+        Some(path) => Check::ok("/etc/shells", format!("{} is registered", path)),
+        None => Check::warning(
+            "/etc/shells",
+            "llm-shell is not registered, so chsh refuses it as a login shell",
+            "run `sudo llm-shell --install`",
+        ),
+    }];
+    let login = std::env::var("SHELL").unwrap_or_default();
+    if Path::new(&login).file_name().is_some_and(|name| name == "llm-shell") {
+        checks.push(Check::ok("$SHELL", login));
+    } else {
+        checks.push(Check::ok("$SHELL", format!("{} (llm-shell is not your login shell)", login)));
+    }
+    checks
+}
+
+// Whether /etc/shells lists `path`, ignoring comments
+fn registered(shells: &str, path: &str) -> bool {
+    shells.lines().map(|line| line.split('#').next().unwrap_or("").trim()).any(|line| line == path)
+}
+
+fn files() -> Vec<Check> {
+    let mut checks = Vec::new();
+    match dirs::home_dir() {
+        Some(home) => {
+            let history = home.join(".llm_shell_history");
+            checks.push(match writable_file(&history) {
+                Ok(()) => Check::ok("history", history.display().to_string()),
+                Err(e) => Check::failure(
+                    "history",
+                    format!("{} is not writable: {}", history.display(), e),
+                    format!("check the owner and permissions of {}", history.display()),
+                ),
+            });
+        }
+        None => checks.push(Check::failure("history", "no home directory", "set HOME")),
+    }
+    let state = path_utils::state_dir().ok();
+    checks.push(match state {
+        Some(dir) => match writable_dir(&dir) {
+            Ok(()) => Check::ok("state", dir.display().to_string()),
+            Err(e) => Check::failure(
+                "state",
+                format!("{} is not writable: {}", dir.display(), e),
+                format!("check the owner and permissions of {} or set XDG_STATE_HOME", dir.display()),
+            ),
+        },
+        None => Check::failure("state", "no state directory", "set HOME or XDG_STATE_HOME"),
+    });
+    if let Some(dir) = crash::crash_dir() {
+        let mut reports: Vec<PathBuf> = fs::read_dir(&dir)
+            .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        reports.sort();
+        if let Some(latest) = reports.last() {
+            checks.push(Check::warning(
+                "crashes",
+                format!("{} crash report(s), the latest is {}", reports.len(), latest.display()),
+                format!("attach it to a bug report, then remove {}", dir.display()),
+            ));
+        }
+    }
+    checks
+}
+
+fn writable_file(path: &Path) -> std::io::Result<()> {
+    if path.exists() {
+        fs::OpenOptions::new().append(true).open(path).map(|_| ())
+    } else {
+        writable_dir(path.parent().unwrap_or(Path::new("/")))
+    }
+}
+
+// Creates the directory if needed and a file in it, which is removed again
+fn writable_dir(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(probe)
+}
+
+fn terminal() -> Vec<Check> {
+    let mut checks = Vec::new();
+    if isatty(libc::STDIN_FILENO).unwrap_or(false) && isatty(libc::STDOUT_FILENO).unwrap_or(false) {
+        checks.push(match window_size() {
+            Some((cols, rows)) => Check::ok("tty", format!("{}x{}", cols, rows)),
+            None => Check::warning("tty", "the terminal reports no size", "run `stty cols 80 rows 24` or resize the window"),
+        });
+    } else {
+        checks.push(Check::warning(
+            "tty",
+            "stdin or stdout is not a terminal; line editing and the prompt are off",
+            "run doctor directly in the terminal you use the shell in",
+        ));
+    }
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    checks.push(if term.is_empty() || term == "dumb" {
+        Check::warning("TERM", format!("'{}' has no cursor movement or colors", term), "export TERM=xterm-256color")
+    } else if terminfo(&term).is_none() {
+        Check::warning(
+            "TERM",
+            format!("{} has no terminfo entry here, so full-screen programs may misbehave", term),
+            format!("install the terminfo for {} (e.g. with `infocmp` on the machine you connect from) or export TERM=xterm-256color", term),
+        )
+    } else {
+        let colors = if std::env::var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit") {
+            "truecolor"
+        } else if term.contains("256color") {
+            "256 colors"
+        } else {
+            "basic colors"
+        };
+        Check::ok("TERM", format!("{} ({})", term, colors))
+    });
+
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let lower = locale.to_lowercase();
+    checks.push(if lower.contains("utf-8") || lower.contains("utf8") {
+        Check::ok("locale", locale)
+    } else {
+        Check::warning(
+            "locale",
+            format!("'{}' is not UTF-8, so the prompt's symbols may show up garbled", locale),
+            "export LANG=C.UTF-8 (or another UTF-8 locale from `locale -a`)",
+        )
+    });
+    checks
+}
+
+fn window_size() -> Option<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } != 0 || size.ws_col == 0 {
+        return None;
+    }
+    Some((size.ws_col, size.ws_row))
+}
+
+// The compiled terminfo entry for `term`, in the places ncurses looks
+fn terminfo(term: &str) -> Option<PathBuf> {
+    let first = term.chars().next()?;
+    let mut dirs: Vec<PathBuf> = std::env::var("TERMINFO").ok().map(PathBuf::from).into_iter().collect();
+    dirs.extend(dirs::home_dir().map(|home| home.join(".terminfo")));
+    dirs.extend(
+        std::env::var("TERMINFO_DIRS")
+            .unwrap_or_default()
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from),
+    );
+    dirs.extend(["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo", "/usr/lib/terminfo"].map(PathBuf::from));
+    dirs.into_iter().find_map(|dir| {
+        // Linux uses the first letter, macOS its hex code
+        [first.to_string(), format!("{:x}", first as u32)]
+            .into_iter()
+            .map(|sub| dir.join(sub).join(term))
+            .find(|path| path.exists())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registered() {
+        let shells = "# /etc/shells\n/bin/sh\n/usr/bin/llm-shell # added by --install\n";
+        assert!(registered(shells, "/usr/bin/llm-shell"));
+        assert!(!registered(shells, "/usr/bin/llm"));
+        assert!(!registered(shells, "/etc/shells"));
+        let models = vec!["llama3:latest".to_string(), "qwen2.5:14b".to_string()];
+        assert!(has_model(&models, "llama3") && has_model(&models, "qwen2.5:14b"));
+        assert!(!has_model(&models, "qwen2.5"));
+    }
+}
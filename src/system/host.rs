@@ -0,0 +1,19 @@
+use std::fs;
+
+pub fn hostname() -> String {
+    hostname::get()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+pub fn os_name() -> String {
+    if let Ok(content) = fs::read_to_string("/etc/os-release") {
+        for line in content.lines() {
+            if let Some(name) = line.strip_prefix("PRETTY_NAME=") {
+                return name.trim_matches('"').to_string();
+            }
+        }
+    }
+
+    std::env::consts::OS.to_string()
+}
@@ -1,2 +1,15 @@
 pub mod login;
-pub mod installer;
\ No newline at end of file
+pub mod installer;
+pub mod clipboard;
+pub mod tmux;
+pub mod host;
+pub mod ssh;
+pub mod cloud;
+pub mod container;
+pub mod remote;
+pub mod scheduler;
+pub mod crontab;
+pub mod notify;
+pub mod wsl;
+pub mod tty;
+pub mod doctor;
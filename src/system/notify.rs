@@ -0,0 +1,52 @@
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use crate::utils::path_utils;
+
+pub struct Notifier;
+
+impl Notifier {
+    pub fn bell() {
+        let mut stdout = std::io::stdout();
+        let _ = write!(stdout, "\x07");
+        let _ = stdout.flush();
+    }
+
+    // Desktop notification via notify-send on Linux or osascript on macOS
+    pub fn notify(title: &str, body: &str) -> Result<()> {
+        let mut command = if Self::is_available("notify-send") {
+            let mut command = Command::new("notify-send");
+            command.arg("--app-name=llm-shell").arg(title).arg(body);
+            command
+        } else if Self::is_available("osascript") {
+            let mut command = Command::new("osascript");
+            command.arg("-e").arg(format!(
+                "display notification \"{}\" with title \"{}\"",
+                Self::escape_applescript(body),
+                Self::escape_applescript(title)
+            ));
+            command
+        } else {
+            return Err(anyhow::anyhow!("no notification tool found (install libnotify's notify-send)"));
+        };
+
+        // Don't make the prompt wait on the notification daemon
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    fn escape_applescript(text: &str) -> String {
+        text.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    fn is_available(tool: &str) -> bool {
+        path_utils::find_executable(tool)
+            .map(|path| path.is_absolute())
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{Result, Context};
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteTarget {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    pub os: Option<String>,
+    pub cwd: Option<String>,
+}
+
+impl RemoteTarget {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let rest = spec
+            .strip_prefix("ssh://")
+            .ok_or_else(|| anyhow::anyhow!("unsupported target '{}', expected ssh://[user@]host[:port]", spec))?;
+        let rest = rest.trim_end_matches('/');
+
+        let (user, host_port) = match rest.split_once('@') {
+            Some((user, host_port)) => (Some(user.to_string()), host_port),
+            None => (None, rest),
+        };
+
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse::<u16>().with_context(|| format!("invalid port: {}", port))?;
+                (host.to_string(), Some(port))
+            }
+            None => (host_port.to_string(), None),
+        };
+
+        if host.is_empty() {
+            return Err(anyhow::anyhow!("missing host in target '{}'", spec));
+        }
+
+        Ok(RemoteTarget { user, host, port, os: None, cwd: None })
+    }
+
+    pub fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        match self.port {
+            Some(port) => format!("ssh://{}:{}", self.destination(), port),
+            None => format!("ssh://{}", self.destination()),
+        }
+    }
+
+    // Every invocation goes through a shared ControlMaster socket so only the first one pays for the handshake
+    fn ssh(&self, options: &[&str]) -> Command {
+        let control_path = dirs::home_dir()
+            .unwrap_or_default()
+            .join(".ssh")
+            .join("llmsh-%r@%h:%p");
+
+        let mut cmd = Command::new("ssh");
+        cmd.arg("-o").arg("ControlMaster=auto")
+            .arg("-o").arg(format!("ControlPath={}", control_path.display()))
+            .arg("-o").arg("ControlPersist=10m");
+        if let Some(port) = self.port {
+            cmd.arg("-p").arg(port.to_string());
+        }
+        cmd.args(options).arg(self.destination());
+        cmd
+    }
+
+    pub fn connect(&mut self) -> Result<()> {
+        let output = self.ssh(&[])
+            .arg("--")
+            .arg("pwd; (. /etc/os-release 2>/dev/null && echo \"$PRETTY_NAME\") || uname -sr")
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to connect to {}", self.url()))?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "ssh to {} failed: {}",
+                self.url(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout.lines();
+        self.cwd = lines.next().map(|s| s.trim().to_string());
+        self.os = lines.next().map(|s| s.trim().to_string());
+        Ok(())
+    }
+
+    pub fn execute(&mut self, command: &str) -> Result<i32> {
+        let status = self.ssh(&["-t"])
+            .arg("--")
+            .arg(self.in_cwd(command))
+            .status()
+            .with_context(|| format!("Failed to run command on {}", self.url()))?;
+
+        let code = status.code().unwrap_or(255);
+        if code == 0 {
+            self.follow_cd(command);
+        }
+        Ok(code)
+    }
+
+    fn in_cwd(&self, command: &str) -> String {
+        match &self.cwd {
+            Some(cwd) => format!("cd {} && {}", shell_quote(cwd), command),
+            None => command.to_string(),
+        }
+    }
+
+    // The command ran on its own remote shell, so the `cd`s in it are
+    // replayed from the old directory to find where it ended up
+    fn follow_cd(&mut self, command: &str) {
+        let changes = directory_changes(command);
+        if changes.is_empty() {
+            return;
+        }
+        let output = self.ssh(&[])
+            .arg("--")
+            .arg(self.in_cwd(&format!("{} && pwd", changes.join(" && "))))
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        if let Some(output) = output.ok().filter(|output| output.status.success()) {
+            let cwd = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !cwd.is_empty() {
+                self.cwd = Some(cwd);
+            }
+        }
+    }
+
+    pub fn disconnect(&self) {
+        let _ = self.ssh(&["-O", "exit"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "Translated commands run on remote host {} ({}) in directory {}",
+            self.host,
+            self.os.as_deref().unwrap_or("unknown OS"),
+            self.cwd.as_deref().unwrap_or("~")
+        )
+    }
+}
+
+// The `cd` commands of a command list, e.g. `cd /srv && cd app` of
+// `cd /srv && make; cd app`; those inside subshells or groups don't count
+fn directory_changes(command: &str) -> Vec<&str> {
+    let mut quote = None;
+    let mut start = 0;
+    let mut chars = command.char_indices().peekable();
+    let mut segments = Vec::new();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == ';' || c == '\n' || c == '&' || c == '|' => {
+                segments.push(&command[start..i]);
+                if chars.peek().is_some_and(|(_, next)| *next == c) {
+                    chars.next();
+                }
+                start = chars.peek().map(|(next, _)| *next).unwrap_or(command.len());
+            }
+            None => {}
+        }
+    }
+    segments.push(&command[start..]);
+    segments
+        .into_iter()
+        .map(str::trim)
+        .filter(|segment| *segment == "cd" || segment.starts_with("cd "))
+        .collect()
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_changes() {
+        assert_eq!(directory_changes("cd /srv && make; cd 'my app'"), vec!["cd /srv", "cd 'my app'"]);
+        assert_eq!(directory_changes("ls | grep cd; echo 'x; cd y'"), Vec::<&str>::new());
+        assert_eq!(directory_changes("(cd /tmp && ls) || cd"), vec!["cd"]);
+    }
+}
@@ -0,0 +1,350 @@
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
+use log::debug;
+use crate::utils::path_utils;
+
+// How often the background instance wakes up to look for due jobs
+const POLL_INTERVAL: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum When {
+    Cron(String),
+    Once(u64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub id: u32,
+    pub when: When,
+    pub command: String,
+    pub cwd: String,
+    pub next_run: u64,
+    pub last_run: Option<u64>,
+}
+
+// Five-field cron expression: minute hour day-of-month month day-of-week
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Cron matches either day field when both are restricted
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow::anyhow!("invalid cron expression '{}': expected 5 fields", spec));
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 mean Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(CronSchedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        })
+    }
+
+    fn day_matches(&self, tm: &libc::tm) -> bool {
+        let day = self.days & (1 << tm.tm_mday) != 0;
+        let weekday = self.weekdays & (1 << tm.tm_wday) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    // First matching minute strictly after `after`, in local time
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let mut time = (after / 60 + 1) * 60;
+
+        // Skipping whole days and hours keeps this to a few thousand steps even for yearly schedules
+        for _ in 0..100_000 {
+            let mut tm = local_time(time);
+            if self.months & (1 << (tm.tm_mon + 1)) == 0 || !self.day_matches(&tm) {
+                tm.tm_mday += 1;
+                tm.tm_hour = 0;
+                tm.tm_min = 0;
+                tm.tm_sec = 0;
+                time = make_time(&mut tm)?;
+            } else if self.hours & (1 << tm.tm_hour) == 0 {
+                time = time - (tm.tm_min as u64) * 60 + 3600;
+            } else if self.minutes & (1 << tm.tm_min) == 0 {
+                time += 60;
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)
+                .with_context(|| format!("invalid step in cron field '{}'", field))?),
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, field)?, parse_value(end, field)?)
+        } else {
+            let value = parse_value(range, field)?;
+            // "5/15" means every 15 starting at 5
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            return Err(anyhow::anyhow!("cron field '{}' out of range {}-{}", field, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, field: &str) -> Result<u32> {
+    value.parse().with_context(|| format!("invalid value '{}' in cron field '{}'", value, field))
+}
+
+pub fn local_time(time: u64) -> libc::tm {
+    let time = time as libc::time_t;
+    // SAFETY: localtime_r only writes into the tm we hand it
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+        tm
+    }
+}
+
+fn make_time(tm: &mut libc::tm) -> Option<u64> {
+    tm.tm_isdst = -1;
+    // SAFETY: mktime normalizes the fields of a valid tm in place
+    let time = unsafe { libc::mktime(tm) };
+    if time < 0 { None } else { Some(time as u64) }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub struct Scheduler;
+
+impl Scheduler {
+    fn state_dir() -> Result<PathBuf> {
+        let dir = path_utils::state_dir()?;
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    // The shell and the background instance both rewrite the job file, so every change holds an flock
+    fn with_jobs<T>(update: impl FnOnce(&mut Vec<ScheduledJob>) -> Result<T>) -> Result<T> {
+        let dir = Self::state_dir()?;
+        let lock = File::create(dir.join("schedule.lock"))?;
+        // SAFETY: flock on a file descriptor we own; released when `lock` is dropped
+        if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to lock schedule");
+        }
+
+        let path = dir.join("schedule.json");
+        let mut jobs: Vec<ScheduledJob> = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).context("Corrupt schedule file")?,
+            Err(_) => Vec::new(),
+        };
+
+        let result = update(&mut jobs)?;
+        fs::write(&path, serde_json::to_string_pretty(&jobs)?)?;
+        Ok(result)
+    }
+
+    pub fn add(when: When, command: &str) -> Result<ScheduledJob> {
+        let next_run = match &when {
+            When::Cron(spec) => CronSchedule::parse(spec)?
+                .next_after(now())
+                .context("cron expression never matches")?,
+            When::Once(at) => *at,
+        };
+
+        let job = Self::with_jobs(|jobs| {
+            let job = ScheduledJob {
+                id: jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1,
+                when,
+                command: command.to_string(),
+                cwd: std::env::current_dir()?.to_string_lossy().to_string(),
+                next_run,
+                last_run: None,
+            };
+            jobs.push(job.clone());
+            Ok(job)
+        })?;
+
+        Self::ensure_daemon()?;
+        Ok(job)
+    }
+
+    pub fn remove(id: u32) -> Result<bool> {
+        Self::with_jobs(|jobs| {
+            let before = jobs.len();
+            jobs.retain(|job| job.id != id);
+            Ok(jobs.len() != before)
+        })
+    }
+
+    pub fn list() -> Result<Vec<ScheduledJob>> {
+        Self::with_jobs(|jobs| Ok(jobs.clone()))
+    }
+
+    // Restarts the background instance after a reboot or crash if jobs are pending
+    pub fn resume() -> Result<()> {
+        if !Self::list()?.is_empty() {
+            Self::ensure_daemon()?;
+        }
+        Ok(())
+    }
+
+    // Starts a detached `llm-shell --scheduler` unless one is already running
+    pub fn ensure_daemon() -> Result<()> {
+        let pid_file = Self::state_dir()?.join("schedule.pid");
+        if let Some(pid) = fs::read_to_string(&pid_file).ok().and_then(|p| p.trim().parse::<i32>().ok()) {
+            if kill(Pid::from_raw(pid), None).is_ok() {
+                return Ok(());
+            }
+        }
+
+        let mut command = Command::new(std::env::current_exe()?);
+        command
+            .arg("--scheduler")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        // SAFETY: setsid is async-signal-safe; it detaches the scheduler from our terminal
+        unsafe {
+            command.pre_exec(|| {
+                nix::unistd::setsid().map_err(std::io::Error::from)?;
+                Ok(())
+            });
+        }
+        let child = command.spawn().context("Failed to start background scheduler")?;
+        fs::write(&pid_file, child.id().to_string())?;
+        Ok(())
+    }
+
+    // Main loop of the background instance; exits once no jobs are left
+    pub fn run_daemon() -> Result<()> {
+        let dir = Self::state_dir()?;
+        fs::write(dir.join("schedule.pid"), std::process::id().to_string())?;
+
+        // Jobs are fire-and-forget; let the kernel reap them
+        // SAFETY: ignoring SIGCHLD has no preconditions
+        unsafe {
+            libc::signal(libc::SIGCHLD, libc::SIG_IGN);
+        }
+
+        loop {
+            let remaining = Self::with_jobs(|jobs| {
+                let now = now();
+                for job in jobs.iter_mut().filter(|job| job.next_run <= now) {
+                    if let Err(e) = Self::run_job(job, &dir) {
+                        debug!("Scheduled job {} failed to start: {}", job.id, e);
+                    }
+                    job.last_run = Some(now);
+                    if let When::Cron(spec) = &job.when {
+                        job.next_run = CronSchedule::parse(spec)
+                            .ok()
+                            .and_then(|cron| cron.next_after(now))
+                            .unwrap_or(u64::MAX);
+                    }
+                }
+                jobs.retain(|job| !(matches!(job.when, When::Once(_)) && job.last_run.is_some()));
+
+                // Dropped while still holding the lock so a job added right now starts a fresh instance
+                if jobs.is_empty() {
+                    let _ = fs::remove_file(dir.join("schedule.pid"));
+                }
+                Ok(jobs.len())
+            })?;
+
+            if remaining == 0 {
+                return Ok(());
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn run_job(job: &ScheduledJob, dir: &std::path::Path) -> Result<()> {
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("schedule.log"))?;
+
+        Command::new("/bin/sh")
+            .arg("-c")
+            .arg(&job.command)
+            .current_dir(&job.cwd)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cron() {
+        let cron = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert_eq!(cron.minutes, (1 << 0) | (1 << 15) | (1 << 30) | (1 << 45));
+        assert_eq!(cron.weekdays, 0b111110);
+        assert!(!cron.days_restricted);
+
+        assert!(CronSchedule::parse("0 9 * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn test_next_after() {
+        let cron = CronSchedule::parse("30 9 * * *").unwrap();
+        let start = now();
+        let next = cron.next_after(start).unwrap();
+        let tm = local_time(next);
+
+        assert!(next > start && next - start <= 25 * 3600);
+        assert_eq!((tm.tm_hour, tm.tm_min), (9, 30));
+    }
+}
@@ -0,0 +1,48 @@
+use std::env;
+use crate::system::host;
+
+#[derive(Debug, Clone)]
+pub struct SshSession {
+    pub client_address: String,
+    pub hostname: String,
+    pub os: String,
+}
+
+impl SshSession {
+    pub fn detect() -> Option<Self> {
+        // SSH_CONNECTION is "client_ip client_port server_ip server_port"; SSH_CLIENT is the older form
+        let connection = env::var("SSH_CONNECTION")
+            .or_else(|_| env::var("SSH_CLIENT"))
+            .ok()
+            .filter(|value| !value.trim().is_empty());
+
+        if !Self::is_active() {
+            return None;
+        }
+
+        let client_address = connection
+            .as_deref()
+            .and_then(|value| value.split_whitespace().next())
+            .unwrap_or("unknown")
+            .to_string();
+
+        Some(SshSession {
+            client_address,
+            hostname: host::hostname(),
+            os: host::os_name(),
+        })
+    }
+
+    pub fn is_active() -> bool {
+        ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+            .iter()
+            .any(|key| env::var(key).map(|v| !v.is_empty()).unwrap_or(false))
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "Running over SSH on remote host {} ({}), connected from {}",
+            self.hostname, self.os, self.client_address
+        )
+    }
+}
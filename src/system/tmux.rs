@@ -0,0 +1,79 @@
+use anyhow::{Result, Context};
+use std::process::Command;
+
+pub struct Tmux;
+
+impl Tmux {
+    pub fn is_active() -> bool {
+        std::env::var("TMUX").map(|v| !v.is_empty()).unwrap_or(false)
+    }
+
+    pub fn pane_exists(target: &str) -> bool {
+        Command::new("tmux")
+            .args(["display-message", "-p", "-t", target, "#{pane_id}"])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    // Type the command into the target pane without pressing Enter, so it can be reviewed there
+    pub fn send_keys(target: &str, command: &str) -> Result<()> {
+        if !Self::is_active() {
+            return Err(anyhow::anyhow!("not running inside tmux"));
+        }
+
+        let status = Command::new("tmux")
+            .args(["send-keys", "-t", target, "-l", command])
+            .status()
+            .context("Failed to run tmux send-keys")?;
+
+        if !status.success() {
+            return Err(anyhow::anyhow!("tmux send-keys to pane {} failed", target));
+        }
+
+        Ok(())
+    }
+}
+
+// Cuts an unquoted `--to-pane` or `--to-pane=PANE` word out of a request,
+// leaving the rest of it as typed. The pane is None for a bare `--to-pane`.
+pub fn split_pane_flag(request: &str) -> Option<(String, Option<String>)> {
+    let mut quote = None;
+    let mut start = None;
+    let mut words = Vec::new();
+    for (i, c) in request.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c.is_whitespace() => words.extend(start.take().map(|start| (start, i))),
+            None => {}
+        }
+        if quote.is_some() || !c.is_whitespace() {
+            start.get_or_insert(i);
+        }
+    }
+    words.extend(start.map(|start| (start, request.len())));
+
+    words.into_iter().find_map(|(start, end)| {
+        let pane = match &request[start..end] {
+            "--to-pane" => None,
+            word => Some(word.strip_prefix("--to-pane=")?.to_string()),
+        };
+        let rest = format!("{} {}", request[..start].trim_end(), request[end..].trim_start());
+        Some((rest.trim().to_string(), pane))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_pane_flag() {
+        assert_eq!(split_pane_flag("list big files --to-pane"), Some(("list big files".into(), None)));
+        assert_eq!(split_pane_flag("--to-pane=%3 show  \"a  b\""), Some(("show  \"a  b\"".into(), Some("%3".into()))));
+        assert_eq!(split_pane_flag("git commit -m \"add --to-pane\""), None);
+        assert_eq!(split_pane_flag("echo --to-panel"), None);
+    }
+}
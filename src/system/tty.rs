@@ -0,0 +1,62 @@
+use anyhow::Result;
+use lazy_static::lazy_static;
+use nix::sys::termios::{self, LocalFlags, SetArg, Termios};
+use nix::unistd::isatty;
+use std::io::Write;
+use std::sync::Mutex;
+
+lazy_static! {
+    // The terminal settings from when the shell started
+    static ref SAVED: Mutex<Option<Termios>> = Mutex::new(None);
+}
+
+// Remembers the current terminal settings, if stdin is a terminal
+pub fn save() {
+    if !isatty(libc::STDIN_FILENO).unwrap_or(false) {
+        return;
+    }
+    if let Ok(settings) = termios::tcgetattr(libc::STDIN_FILENO) {
+        *SAVED.lock().unwrap() = Some(settings);
+    }
+}
+
+// Puts back the settings `save` found, undoing raw mode and anything a
+// program changed without restoring it
+pub fn restore() -> Result<()> {
+    let _ = std::io::stdout().flush();
+    if let Some(settings) = &*SAVED.lock().unwrap() {
+        termios::tcsetattr(libc::STDIN_FILENO, SetArg::TCSADRAIN, settings)?;
+    }
+    Ok(())
+}
+
+// Leaves the alternate screen without moving the cursor, shows the cursor,
+// resets colors and turns off mouse reporting and application keypad mode
+const SANE_MODES: &str = "\x1b[?1047l\x1b[?25h\x1b[0m\x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l\x1b[?1l\x1b>";
+
+// Restores the settings and undoes the screen modes full-screen programs use
+pub fn repair() -> Result<()> {
+    restore()?;
+    if isatty(libc::STDOUT_FILENO).unwrap_or(false) {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(SANE_MODES.as_bytes())?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+// After a foreground command: a program killed by a signal (status above 128)
+// or one that left the terminal in raw mode didn't get to clean up after itself
+pub fn check_after_command(status: i32) {
+    if SAVED.lock().unwrap().is_none() {
+        return;
+    }
+    let raw = termios::tcgetattr(libc::STDIN_FILENO)
+        .map(|settings| !settings.local_flags.contains(LocalFlags::ICANON))
+        .unwrap_or(false);
+    if status > 128 || raw {
+        if let Err(e) = repair() {
+            log::debug!("Failed to repair the terminal: {}", e);
+        }
+    }
+}
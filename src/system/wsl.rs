@@ -0,0 +1,107 @@
+use lazy_static::lazy_static;
+use std::fs;
+
+lazy_static! {
+    static ref ACTIVE: bool = detect();
+}
+
+// Windows drives are mounted here unless /etc/wsl.conf says otherwise
+const MOUNT_ROOT: &str = "/mnt/";
+
+fn detect() -> bool {
+    if std::env::var_os("WSL_DISTRO_NAME").is_some() || std::env::var_os("WSL_INTEROP").is_some() {
+        return true;
+    }
+    fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|release| release.to_lowercase().contains("microsoft"))
+        .unwrap_or(false)
+}
+
+// Running under the Windows Subsystem for Linux
+pub fn is_active() -> bool {
+    *ACTIVE
+}
+
+pub fn describe() -> String {
+    let distro = std::env::var("WSL_DISTRO_NAME").unwrap_or_else(|_| "Linux".to_string());
+    format!(
+        "Running under WSL ({}); Windows drives are mounted under {}<drive>, Windows programs run as NAME.exe",
+        distro, MOUNT_ROOT
+    )
+}
+
+// `C:\Users\me` or `C:/Users/me` -> `/mnt/c/Users/me`
+pub fn to_unix_path(path: &str) -> Option<String> {
+    let mut chars = path.chars();
+    let drive = chars.next().filter(|c| c.is_ascii_alphabetic())?;
+    if chars.next() != Some(':') {
+        return None;
+    }
+    let rest = chars.as_str();
+    if !rest.is_empty() && !rest.starts_with(['\\', '/']) {
+        return None;
+    }
+    Some(format!("{}{}{}", MOUNT_ROOT, drive.to_ascii_lowercase(), rest.replace('\\', "/")))
+}
+
+// `/mnt/c/Users/me` -> `C:\Users\me`, for showing paths the way Windows users know them
+pub fn to_windows_path(path: &str) -> Option<String> {
+    let rest = path.strip_prefix(MOUNT_ROOT)?;
+    let mut chars = rest.chars();
+    let drive = chars.next().filter(|c| c.is_ascii_alphabetic())?;
+    let rest = chars.as_str();
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+    Some(format!("{}:{}", drive.to_ascii_uppercase(), if rest.is_empty() { "\\".to_string() } else { rest.replace('/', "\\") }))
+}
+
+// Whether a directory lives on a Windows drive, where scanning is slow and
+// every file looks executable
+pub fn is_windows_mount(dir: &str) -> bool {
+    to_windows_path(dir).is_some()
+}
+
+// PATH as imported from Windows can hold `C:\...` entries, which a plain
+// split on ':' cuts after the drive letter, and duplicates. Windows entries
+// are rewritten to their mount points; repeats and blanks are dropped.
+pub fn normalize_path_var(path: &str) -> String {
+    let parts: Vec<&str> = path.split(':').collect();
+    let mut entries: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < parts.len() {
+        let mut entry = parts[i].to_string();
+        let is_drive = entry.len() == 1 && entry.chars().all(|c| c.is_ascii_alphabetic());
+        if is_drive && parts.get(i + 1).map(|next| next.starts_with('\\')).unwrap_or(false) {
+            entry = format!("{}:{}", entry, parts[i + 1]);
+            i += 1;
+        }
+        i += 1;
+        let entry = to_unix_path(&entry).unwrap_or(entry);
+        if !entry.is_empty() && !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+    entries.join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_conversion() {
+        assert_eq!(to_unix_path("C:\\Users\\me"), Some("/mnt/c/Users/me".to_string()));
+        assert_eq!(to_unix_path("d:/src"), Some("/mnt/d/src".to_string()));
+        assert_eq!(to_unix_path("/usr/bin"), None);
+        assert_eq!(to_unix_path("ab:c"), None);
+        assert_eq!(to_windows_path("/mnt/c/Users/me"), Some("C:\\Users\\me".to_string()));
+        assert_eq!(to_windows_path("/mnt/c"), Some("C:\\".to_string()));
+        assert_eq!(to_windows_path("/mnt/wsl/x"), None);
+        assert_eq!(
+            normalize_path_var("/usr/bin:/mnt/c/Windows:/usr/bin::/mnt/c/Program Files/Git/cmd"),
+            "/usr/bin:/mnt/c/Windows:/mnt/c/Program Files/Git/cmd"
+        );
+        assert_eq!(normalize_path_var("/bin:C:\\Windows\\system32"), "/bin:/mnt/c/Windows/system32");
+    }
+}
@@ -1,33 +1,109 @@
 use anyhow::Result;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use lazy_static::lazy_static;
+use crate::system::wsl;
+
+// The host files' modification times and the hosts read from them
+type HostCache = (Vec<Option<SystemTime>>, Vec<String>);
+
+lazy_static! {
+    static ref KNOWN_HOSTS: Mutex<Option<HostCache>> = Mutex::new(None);
+}
+
+// What an argument position completes to
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgKind {
+    Files,
+    Dirs,
+    Hosts,
+    Commands,
+    Variables,
+    Words(Vec<String>),
+    Nothing,
+}
+
+// Completion specs are `COMMAND KIND...`: one kind per argument position, the last
+// one repeating. Kinds are files, dirs, hosts, commands, variables, none, or a
+// word list like {start,stop,status}.
+const DEFAULT_SPECS: &[&str] = &[
+    "ssh hosts",
+    "mosh hosts",
+    "ping hosts",
+    "sudo commands",
+    "which commands",
+    "man commands",
+    "nohup commands",
+    "git {add,branch,checkout,clone,commit,diff,fetch,log,merge,pull,push,rebase,reset,stash,status,switch} files",
+    "systemctl {start,stop,restart,reload,status,enable,disable} none",
+];
+
+pub fn parse_spec(spec: &str) -> Result<(String, Vec<ArgKind>)> {
+    let mut words = spec.split_whitespace();
+    let command = words
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("empty completion spec"))?;
+    let kinds = words
+        .map(|word| match word {
+            "files" => Ok(ArgKind::Files),
+            "dirs" => Ok(ArgKind::Dirs),
+            "hosts" => Ok(ArgKind::Hosts),
+            "commands" => Ok(ArgKind::Commands),
+            "variables" => Ok(ArgKind::Variables),
+            "none" => Ok(ArgKind::Nothing),
+            _ => match word.strip_prefix('{').and_then(|w| w.strip_suffix('}')) {
+                Some(list) => Ok(ArgKind::Words(list.split(',').map(|w| w.to_string()).collect())),
+                None => Err(anyhow::anyhow!("unknown completion kind '{}' in '{}'", word, spec)),
+            },
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((command.to_string(), kinds))
+}
 
 pub struct CompletionEngine {
     commands: HashSet<String>,
+    rules: HashMap<String, Vec<ArgKind>>,
+    // Workspace bookmarks, offered to `cd` as @name
+    bookmarks: Vec<String>,
+    // Alias names, refreshed by the shell before each prompt
+    aliases: Vec<String>,
 }
 
 impl CompletionEngine {
     pub fn new() -> Self {
-        CompletionEngine {
+        let mut engine = CompletionEngine {
             commands: HashSet::new(),
+            rules: HashMap::new(),
+            bookmarks: Vec::new(),
+            aliases: Vec::new(),
+        };
+        for spec in DEFAULT_SPECS {
+            engine.add_rule(spec).expect("default completion specs are valid");
         }
+        engine
     }
-    
-    pub fn initialize(&mut self) -> Result<()> {
+
+    pub fn initialize(&mut self, scan_path: bool) -> Result<()> {
         // Load commands from PATH
-        self.load_commands_from_path()?;
-        
-        // Add built-in commands
-        self.add_builtin_commands();
-        
+        if scan_path {
+            self.load_commands_from_path()?;
+        }
+
         Ok(())
     }
-    
+
     fn load_commands_from_path(&mut self) -> Result<()> {
         if let Ok(path) = std::env::var("PATH") {
             for path_entry in path.split(':') {
                 let path_dir = Path::new(path_entry);
+                // On Windows drives only .exe files are programs, even though every file has exec bits
+                if wsl::is_windows_mount(path_entry) {
+                    self.load_windows_commands(path_dir);
+                    continue;
+                }
                 if path_dir.exists() && path_dir.is_dir() {
                     if let Ok(entries) = fs::read_dir(path_dir) {
                         for entry in entries.flatten() {
@@ -57,85 +133,304 @@ impl CompletionEngine {
                 }
             }
         }
-        
+
+        Ok(())
+    }
+
+    fn load_windows_commands(&mut self, dir: &Path) {
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                if let Some(name) = entry.file_name().to_str() {
+                    if name.to_lowercase().ends_with(".exe") {
+                        self.commands.insert(name.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    // Builtins are registered by the shell, which owns their definitions
+    pub fn add_commands(&mut self, names: &[&str]) {
+        for name in names {
+            self.commands.insert(name.to_string());
+        }
+    }
+
+    // Later rules for the same command replace earlier ones
+    pub fn add_rule(&mut self, spec: &str) -> Result<()> {
+        let (command, kinds) = parse_spec(spec)?;
+        self.rules.insert(command, kinds);
         Ok(())
     }
-    
-    fn add_builtin_commands(&mut self) {
-        // Add shell built-ins
-        let builtins = [
-            "cd", "alias", "unalias", "exit", "help", "jobs", "fg", "bg",
-            "echo", "export", "source", ".", "history", "pwd", "type",
-        ];
-        
-        for cmd in builtins {
-            self.commands.insert(cmd.to_string());
+
+    pub fn set_bookmarks(&mut self, bookmarks: Vec<String>) {
+        self.bookmarks = bookmarks;
+    }
+
+    pub fn set_aliases(&mut self, aliases: Vec<String>) {
+        self.aliases = aliases;
+    }
+
+    // Returns where the word under the cursor starts and its candidates
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let partial = &before[start..];
+        let words: Vec<&str> = before[..start].split_whitespace().collect();
+
+        let mut candidates = match words.first() {
+            None if partial.contains('/') => self.complete_path(partial, false),
+            None => self.complete_command(partial),
+            Some(_) if partial.starts_with('$') => Self::complete_variable(partial),
+            Some(_) if partial.starts_with('-') => Vec::new(),
+            Some(command) => {
+                // Options don't take up an argument position
+                let position = words[1..].iter().filter(|word| !word.starts_with('-')).count();
+                self.complete_argument(command, position, partial)
+            }
+        };
+        candidates.sort();
+        candidates.dedup();
+        (start, candidates)
+    }
+
+    fn complete_argument(&self, command: &str, position: usize, partial: &str) -> Vec<String> {
+        let kind = self
+            .rules
+            .get(command)
+            .and_then(|kinds| kinds.get(position).or(kinds.last()))
+            .unwrap_or(&ArgKind::Files);
+
+        match kind {
+            ArgKind::Files => self.complete_path(partial, false),
+            ArgKind::Dirs => {
+                let mut candidates = self.complete_path(partial, true);
+                if command == "cd" || command == "pushd" {
+                    candidates.extend(self.complete_cd_extras(partial));
+                }
+                candidates
+            }
+            ArgKind::Hosts => known_hosts()
+                .into_iter()
+                .filter(|host| host.starts_with(partial))
+                .collect(),
+            ArgKind::Commands => self.complete_command(partial),
+            ArgKind::Variables => std::env::vars()
+                .map(|(name, _)| name)
+                .filter(|name| name.starts_with(partial))
+                .collect(),
+            ArgKind::Words(words) => words
+                .iter()
+                .filter(|word| word.starts_with(partial))
+                .cloned()
+                .collect(),
+            ArgKind::Nothing => Vec::new(),
+        }
+    }
+
+    // Bookmarks as @name, and directories reachable through CDPATH
+    fn complete_cd_extras(&self, partial: &str) -> Vec<String> {
+        if let Some(name) = partial.strip_prefix('@') {
+            return self
+                .bookmarks
+                .iter()
+                .filter(|bookmark| bookmark.starts_with(name))
+                .map(|bookmark| format!("@{}", bookmark))
+                .collect();
+        }
+        if partial.contains('/') || partial.starts_with('.') || partial.starts_with('~') {
+            return Vec::new();
         }
+
+        let cdpath = std::env::var("CDPATH").unwrap_or_default();
+        cdpath
+            .split(':')
+            .filter(|dir| !dir.is_empty() && *dir != ".")
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.flatten())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(|name| name.to_string()))
+            .filter(|name| name.starts_with(partial) && !name.starts_with('.'))
+            .map(|name| format!("{}/", name))
+            .collect()
     }
-    
-    pub fn get_commands(&self) -> Vec<String> {
-        self.commands.iter().cloned().collect()
+
+    fn complete_variable(partial: &str) -> Vec<String> {
+        let (prefix, name) = match partial.strip_prefix("${") {
+            Some(name) => ("${", name),
+            None => ("$", &partial[1..]),
+        };
+        std::env::vars()
+            .map(|(var, _)| var)
+            .filter(|var| var.starts_with(name))
+            .map(|var| if prefix == "${" { format!("${{{}}}", var) } else { format!("${}", var) })
+            .collect()
     }
-    
+
     pub fn complete_command(&self, partial: &str) -> Vec<String> {
         self.commands
             .iter()
+            .chain(&self.aliases)
             .filter(|cmd| cmd.starts_with(partial))
             .cloned()
             .collect()
     }
-    
-    pub fn complete_path(&self, partial: &str) -> Vec<String> {
+
+    // Commands whose arguments complete to host names
+    pub fn commands_completing_hosts(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|(_, kinds)| kinds.contains(&ArgKind::Hosts))
+            .map(|(command, _)| command.clone())
+            .collect()
+    }
+
+    // Candidates keep the directory part as typed (including `~`)
+    pub fn complete_path(&self, partial: &str, dirs_only: bool) -> Vec<String> {
+        // Under WSL, `C:\Us` completes as its mount point /mnt/c/Us
+        if wsl::is_active() {
+            if let Some(unix_path) = wsl::to_unix_path(partial) {
+                return self.complete_path(&unix_path, dirs_only);
+            }
+        }
         let mut results = Vec::new();
-        
+
+        // Split into what was typed before the last slash and the name being completed
+        let (typed_dir, file_part) = match partial.rfind('/') {
+            Some(last_slash) => (&partial[..=last_slash], &partial[last_slash + 1..]),
+            None => ("", partial),
+        };
+
         // Handle home directory expansion
-        let expanded_partial = if partial.starts_with('~') {
-            if let Some(home) = dirs::home_dir() {
-                if partial.len() == 1 {
-                    // Just "~"
-                    home.to_string_lossy().to_string()
-                } else {
-                    // "~/something"
-                    home.join(&partial[2..]).to_string_lossy().to_string()
-                }
-            } else {
-                partial.to_string()
+        let dir_part = if typed_dir.is_empty() {
+            PathBuf::from(".")
+        } else if let Some(rest) = typed_dir.strip_prefix('~') {
+            match dirs::home_dir() {
+                Some(home) => home.join(rest.trim_start_matches('/')),
+                None => PathBuf::from(typed_dir),
             }
         } else {
-            partial.to_string()
-        };
-        
-        // Split into directory and file parts
-        let (dir_part, file_part) = if let Some(last_slash) = expanded_partial.rfind('/') {
-            let dir = &expanded_partial[..=last_slash];
-            let file = &expanded_partial[last_slash + 1..];
-            (PathBuf::from(dir), file.to_string())
-        } else {
-            (PathBuf::from("."), expanded_partial)
+            PathBuf::from(typed_dir)
         };
-        
+
         // Read directory entries
-        if dir_part.exists() && dir_part.is_dir() {
-            if let Ok(entries) = fs::read_dir(&dir_part) {
-                for entry in entries.flatten() {
-                    if let Some(name) = entry.file_name().to_str() {
-                        if name.starts_with(&file_part) {
-                            let mut full_path = dir_part.join(name);
-                            
-                            // Add trailing slash for directories
-                            if let Ok(metadata) = entry.metadata() {
-                                if metadata.is_dir() {
-                                    full_path = full_path.join("");
-                                }
-                            }
-                            
-                            results.push(full_path.to_string_lossy().to_string());
-                        }
+        if let Ok(entries) = fs::read_dir(&dir_part) {
+            for entry in entries.flatten() {
+                if let Some(name) = entry.file_name().to_str() {
+                    // Hidden entries only when asked for
+                    if !name.starts_with(file_part) || (name.starts_with('.') && !file_part.starts_with('.')) {
+                        continue;
+                    }
+
+                    // Follow symlinks so links to directories count as directories
+                    let is_dir = entry.path().is_dir();
+                    if dirs_only && !is_dir {
+                        continue;
                     }
+
+                    // Add trailing slash for directories
+                    let suffix = if is_dir { "/" } else { "" };
+                    results.push(format!("{}{}{}", typed_dir, name, suffix));
                 }
             }
         }
-        
+
         results
     }
-}
\ No newline at end of file
+}
+
+// Hosts from ~/.ssh/config, ~/.ssh/known_hosts and /etc/hosts, read again
+// only when one of them changes
+pub fn known_hosts() -> Vec<String> {
+    let ssh = dirs::home_dir().map(|home| home.join(".ssh"));
+    let files = [ssh.as_ref().map(|ssh| ssh.join("config")), ssh.map(|ssh| ssh.join("known_hosts")), Some(PathBuf::from("/etc/hosts"))];
+    let stamps: Vec<Option<SystemTime>> = files
+        .iter()
+        .map(|file| file.as_ref().and_then(|file| fs::metadata(file).and_then(|meta| meta.modified()).ok()))
+        .collect();
+    if let Some((cached, hosts)) = &*KNOWN_HOSTS.lock().unwrap() {
+        if *cached == stamps {
+            return hosts.clone();
+        }
+    }
+
+    let read = |file: &Option<PathBuf>| file.as_ref().and_then(|file| fs::read_to_string(file).ok());
+    let mut hosts = Vec::new();
+    if let Some(config) = read(&files[0]) {
+        for line in config.lines() {
+            let mut words = line.split_whitespace();
+            if words.next().map(|w| w.eq_ignore_ascii_case("host")).unwrap_or(false) {
+                hosts.extend(words.filter(|w| !w.contains(['*', '?', '!'])).map(|w| w.to_string()));
+            }
+        }
+    }
+    if let Some(known) = read(&files[1]) {
+        for line in known.lines() {
+            // Hashed entries (|1|...) can't be completed
+            let field = line.split_whitespace().next().unwrap_or("");
+            if field.starts_with('|') || field.starts_with('#') {
+                continue;
+            }
+            for host in field.split(',') {
+                let host = host.trim_start_matches('[').split(']').next().unwrap_or(host);
+                if !host.is_empty() {
+                    hosts.push(host.to_string());
+                }
+            }
+        }
+    }
+    if let Some(etc_hosts) = read(&files[2]) {
+        for line in etc_hosts.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            hosts.extend(line.split_whitespace().skip(1).map(|w| w.to_string()));
+        }
+    }
+    *KNOWN_HOSTS.lock().unwrap() = Some((stamps, hosts.clone()));
+    hosts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        let (command, kinds) = parse_spec("git {add,commit} files").unwrap();
+        assert_eq!(command, "git");
+        assert_eq!(
+            kinds,
+            vec![ArgKind::Words(vec!["add".to_string(), "commit".to_string()]), ArgKind::Files]
+        );
+        assert!(parse_spec("cd folders").is_err());
+        assert!(parse_spec("").is_err());
+    }
+
+    #[test]
+    fn test_argument_positions() {
+        let mut engine = CompletionEngine::new();
+        engine.add_rule("cd dirs").unwrap();
+
+        let dir = std::env::temp_dir().join(format!("llm-shell-complete-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+        let prefix = format!("{}/", dir.display());
+
+        let line = format!("cd {}", prefix);
+        let (start, candidates) = engine.complete(&line, line.len());
+        assert_eq!(start, 3);
+        assert_eq!(candidates, vec![format!("{}sub/", prefix)]);
+
+        let line = format!("cat {}", prefix);
+        let (_, candidates) = engine.complete(&line, line.len());
+        assert_eq!(candidates, vec![format!("{}file", prefix), format!("{}sub/", prefix)]);
+
+        let (_, candidates) = engine.complete("git co", 6);
+        assert_eq!(candidates, vec!["commit".to_string()]);
+
+        engine.set_aliases(vec!["gco".to_string()]);
+        let (_, candidates) = engine.complete("sudo gc", 7);
+        assert_eq!(candidates, vec!["gco".to_string()]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+use colored::*;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::{Hint, Hinter};
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Cmd, ConditionalEventHandler, Context, Event, EventContext, Helper, Movement, RepeatCount};
+use std::borrow::Cow;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use unicode_width::UnicodeWidthStr;
+use super::completion::CompletionEngine;
+use super::rprompt::{self, PromptInfo};
+
+// Called with the current line on every keystroke; may return text to show as an inline hint
+pub type InputListener = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+// Turns the line into a command; None when that isn't possible right now
+pub type Translator = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+// Whether the input leaves a compound command open, so Enter starts
+// another line instead of running it
+pub type Continuation = fn(&str) -> bool;
+
+// Replaces the buffer with its translation. An empty line keeps the key's
+// usual meaning.
+pub struct TranslateHandler {
+    pub translator: Translator,
+    // The translation while it is still in the buffer unchanged
+    pub last: Arc<Mutex<Option<String>>>,
+}
+
+impl ConditionalEventHandler for TranslateHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        let line = ctx.line();
+        if line.trim().is_empty() {
+            return None;
+        }
+        // Shown after the line until the redraw replaces it
+        let mut stdout = std::io::stdout();
+        let _ = write!(stdout, "\x1b7 {}\x1b8", "translating…".dimmed());
+        let _ = stdout.flush();
+        match (self.translator)(line) {
+            Some(command) if command != line => {
+                *self.last.lock().unwrap() = Some(command.clone());
+                Some(Cmd::Replace(Movement::WholeBuffer, Some(command)))
+            }
+            _ => {
+                let _ = write!(stdout, "\x1b[0K\x07");
+                let _ = stdout.flush();
+                Some(Cmd::Noop)
+            }
+        }
+    }
+}
+
+// rustyline records a replacement as a deletion and an insertion; undo
+// (Ctrl+_) right after a translation reverts both, restoring the request
+pub struct UndoTranslationHandler(pub Arc<Mutex<Option<String>>>);
+
+impl ConditionalEventHandler for UndoTranslationHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        let last = self.0.lock().unwrap().take();
+        (last.as_deref() == Some(ctx.line())).then_some(Cmd::Undo(2))
+    }
+}
+
+pub struct ShellHelper {
+    pub listener: Option<InputListener>,
+    pub completion: CompletionEngine,
+    // The prompt with its colors. rustyline is given it without escape
+    // sequences so it measures exactly what is visible, and draws this instead
+    pub styled_prompt: String,
+    // Right prompt template, drawn at the right edge of the input line while
+    // the line leaves room; rendered on every keystroke so the LLM indicator
+    // stays current
+    pub rprompt: Option<String>,
+    pub prompt_info: PromptInfo,
+    pub continuation: Option<Continuation>,
+}
+
+pub struct ShellHint {
+    text: String,
+    // Suggestions can be accepted with the right arrow; the right prompt can't
+    accept: bool,
+}
+
+impl Hint for ShellHint {
+    fn display(&self) -> &str {
+        &self.text
+    }
+
+    fn completion(&self) -> Option<&str> {
+        if self.accept { Some(&self.text) } else { None }
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = ShellHint;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<ShellHint> {
+        // Only suggest when the cursor is at the end of the line
+        let suggestion = if pos < line.len() { None } else { self.listener.as_ref().and_then(|listener| listener(line)) };
+        match suggestion {
+            Some(text) => Some(ShellHint { text, accept: true }),
+            None => self.right_prompt(line).map(|text| ShellHint { text, accept: false }),
+        }
+    }
+}
+
+impl ShellHelper {
+    // The right prompt padded to the edge, or None once the line reaches it
+    fn right_prompt(&self, line: &str) -> Option<String> {
+        let rprompt = rprompt::render(self.rprompt.as_deref()?, &self.prompt_info);
+        if rprompt.is_empty() || line.contains('\n') {
+            return None;
+        }
+        let prompt = strip_ansi(&self.styled_prompt);
+        let used = prompt.rsplit('\n').next().unwrap_or("").width() + line.width();
+        // The last column stays free so the terminal doesn't wrap
+        let free = columns().checked_sub(used + strip_ansi(&rprompt).width() + 1)?;
+        (free > 0).then(|| format!("{}{}", " ".repeat(free), rprompt))
+    }
+}
+
+impl Highlighter for ShellHelper {
+    // `default` is false for prompts rustyline draws itself, like reverse search
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, default: bool) -> Cow<'b, str> {
+        if default && !self.styled_prompt.is_empty() {
+            Cow::Borrowed(&self.styled_prompt)
+        } else {
+            Cow::Borrowed(prompt)
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(hint.dimmed().to_string())
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.completion.complete(line, pos))
+    }
+}
+
+impl Validator for ShellHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        match self.continuation {
+            Some(open) if open(ctx.input()) => Ok(ValidationResult::Incomplete),
+            _ => Ok(ValidationResult::Valid(None)),
+        }
+    }
+}
+
+impl Helper for ShellHelper {}
+
+// Removes ANSI escape sequences: CSI (colors, cursor movement), OSC (titles,
+// hyperlinks) and two-character escapes
+pub fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // Ends with BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+pub fn columns() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+        size.ws_col as usize
+    } else {
+        80
+    }
+}
+
+pub fn rows() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_row > 0 {
+        size.ws_row as usize
+    } else {
+        24
+    }
+}
+
+// Terminal rows taken by text without escape sequences on a screen `columns`
+// wide; like rustyline, a line that exactly fills the width moves the cursor on
+pub fn rows_used(text: &str, columns: usize) -> usize {
+    let columns = columns.max(1);
+    text.split('\n').map(|line| line.width() / columns + 1).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\n\x1b[94m┌─[\x1b[0m\x1b[1;92mme\x1b[0m\n└─\x1b[95m❯\x1b[0m "), "\n┌─[me\n└─❯ ");
+        assert_eq!(rows_used("\n┌─[me]\n└─❯ ls", 80), 3);
+        assert_eq!(rows_used(&format!("❯ {}", "x".repeat(100)), 40), 3);
+        assert_eq!(rows_used("日本", 4), 2);
+        assert_eq!(strip_ansi("\x1b]0;title\x07a\x1b]8;;https://x\x1b\\b\x1b7c"), "abc");
+    }
+}
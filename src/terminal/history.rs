@@ -3,11 +3,14 @@ use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
 use dirs;
+use crate::config::CONFIG;
 
 pub struct History {
     history_file: PathBuf,
     max_history_size: usize,
     entries: Vec<String>,
+    // Write each entry to the end of the file instead of rewriting it, so concurrent shells keep theirs
+    append: bool,
 }
 
 impl History {
@@ -17,8 +20,9 @@ impl History {
         
         let mut history = History {
             history_file,
-            max_history_size: 1000,
+            max_history_size: CONFIG.history_size,
             entries: Vec::new(),
+            append: false,
         };
         
         history.load()?;
@@ -51,6 +55,11 @@ impl History {
     }
     
     pub fn save(&self) -> Result<()> {
+        // Appended entries are already on disk
+        if self.append {
+            return Ok(());
+        }
+        
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
@@ -84,10 +93,22 @@ impl History {
             self.entries.remove(0);
         }
         
-        self.save()?;
+        if self.append {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.history_file)?;
+            writeln!(file, "{}", entry)?;
+        } else {
+            self.save()?;
+        }
         Ok(())
     }
     
+    pub fn set_append(&mut self, append: bool) {
+        self.append = append;
+    }
+    
     pub fn get_entries(&self) -> &[String] {
         &self.entries
     }
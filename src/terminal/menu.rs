@@ -0,0 +1,163 @@
+use anyhow::Result;
+use colored::*;
+use std::io::{IsTerminal, Write};
+use unicode_width::UnicodeWidthChar;
+use super::helper;
+
+// A key press, with arrow keys decoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Escape,
+    Interrupt,
+}
+
+// Reads one key press without waiting for Enter. None when stdin isn't a
+// terminal or input ends.
+pub fn read_key() -> Result<Option<Key>> {
+    use nix::sys::termios::{self, LocalFlags, SetArg, SpecialCharacterIndices};
+    let fd = libc::STDIN_FILENO;
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    let saved = termios::tcgetattr(fd)?;
+    let mut raw = saved.clone();
+    raw.local_flags.remove(LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ISIG);
+    raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+    raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+    termios::tcsetattr(fd, SetArg::TCSANOW, &raw)?;
+    let key = read_raw_key(fd, &mut raw);
+    termios::tcsetattr(fd, SetArg::TCSANOW, &saved)?;
+    Ok(key)
+}
+
+fn read_raw_key(fd: i32, raw: &mut nix::sys::termios::Termios) -> Option<Key> {
+    use nix::sys::termios::{self, SetArg, SpecialCharacterIndices};
+    let mut bytes = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        if nix::unistd::read(fd, &mut byte).ok()? != 1 {
+            return None;
+        }
+        bytes.push(byte[0]);
+        match bytes.as_slice() {
+            [b'\r' | b'\n'] => return Some(Key::Enter),
+            [0x03] => return Some(Key::Interrupt),
+            [0x1b] => {
+                // An arrow key follows its escape within a moment; Escape alone doesn't
+                raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+                raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 1;
+                termios::tcsetattr(fd, SetArg::TCSANOW, raw).ok()?;
+                let mut sequence = [0u8; 3];
+                let read = nix::unistd::read(fd, &mut sequence).unwrap_or(0);
+                return Some(match &sequence[..read] {
+                    [b'[' | b'O', b'A'] => Key::Up,
+                    [b'[' | b'O', b'B'] => Key::Down,
+                    [b'[', b'5', b'~'] => Key::PageUp,
+                    [b'[', b'6', b'~'] => Key::PageDown,
+                    _ => Key::Escape,
+                });
+            }
+            _ => {}
+        }
+        // A multi-byte character ends once it decodes
+        match std::str::from_utf8(&bytes) {
+            Ok(text) => return text.chars().next().map(Key::Char),
+            Err(_) if bytes.len() < 4 => continue,
+            Err(_) => return None,
+        }
+    }
+}
+
+// How a menu was left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    // Enter on this item
+    Chosen(usize),
+    // Its number was pressed
+    Numbered(usize),
+    Cancelled,
+}
+
+// An arrow-key menu: ↑/↓ (or k/j) move, Enter chooses, a number picks that
+// item directly, and Escape, q or Ctrl+C leave. Lines wider than the
+// terminal are cut so the menu can be redrawn in place.
+pub fn select(items: &[String], hint: &str) -> Result<Selection> {
+    if items.is_empty() {
+        return Ok(Selection::Cancelled);
+    }
+    let mut current = 0;
+    let mut stdout = std::io::stdout();
+    // Hidden so it doesn't blink on the line below the menu
+    write!(stdout, "\x1b[?25l")?;
+    let selection = loop {
+        draw(&mut stdout, items, current, hint)?;
+        let Some(key) = read_key()? else {
+            break Selection::Cancelled;
+        };
+        match key {
+            Key::Up | Key::Char('k') => current = current.checked_sub(1).unwrap_or(items.len() - 1),
+            Key::Down | Key::Char('j') | Key::Char('\t') => current = (current + 1) % items.len(),
+            Key::Enter => break Selection::Chosen(current),
+            Key::Char(c) => match c.to_digit(10).map(|n| n as usize) {
+                Some(n) if (1..=items.len().min(9)).contains(&n) => break Selection::Numbered(n - 1),
+                _ if c == 'q' => break Selection::Cancelled,
+                _ => {}
+            },
+            Key::Escape | Key::Interrupt => break Selection::Cancelled,
+            Key::PageUp | Key::PageDown => {}
+        }
+        // Back to the first line of the menu for the redraw
+        write!(stdout, "\x1b[{}A\r", items.len() + 1)?;
+    };
+    write!(stdout, "\x1b[?25h")?;
+    stdout.flush()?;
+    Ok(selection)
+}
+
+fn draw(stdout: &mut std::io::Stdout, items: &[String], current: usize, hint: &str) -> Result<()> {
+    let width = helper::columns().saturating_sub(6);
+    for (i, item) in items.iter().enumerate() {
+        let item = truncate(item, width);
+        if i == current {
+            writeln!(stdout, "\x1b[2K{} {}", "❯".bright_purple(), item.bright_cyan().bold())?;
+        } else {
+            let number = if i < 9 { format!("{}", i + 1) } else { " ".to_string() };
+            writeln!(stdout, "\x1b[2K{} {}", number.dimmed(), item.cyan())?;
+        }
+    }
+    writeln!(stdout, "\x1b[2K{}", truncate(hint, width).dimmed())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+pub(super) fn truncate(text: &str, width: usize) -> String {
+    let mut used = 0;
+    let mut result = String::new();
+    for c in text.chars() {
+        used += c.width().unwrap_or(0);
+        if used >= width {
+            result.push('…');
+            return result;
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("git status", 20), "git status");
+        assert_eq!(truncate("git status", 5), "git …");
+        assert_eq!(truncate("日本語", 4), "日…");
+    }
+}
@@ -1,31 +1,65 @@
 mod history;
 mod completion;
+mod helper;
+mod rprompt;
+mod menu;
+mod pager;
 
 use anyhow::Result;
-use rustyline::{DefaultEditor, Config, EditMode};
+use rustyline::{Editor, Config, EditMode, EventHandler, KeyEvent};
+use rustyline::config::Configurer;
+use rustyline::history::DefaultHistory;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use colored::*;
 use std::env;
+use std::io::{BufRead, IsTerminal};
 use std::process::Command;
 use self::history::History;
 use self::completion::CompletionEngine;
+use self::helper::{ShellHelper, TranslateHandler, UndoTranslationHandler};
+pub use self::helper::{Continuation, InputListener, Translator};
+pub use self::rprompt::{LlmStatus, PromptInfo};
+pub use self::completion::known_hosts;
+pub use self::menu::{read_key, select, Key, Selection};
+pub use self::pager::{Pager, PagerAction};
+use crate::system::ssh::SshSession;
+use crate::system::cloud::CloudContext;
+use crate::system::wsl;
+use crate::config::CONFIG;
+use crate::utils::i18n;
 
 pub struct Terminal {
-    editor: DefaultEditor,
+    editor: Editor<ShellHelper, DefaultHistory>,
     history: History,
-    completion_engine: CompletionEngine,
+    over_ssh: bool,
+    target: Option<String>,
+    workspace: Option<String>,
+    accent: Color,
+    // False when stdin or stdout is a pipe or file; input is then read
+    // without a prompt, line editing or history
+    interactive: bool,
+    // Text the next prompt starts with, ready to edit
+    prefill: Option<String>,
+    // Filled into the right prompt template
+    prompt_info: PromptInfo,
+    continuation: Option<Continuation>,
 }
 
 impl Terminal {
     pub fn new() -> Self {
         // Configure rustyline
-        let config = Config::builder()
+        let mut config = Config::builder()
             .edit_mode(EditMode::Emacs)
             .auto_add_history(false)
-            .completion_type(rustyline::CompletionType::List)
-            .build();
+            .completion_type(rustyline::CompletionType::List);
+        // Up-arrow recall reaches as far back as the history file keeps
+        if let Err(e) = config.set_max_history_size(CONFIG.history_size) {
+            log::debug!("Failed to set the history size: {}", e);
+        }
+        let config = config.build();
             
-        let editor = DefaultEditor::with_config(config).unwrap_or_else(|_| DefaultEditor::new().unwrap());
+        let mut editor = Editor::with_config(config).unwrap_or_else(|_| Editor::new().unwrap());
         
         // Initialize history
         let history = History::new().unwrap_or_else(|e| {
@@ -33,44 +67,77 @@ impl Terminal {
             History::new().unwrap()
         });
         
+        // Skip expensive local-only work when running over SSH
+        let over_ssh = SshSession::is_active();
+        
         // Initialize completion engine
         let mut completion_engine = CompletionEngine::new();
-        completion_engine.initialize().unwrap_or_else(|e| {
+        completion_engine.initialize(!over_ssh).unwrap_or_else(|e| {
             eprintln!("Warning: Failed to initialize completion engine: {}", e);
         });
+        editor.set_helper(Some(ShellHelper { listener: None, completion: completion_engine, styled_prompt: String::new(),
+            rprompt: CONFIG.rprompt.clone(),
+            prompt_info: PromptInfo::default(),
+            continuation: None,
+        }));
         
         Terminal {
             editor,
             history,
-            completion_engine,
+            over_ssh,
+            target: None,
+            workspace: None,
+            accent: Color::BrightBlue,
+            interactive: std::io::stdin().is_terminal() && std::io::stdout().is_terminal(),
+            prefill: None,
+            prompt_info: PromptInfo::default(),
+            continuation: None,
         }
     }
 
-    pub fn read_line(&mut self) -> Result<(String, bool)> {
+    // Returns None at end of input (Ctrl+D on an empty line)
+    pub fn read_line(&mut self) -> Result<Option<(String, bool)>> {
+        if !self.interactive {
+            let mut line = String::new();
+            if std::io::stdin().lock().read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            // A compound command read from a pipe goes on until it is closed
+            // or the input ends
+            while self.continuation.is_some_and(|open| open(line.trim())) {
+                if std::io::stdin().lock().read_line(&mut line)? == 0 {
+                    break;
+                }
+            }
+            return Ok(Some((line.trim().to_string(), false)));
+        }
         let prompt = self.create_prompt()?;
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.prompt_info = self.prompt_info.clone();
+        }
         
         // Read input with tab completion
-        let line = match self.editor.readline(&prompt) {
-            Ok(line) => line,
-            Err(err) => {
-                // Handle different error types
-                if err.to_string().contains("interrupted") {
-                    // Ctrl+C was pressed
-                    return Ok(("".to_string(), false));
-                } else if err.to_string().contains("eof") {
-                    // Ctrl+D was pressed - exit
-                    return Ok(("exit".to_string(), false));
-                } else {
-                    return Err(anyhow::anyhow!("Error reading input: {}", err));
+        let prefill = self.prefill.take();
+        let readline = self.readline_styled(&prompt, prefill.as_deref());
+        let line = match readline {
+            Ok(line) => {
+                if CONFIG.transient_prompt {
+                    self.collapse_prompt(&prompt, &line);
                 }
+                line
             }
+            // Ctrl+C was pressed
+            Err(rustyline::error::ReadlineError::Interrupted) => return Ok(Some(("".to_string(), false))),
+            Err(rustyline::error::ReadlineError::Eof) => return Ok(None),
+            Err(err) => return Err(anyhow::anyhow!("Error reading input: {}", err)),
         };
         
         let trimmed = line.trim();
         
         // Consider showing suggestions if the line ends with '??'
+        // (a lone trailing '?' is left alone so `echo $?` works)
         let show_suggestions = trimmed.ends_with("??");
-        let line = trimmed.trim_end_matches('?').to_string();
+        let line = if show_suggestions { trimmed.trim_end_matches('?') } else { trimmed }.to_string();
         
         // Add to history if non-empty
         if !line.is_empty() {
@@ -78,7 +145,7 @@ impl Terminal {
             self.editor.add_history_entry(&line)?;
         }
         
-        Ok((line, show_suggestions))
+        Ok(Some((line, show_suggestions)))
     }
 
     fn create_prompt(&self) -> Result<String> {
@@ -89,15 +156,31 @@ impl Terminal {
         let username = env::var("USER").unwrap_or_else(|_| "user".to_string());
         let hostname = self.get_hostname();
         let git_info = self.get_git_info()?;
+        let mut ssh_info = if self.over_ssh {
+            format!("─[{}]", "ssh".bright_red())
+        } else {
+            String::new()
+        };
+        if let Some(target) = &self.target {
+            ssh_info.push_str(&format!("─[{}{}]", "→ ".bright_red(), target.bright_red()));
+        }
+        
+        if let Some(workspace) = &self.workspace {
+            ssh_info.push_str(&format!("─[{}]", workspace.color(self.accent).bold()));
+        }
+        
+        if CONFIG.llm_prompt {
+            ssh_info.push_str(&format!("─[{}]", rprompt::llm_segment(&self.prompt_info)));
+        }
         
         // Create a fancy multi-line prompt
         Ok(format!("\n{}{}{}{}{}",
-            "┌─[".bright_blue(),
+            "┌─[".color(self.accent),
             username.bright_green(),
-            "@".bright_blue(),
+            "@".color(self.accent),
             hostname.bright_cyan(),
-            "]".bright_blue(),
-        ) + &format!("─[{}]", path.bright_yellow()) + &git_info + "\n" +
+            "]".color(self.accent),
+        ) + &ssh_info + &format!("─[{}]", path.bright_yellow()) + &git_info + &self.get_cloud_info() + "\n" +
             &format!("└─{} ", "❯".bright_purple()))
     }
 
@@ -119,6 +202,9 @@ impl Terminal {
             } else {
                 format!("~/{}", stripped.to_string_lossy())
             }
+        } else if wsl::is_active() {
+            // Windows drives read the way Windows shows them
+            wsl::to_windows_path(&path_str).unwrap_or_else(|| path_str.to_string())
         } else {
             path_str.to_string()
         }
@@ -149,24 +235,29 @@ impl Terminal {
                 }
             });
         
-        // Try to get git status
-        let status_clean = Command::new("git")
-            .args(&["diff", "--quiet"])
-            .status()
-            .map(|status| status.success())
-            .unwrap_or(true);
+        // Try to get git status (skipped over SSH, where the worktree may be
+        // on a slow mount; then there's no marker at all)
+        let status_clean = if self.over_ssh {
+            None
+        } else {
+            Command::new("git").args(["diff", "--quiet"]).status().map(|status| status.success()).ok()
+        };
         
         match branch {
             Some(branch) => {
                 let branch = branch.trim();
-                let status_symbol = if status_clean {
-                    "✓".green()
-                } else {
-                    "✗".red()
+                let status_symbol = match status_clean {
+                    Some(true) => "✓".green(),
+                    Some(false) => "✗".red(),
+                    None => "".normal(),
                 };
                 
                 // Get ahead/behind status
-                let ahead_behind = self.get_git_ahead_behind()?;
+                let ahead_behind = if self.over_ssh {
+                    String::new()
+                } else {
+                    self.get_git_ahead_behind()?
+                };
                 
                 Ok(format!("─[{}{}{}", 
                     branch.bright_purple(), 
@@ -178,6 +269,28 @@ impl Terminal {
         }
     }
     
+    fn get_cloud_info(&self) -> String {
+        if !CONFIG.cloud_prompt {
+            return String::new();
+        }
+        
+        let cloud = CloudContext::current();
+        let mut info = String::new();
+        
+        if let Some(context) = &cloud.kube_context {
+            let namespace = cloud.kube_namespace.as_deref().unwrap_or("default");
+            info.push_str(&format!("─[{}{}{}]", context.bright_blue(), ":".normal(), namespace.blue()));
+        }
+        if let Some(profile) = &cloud.aws_profile {
+            info.push_str(&format!("─[{}{}]", "aws:".yellow(), profile.bright_yellow()));
+        }
+        if let Some(project) = &cloud.gcloud_project {
+            info.push_str(&format!("─[{}{}]", "gcp:".cyan(), project.bright_cyan()));
+        }
+        
+        info
+    }
+    
     fn get_git_ahead_behind(&self) -> Result<String> {
         // Get ahead/behind counts
         let output = Command::new("git")
@@ -215,6 +328,140 @@ impl Terminal {
         }
     }
     
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    pub fn set_prefill(&mut self, text: &str) {
+        if self.interactive {
+            self.prefill = Some(text.to_string());
+        }
+    }
+
+    // Numbered menu for `select`
+    pub fn print_menu(&self, items: &[String]) {
+        let width = items.len().to_string().len();
+        for (i, item) in items.iter().enumerate() {
+            eprintln!("{}) {}", format!("{:>width$}", i + 1, width = width).color(self.accent), item);
+        }
+    }
+    
+    // Rewrites the prompt and the accepted line, which the cursor is now
+    // below, as one compact line
+    fn collapse_prompt(&self, prompt: &str, line: &str) {
+        let rows = helper::rows_used(&format!("{}{}", helper::strip_ansi(prompt), line), helper::columns());
+        print!("\x1b[{}A\r\x1b[J{} {}\n", rows, "❯".bright_purple(), line);
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+    }
+
+    // rustyline measures the prompt without its escape sequences, so wrapped
+    // lines are redrawn in the right place; the helper draws the colors
+    fn readline_styled(&mut self, prompt: &str, initial: Option<&str>) -> rustyline::Result<String> {
+        let plain = helper::strip_ansi(prompt);
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.styled_prompt = prompt.to_string();
+        }
+        match initial {
+            Some(text) => self.editor.readline_with_initial(&plain, (text, "")),
+            None => self.editor.readline(&plain),
+        }
+    }
+
+    // Reads a reply to a menu or question; None once input ends or on Ctrl+C
+    pub fn read_reply(&mut self, prompt: &str) -> Result<Option<String>> {
+        match self.readline_styled(prompt, None) {
+            Ok(line) => Ok(Some(line.trim().to_string())),
+            Err(rustyline::error::ReadlineError::Interrupted) | Err(rustyline::error::ReadlineError::Eof) => Ok(None),
+            Err(err) => Err(anyhow::anyhow!("Error reading input: {}", err)),
+        }
+    }
+    
+    pub fn add_completion_commands(&mut self, names: &[&str]) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.completion.add_commands(names);
+        }
+    }
+    
+    pub fn add_completion_rule(&mut self, spec: &str) -> Result<()> {
+        match self.editor.helper_mut() {
+            Some(helper) => helper.completion.add_rule(spec),
+            None => Ok(()),
+        }
+    }
+    
+    pub fn commands_completing_hosts(&self) -> Vec<String> {
+        self.editor.helper().map(|helper| helper.completion.commands_completing_hosts()).unwrap_or_default()
+    }
+    
+    pub fn set_completion_bookmarks(&mut self, bookmarks: Vec<String>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.completion.set_bookmarks(bookmarks);
+        }
+    }
+    
+    pub fn set_prompt_info(&mut self, info: PromptInfo) {
+        self.prompt_info = info;
+    }
+    
+    pub fn set_completion_aliases(&mut self, aliases: Vec<String>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.completion.set_aliases(aliases);
+        }
+    }
+    
+    pub fn set_continuation(&mut self, continuation: Continuation) {
+        self.continuation = Some(continuation);
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.continuation = Some(continuation);
+        }
+    }
+
+    pub fn set_input_listener(&mut self, listener: InputListener) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.listener = Some(listener);
+        }
+    }
+
+    // Ctrl+G translates whatever is in the buffer in place, and Ctrl+_
+    // brings the request back
+    pub fn set_translator(&mut self, translator: Translator) {
+        let last = Arc::new(Mutex::new(None));
+        self.editor.bind_sequence(
+            KeyEvent::ctrl('G'),
+            EventHandler::Conditional(Box::new(TranslateHandler { translator, last: Arc::clone(&last) })),
+        );
+        self.editor.bind_sequence(KeyEvent::ctrl('_'), EventHandler::Conditional(Box::new(UndoTranslationHandler(last))));
+    }
+    
+    // Workspaces can recolor the prompt frame, e.g. red for production
+    pub fn set_workspace(&mut self, name: Option<String>, theme: Option<&str>) -> Result<()> {
+        self.accent = match theme.unwrap_or("default") {
+            "default" | "blue" => Color::BrightBlue,
+            "red" => Color::BrightRed,
+            "green" => Color::BrightGreen,
+            "yellow" => Color::BrightYellow,
+            "magenta" => Color::BrightMagenta,
+            "cyan" => Color::BrightCyan,
+            theme => return Err(anyhow::anyhow!("unknown prompt theme '{}' (use default, red, green, yellow, magenta or cyan)", theme)),
+        };
+        self.workspace = name;
+        Ok(())
+    }
+    
+    pub fn set_target_indicator(&mut self, target: Option<String>) {
+        self.target = target;
+    }
+    
+    pub fn set_history_append(&mut self, append: bool) {
+        self.history.set_append(append);
+    }
+    
+    pub fn save_history(&self) {
+        if let Err(e) = self.history.save() {
+            eprintln!("Warning: Failed to save history: {}", e);
+        }
+    }
+    
     pub fn get_history(&self) -> &History {
         &self.history
     }
@@ -224,11 +471,58 @@ impl Terminal {
     }
 }
 
+// Clears `text`, printed from the start of the current line, so what
+// replaces it starts in the same place
+pub fn erase(text: &str) {
+    let rows = helper::rows_used(text, helper::columns());
+    if rows > 1 {
+        print!("\r\x1b[{}A\x1b[J", rows - 1);
+    } else {
+        print!("\r\x1b[J");
+    }
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+}
+
+// Asks a yes/no question; "y", "yes" or the word for yes in the user's
+// language count as yes. Ctrl+C and end of input answer no, and so does a
+// stdin that isn't a terminal, so piped scripts can't answer for the user.
+// A separate editor keeps the answer out of the history.
+pub fn confirm(prompt: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        eprintln!("{}{}", prompt, i18n::tr("confirm.not_terminal"));
+        return Ok(false);
+    }
+    let mut editor = rustyline::DefaultEditor::new()?;
+    match editor.readline(prompt) {
+        Ok(reply) => Ok(i18n::is_yes(&reply)),
+        Err(rustyline::error::ReadlineError::Interrupted) | Err(rustyline::error::ReadlineError::Eof) => {
+            println!();
+            Ok(false)
+        }
+        Err(err) => Err(anyhow::anyhow!("Error reading input: {}", err)),
+    }
+}
+
+// Like `confirm`, but proceeds only when `expected` is typed exactly
+pub fn confirm_typed(prompt: &str, expected: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        eprintln!("{}{}", prompt, i18n::tr("confirm.not_terminal"));
+        return Ok(false);
+    }
+    let mut editor = rustyline::DefaultEditor::new()?;
+    match editor.readline(prompt) {
+        Ok(reply) => Ok(reply.trim() == expected),
+        Err(rustyline::error::ReadlineError::Interrupted) | Err(rustyline::error::ReadlineError::Eof) => {
+            println!();
+            Ok(false)
+        }
+        Err(err) => Err(anyhow::anyhow!("Error reading input: {}", err)),
+    }
+}
+
 impl Drop for Terminal {
     fn drop(&mut self) {
         // Save history when terminal is dropped
-        if let Err(e) = self.history.save() {
-            eprintln!("Warning: Failed to save history: {}", e);
-        }
+        self.save_history();
     }
 }
\ No newline at end of file
@@ -0,0 +1,311 @@
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::Write;
+use super::helper;
+use super::menu::{read_key, truncate, Key};
+
+// Lines around an error line sent with it, so the model sees what led to it
+const CONTEXT_LINES: usize = 5;
+
+lazy_static! {
+    static ref ERROR_RE: Regex = Regex::new(
+        r"(?i)\b(error|errors|failed|failure|fatal|panic|panicked|exception|traceback|denied|cannot|can't|unable to|not found|no such)\b"
+    ).unwrap();
+}
+
+// What the caller should do after a key press; the LLM actions need the
+// model, which the pager doesn't have
+#[derive(Debug, Clone, PartialEq)]
+pub enum PagerAction {
+    None,
+    Quit,
+    // Summarize the lines on screen
+    Summarize,
+    // Explain this line, with the lines around it
+    Explain(usize),
+    // Turn the lines on screen into CSV
+    Table,
+}
+
+// A less-like view of captured text: ↑/↓ (k/j) scroll a line, Space/b (or
+// PageDown/PageUp) a screen, g/G jump to the ends, / searches and n finds
+// the next match. s, e and t ask for the LLM actions.
+pub struct Pager {
+    title: String,
+    lines: Vec<String>,
+    top: usize,
+    // The line the last search found, which `e` explains when it's on screen
+    marked: Option<usize>,
+    search: Option<String>,
+    // Shown in place of the key hints until the next key
+    status: Option<String>,
+    actions: bool,
+    height: usize,
+}
+
+impl Pager {
+    pub fn new(title: &str, text: &str) -> Self {
+        let lines = helper::strip_ansi(text).replace('\t', "    ").lines().map(str::to_string).collect();
+        Pager {
+            title: title.to_string(),
+            lines,
+            top: 0,
+            marked: None,
+            search: None,
+            status: None,
+            actions: true,
+            height: helper::rows().saturating_sub(1).max(1),
+        }
+    }
+
+    // Switches to the alternate screen, so the shell's screen comes back as it was
+    pub fn open(&self) -> Result<()> {
+        let mut stdout = std::io::stdout();
+        write!(stdout, "\x1b[?1049h\x1b[?25l")?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    pub fn close(&self) -> Result<()> {
+        let mut stdout = std::io::stdout();
+        write!(stdout, "\x1b[?25h\x1b[?1049l")?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    pub fn set_status(&mut self, status: &str) {
+        self.status = Some(status.to_string());
+    }
+
+    // The lines on screen
+    pub fn visible(&self) -> String {
+        self.lines[self.top..self.bottom()].join("\n")
+    }
+
+    // An error line with the lines around it, the line itself marked with `>>>`
+    pub fn around(&self, index: usize) -> String {
+        let first = index.saturating_sub(CONTEXT_LINES);
+        let last = (index + CONTEXT_LINES + 1).min(self.lines.len());
+        (first..last)
+            .map(|i| format!("{} {}", if i == index { ">>>" } else { "   " }, self.lines[i]))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn bottom(&self) -> usize {
+        (self.top + self.height).min(self.lines.len())
+    }
+
+    fn last_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    fn scroll(&mut self, by: isize) {
+        self.top = self.top.saturating_add_signed(by).min(self.last_top());
+    }
+
+    pub fn draw(&self) -> Result<()> {
+        let mut stdout = std::io::stdout();
+        let width = helper::columns();
+        write!(stdout, "\x1b[H")?;
+        for row in 0..self.height {
+            write!(stdout, "\x1b[2K")?;
+            if let Some(line) = self.lines.get(self.top + row) {
+                let line = truncate(line, width);
+                if self.marked == Some(self.top + row) {
+                    write!(stdout, "\x1b[7m{}\x1b[0m", line)?;
+                } else {
+                    write!(stdout, "{}", line)?;
+                }
+            } else {
+                write!(stdout, "~")?;
+            }
+            write!(stdout, "\r\n")?;
+        }
+        let status = match &self.status {
+            Some(status) => status.clone(),
+            None => {
+                let position = if self.lines.len() <= self.height {
+                    "all".to_string()
+                } else {
+                    format!("{}-{}/{}", self.top + 1, self.bottom(), self.lines.len())
+                };
+                let keys = if self.actions { "s summarize · e explain error · t table to CSV · / search · q quit" } else { "q back" };
+                format!("{}  {}  {}", self.title, position, keys)
+            }
+        };
+        write!(stdout, "\x1b[2K\x1b[7m{}\x1b[0m", truncate(&status, width))?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    pub fn handle(&mut self, key: Key) -> Result<PagerAction> {
+        self.status = None;
+        let page = self.height as isize;
+        match key {
+            Key::Up | Key::Char('k') => self.scroll(-1),
+            Key::Down | Key::Enter | Key::Char('j') => self.scroll(1),
+            Key::PageDown | Key::Char(' ' | 'f') => self.scroll(page),
+            Key::PageUp | Key::Char('b') => self.scroll(-page),
+            Key::Char('g') => self.top = 0,
+            Key::Char('G') => self.top = self.last_top(),
+            Key::Char('/') => {
+                if let Some(query) = self.prompt("/", "")?.filter(|query| !query.is_empty()) {
+                    self.search = Some(query);
+                    self.find_next(self.top);
+                }
+            }
+            Key::Char('n') => match self.marked {
+                Some(marked) => self.find_next(marked + 1),
+                None => self.find_next(self.top),
+            },
+            Key::Char('q') | Key::Escape | Key::Interrupt => return Ok(PagerAction::Quit),
+            Key::Char('s') if self.actions => return Ok(PagerAction::Summarize),
+            Key::Char('t') if self.actions => return Ok(PagerAction::Table),
+            Key::Char('e') if self.actions => match self.error_line() {
+                Some(line) => return Ok(PagerAction::Explain(line)),
+                None => self.set_status("No error line on screen; search for one with / to mark it"),
+            },
+            _ => {}
+        }
+        Ok(PagerAction::None)
+    }
+
+    // Marks the next line from `start` with the search text and scrolls to it
+    fn find_next(&mut self, start: usize) {
+        let Some(query) = self.search.as_ref().map(|query| query.to_lowercase()) else {
+            return;
+        };
+        match (start..self.lines.len()).find(|&i| self.lines[i].to_lowercase().contains(&query)) {
+            Some(found) => {
+                self.marked = Some(found);
+                if found < self.top || found >= self.bottom() {
+                    self.top = found.saturating_sub(2).min(self.last_top());
+                }
+            }
+            None => self.set_status(&format!("Pattern not found: {}", query)),
+        }
+    }
+
+    // The marked line when it's on screen, otherwise the first one on
+    // screen that looks like an error
+    fn error_line(&self) -> Option<usize> {
+        let visible = self.top..self.bottom();
+        self.marked
+            .filter(|marked| visible.contains(marked))
+            .or_else(|| visible.clone().find(|&i| ERROR_RE.is_match(&self.lines[i])))
+    }
+
+    // Reads a line of text typed on the status line; None when cancelled
+    pub fn prompt(&self, label: &str, initial: &str) -> Result<Option<String>> {
+        let mut stdout = std::io::stdout();
+        let mut text = initial.to_string();
+        write!(stdout, "\x1b[?25h")?;
+        let answer = loop {
+            write!(stdout, "\r\x1b[2K{}{}", label, text)?;
+            stdout.flush()?;
+            match read_key()? {
+                Some(Key::Enter) => break Some(text),
+                Some(Key::Char('\x7f' | '\x08')) => {
+                    text.pop();
+                }
+                Some(Key::Char(c)) if !c.is_control() => text.push(c),
+                None | Some(Key::Escape | Key::Interrupt) => break None,
+                Some(_) => {}
+            }
+        };
+        write!(stdout, "\x1b[?25l")?;
+        Ok(answer)
+    }
+
+    // Shows text, wrapped to the screen, in a pager of its own until q
+    pub fn show(title: &str, text: &str) -> Result<()> {
+        let width = helper::columns().saturating_sub(1).max(20);
+        let mut pager = Pager::new(title, &wrap(text, width));
+        pager.actions = false;
+        loop {
+            pager.draw()?;
+            match read_key()? {
+                None => return Ok(()),
+                Some(key) => {
+                    if pager.handle(key)? == PagerAction::Quit {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Wraps lines longer than `width` at spaces, keeping their indentation
+fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = Vec::new();
+    for line in text.lines() {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let mut current = String::new();
+        for word in line.split_whitespace() {
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+                wrapped.push(std::mem::take(&mut current));
+            }
+            if current.is_empty() {
+                current.push_str(indent);
+            } else {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        wrapped.push(current);
+    }
+    wrapped.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pager(text: &str, height: usize) -> Pager {
+        let mut pager = Pager::new("test", text);
+        pager.height = height;
+        pager
+    }
+
+    #[test]
+    fn test_scrolling() {
+        let text: String = (1..=10).map(|n| format!("line {}\n", n)).collect();
+        let mut pager = pager(&text, 4);
+        pager.handle(Key::Char(' ')).unwrap();
+        assert_eq!(pager.visible(), "line 5\nline 6\nline 7\nline 8");
+        pager.handle(Key::Char(' ')).unwrap();
+        assert_eq!(pager.top, 6);
+        pager.handle(Key::Up).unwrap();
+        pager.handle(Key::Char('g')).unwrap();
+        assert_eq!(pager.top, 0);
+        pager.handle(Key::Char('k')).unwrap();
+        assert_eq!(pager.top, 0);
+        assert_eq!(pager.handle(Key::Char('q')).unwrap(), PagerAction::Quit);
+    }
+
+    #[test]
+    fn test_error_line() {
+        let mut pager = pager("compiling\nerror: missing semicolon\nok\n\x1b[31mfatal\x1b[0m: no\n", 3);
+        assert_eq!(pager.handle(Key::Char('e')).unwrap(), PagerAction::Explain(1));
+        assert!(pager.around(1).starts_with("    compiling\n>>> error: missing semicolon\n"));
+        pager.search = Some("FATAL".to_string());
+        pager.find_next(0);
+        assert_eq!((pager.marked, pager.lines[3].as_str()), (Some(3), "fatal: no"));
+        assert_eq!(pager.handle(Key::Char('e')).unwrap(), PagerAction::Explain(3));
+        pager.handle(Key::Char('g')).unwrap();
+        pager.marked = None;
+        pager.lines[1] = "fine".to_string();
+        assert_eq!(pager.handle(Key::Char('e')).unwrap(), PagerAction::None);
+        assert!(pager.status.is_some());
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("one two three four", 9), "one two\nthree\nfour");
+        assert_eq!(wrap("  - a b c", 6), "  - a\n  b c");
+        assert_eq!(wrap("x\n\ny", 9), "x\n\ny");
+    }
+}
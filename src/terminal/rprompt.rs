@@ -0,0 +1,102 @@
+use colored::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use crate::system::scheduler;
+#[cfg(test)]
+use super::helper::strip_ansi;
+
+// Whether multi-word input would reach the model
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LlmStatus {
+    #[default]
+    Ready,
+    // A request failed; the endpoint is checked again after a while
+    Degraded,
+    // Natural-language detection is off or the hard budget is spent
+    Off,
+}
+
+// What the shell knows when it draws the prompt
+#[derive(Debug, Clone, Default)]
+pub struct PromptInfo {
+    pub status: i32,
+    pub jobs: usize,
+    pub model: String,
+    pub llm: LlmStatus,
+    // LLM requests waiting for a response, read whenever the prompt is drawn
+    pub in_flight: Arc<AtomicUsize>,
+}
+
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+// The model and an indicator: a spinner while a request is in flight,
+// otherwise ● ready, ◐ degraded or ○ off
+pub fn llm_segment(info: &PromptInfo) -> String {
+    let indicator = if info.in_flight.load(Ordering::Relaxed) > 0 {
+        let tick = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() / 100)
+            .unwrap_or(0);
+        SPINNER[tick as usize % SPINNER.len()].to_string().bright_cyan()
+    } else {
+        match info.llm {
+            LlmStatus::Ready => "●".bright_green(),
+            LlmStatus::Degraded => "◐".bright_yellow(),
+            LlmStatus::Off => "○".dimmed(),
+        }
+    };
+    format!("{} {}", info.model, indicator)
+}
+
+// Expands a right prompt template such as `{status} {jobs} {model} {time}`.
+// Placeholders with nothing to show (status 0, no jobs) disappear along with
+// the spaces around them.
+pub fn render(template: &str, info: &PromptInfo) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            rest = &rest[start..];
+            break;
+        };
+        match &rest[start + 1..end] {
+            "status" if info.status != 0 => result.push_str(&format!("✘ {}", info.status)),
+            "jobs" if info.jobs == 1 => result.push_str("1 job"),
+            "jobs" if info.jobs > 1 => result.push_str(&format!("{} jobs", info.jobs)),
+            "model" => result.push_str(&info.model),
+            "llm" => result.push_str(&llm_segment(info)),
+            "time" => result.push_str(&clock()),
+            "status" | "jobs" => {}
+            // Unknown placeholders are shown as written
+            other => result.push_str(&format!("{{{}}}", other)),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn clock() -> String {
+    let tm = scheduler::local_time(scheduler::now());
+    format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let info = PromptInfo { model: "qwen2.5:14b".to_string(), ..PromptInfo::default() };
+        assert_eq!(render("{status} {jobs} {model}", &info), "qwen2.5:14b");
+        let info = PromptInfo { status: 130, jobs: 2, ..info };
+        assert_eq!(render("[{status}] {jobs} {unknown}", &info), "[✘ 130] 2 jobs {unknown}");
+        assert_eq!(render("{time}", &info).len(), 8);
+        assert_eq!(render("left {model", &info), "left {model");
+
+        assert_eq!(strip_ansi(&llm_segment(&info)), "qwen2.5:14b ●");
+        info.in_flight.fetch_add(1, Ordering::Relaxed);
+        assert!(SPINNER.iter().any(|c| strip_ansi(&render("{llm}", &info)).ends_with(*c)));
+    }
+}
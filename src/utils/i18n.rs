@@ -0,0 +1,194 @@
+use lazy_static::lazy_static;
+
+// User-facing messages by key, per language. English is the fallback for
+// languages and keys that aren't translated; `{}` is filled in by `tr_args`.
+type Catalog = &'static [(&'static str, &'static str)];
+
+const EN: Catalog = &[
+    ("welcome.title", "Welcome to LLM Shell"),
+    ("welcome.natural", "Use natural language for commands"),
+    ("welcome.suggest", "Type '??' after a command for help"),
+    ("welcome.ask", "Start with '?' to ask a question"),
+    ("welcome.help", "Type 'help' for more information"),
+    ("welcome.demo", "DEMO MODE: nothing will be executed"),
+    ("help.title", "LLM Shell Help"),
+    ("help.builtins", "Basic Commands:"),
+    ("help.builtin_hint", "Type 'help NAME' for a builtin's usage and options"),
+    ("help.features", "Special Features:"),
+    ("help.examples", "Examples:"),
+    ("confirm.proceed", "Proceed? [y/N] "),
+    ("confirm.run_it", "Run it? [y/N] "),
+    ("confirm.yes", "y|yes"),
+    ("confirm.not_terminal", "n (not a terminal)"),
+    ("confirm.aborted", "Command aborted."),
+    ("confirm.apply", "Apply these changes? [y/N] "),
+    ("confirm.escalation", "This generated command runs with elevated privileges ({})."),
+    ("confirm.type_host", "Type the hostname ({}) to proceed: "),
+    ("warn.destructive", "Warning: This command may modify or delete data."),
+    ("warn.no_terminal", "Refusing to run without a terminal to confirm: {}"),
+    ("eof.hint", "Use \"{}\" to leave the shell."),
+    ("logout.not_login", "logout: not login shell: use `exit'"),
+];
+
+const ES: Catalog = &[
+    ("welcome.title", "Bienvenido a LLM Shell"),
+    ("welcome.natural", "Escribe órdenes en lenguaje natural"),
+    ("welcome.suggest", "Añade '??' a una orden para ayuda"),
+    ("welcome.ask", "Empieza con '?' para preguntar"),
+    ("welcome.help", "Escribe 'help' para más información"),
+    ("welcome.demo", "MODO DEMO: no se ejecutará nada"),
+    ("help.title", "Ayuda de LLM Shell"),
+    ("help.builtins", "Órdenes básicas:"),
+    ("help.builtin_hint", "Escribe 'help NOMBRE' para ver el uso y las opciones"),
+    ("help.features", "Funciones especiales:"),
+    ("help.examples", "Ejemplos:"),
+    ("confirm.proceed", "¿Continuar? [s/N] "),
+    ("confirm.run_it", "¿Ejecutarla? [s/N] "),
+    ("confirm.yes", "s|si|sí|y|yes"),
+    ("confirm.not_terminal", "n (no es una terminal)"),
+    ("confirm.aborted", "Orden cancelada."),
+    ("confirm.apply", "¿Aplicar estos cambios? [s/N] "),
+    ("confirm.escalation", "Esta orden generada se ejecuta con privilegios elevados ({})."),
+    ("confirm.type_host", "Escribe el nombre del equipo ({}) para continuar: "),
+    ("warn.destructive", "Aviso: esta orden puede modificar o borrar datos."),
+    ("warn.no_terminal", "No se ejecuta sin una terminal para confirmar: {}"),
+    ("eof.hint", "Usa \"{}\" para salir de la shell."),
+];
+
+const DE: Catalog = &[
+    ("welcome.title", "Willkommen bei LLM Shell"),
+    ("welcome.natural", "Befehle in natürlicher Sprache"),
+    ("welcome.suggest", "'??' nach einem Befehl für Hilfe"),
+    ("welcome.ask", "Mit '?' beginnen, um zu fragen"),
+    ("welcome.help", "'help' für weitere Informationen"),
+    ("welcome.demo", "DEMO-MODUS: nichts wird ausgeführt"),
+    ("help.title", "LLM Shell Hilfe"),
+    ("help.builtins", "Grundbefehle:"),
+    ("help.builtin_hint", "'help NAME' zeigt Aufruf und Optionen eines Befehls"),
+    ("help.features", "Besondere Funktionen:"),
+    ("help.examples", "Beispiele:"),
+    ("confirm.proceed", "Fortfahren? [j/N] "),
+    ("confirm.run_it", "Ausführen? [j/N] "),
+    ("confirm.yes", "j|ja|y|yes"),
+    ("confirm.not_terminal", "n (kein Terminal)"),
+    ("confirm.aborted", "Befehl abgebrochen."),
+    ("confirm.apply", "Diese Änderungen übernehmen? [j/N] "),
+    ("confirm.escalation", "Dieser erzeugte Befehl läuft mit erhöhten Rechten ({})."),
+    ("confirm.type_host", "Zum Fortfahren den Rechnernamen ({}) eingeben: "),
+    ("warn.destructive", "Warnung: Dieser Befehl kann Daten ändern oder löschen."),
+    ("warn.no_terminal", "Ohne Terminal zur Bestätigung nicht ausgeführt: {}"),
+    ("eof.hint", "\"{}\" beendet die Shell."),
+];
+
+const FR: Catalog = &[
+    ("welcome.title", "Bienvenue dans LLM Shell"),
+    ("welcome.natural", "Tapez vos commandes en langage naturel"),
+    ("welcome.suggest", "Ajoutez '??' à une commande pour l'aide"),
+    ("welcome.ask", "Commencez par '?' pour poser une question"),
+    ("welcome.help", "Tapez 'help' pour plus d'informations"),
+    ("welcome.demo", "MODE DÉMO : rien ne sera exécuté"),
+    ("help.title", "Aide de LLM Shell"),
+    ("help.builtins", "Commandes de base :"),
+    ("help.builtin_hint", "Tapez 'help NOM' pour l'usage et les options"),
+    ("help.features", "Fonctions spéciales :"),
+    ("help.examples", "Exemples :"),
+    ("confirm.proceed", "Continuer ? [o/N] "),
+    ("confirm.run_it", "L'exécuter ? [o/N] "),
+    ("confirm.yes", "o|oui|y|yes"),
+    ("confirm.not_terminal", "n (pas un terminal)"),
+    ("confirm.aborted", "Commande annulée."),
+    ("confirm.apply", "Appliquer ces modifications ? [o/N] "),
+    ("confirm.escalation", "Cette commande générée s'exécute avec des privilèges élevés ({})."),
+    ("confirm.type_host", "Tapez le nom de la machine ({}) pour continuer : "),
+    ("warn.destructive", "Attention : cette commande peut modifier ou supprimer des données."),
+    ("warn.no_terminal", "Refusé sans terminal pour confirmer : {}"),
+    ("eof.hint", "Utilisez \"{}\" pour quitter le shell."),
+];
+
+// Language code, catalog and the language's English name for system prompts
+const LANGUAGES: &[(&str, Catalog, &str)] = &[
+    ("en", EN, "English"),
+    ("es", ES, "Spanish"),
+    ("de", DE, "German"),
+    ("fr", FR, "French"),
+];
+
+lazy_static! {
+    static ref LANGUAGE: String = language_from_env();
+}
+
+// LC_ALL, then LC_MESSAGES, then LANG, as for other programs; "es_ES.UTF-8" -> "es"
+fn language_from_env() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|value| !value.is_empty())
+        .map(|locale| parse_language(&locale))
+        .unwrap_or_else(|| "en".to_string())
+}
+
+fn parse_language(locale: &str) -> String {
+    let language = locale
+        .split(['_', '.', '@'])
+        .next()
+        .unwrap_or("")
+        .to_lowercase();
+    match language.as_str() {
+        "" | "c" | "posix" => "en".to_string(),
+        _ => language,
+    }
+}
+
+fn lookup(language: &str, key: &str) -> Option<&'static str> {
+    let (_, catalog, _) = LANGUAGES.iter().find(|(code, _, _)| *code == language)?;
+    catalog.iter().find(|(k, _)| *k == key).map(|(_, text)| *text)
+}
+
+fn translate(language: &str, key: &'static str) -> &'static str {
+    lookup(language, key).or_else(|| lookup("en", key)).unwrap_or(key)
+}
+
+// The message for `key` in the user's language
+pub fn tr(key: &'static str) -> &'static str {
+    translate(&LANGUAGE, key)
+}
+
+// Like `tr`, filling each `{}` with the next argument
+pub fn tr_args(key: &'static str, args: &[&str]) -> String {
+    let mut args = args.iter();
+    tr(key)
+        .split("{}")
+        .enumerate()
+        .map(|(i, part)| if i == 0 { part.to_string() } else { format!("{}{}", args.next().unwrap_or(&""), part) })
+        .collect()
+}
+
+// Whether a reply to a y/N question means yes in the user's language
+pub fn is_yes(reply: &str) -> bool {
+    let reply = reply.trim().to_lowercase();
+    tr("confirm.yes").split('|').any(|yes| yes == reply)
+}
+
+// English name of the user's language when it isn't English, for asking the
+// model to answer in it
+pub fn user_language_name() -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(code, _, _)| *code == LANGUAGE.as_str() && *code != "en")
+        .map(|(_, _, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        assert_eq!(parse_language("es_ES.UTF-8"), "es");
+        assert_eq!(parse_language("C.UTF-8"), "en");
+        assert_eq!(translate("es", "confirm.aborted"), "Orden cancelada.");
+        // Untranslated keys and languages fall back to English
+        assert_eq!(translate("es", "logout.not_login"), "logout: not login shell: use `exit'");
+        assert_eq!(translate("ja", "confirm.aborted"), "Command aborted.");
+    }
+}
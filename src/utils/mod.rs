@@ -1,4 +1,9 @@
 pub mod performance;
 pub mod path_utils;
+pub mod duration;
+pub mod pattern;
+pub mod i18n;
+pub mod crash;
+pub mod telemetry;
 
 pub use performance::*;
\ No newline at end of file
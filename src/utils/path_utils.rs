@@ -1,7 +1,55 @@
 // src/utils/path_utils.rs
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
 use std::path::{Path, PathBuf};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+
+// Where programs were found, so PATH is searched once per program. Like
+// bash's hash table it is dropped when PATH changes.
+#[derive(Default)]
+struct HashTable {
+    path: String,
+    // Location and number of lookups answered from the table
+    entries: HashMap<String, (PathBuf, usize)>,
+}
+
+lazy_static! {
+    static ref HASH_TABLE: Mutex<HashTable> = Mutex::new(HashTable::default());
+}
+
+// Where the shell keeps its state: $XDG_STATE_HOME/llm-shell, or
+// ~/.local/state/llm-shell
+pub fn state_dir() -> Result<PathBuf> {
+    dirs::state_dir()
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local").join("state")))
+        .map(|dir| dir.join("llm-shell"))
+        .context("Could not determine state directory")
+}
+
+// Writes a state file, creating its directory first
+pub fn write_state(path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+// A JSON file in the state directory; missing or unreadable ones give the default
+pub fn load_json<T: DeserializeOwned + Default>(name: &str) -> T {
+    state_dir()
+        .ok()
+        .and_then(|dir| fs::read_to_string(dir.join(name)).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_json<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    write_state(&state_dir()?.join(name), serde_json::to_string_pretty(value)?)
+}
 
 pub fn find_executable(command: &str) -> Option<PathBuf> {
     // If the command contains a path separator, check if it exists directly
@@ -13,6 +61,88 @@ pub fn find_executable(command: &str) -> Option<PathBuf> {
         return None;
     }
 
+    // If not found in PATH, just return the command itself
+    // This allows the shell to handle the error more gracefully
+    Some(hash(command).unwrap_or_else(|| PathBuf::from(command)))
+}
+
+// Like find_executable, but None when a program isn't found on PATH
+pub fn lookup(command: &str) -> Option<PathBuf> {
+    if command.contains('/') {
+        find_executable(command)
+    } else {
+        hash(command)
+    }
+}
+
+// Looks a program up in the hash table, searching PATH and remembering the
+// result if it isn't there yet
+pub fn hash(command: &str) -> Option<PathBuf> {
+    let path_var = env::var("PATH").unwrap_or_default();
+    {
+        let mut table = HASH_TABLE.lock().unwrap();
+        if table.path != path_var {
+            *table = HashTable { path: path_var.clone(), entries: HashMap::new() };
+        }
+        if let Some((path, hits)) = table.entries.get_mut(command) {
+            // A program removed since is looked up again
+            if is_executable(path) {
+                *hits += 1;
+                return Some(path.clone());
+            }
+            table.entries.remove(command);
+        }
+    }
+    let found = search(command, &path_var)?;
+    let mut table = HASH_TABLE.lock().unwrap();
+    if table.path == path_var {
+        table.entries.insert(command.to_string(), (found.clone(), 0));
+    }
+    Some(found)
+}
+
+// The hash table's entries as (name, hits, path), by name
+pub fn hashed() -> Vec<(String, usize, PathBuf)> {
+    let table = HASH_TABLE.lock().unwrap();
+    let mut entries: Vec<_> = table.entries.iter().map(|(name, (path, hits))| (name.clone(), *hits, path.clone())).collect();
+    entries.sort();
+    entries
+}
+
+// Whether a program is in the hash table for the current PATH
+pub fn is_hashed(command: &str) -> bool {
+    let table = HASH_TABLE.lock().unwrap();
+    table.path == env::var("PATH").unwrap_or_default() && table.entries.contains_key(command)
+}
+
+pub fn forget_hashed() {
+    HASH_TABLE.lock().unwrap().entries.clear();
+}
+
+// The directory `~NAME` stands for: `~` is $HOME, `~user` that user's home
+// directory, `~+` the current directory and `~-` $OLDPWD
+pub fn tilde_directory(name: &str) -> Option<String> {
+    match name {
+        "" => env::var("HOME").ok().or_else(|| dirs::home_dir().map(|home| home.to_string_lossy().to_string())),
+        "+" => env::current_dir().ok().map(|dir| dir.to_string_lossy().to_string()),
+        "-" => env::var("OLDPWD").ok(),
+        user => nix::unistd::User::from_name(user).ok().flatten().map(|user| user.dir.to_string_lossy().to_string()),
+    }
+}
+
+// A single unquoted word with its leading `~` or `~user` expanded
+pub fn expand_tilde(word: &str) -> String {
+    let Some(rest) = word.strip_prefix('~') else {
+        return word.to_string();
+    };
+    let (name, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    match tilde_directory(name) {
+        Some(dir) => format!("{}{}", dir, path),
+        None => word.to_string(),
+    }
+}
+
+fn search(command: &str, path_var: &str) -> Option<PathBuf> {
     // For common commands, try direct paths first
     let common_paths = [
         "/bin", "/usr/bin", "/usr/local/bin", "/sbin", "/usr/sbin"
@@ -26,18 +156,13 @@ pub fn find_executable(command: &str) -> Option<PathBuf> {
     }
 
     // Otherwise, search in PATH
-    if let Ok(path_var) = env::var("PATH") {
-        for dir in path_var.split(':') {
-            let path = Path::new(dir).join(command);
-            if path.exists() && is_executable(&path) {
-                return Some(path);
-            }
+    for dir in path_var.split(':').filter(|dir| !dir.is_empty()) {
+        let path = Path::new(dir).join(command);
+        if path.exists() && is_executable(&path) {
+            return Some(path);
         }
     }
-
-    // If not found in PATH, just return the command itself
-    // This allows the shell to handle the error more gracefully
-    Some(PathBuf::from(command))
+    None
 }
 
 #[cfg(unix)]
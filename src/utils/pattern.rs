@@ -0,0 +1,181 @@
+// Shell-style glob matching: `*`, `?`, `[abc]`, `[a-z]`, `[!x]` and backslash escapes
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the most recent `*` if the rest fails to match
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match match_class(&pattern[p..], text[t]) {
+                Some((true, len)) => Some(len),
+                Some((false, _)) => None,
+                // An unterminated bracket is matched literally
+                None if text[t] == '[' => Some(1),
+                None => None,
+            },
+            Some('\\') if p + 1 < pattern.len() => {
+                if pattern[p + 1] == text[t] { Some(2) } else { None }
+            }
+            Some(c) if *c == text[t] => Some(1),
+            _ => None,
+        };
+
+        match step {
+            Some(len) => {
+                p += len;
+                t += 1;
+            }
+            None => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// Whether the pattern has a `*`, `?` or `[` that isn't escaped
+pub fn has_wildcards(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+// The paths matching a pattern, sorted, with wildcards allowed in every
+// `/`-separated part. As in sh, hidden files only match a part starting
+// with `.`, and a trailing `/` only matches directories.
+pub fn glob_paths(pattern: &str) -> Vec<String> {
+    let mut paths = vec![if pattern.starts_with('/') { "/".to_string() } else { String::new() }];
+    let join = |base: &str, name: &str| if base.is_empty() || base.ends_with('/') { format!("{}{}", base, name) } else { format!("{}/{}", base, name) };
+    for part in pattern.split('/').filter(|part| !part.is_empty()) {
+        if !has_wildcards(part) {
+            let name = part.replace('\\', "");
+            paths = paths.iter().map(|base| join(base, &name)).collect();
+            continue;
+        }
+        paths = paths
+            .iter()
+            .flat_map(|base| {
+                let entries = std::fs::read_dir(if base.is_empty() { "." } else { base.as_str() });
+                entries
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .filter(|name| (!name.starts_with('.') || part.starts_with('.')) && glob_match(part, name))
+                    .map(|name| join(base, &name))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+    let mut paths: Vec<String> = paths
+        .into_iter()
+        .filter(|path| !path.is_empty() && std::path::Path::new(path).exists())
+        .filter(|path| !pattern.ends_with('/') || std::path::Path::new(path).is_dir())
+        .map(|path| if pattern.ends_with('/') { path + "/" } else { path })
+        .collect();
+    paths.sort();
+    paths
+}
+
+// Returns whether `c` is in the bracket expression and how many pattern chars it spans
+fn match_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() {
+        if pattern[i] == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            if pattern[i] <= c && c <= pattern[i + 2] {
+                matched = true;
+            }
+            i += 3;
+        } else {
+            if pattern[i] == c {
+                matched = true;
+            }
+            i += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.tar.gz", "backup.tar.gz"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b", "acd"));
+        assert!(glob_match("v?.?", "v1.2"));
+        assert!(!glob_match("v?", "v10"));
+    }
+
+    #[test]
+    fn test_brackets_and_escapes() {
+        assert!(glob_match("[yY]*", "yes"));
+        assert!(glob_match("[a-c]x", "bx"));
+        assert!(!glob_match("[!a-c]x", "bx"));
+        assert!(glob_match("\\*", "*"));
+        assert!(!glob_match("\\*", "a"));
+        assert!(glob_match("[", "["));
+    }
+
+    #[test]
+    fn test_glob_paths() {
+        let dir = std::env::temp_dir().join(format!("llm-shell-glob-{}", std::process::id()));
+        for path in ["src/a.rs", "src/b.rs", "src/c.txt", "docs/a.md", ".hidden.rs"] {
+            std::fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(path), "").unwrap();
+        }
+        let root = dir.to_string_lossy().to_string();
+        let glob = |pattern: &str| -> Vec<String> {
+            glob_paths(&format!("{}/{}", root, pattern)).iter().map(|path| path[root.len() + 1..].to_string()).collect()
+        };
+        assert_eq!(glob("src/*.rs"), ["src/a.rs", "src/b.rs"]);
+        assert_eq!(glob("*/a.*"), ["docs/a.md", "src/a.rs"]);
+        assert_eq!(glob("*/"), ["docs/", "src/"]);
+        assert_eq!(glob(".*.rs"), [".hidden.rs"]);
+        assert!(glob("*.rs").is_empty());
+        assert!(glob("src/\\*.rs").is_empty());
+        assert!(has_wildcards("a[0-9]") && !has_wildcards("a\\*b"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,42 +1,243 @@
 use std::time::Duration;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
 use lazy_static::lazy_static;
 use std::sync::Mutex;
+use log::debug;
+use crate::utils::path_utils;
 
 lazy_static! {
-    pub static ref PERFORMANCE_MONITOR: Mutex<PerformanceMonitor> = Mutex::new(PerformanceMonitor::new(100));
+    pub static ref PERFORMANCE_MONITOR: Mutex<PerformanceMonitor> = Mutex::new(PerformanceMonitor::new(100).with_log(log_path()));
+}
+
+// A command the executor ran to completion
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    pub command: String,
+    // Seconds since the epoch
+    pub started: u64,
+    pub duration: Duration,
+    pub status: i32,
+}
+
+impl Execution {
+    // "STARTED<TAB>MILLISECONDS<TAB>STATUS<TAB>COMMAND"
+    fn format(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.started,
+            self.duration.as_millis(),
+            self.status,
+            self.command.replace(['\t', '\n'], " ")
+        )
+    }
+}
+
+// Count, failures, mean, 95th percentile and maximum of one series of timings
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub failed: usize,
+    pub average: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl Summary {
+    fn of(samples: impl Iterator<Item = (Duration, bool)>) -> Self {
+        let mut failed = 0;
+        let mut durations: Vec<Duration> = samples
+            .map(|(duration, succeeded)| {
+                failed += usize::from(!succeeded);
+                duration
+            })
+            .collect();
+        if durations.is_empty() {
+            return Summary::default();
+        }
+        durations.sort();
+        let count = durations.len();
+        Summary {
+            count,
+            failed,
+            average: durations.iter().sum::<Duration>() / count as u32,
+            p95: durations[(count * 95).div_ceil(100) - 1],
+            max: durations[count - 1],
+        }
+    }
 }
 
 pub struct PerformanceMonitor {
-    command_timings: VecDeque<(String, Duration)>,
+    command_timings: VecDeque<Execution>,
+    // Round trips to the LLM host and whether they succeeded, kept apart so
+    // waiting for the model never counts as command time
+    llm_timings: VecDeque<(Duration, bool)>,
     max_samples: usize,
+    // Executions recorded so far, including the ones no longer kept
+    recorded: usize,
+    // The history of timings, appended to as commands finish
+    log: Option<PathBuf>,
 }
 
 impl PerformanceMonitor {
     pub fn new(max_samples: usize) -> Self {
         PerformanceMonitor {
             command_timings: VecDeque::new(),
+            llm_timings: VecDeque::new(),
             max_samples,
+            recorded: 0,
+            log: None,
         }
     }
 
-    pub fn record_execution(&mut self, command: &str, duration: Duration) {
-        self.command_timings.push_back((command.to_string(), duration));
+    pub fn with_log(mut self, path: Option<PathBuf>) -> Self {
+        self.log = path;
+        self
+    }
+
+    pub fn record_execution(&mut self, execution: Execution) {
+        if let Some(path) = &self.log {
+            if let Err(e) = append(path, &execution) {
+                debug!("Failed to record timing in {}: {}", path.display(), e);
+            }
+        }
+        self.command_timings.push_back(execution);
+        self.recorded += 1;
         if self.command_timings.len() > self.max_samples {
             self.command_timings.pop_front();
         }
     }
 
+    pub fn record_llm_request(&mut self, duration: Duration, succeeded: bool) {
+        self.llm_timings.push_back((duration, succeeded));
+        if self.llm_timings.len() > self.max_samples {
+            self.llm_timings.pop_front();
+        }
+    }
+
+    pub fn commands(&self) -> Summary {
+        Summary::of(self.command_timings.iter().map(|execution| (execution.duration, execution.status == 0)))
+    }
+
+    pub fn llm_requests(&self) -> Summary {
+        Summary::of(self.llm_timings.iter().copied())
+    }
+
+    // The slowest recent commands, slowest first
+    pub fn slowest(&self, count: usize) -> Vec<&Execution> {
+        let mut executions: Vec<&Execution> = self.command_timings.iter().collect();
+        executions.sort_by_key(|execution| std::cmp::Reverse(execution.duration));
+        executions.truncate(count);
+        executions
+    }
+
+    // A marker to pass to `since` later
+    pub fn recorded(&self) -> usize {
+        self.recorded
+    }
+
+    // Executions recorded after `recorded()` returned `mark`
+    pub fn since(&self, mark: usize) -> impl Iterator<Item = &Execution> {
+        let count = self.recorded.saturating_sub(mark).min(self.command_timings.len());
+        self.command_timings.iter().skip(self.command_timings.len() - count)
+    }
+
     pub fn get_average_duration(&self) -> Duration {
         if self.command_timings.is_empty() {
             return Duration::from_secs(0);
         }
-        
+
         let total = self.command_timings
             .iter()
-            .map(|(_, duration)| duration.as_millis())
+            .map(|execution| execution.duration.as_millis())
             .sum::<u128>();
-            
+
         Duration::from_millis((total / self.command_timings.len() as u128) as u64)
     }
-}
\ No newline at end of file
+}
+
+// Stored in $XDG_STATE_HOME/llm-shell/timings, next to the command history
+fn log_path() -> Option<PathBuf> {
+    Some(path_utils::state_dir().ok()?.join("timings"))
+}
+
+// The programs run most often according to the timings, most used first
+pub fn frequent_programs(limit: usize) -> Vec<String> {
+    log_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|content| rank_programs(&content, limit))
+        .unwrap_or_default()
+}
+
+fn rank_programs(content: &str, limit: usize) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for line in content.lines() {
+        // Every stage of a pipeline counts
+        if let Some(command) = line.splitn(4, '\t').nth(3) {
+            for stage in command.split(" | ") {
+                if let Some(program) = stage.split_whitespace().next() {
+                    *counts.entry(program).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    let mut ranked: Vec<(&str, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    ranked.into_iter().take(limit).map(|(program, _)| program.to_string()).collect()
+}
+
+fn append(path: &PathBuf, execution: &Execution) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", execution.format())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execution(command: &str, millis: u64) -> Execution {
+        Execution { command: command.to_string(), started: 1_700_000_000, duration: Duration::from_millis(millis), status: 0 }
+    }
+
+    #[test]
+    fn test_since() {
+        let mut monitor = PerformanceMonitor::new(2);
+        monitor.record_execution(execution("ls", 10));
+        let mark = monitor.recorded();
+        monitor.record_execution(execution("make", 30));
+        monitor.record_execution(execution("make\ttest", 50));
+        let recent: Vec<&str> = monitor.since(mark).map(|execution| execution.command.as_str()).collect();
+        assert_eq!(recent, vec!["make", "make\ttest"]);
+        assert_eq!(monitor.get_average_duration(), Duration::from_millis(40));
+        assert_eq!(monitor.since(monitor.recorded()).count(), 0);
+        assert_eq!(monitor.slowest(1)[0].command, "make\ttest");
+        assert_eq!(execution("a\tb", 1500).format(), "1700000000\t1500\t0\ta b");
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut monitor = PerformanceMonitor::new(100);
+        assert_eq!(monitor.llm_requests(), Summary::default());
+        for millis in 1..=20 {
+            monitor.record_llm_request(Duration::from_millis(millis * 100), millis != 7);
+        }
+        let summary = monitor.llm_requests();
+        assert_eq!((summary.count, summary.failed), (20, 1));
+        assert_eq!(summary.average, Duration::from_millis(1050));
+        assert_eq!(summary.p95, Duration::from_millis(1900));
+        assert_eq!(summary.max, Duration::from_millis(2000));
+        assert_eq!(monitor.commands().count, 0);
+    }
+
+    #[test]
+    fn test_rank_programs() {
+        let log = "1\t5\t0\tgit status\n2\t9\t0\tls -l | grep x\n3\t1\t1\tgit push\nbroken line\n4\t2\t0\tls\n";
+        assert_eq!(rank_programs(log, 2), vec!["git", "ls"]);
+        assert_eq!(rank_programs(log, 5), vec!["git", "ls", "grep"]);
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use log::debug;
+use crate::utils::path_utils;
+
+// Uploads, when enabled, are at most this frequent
+const UPLOAD_INTERVAL: u64 = 24 * 60 * 60;
+
+lazy_static! {
+    static ref PENDING: Mutex<Pending> = Mutex::new(Pending::default());
+}
+
+// Counts since the last flush; the file is merged on flush so several
+// shells can count at once
+#[derive(Default)]
+struct Pending {
+    counts: BTreeMap<String, u64>,
+    // Read from the file on first use
+    collecting: Option<bool>,
+}
+
+// Stored in $XDG_STATE_HOME/llm-shell/telemetry.json. Only feature names and
+// counts, never commands, paths or requests.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Usage {
+    #[serde(default = "now")]
+    pub since: u64,
+    #[serde(default = "collecting_by_default")]
+    pub collect: bool,
+    // Off unless the user turns it on with `telemetry upload on`
+    #[serde(default)]
+    pub upload: bool,
+    #[serde(default)]
+    pub uploaded: u64,
+    #[serde(default)]
+    pub counters: BTreeMap<String, u64>,
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Usage { since: now(), collect: true, upload: false, uploaded: 0, counters: BTreeMap::new() }
+    }
+}
+
+fn collecting_by_default() -> bool {
+    true
+}
+
+impl Usage {
+    pub fn get(&self, name: &str) -> u64 {
+        self.counters.get(name).copied().unwrap_or(0)
+    }
+
+    // Translations run after being shown, out of those the user decided on
+    pub fn acceptance(&self) -> Option<f64> {
+        let accepted = self.get("translation.accepted");
+        let decided = accepted + self.get("translation.rejected");
+        (decided > 0).then(|| accepted as f64 / decided as f64)
+    }
+
+    fn merge(&mut self, counts: &BTreeMap<String, u64>) {
+        for (name, count) in counts {
+            *self.counters.entry(name.clone()).or_insert(0) += count;
+        }
+    }
+
+    // What an upload sends
+    pub fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "os": std::env::consts::OS,
+            "since": self.since,
+            "counters": self.counters,
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Stored in $XDG_STATE_HOME/llm-shell
+const FILE: &str = "telemetry.json";
+
+pub fn load() -> Usage {
+    path_utils::load_json(FILE)
+}
+
+fn save(usage: &Usage) -> Result<()> {
+    path_utils::save_json(FILE, usage)
+}
+
+// Counts one use of a feature, unless collection is off
+pub fn count(name: &str) {
+    let mut pending = PENDING.lock().unwrap();
+    if *pending.collecting.get_or_insert_with(|| load().collect) {
+        *pending.counts.entry(name.to_string()).or_insert(0) += 1;
+    }
+}
+
+// Adds what was counted to the file
+pub fn flush() {
+    let mut pending = PENDING.lock().unwrap();
+    if pending.counts.is_empty() {
+        return;
+    }
+    let mut usage = load();
+    usage.merge(&pending.counts);
+    match save(&usage) {
+        Ok(()) => pending.counts.clear(),
+        Err(e) => debug!("Failed to save usage counters: {}", e),
+    }
+}
+
+// Turns local counting on or off; turning it off also stops uploads
+pub fn set_collect(collect: bool) -> Result<()> {
+    flush();
+    let mut pending = PENDING.lock().unwrap();
+    pending.counts.clear();
+    pending.collecting = Some(collect);
+    let mut usage = load();
+    usage.collect = collect;
+    usage.upload &= collect;
+    save(&usage)
+}
+
+pub fn set_upload(upload: bool) -> Result<()> {
+    flush();
+    let mut usage = load();
+    if upload && !usage.collect {
+        anyhow::bail!("counting is off; turn it on with `telemetry on` first");
+    }
+    usage.upload = upload;
+    save(&usage)
+}
+
+// Starts the counts over, keeping the settings
+pub fn reset() -> Result<()> {
+    PENDING.lock().unwrap().counts.clear();
+    let usage = load();
+    save(&Usage { since: now(), counters: BTreeMap::new(), uploaded: 0, ..usage })
+}
+
+// Where uploads go; there is no default
+pub fn upload_url() -> Option<String> {
+    std::env::var("LLM_SHELL_TELEMETRY_URL").ok().filter(|url| !url.trim().is_empty())
+}
+
+// Sends the counters if the user enabled uploads and a day has passed
+pub async fn upload_if_due() {
+    flush();
+    let mut usage = load();
+    let Some(url) = upload_url() else {
+        return;
+    };
+    if !usage.upload || !usage.collect || now().saturating_sub(usage.uploaded) < UPLOAD_INTERVAL {
+        return;
+    }
+    let sent = reqwest::Client::new()
+        .post(&url)
+        .timeout(Duration::from_secs(5))
+        .json(&usage.report())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match sent {
+        Ok(_) => {
+            usage.uploaded = now();
+            if let Err(e) = save(&usage) {
+                debug!("Failed to save usage counters: {}", e);
+            }
+        }
+        Err(e) => debug!("Failed to upload usage counters to {}: {}", url, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage() {
+        let mut usage: Usage = serde_json::from_str(r#"{"counters": {"chat": 2, "translation.accepted": 3}}"#).unwrap();
+        assert!(usage.collect && !usage.upload);
+        let counts = BTreeMap::from([("chat".to_string(), 1), ("translation.rejected".to_string(), 1)]);
+        usage.merge(&counts);
+        assert_eq!(usage.get("chat"), 3);
+        assert_eq!(usage.acceptance(), Some(0.75));
+        assert_eq!(Usage::default().acceptance(), None);
+        assert_eq!(usage.report()["counters"]["translation.rejected"], 1);
+    }
+}